@@ -0,0 +1,669 @@
+use std::{num::NonZeroUsize, path::PathBuf};
+
+use super::display::Format;
+use savefile::{
+    selection::{Cutoff, IdRanges},
+    size::ByteSize,
+    BackupCause, BackupRef, ProfileName,
+};
+
+/// Top-level CLI argument parser
+#[derive(clap::Parser)]
+#[clap(
+    after_long_help = "Run \"savefile docs\" to print the help of every command at once.\n\nExamples:\n  savefile profile create --name my-game --edit\n  savefile watch --name my-game\n  savefile backup restore --name my-game"
+)]
+pub struct Args {
+    /// Answer "yes" to all confirmation prompts
+    #[clap(
+        short,
+        long,
+        global = true,
+        default_value_t = false,
+        env = "SAVEFILE_YES"
+    )]
+    pub yes: bool,
+    /// Don't color the output (also disabled by setting NO_COLOR)
+    #[clap(long, global = true)]
+    pub no_color: bool,
+    #[clap(subcommand)]
+    pub cmd: SubCmd,
+}
+
+/// Top-level CLI subcommands
+#[derive(clap::Subcommand)]
+pub enum SubCmd {
+    /// Manage profiles
+    #[clap(
+        after_long_help = "Examples:\n  savefile profile create --name my-game --edit\n  savefile profile list --prefix my"
+    )]
+    #[clap(subcommand)]
+    Profile(ProfileCmd),
+    /// Manage backups
+    #[clap(
+        after_long_help = "Examples:\n  savefile backup create --name my-game\n  savefile backup list --name my-game --count 10\n  savefile backup restore --name my-game --id 42"
+    )]
+    #[clap(subcommand)]
+    Backup(BackupCmd),
+    /// Manage the global configuration
+    #[clap(
+        after_long_help = "Examples:\n  savefile config show\n  savefile config set per_profile_database true"
+    )]
+    #[clap(subcommand)]
+    Config(ConfigCmd),
+    /// Manage secrets stored in the OS keyring
+    #[clap(
+        after_long_help = "Secrets are looked up by name:\n  database-key          key of the metadata databases (sqlcipher feature)\n  smtp-password         password of the mail server used for alerts\n  sftp:<user>@<host>    password for logging in to an SFTP server\n\nExamples:\n  savefile secret set smtp-password\n  savefile secret delete sftp:steam@example.com"
+    )]
+    #[clap(subcommand)]
+    Secret(SecretCmd),
+    /// Show watcher and backup status of profiles
+    #[clap(after_long_help = "Examples:\n  savefile status\n  savefile status --name my-game")]
+    Status {
+        /// Name of the profile to show (all profiles if omitted)
+        #[clap(short, long)]
+        name: Option<ProfileName>,
+    },
+    /// Follow the events of watched profiles
+    #[clap(
+        after_long_help = "Watchers append each event to a log per profile, one JSON object per line,\nwhich other tools can read too. See the README for the events.\n\nExamples:\n  savefile events tail --name my-game\n  savefile events tail --name my-game --lines 50"
+    )]
+    #[clap(subcommand)]
+    Events(EventsCmd),
+    /// Follow a single file through the backups
+    #[clap(
+        after_long_help = "Examples:\n  savefile file history --name my-game --path saves/slot1.sav\n  savefile file restore --name my-game --path saves/slot1.sav --id 42"
+    )]
+    #[clap(subcommand)]
+    File(FileCmd),
+    /// Take turns on a save shared with other players
+    #[clap(
+        after_long_help = "For games played by passing the save around, e.g. hotseat games. Set the\nprofile's \"coop\" field to the shared directory and your player name first.\n\nExamples:\n  savefile coop status --name my-game\n  savefile coop take --name my-game\n  savefile coop pass --name my-game"
+    )]
+    #[clap(subcommand)]
+    Coop(CoopCmd),
+    /// Show the log of backups made, restored and deleted
+    #[clap(
+        after_long_help = "Every backup, restore, delete and prune is logged with the process that\ndid it (cli, watcher or daemon) and whether it succeeded, oldest first.\n\nExamples:\n  savefile history\n  savefile history --name my-game --count 20"
+    )]
+    History {
+        /// Name of the profile to show (all profiles if omitted)
+        #[clap(short, long)]
+        name: Option<ProfileName>,
+        /// Only show the most recent entries
+        #[clap(short, long)]
+        count: Option<usize>,
+    },
+    /// Check profiles and backups for problems
+    #[clap(after_long_help = "Examples:\n  savefile doctor\n  savefile doctor --name my-game")]
+    Doctor {
+        /// Name of the profile to check (all profiles if omitted)
+        #[clap(short, long)]
+        name: Option<ProfileName>,
+    },
+    /// Find the backups whose files contain some text
+    #[clap(
+        after_long_help = "Searches the content index of a profile, which holds the text of its small\ntext files, e.g. JSON or INI saves. Set the profile's \"content_index\" field to\nindex each new backup, and pass --reindex to index existing backups.\n\nExamples:\n  savefile search --name my-game \"character_name\"\n  savefile search --name my-game --ignore-case \"gold\"\n  savefile search --name my-game --reindex \"level\""
+    )]
+    Search {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Text to search for
+        query: String,
+        /// Ignore differences between upper and lower case
+        #[clap(short, long)]
+        ignore_case: bool,
+        /// Rebuild the content index from all backups before searching
+        #[clap(long)]
+        reindex: bool,
+    },
+    /// Upgrade data written by older versions
+    #[clap(after_long_help = "Examples:\n  savefile migrate --dry-run\n  savefile migrate --yes")]
+    Migrate {
+        /// Only report what would be changed
+        #[clap(short, long, default_value_t = false)]
+        dry_run: bool,
+    },
+    /// Automatically back up files
+    #[clap(
+        after_long_help = "Backs up the profile's files a short delay after they change on disk,\nuntil interrupted with Ctrl+C. Only one watcher may run per profile.\n\nWith several --name options or --all, each profile is watched on its own\nthread, and watchers that fail are restarted.\n\nWith --foreground, SIGTERM stops the watchers cleanly after running backups\nfinish, as needed when running as the main process of a container.\n\nExamples:\n  savefile watch --name my-game\n  savefile watch --name my-game --name other-game\n  savefile watch --all --foreground\n  savefile watch pause --name my-game",
+        args_conflicts_with_subcommands = true,
+        subcommand_negates_reqs = true
+    )]
+    Watch {
+        #[clap(subcommand)]
+        cmd: Option<WatchCmd>,
+        /// Name of a profile to watch (may be repeated)
+        #[clap(short, long, required_unless_present = "all")]
+        name: Vec<ProfileName>,
+        /// Watch all profiles
+        #[clap(long, conflicts_with = "name")]
+        all: bool,
+        /// Stop cleanly on SIGTERM and SIGINT, e.g. in a container
+        #[clap(long)]
+        foreground: bool,
+    },
+    /// Manage Windows Explorer context-menu entries
+    #[clap(
+        after_long_help = "Adds \"Back up with savefile\" and \"Restore latest backup\" entries to the\ncontext menu of each profile's base directory. Run install again after\nadding, removing or moving profiles.\n\nExamples:\n  savefile shell-integration install\n  savefile shell-integration uninstall"
+    )]
+    #[clap(subcommand)]
+    ShellIntegration(ShellIntegrationCmd),
+    /// Show a system tray icon for controlling watchers
+    #[cfg(feature = "tray")]
+    #[clap(
+        after_long_help = "Each profile gets a submenu for starting or stopping its watcher and for\ncreating a backup right away. Watchers keep running after the tray exits.\n\nExamples:\n  savefile tray"
+    )]
+    Tray,
+    /// Print a shell completion script
+    #[clap(
+        after_long_help = "Examples:\n  savefile completions bash > ~/.local/share/bash-completion/completions/savefile\n  savefile completions powershell | Out-String | Invoke-Expression"
+    )]
+    Completions {
+        /// Shell to generate the script for
+        shell: clap_complete::Shell,
+    },
+    /// Print the long help of every command
+    #[clap(hide = true)]
+    Docs {
+        /// Only print the docs for this command, e.g. "backup restore"
+        command: Vec<String>,
+    },
+    /// Print the names of all profiles, one per line (used by completion scripts)
+    #[clap(name = "__profile-names", hide = true)]
+    ProfileNames,
+    /// Mount a profile's backups as a read-only filesystem
+    #[clap(
+        after_long_help = "The mount stays active until the command is interrupted.\n\nExamples:\n  savefile mount --name my-game --path /mnt/my-game"
+    )]
+    #[cfg(feature = "mount")]
+    Mount {
+        /// Name of the profile to mount
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Directory to mount the backups at
+        #[clap(short, long)]
+        path: PathBuf,
+    },
+}
+
+/// "profile" subcommand
+#[derive(clap::Subcommand)]
+pub enum ProfileCmd {
+    /// List all profiles
+    #[clap(
+        after_long_help = "With --health, also shows each profile's number of backups, the age of its\nlatest backup, the total size of its backups, and whether its base directory\nexists, e.g. to spot games that were uninstalled.\n\nExamples:\n  savefile profile list\n  savefile profile list --prefix my\n  savefile profile list --health"
+    )]
+    List {
+        /// Optional prefix to filter profiles by
+        #[clap(short, long)]
+        prefix: Option<String>,
+        /// Show backup statistics and whether the base directory exists
+        #[clap(long)]
+        health: bool,
+    },
+    /// Add a new profile
+    #[clap(
+        after_long_help = "Without --wizard, creates a profile without includes whose base directory\nis the current directory. With --wizard, asks for the base directory and\nthe files to include, and previews the matched files before saving.\n\nExamples:\n  savefile profile create --name my-game --wizard\n  savefile profile create --name my-game --edit"
+    )]
+    Create {
+        /// Name of the profile to add
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Open the profile in an editor after creating it
+        #[clap(short, long, default_value_t = false)]
+        edit: bool,
+        /// Ask for the base directory and files to include
+        #[clap(short, long, default_value_t = false)]
+        wizard: bool,
+    },
+    /// Remove a profile
+    #[clap(
+        after_long_help = "All backups of the profile are deleted as well.\n\nExamples:\n  savefile profile delete --name my-game"
+    )]
+    Delete {
+        /// Name of the profile to remove
+        #[clap(short, long)]
+        name: ProfileName,
+    },
+    /// Print a single field of a profile
+    #[clap(
+        after_long_help = "Strings are printed as they are, other values as JSON.\n\nExamples:\n  savefile profile get --name my-game base\n  savefile profile get --name my-game include"
+    )]
+    Get {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Name of the field
+        key: String,
+    },
+    /// Set a single field of a profile
+    #[clap(
+        after_long_help = "The value is parsed as JSON, or taken as a plain string if it isn't valid\nJSON. The profile is validated before it is saved.\n\nExamples:\n  savefile profile set --name my-game delay 10\n  savefile profile set --name my-game include '[\"saves/*.sav\"]'"
+    )]
+    Set {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Name of the field
+        key: String,
+        /// New value, as JSON or a plain string
+        value: String,
+    },
+    /// Point a profile at the new location of its base directory
+    #[clap(
+        after_long_help = "Use this when the base directory moved, e.g. because a drive letter changed\nor a Steam library was moved. \"savefile doctor\" suggests the new location\nwhen it can find one. The profile's includes must match files in the new\nbase, unless --force is given. Existing backups are kept.\n\nExamples:\n  savefile profile remap --name my-game --base \"E:/SteamLibrary/steamapps/common/My Game\"\n  savefile profile remap --name my-game --base /mnt/games/my-game --force"
+    )]
+    Remap {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// New base directory
+        #[clap(short, long)]
+        base: PathBuf,
+        /// Remap even if the includes match no files in the new base
+        #[clap(long)]
+        force: bool,
+    },
+    /// Add an include pattern to a profile
+    #[clap(
+        after_long_help = "The pattern is validated before the profile is saved, and the number of\nfiles it currently matches is reported. Prefix the pattern with \"re:\" for a\nregular expression.\n\nExamples:\n  savefile profile add-include --name my-game \"saves/**\"\n  savefile profile add-include --name my-game \"*.sav\" --max-size 100MB"
+    )]
+    AddInclude {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Glob pattern relative to the base directory
+        pattern: String,
+        /// Skip files larger than this, e.g. "500MB"
+        #[clap(long)]
+        max_size: Option<ByteSize>,
+        /// Match the pattern regardless of case
+        #[clap(long, default_value_t = false)]
+        case_insensitive: bool,
+        /// Skip paths reached through symbolic links
+        #[clap(long, default_value_t = false)]
+        no_follow_symlinks: bool,
+    },
+    /// Remove an include pattern from a profile
+    #[clap(
+        after_long_help = "Examples:\n  savefile profile remove-include --name my-game \"saves/**\""
+    )]
+    RemoveInclude {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Pattern to remove, exactly as it appears in the profile
+        pattern: String,
+    },
+    /// Browse profiles in a file manager
+    Browse,
+    /// Edit a profile in the default editor
+    #[clap(after_long_help = "Examples:\n  savefile profile edit --name my-game")]
+    Edit {
+        /// Name of the profile to edit
+        #[clap(short, long)]
+        name: ProfileName,
+    },
+}
+
+/// "backup" subcommand
+#[derive(clap::Subcommand)]
+pub enum BackupCmd {
+    /// Create a new backup
+    #[clap(
+        after_long_help = "Use --scheduled when running the command from a scheduler, such as cron or\nthe Windows Task Scheduler, so that the backup is listed as scheduled.\n\nExamples:\n  savefile backup create --name my-game\n  savefile backup create --name my-game --scheduled"
+    )]
+    Create {
+        /// Name of the profile to back up
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Record the backup as made by a scheduler instead of manually
+        #[clap(long)]
+        scheduled: bool,
+    },
+    /// Restore the given backup
+    #[clap(
+        after_long_help = "Lists the files that will be overwritten or created before asking for\nconfirmation. Files that are not part of the backup are left untouched.\n\nExamples:\n  savefile backup restore --name my-game\n  savefile backup restore --name my-game --id 42 --yes\n  savefile backup restore --name my-game --last-good\n  savefile backup restore --name my-game --id 42 --snapshot\n  savefile backup restore --name my-game --from-file E:/backups/42.zip"
+    )]
+    Restore {
+        /// Name of the profile containing the backup
+        #[clap(short, long)]
+        name: ProfileName,
+        // /// Restore by tag
+        // #[clap(short, long, conflicts_with = "latest")]
+        // tag: Option<String>,
+        /// ID or unique ID of the backup to restore (latest if omitted)
+        #[clap(short, long)]
+        id: Option<BackupRef>,
+        /// Restore the latest backup whose files passed the profile's validators
+        #[clap(long, conflicts_with = "id")]
+        last_good: bool,
+        /// Restore a zip archive of a backup without importing it
+        #[clap(long, value_name = "PATH", conflicts_with_all = ["id", "last_good"])]
+        from_file: Option<PathBuf>,
+        /// Back up the live files before restoring over them
+        #[clap(long)]
+        snapshot: bool,
+    },
+    /// Pack a backup into a zip archive to share it
+    #[clap(
+        after_long_help = "The archive holds the backup's files as they would be restored, e.g. to send\na save state to a friend, who can restore it with \"backup unpack\".\n\nExamples:\n  savefile backup pack --name my-game --out save.zip\n  savefile backup pack --name my-game --id 42 --out save.zip"
+    )]
+    Pack {
+        /// Name of the profile containing the backup
+        #[clap(short, long)]
+        name: ProfileName,
+        /// ID or unique ID of the backup to pack (latest if omitted)
+        #[clap(short, long)]
+        id: Option<BackupRef>,
+        /// Path of the zip archive to write
+        #[clap(short, long)]
+        out: PathBuf,
+    },
+    /// Restore the files of an archive written by "backup pack"
+    #[clap(
+        after_long_help = "Before anything is written, the archive is checked: no entry may point\noutside the profile's base directory, it must hold at least one file, and\nevery file must be matched by the profile's includes. This is the same as\n\"backup restore --from-file\".\n\nExamples:\n  savefile backup unpack --name my-game --file save.zip\n  savefile backup unpack --name my-game --file save.zip --snapshot"
+    )]
+    Unpack {
+        /// Name of the profile to restore the files to
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Path of the zip archive
+        #[clap(short, long)]
+        file: PathBuf,
+        /// Back up the live files before restoring over them
+        #[clap(long)]
+        snapshot: bool,
+    },
+    /// Undo the most recent restore
+    #[clap(
+        after_long_help = "Restores the snapshot of the live files taken by the most recent restore,\nwhich must have been run with --snapshot. The restore is found in the\naudit log (see \"savefile history\").\n\nExamples:\n  savefile backup undo-restore --name my-game\n  savefile backup undo-restore --name my-game --snapshot"
+    )]
+    UndoRestore {
+        /// Name of the profile whose restore to undo
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Back up the live files before restoring over them
+        #[clap(long)]
+        snapshot: bool,
+    },
+    /// Show the files contained in a backup
+    #[clap(
+        after_long_help = "Examples:\n  savefile backup show --name my-game\n  savefile backup show --name my-game --id 42"
+    )]
+    Show {
+        /// Name of the profile containing the backup
+        #[clap(short, long)]
+        name: ProfileName,
+        /// ID or unique ID of the backup to show (latest if omitted)
+        #[clap(short, long)]
+        id: Option<BackupRef>,
+    },
+    /// List all backups for the given profile
+    #[clap(
+        after_long_help = "With --format csv, the backups are printed as comma-separated values with the\ncolumns profile, id, ulid, timestamp, tag, size (in bytes), cause, changed,\nhost, os and savefile_version, e.g. for analysis in a spreadsheet. The host, os and\nsavefile version are only recorded for profiles with \"capture_env\" set.\n\nExamples:\n  savefile backup list --name my-game\n  savefile backup list --name my-game --count 5\n  savefile backup list --name my-game --cause watcher\n  savefile backup list --name my-game --host desktop\n  savefile backup list --name my-game --format csv > my-game.csv"
+    )]
+    List {
+        /// Name of the profile to list backups for
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Number of backups to list
+        #[clap(short, long)]
+        count: Option<usize>,
+        /// List only backups made for this reason: watcher, manual, scheduled,
+        /// pre-restore, pre-update or unknown
+        #[clap(long)]
+        cause: Option<BackupCause>,
+        /// List only backups made on this machine
+        #[clap(long)]
+        host: Option<String>,
+        /// Output format
+        #[clap(long, value_enum, default_value_t)]
+        format: Format,
+    },
+    /// Export the backups of all profiles as CSV
+    #[clap(
+        after_long_help = "Writes the same columns as \"backup list --format csv\", for the backups of\nevery profile.\n\nExamples:\n  savefile backup export-all\n  savefile backup export-all --output backups.csv"
+    )]
+    ExportAll {
+        /// File to write to instead of printing
+        #[clap(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Compare backups against the live files and a cloud copy of them
+    #[clap(
+        after_long_help = "Helps resolving save conflicts, e.g. when Steam Cloud asks whether to keep\nthe local files or download the cloud files. Shows which side was modified\nmore recently, and which backups match each side.\n\nThe cloud directory must have the same layout as the base directory. For\nSteam, this is usually <Steam>/userdata/<user id>/<app id>/remote.\n\nExamples:\n  savefile backup compare --name my-game --cloud ~/.steam/steam/userdata/123/456/remote"
+    )]
+    Compare {
+        /// Name of the profile to compare backups of
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Directory containing the cloud copy of the saves
+        #[clap(short, long)]
+        cloud: PathBuf,
+    },
+    /// Delete backups for the given profile
+    #[clap(
+        after_long_help = "Without --id, --ids or --before, all backups of the profile are deleted.\nWith both --ids and --before, only backups matching both are deleted.\n\nBackups copied to the profile's remotes are only deleted with --remote or\n--keep-remote. With --remote, the copies are listed along with a token, and\nrunning the command again with --token deletes them, then the local backups.\n\nExamples:\n  savefile backup delete --name my-game --id 42\n  savefile backup delete --name my-game --ids 3,5,9-12\n  savefile backup delete --name my-game --before 2024-05-01\n  savefile backup delete --name my-game\n  savefile backup delete --name my-game --id 42 --remote\n  savefile backup delete --name my-game --id 42 --remote --token 3f2a9c1e"
+    )]
+    Delete {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Delete only the backup with the given ID or unique ID
+        #[clap(short, long, default_value = None, conflicts_with = "ids")]
+        id: Option<BackupRef>,
+        /// Delete only the backups with these IDs, ID ranges and unique IDs, e.g. "3,5,9-12"
+        #[clap(long)]
+        ids: Option<IdRanges>,
+        /// Delete only the backups made before this date (e.g. "2024-05-01") or RFC 3339 time
+        #[clap(long)]
+        before: Option<Cutoff>,
+        /// Also delete the copies in the profile's remotes
+        #[clap(long, conflicts_with = "keep_remote")]
+        remote: bool,
+        /// Delete only the local backups, keeping remote copies
+        #[clap(long)]
+        keep_remote: bool,
+        /// Token printed by a previous --remote run, confirming the listed copies
+        #[clap(long, requires = "remote")]
+        token: Option<String>,
+    },
+    /// Check the remote copies of backups without downloading them
+    #[clap(
+        after_long_help = "Compares the files listed by each of the profile's remotes against the\nbackups' manifests, by size and by the hashes the remote keeps. Copies in\nstores without hashes are only compared by size.\n\nExamples:\n  savefile backup verify-remote --name my-game\n  savefile backup verify-remote --name my-game --id 42"
+    )]
+    VerifyRemote {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Check only the backup with the given ID or unique ID
+        #[clap(short, long)]
+        id: Option<BackupRef>,
+    },
+    /// Retain only the "count" latest backups
+    #[clap(after_long_help = "Examples:\n  savefile backup retain --name my-game --count 20")]
+    Retain {
+        #[clap(short, long)]
+        name: ProfileName,
+        #[clap(short, long)]
+        count: usize,
+    },
+    /// Thin out backups, keeping only every Nth one
+    #[clap(
+        after_long_help = "Backups are counted by their IDs, so that thinning again deletes nothing more:\nbackups whose ID is a multiple of N are kept, as is the most recent one. With\n--after or --before, only backups within that window are thinned, and the most\nrecent backup in the window is kept.\n\nExamples:\n  savefile backup thin --name my-game --keep-every 5\n  savefile backup thin --name my-game --keep-every 10 --before 2024-05-01\n  savefile backup thin --name my-game --keep-every 3 --after 2024-04-01 --before 2024-05-01"
+    )]
+    Thin {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Keep one backup out of this many
+        #[clap(short, long)]
+        keep_every: NonZeroUsize,
+        /// Only thin backups made at or after this date (e.g. "2024-05-01") or RFC 3339 time
+        #[clap(long)]
+        after: Option<Cutoff>,
+        /// Only thin backups made before this date (e.g. "2024-05-01") or RFC 3339 time
+        #[clap(long)]
+        before: Option<Cutoff>,
+    },
+}
+
+/// "shell-integration" subcommand
+#[derive(clap::Subcommand)]
+pub enum ShellIntegrationCmd {
+    /// Add context-menu entries for all profiles
+    Install,
+    /// Remove all context-menu entries
+    Uninstall,
+}
+
+/// "config" subcommand
+#[derive(clap::Subcommand)]
+pub enum ConfigCmd {
+    /// Print the current configuration
+    #[clap(after_long_help = "Examples:\n  savefile config show")]
+    Show,
+    /// Set a configuration value
+    #[clap(after_long_help = "Examples:\n  savefile config set per_profile_database true")]
+    Set {
+        /// Name of the setting
+        key: String,
+        /// New value, as JSON or a plain string
+        value: String,
+    },
+}
+
+/// "watch" subcommand
+#[derive(clap::Subcommand)]
+pub enum WatchCmd {
+    /// Pause a profile's automatic backups
+    #[clap(
+        after_long_help = "Changes are ignored until the profile is resumed, e.g. while restoring a\nbackup or editing the save folder by hand. The watcher keeps running, and the\npause also applies to watchers started later.\n\nExamples:\n  savefile watch pause --name my-game"
+    )]
+    Pause {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+    },
+    /// Resume a profile's automatic backups
+    #[clap(
+        after_long_help = "Changes made while paused don't cause a backup; the next change does.\n\nExamples:\n  savefile watch resume --name my-game"
+    )]
+    Resume {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+    },
+}
+
+/// "events" subcommand
+#[derive(clap::Subcommand)]
+pub enum EventsCmd {
+    /// Print the latest events of a profile, then new ones as they happen
+    #[clap(
+        after_long_help = "Prints the events as they are logged, one JSON object per line, until\ninterrupted with Ctrl+C.\n\nExamples:\n  savefile events tail --name my-game\n  savefile events tail --name my-game --lines 0 | jq .event"
+    )]
+    Tail {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Number of past events to print first
+        #[clap(short, long, default_value_t = 10)]
+        lines: usize,
+    },
+}
+
+/// "file" subcommand
+#[derive(clap::Subcommand)]
+pub enum FileCmd {
+    /// List the backups in which a file changed
+    #[clap(
+        after_long_help = "Lists each backup holding a version of the file that differs from the previous\nbackup holding it, compared by hash. The Live column marks the version that\nmatches the live file. Paths are relative to the profile's base directory.\n\nExamples:\n  savefile file history --name my-game --path saves/slot1.sav"
+    )]
+    History {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Path of the file, relative to the base directory
+        #[clap(short, long)]
+        path: PathBuf,
+    },
+    /// Restore a single file from a backup
+    #[clap(
+        after_long_help = "Only the given file is restored, other live files are left untouched. Asks\nfor confirmation before overwriting the live file, like \"backup restore\".\n\nExamples:\n  savefile file restore --name my-game --path saves/slot1.sav --id 42\n  savefile file restore --name my-game --path saves/slot1.sav --id 42 --snapshot"
+    )]
+    Restore {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Path of the file, relative to the base directory
+        #[clap(short, long)]
+        path: PathBuf,
+        /// ID or unique ID of the backup to restore the file from
+        #[clap(short, long)]
+        id: BackupRef,
+        /// Back up the live files before restoring over them
+        #[clap(long)]
+        snapshot: bool,
+    },
+}
+
+/// "coop" subcommand
+#[derive(clap::Subcommand)]
+pub enum CoopCmd {
+    /// Show who holds the turn
+    #[clap(after_long_help = "Examples:\n  savefile coop status --name my-game")]
+    Status {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+    },
+    /// Take the turn and restore the shared save
+    #[clap(
+        after_long_help = "Fails while another player holds the turn. Once taken, the files that the\nshared save will overwrite or create are listed before asking for\nconfirmation, like \"backup restore\".\n\nExamples:\n  savefile coop take --name my-game\n  savefile coop take --name my-game --snapshot\n  savefile coop take --name my-game --steal"
+    )]
+    Take {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Take the turn even if another player holds it
+        #[clap(long)]
+        steal: bool,
+        /// Back up the live files before restoring over them
+        #[clap(long)]
+        snapshot: bool,
+    },
+    /// Share the live files and pass the turn on
+    #[clap(
+        after_long_help = "Replaces the shared save with the profile's included files, then releases\nthe turn so that the next player can take it.\n\nExamples:\n  savefile coop pass --name my-game\n  savefile coop pass --name my-game --no-push"
+    )]
+    Pass {
+        /// Name of the profile
+        #[clap(short, long)]
+        name: ProfileName,
+        /// Release the turn without sharing the live files
+        #[clap(long)]
+        no_push: bool,
+    },
+}
+
+/// "secret" subcommand
+#[derive(clap::Subcommand)]
+pub enum SecretCmd {
+    /// Store a secret, replacing any previous value
+    #[clap(
+        after_long_help = "The value is asked for without echoing it, or read from the first line of\nstdin if it is not a terminal.\n\nExamples:\n  savefile secret set smtp-password\n  echo \"$PASSWORD\" | savefile secret set sftp:steam@example.com"
+    )]
+    Set {
+        /// Name of the secret
+        name: String,
+    },
+    /// Delete a secret
+    #[clap(after_long_help = "Examples:\n  savefile secret delete smtp-password")]
+    Delete {
+        /// Name of the secret
+        name: String,
+    },
+}
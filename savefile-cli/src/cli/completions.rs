@@ -0,0 +1,30 @@
+use clap_complete::Shell;
+
+/// Bash: complete the value of `--name` with profile names, and defer to the
+/// generated completion for everything else.
+const BASH: &str = r#"
+_savefile_profiles() {
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "--name" || "$prev" == "-n" ]]; then
+        COMPREPLY=( $(compgen -W "$(savefile __profile-names 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}") )
+        return 0
+    fi
+    _savefile "$@"
+}
+complete -F _savefile_profiles -o bashdefault -o default savefile
+"#;
+
+/// Fish: add profile names as candidates for `--name`.
+const FISH: &str = r#"
+complete -c savefile -s n -l name -f -a "(savefile __profile-names 2>/dev/null)"
+"#;
+
+/// Returns a snippet to append to the generated script so that `--name`
+/// completes to existing profile names, if supported for the shell.
+pub fn profile_name_hook(shell: Shell) -> Option<&'static str> {
+    match shell {
+        Shell::Bash => Some(BASH),
+        Shell::Fish => Some(FISH),
+        _ => None,
+    }
+}
@@ -0,0 +1,654 @@
+use std::{
+    fmt::{self, Display},
+    io::IsTerminal,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use chrono::{SecondsFormat, Utc};
+use savefile::{
+    archive,
+    audit::AuditEntry,
+    cold,
+    diff::BackupMatch,
+    file_history::FileVersion,
+    lock::{BackupOutcome, WatcherState},
+    search::Match,
+    size::ByteSize,
+    store::{DeleteOutcome, RemoteCopy},
+    Backup, BackupCause, BackupId, BackupStatus, FileRecord, ProfileName, Timestamp,
+};
+use tabled::{builder::Builder, settings::Style};
+
+use super::util::path_str;
+
+/// Whether output may be colored, see [`set_color`].
+static COLOR: AtomicBool = AtomicBool::new(true);
+
+/// Enable or disable colored output, e.g. for `--no-color`.
+///
+/// Even when enabled, output is only colored when written to a terminal, and
+/// never if the `NO_COLOR` environment variable is set to a non-empty value.
+pub fn set_color(enabled: bool) {
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    COLOR.store(enabled && !no_color, Ordering::Relaxed);
+}
+
+/// The kinds of highlighted text, each shown in its own color.
+#[derive(Clone, Copy, Debug)]
+pub enum Tone {
+    /// Something that succeeded.
+    Success,
+    /// Something that needs attention.
+    Warn,
+    /// Something that failed.
+    Error,
+    /// Something to find at a glance, such as the latest backup.
+    Highlight,
+}
+
+impl Tone {
+    /// Returns the ANSI style of the tone.
+    fn code(self) -> &'static str {
+        match self {
+            Tone::Success => "32",
+            Tone::Warn => "33",
+            Tone::Error => "31",
+            Tone::Highlight => "1;36",
+        }
+    }
+}
+
+/// Returns `text` in the color of `tone`, if output to stdout is colored.
+pub fn paint(tone: Tone, text: impl Display) -> String {
+    paint_if(std::io::stdout().is_terminal(), tone, text)
+}
+
+/// Returns `text` in the color of `tone`, if output to stderr is colored.
+pub fn paint_err(tone: Tone, text: impl Display) -> String {
+    paint_if(std::io::stderr().is_terminal(), tone, text)
+}
+
+/// Returns `text` in the color of `tone` if `terminal` is set and colors are enabled.
+fn paint_if(terminal: bool, tone: Tone, text: impl Display) -> String {
+    if terminal && COLOR.load(Ordering::Relaxed) {
+        format!("\x1b[{}m{}\x1b[0m", tone.code(), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// A list of backups.
+///
+/// Primarily used for displaying backups in a table.
+pub struct BackupList<'a> {
+    profile_name: &'a ProfileName,
+    backups: Vec<Backup>,
+    /// The profile's latest backup, which is highlighted if listed.
+    latest: Option<BackupId>,
+}
+
+impl<'a> BackupList<'a> {
+    pub fn new(
+        profile_name: &'a ProfileName,
+        backups: Vec<Backup>,
+        latest: Option<BackupId>,
+    ) -> Self {
+        Self {
+            profile_name,
+            backups,
+            latest,
+        }
+    }
+}
+
+impl Records for BackupList<'_> {
+    fn header(&self) -> Vec<String> {
+        vec![
+            t!("header-id"),
+            t!("header-timestamp"),
+            t!("header-version"),
+            t!("header-tag"),
+            t!("header-cause"),
+            t!("header-host"),
+            t!("header-changed"),
+            t!("header-valid"),
+            t!("header-path"),
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.backups
+            .iter()
+            .map(|backup| {
+                let path = match (
+                    cold::store(self.profile_name, backup),
+                    archive::local_path(self.profile_name, backup),
+                ) {
+                    (Ok(Some(store)), _) => t!("value-cold", store = store.describe()),
+                    (Ok(None), Ok(path)) => path_str(&path),
+                    _ => t!("value-invalid"),
+                };
+                let id = match Some(backup.id()) == self.latest {
+                    true => paint(Tone::Highlight, backup.id()),
+                    false => backup.id().to_string(),
+                };
+                vec![
+                    id,
+                    backup.timestamp().to_string(),
+                    backup.version().unwrap_or_default().to_owned(),
+                    backup.tag().unwrap_or_default().to_owned(),
+                    match backup.cause() {
+                        BackupCause::Unknown => String::new(),
+                        cause => cause.to_string(),
+                    },
+                    backup
+                        .environment()
+                        .map(|env| env.host.clone())
+                        .unwrap_or_default(),
+                    backup
+                        .changed_files()
+                        .map(|count| count.to_string())
+                        .unwrap_or_default(),
+                    match backup.status() {
+                        BackupStatus::Valid => paint(Tone::Success, t!("value-yes")),
+                        BackupStatus::Unchecked => String::new(),
+                    },
+                    path,
+                ]
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for BackupList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_table())
+    }
+}
+
+/// A backup with the fields exported for spreadsheets.
+pub struct BackupRecord {
+    pub profile: ProfileName,
+    pub backup: Backup,
+    /// Total size of the backup's files.
+    pub size: u64,
+}
+
+/// Backups of one or more profiles, primarily exported as CSV.
+///
+/// Unlike [`BackupList`], fields are plain values in fixed units and the
+/// header isn't translated, so that the output is easy to process.
+pub struct BackupRecords(pub Vec<BackupRecord>);
+
+impl Records for BackupRecords {
+    fn header(&self) -> Vec<String> {
+        [
+            "profile",
+            "id",
+            "ulid",
+            "timestamp",
+            "tag",
+            "size",
+            "cause",
+            "changed",
+            "host",
+            "os",
+            "savefile_version",
+        ]
+        .map(str::to_owned)
+        .to_vec()
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.0
+            .iter()
+            .map(|record| {
+                let backup = &record.backup;
+                let env = backup.environment();
+                vec![
+                    record.profile.to_string(),
+                    backup.id().to_string(),
+                    backup.uid().map(|uid| uid.to_string()).unwrap_or_default(),
+                    backup
+                        .timestamp()
+                        .to_rfc3339_opts(SecondsFormat::Secs, true),
+                    backup.tag().unwrap_or_default().to_owned(),
+                    record.size.to_string(),
+                    backup.cause().to_string(),
+                    backup
+                        .changed_files()
+                        .map(|count| count.to_string())
+                        .unwrap_or_default(),
+                    env.map(|env| env.host.clone()).unwrap_or_default(),
+                    env.map(|env| env.os.clone()).unwrap_or_default(),
+                    env.map(|env| env.savefile_version.clone())
+                        .unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+}
+
+/// How each backup matches the live files and a cloud copy.
+pub struct CompareList(pub Vec<BackupMatch>);
+
+impl fmt::Display for CompareList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-id"),
+            t!("header-timestamp"),
+            t!("header-live"),
+            t!("header-cloud"),
+        ]);
+        let matched = |count: usize, files: usize| {
+            if files > 0 && count == files {
+                paint(Tone::Success, t!("value-match"))
+            } else if count > 0 {
+                format!("{}/{}", count, files)
+            } else {
+                String::new()
+            }
+        };
+        self.0.iter().for_each(|m| {
+            table.push_record(vec![
+                m.backup.id().to_string(),
+                m.backup.timestamp().to_string(),
+                matched(m.live, m.files),
+                matched(m.other, m.files),
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// A list of files contained in a backup.
+///
+/// Primarily used for displaying a backup's manifest in a table.
+pub struct FileList(pub Vec<FileRecord>);
+
+impl fmt::Display for FileList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-path"),
+            t!("header-size"),
+            t!("header-changed"),
+            t!("header-triggered"),
+        ]);
+        let flag = |b: bool| if b { t!("value-yes") } else { String::new() };
+        self.0.iter().for_each(|file| {
+            table.push_record(vec![
+                path_str(&file.path),
+                file.size.to_string(),
+                flag(file.changed),
+                flag(file.triggered),
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// Versions of a single file, each with the first backup holding it.
+pub struct FileHistoryList {
+    versions: Vec<FileVersion>,
+    /// Hash of the live file, whose version is marked, if it exists.
+    live_hash: Option<String>,
+}
+
+impl FileHistoryList {
+    pub fn new(versions: Vec<FileVersion>, live_hash: Option<String>) -> Self {
+        Self {
+            versions,
+            live_hash,
+        }
+    }
+}
+
+impl fmt::Display for FileHistoryList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-id"),
+            t!("header-timestamp"),
+            t!("header-size"),
+            t!("header-hash"),
+            t!("header-live"),
+        ]);
+        self.versions.iter().for_each(|version| {
+            let live = match self.live_hash.as_ref() == Some(&version.file.hash) {
+                true => paint(Tone::Success, t!("value-yes")),
+                false => String::new(),
+            };
+            table.push_record(vec![
+                version.backup.id().to_string(),
+                version.backup.timestamp().to_string(),
+                ByteSize(version.file.size).to_string(),
+                version.file.hash.chars().take(12).collect(),
+                live,
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// Files in backups containing searched text.
+pub struct SearchList(pub Vec<Match>);
+
+impl fmt::Display for SearchList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-id"),
+            t!("header-timestamp"),
+            t!("header-path"),
+            t!("header-match"),
+        ]);
+        self.0.iter().for_each(|found| {
+            table.push_record(vec![
+                found.backup.id().to_string(),
+                found.backup.timestamp().to_string(),
+                path_str(&found.path),
+                found.excerpt.clone(),
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// Copies of backups in remote stores, which are about to be deleted.
+pub struct RemoteCopyList(pub Vec<RemoteCopy>);
+
+impl fmt::Display for RemoteCopyList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-id"),
+            t!("header-timestamp"),
+            t!("header-store"),
+        ]);
+        self.0.iter().for_each(|copy| {
+            table.push_record(vec![
+                copy.backup.id().to_string(),
+                copy.backup.timestamp().to_string(),
+                copy.store.clone(),
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// What became of each remote copy that was to be deleted.
+pub struct DeleteReport(pub Vec<(RemoteCopy, DeleteOutcome)>);
+
+impl fmt::Display for DeleteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-id"),
+            t!("header-store"),
+            t!("header-result"),
+        ]);
+        self.0.iter().for_each(|(copy, outcome)| {
+            table.push_record(vec![
+                copy.backup.id().to_string(),
+                copy.store.clone(),
+                match outcome {
+                    DeleteOutcome::Deleted => paint(Tone::Success, t!("remote-deleted")),
+                    DeleteOutcome::StillPresent => paint(Tone::Warn, t!("remote-still-present")),
+                    DeleteOutcome::Failed(e) => {
+                        paint(Tone::Error, t!("outcome-failed", error = e.to_string()))
+                    }
+                },
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// A list of profiles.
+///
+/// Primarily used for displaying profiles in a table.
+pub struct ProfileList(pub Vec<PathBuf>);
+
+impl fmt::Display for ProfileList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![t!("header-name"), t!("header-path")]);
+        self.0.iter().for_each(|path| {
+            table.push_record(vec![
+                path.file_stem().unwrap().to_str().unwrap().to_owned(),
+                path.display().to_string().replace("\\", "/"),
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// Backup statistics of a single profile.
+pub struct ProfileHealth {
+    pub name: ProfileName,
+    /// Number of backups.
+    pub backups: usize,
+    /// Time of the most recent backup, if any.
+    pub latest: Option<Timestamp>,
+    /// Total size of the files in all backups.
+    pub size: ByteSize,
+    /// Whether the base directory exists, or `None` if it can't be checked.
+    pub base_exists: Option<bool>,
+}
+
+/// A list of profiles with their backup statistics.
+///
+/// Primarily used for displaying the output of "profile list --health".
+pub struct ProfileHealthList(pub Vec<ProfileHealth>);
+
+impl fmt::Display for ProfileHealthList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-name"),
+            t!("header-backups"),
+            t!("header-last-backup"),
+            t!("header-size"),
+            t!("header-base"),
+        ]);
+        let now = Utc::now();
+        self.0.iter().for_each(|health| {
+            let base = match health.base_exists {
+                Some(true) => paint(Tone::Success, t!("value-base-found")),
+                Some(false) => paint(Tone::Warn, t!("value-base-missing")),
+                None => "-".to_owned(),
+            };
+            table.push_record(vec![
+                health.name.to_string(),
+                health.backups.to_string(),
+                health
+                    .latest
+                    .map(|latest| age_str(now - latest))
+                    .unwrap_or_else(|| t!("value-never")),
+                health.size.to_string(),
+                base,
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// How listings are written to the output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A table for reading in the terminal.
+    #[default]
+    Table,
+    /// Comma-separated values, e.g. for spreadsheets.
+    Csv,
+}
+
+/// Output made of a header and rows of fields, which can be written in any [`Format`].
+pub trait Records {
+    /// Returns the name of each column.
+    fn header(&self) -> Vec<String>;
+
+    /// Returns the fields of each row.
+    fn rows(&self) -> Vec<Vec<String>>;
+
+    /// Format the records as a table.
+    fn to_table(&self) -> String {
+        let mut table = Builder::new();
+        table.set_header(self.header());
+        for row in self.rows() {
+            table.push_record(row);
+        }
+        table.build().with(Style::ascii_rounded()).to_string()
+    }
+
+    /// Format the records as CSV, with a header line and a trailing newline.
+    fn to_csv(&self) -> String {
+        std::iter::once(self.header())
+            .chain(self.rows())
+            .map(|row| {
+                let fields = row.iter().map(|field| csv_field(field)).collect::<Vec<_>>();
+                format!("{}\n", fields.join(","))
+            })
+            .collect()
+    }
+}
+
+/// Quote a CSV field if it contains a separator, quote or line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Returns a short description of an age, e.g. "3d ago".
+fn age_str(age: chrono::Duration) -> String {
+    if age.num_days() > 0 {
+        t!("value-age-days", count = age.num_days())
+    } else if age.num_hours() > 0 {
+        t!("value-age-hours", count = age.num_hours())
+    } else if age.num_minutes() > 0 {
+        t!("value-age-minutes", count = age.num_minutes())
+    } else {
+        t!("value-age-now")
+    }
+}
+
+/// Status of a single profile.
+pub struct ProfileStatus {
+    pub name: ProfileName,
+    /// State of the running watcher, if any.
+    pub watcher: Option<WatcherState>,
+    /// Most recent backup in the database, if any.
+    pub latest: Option<Backup>,
+}
+
+/// A list of profile statuses.
+///
+/// Primarily used for displaying the output of the "status" command.
+pub struct StatusList(pub Vec<ProfileStatus>);
+
+impl fmt::Display for StatusList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-profile"),
+            t!("header-watcher"),
+            t!("header-last-backup"),
+            t!("header-result"),
+            t!("header-next-backup"),
+        ]);
+        self.0.iter().for_each(|status| {
+            let watcher = match &status.watcher {
+                Some(state) if state.unavailable => {
+                    paint(Tone::Warn, t!("watcher-unavailable", pid = state.pid))
+                }
+                Some(state) if state.paused => {
+                    paint(Tone::Warn, t!("watcher-paused", pid = state.pid))
+                }
+                Some(state) => paint(Tone::Success, t!("watcher-running", pid = state.pid)),
+                None => t!("watcher-stopped"),
+            };
+            let last = status.watcher.as_ref().and_then(|s| s.last_backup.as_ref());
+            let (last_time, result) = match (last, &status.latest) {
+                (Some(outcome), _) => (outcome.time.to_string(), outcome_str(outcome)),
+                (None, Some(backup)) => (
+                    backup.timestamp().to_string(),
+                    paint(Tone::Success, t!("outcome-ok-id", id = backup.id().get())),
+                ),
+                (None, None) => (t!("value-never"), "-".to_owned()),
+            };
+            let next = status
+                .watcher
+                .as_ref()
+                .and_then(|s| s.next_backup)
+                .map(|t| t.to_string())
+                .unwrap_or_else(|| "-".to_owned());
+            table.push_record(vec![
+                status.name.to_string(),
+                watcher,
+                last_time,
+                result,
+                next,
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
+
+/// Returns a short description of a backup outcome.
+fn outcome_str(outcome: &BackupOutcome) -> String {
+    match (&outcome.id, &outcome.error) {
+        (_, Some(error)) => paint(Tone::Error, t!("outcome-failed", error = error.as_str())),
+        (Some(id), None) => paint(Tone::Success, t!("outcome-ok-id", id = id.get())),
+        (None, None) => paint(Tone::Success, t!("outcome-ok")),
+    }
+}
+
+/// A list of audit log entries.
+///
+/// Primarily used for displaying the output of the "history" command.
+pub struct HistoryList(pub Vec<AuditEntry>);
+
+impl fmt::Display for HistoryList {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut table = Builder::new();
+        table.set_header(vec![
+            t!("header-time"),
+            t!("header-profile"),
+            t!("header-operation"),
+            t!("header-actor"),
+            t!("header-backups"),
+            t!("header-result"),
+        ]);
+        self.0.iter().for_each(|entry| {
+            let backups = entry
+                .backups
+                .iter()
+                .map(|id| id.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let operation = match entry.cause {
+                Some(cause) => format!("{} ({})", entry.operation, cause),
+                None => entry.operation.to_string(),
+            };
+            let result = match &entry.error {
+                Some(error) => paint(Tone::Error, t!("outcome-failed", error = error.as_str())),
+                None => paint(Tone::Success, t!("outcome-ok")),
+            };
+            table.push_record(vec![
+                entry.time.to_string(),
+                entry.profile.clone(),
+                operation,
+                entry.actor.to_string(),
+                backups,
+                result,
+            ]);
+        });
+        write!(f, "{}", table.build().with(Style::ascii_rounded()))
+    }
+}
@@ -0,0 +1,103 @@
+/// Localization of CLI output.
+///
+/// Messages are looked up in [Fluent](https://projectfluent.org) catalogs
+/// embedded in the binary. The language is taken from `SAVEFILE_LANG`, the
+/// usual locale variables, or the system locale, in that order. Messages
+/// missing from a translation fall back to English.
+///
+/// To add a translation, copy `i18n/en.ftl` to `i18n/<language>.ftl`,
+/// translate the messages and add the file to [`CATALOGS`].
+use fluent_bundle::{FluentArgs, FluentBundle, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+/// Embedded catalogs by language. English must come first.
+const CATALOGS: &[(&str, &str)] = &[("en", include_str!("i18n/en.ftl"))];
+
+/// Look up a localized message, formatting it with optional named arguments.
+///
+/// Prefer the [`t!`] macro over calling this directly.
+macro_rules! t {
+    ($id:literal) => {
+        crate::cli::i18n::tr($id, None)
+    };
+    ($id:literal, $($name:ident = $value:expr),+ $(,)?) => {{
+        let mut args = fluent_bundle::FluentArgs::new();
+        $(args.set(stringify!($name), $value);)+
+        crate::cli::i18n::tr($id, Some(&args))
+    }};
+}
+
+thread_local! {
+    /// Bundles to look messages up in, in order of preference.
+    static BUNDLES: Vec<FluentBundle<FluentResource>> = load_bundles();
+}
+
+/// Returns the message with the given ID in the user's language.
+///
+/// Returns the ID itself if no catalog contains the message.
+pub fn tr(id: &str, args: Option<&FluentArgs>) -> String {
+    BUNDLES.with(|bundles| {
+        bundles
+            .iter()
+            .find_map(|bundle| {
+                let pattern = bundle.get_message(id)?.value()?;
+                let mut errors = vec![];
+                Some(
+                    bundle
+                        .format_pattern(pattern, args, &mut errors)
+                        .into_owned(),
+                )
+            })
+            .unwrap_or_else(|| id.to_owned())
+    })
+}
+
+/// Create the bundles for the user's language, followed by English.
+fn load_bundles() -> Vec<FluentBundle<FluentResource>> {
+    let (fallback, translations) = CATALOGS.split_first().expect("english catalog exists");
+    let mut bundles = vec![];
+    if let Some(requested) = requested_language() {
+        let primary = requested.split('-').next().unwrap_or_default();
+        let catalog = translations
+            .iter()
+            .find(|(lang, _)| lang.eq_ignore_ascii_case(&requested))
+            .or_else(|| {
+                translations
+                    .iter()
+                    .find(|(lang, _)| lang.eq_ignore_ascii_case(primary))
+            });
+        if let Some((lang, source)) = catalog {
+            bundles.push(bundle(lang, source));
+        }
+    }
+    bundles.push(bundle(fallback.0, fallback.1));
+    bundles
+}
+
+/// Create a bundle from an embedded catalog.
+fn bundle(lang: &str, source: &str) -> FluentBundle<FluentResource> {
+    let lang: LanguageIdentifier = lang.parse().expect("catalog language is valid");
+    let resource = FluentResource::try_new(source.to_owned()).expect("catalog is valid fluent");
+    let mut bundle = FluentBundle::new(vec![lang]);
+    // isolation marks show up as garbage in most terminals
+    bundle.set_use_isolating(false);
+    bundle
+        .add_resource(resource)
+        .expect("catalog has no duplicate messages");
+    bundle
+}
+
+/// Returns the language requested by the user, e.g. `de-DE`.
+fn requested_language() -> Option<String> {
+    ["SAVEFILE_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .or_else(sys_locale::get_locale)
+        .map(|locale| {
+            // "de_DE.UTF-8" -> "de-DE"
+            let locale = locale.split(['.', '@']).next().unwrap_or_default();
+            locale.replace('_', "-")
+        })
+        .filter(|locale| !locale.is_empty() && locale != "C" && locale != "POSIX")
+}
@@ -0,0 +1,171 @@
+# English messages for the savefile CLI.
+#
+# This catalog is the fallback for every other language, so it must contain
+# every message used by the CLI.
+
+## Prompts
+
+prompt-delete-profile = Removing a profile will remove all its backups. Continue?
+prompt-overwrite = This will overwrite your current files. Continue?
+prompt-delete-backups = This will delete the backup(s) permanently. Continue?
+prompt-retain = Delete all but the { $count } most recent backup(s)?
+prompt-overwrite-file = { $path } already exists. Overwrite it?
+prompt-thin = Delete { $count } backup(s), keeping one in every { $every }?
+prompt-migrate = Apply { $count } change(s)?
+
+prompt-save-profile = Save this profile?
+prompt-secret = Value of { $name }
+prompt-type-name = { $question } Type "{ $name }" to confirm
+prompt-name-mismatch = The name doesn't match, nothing was changed
+prompt-delete-remote = Delete { $count } remote copies, then the local backup(s)?
+
+## Profile wizard
+
+wizard-base = Base directory (e.g. the game's save folder)
+wizard-base-invalid = Not an existing directory
+wizard-base-empty = The base directory is empty, add includes later with "profile add-include"
+wizard-includes = Files and folders to include (space to select, enter to confirm)
+wizard-matches = The profile currently matches { $count } file(s):
+wizard-more = ...and { $count } more
+wizard-cancelled = Profile not created
+
+## Restore preview
+
+restore-up-to-date = Current files already match backup { $id }, nothing to restore
+restore-file-up-to-date = { $path } already matches backup { $id }, nothing to restore
+search-indexed = Indexed { $count } distinct file content(s)
+search-no-matches = No backed up file contains "{ $query }"
+search-matches = { $count } file(s) in { $backups } backup(s)
+file-versions = { $count } version(s) of { $path }
+restore-archive-up-to-date = Current files already match { $path }, nothing to restore
+coop-turn-free = Nobody holds the turn of { $name }
+coop-turn-held = { $player } holds the turn of { $name } since { $since }
+coop-turn-taken = You hold the turn of { $name }
+coop-no-save = No save has been shared yet, so there is nothing to restore
+coop-up-to-date = Current files already match the shared save, nothing to restore
+coop-passed = Shared { $count } file(s) and passed the turn of { $name }
+coop-released = Passed the turn of { $name } without sharing the live files
+backup-packed = Packed backup { $id } into { $path }
+restore-overwritten = { $count } file(s) ({ $size }) will be overwritten:
+restore-created = { $count } file(s) will be created:
+restore-unchanged = { $count } file(s) are unchanged
+undo-restore = Undoing the restore of backup { $id } at { $time } by restoring snapshot { $snapshot }
+restore-version = Backup { $id } was made with version { $version }
+restore-version-changed = Backup { $id } was made with version { $version }, but the current version is { $current }
+
+## Cloud comparison
+
+compare-live-modified = Live files last modified: { $time }
+compare-cloud-modified = Cloud files last modified: { $time }
+compare-live-backup = The live files match backup { $id }
+compare-live-unsaved = No backup matches the live files
+compare-cloud-backup = The cloud files match backup { $id }
+compare-cloud-unsaved = No backup matches the cloud files, back them up before overwriting them
+
+## Table headers
+
+header-id = ID
+header-timestamp = Timestamp
+header-version = Version
+header-tag = Tag
+header-cause = Cause
+header-valid = Valid
+header-live = Live
+header-cloud = Cloud
+header-path = Path
+header-size = Size
+header-changed = Changed
+header-host = Host
+header-hash = Hash
+header-match = Match
+header-triggered = Triggered
+header-name = Name
+header-profile = Profile
+header-watcher = Watcher
+header-last-backup = Last Backup
+header-result = Result
+header-next-backup = Next Backup
+header-store = Store
+header-time = Time
+header-operation = Operation
+header-actor = Actor
+header-backups = Backups
+header-base = Base
+
+## Table values
+
+value-yes = yes
+value-invalid = (invalid)
+value-cold = cold storage ({ $store })
+value-never = never
+value-match = match
+value-no-files = no files
+value-base-found = found
+value-base-missing = missing
+value-age-now = just now
+value-age-minutes = { $count }m ago
+value-age-hours = { $count }h ago
+value-age-days = { $count }d ago
+watcher-running = running (pid { $pid })
+watcher-unavailable = paused, base directory unavailable (pid { $pid })
+watcher-stopped = stopped
+watcher-paused = paused (pid { $pid })
+outcome-ok = ok
+outcome-ok-id = ok (id { $id })
+outcome-failed = failed: { $error }
+remote-deleted = deleted
+remote-still-present = still present
+
+## Remote deletes
+
+retain-remote-kept = { $count } remote copies of these backups are kept in the profile's remotes
+delete-remote-token = Run the command again with --token { $token } to delete these copies and the local backup(s)
+
+## Remote verification
+
+verify-remote-none = The profile has no remotes
+verify-remote-summary = Checked { $checked } remote copies, { $problems } problem(s) found
+verify-remote-size-only = { $count } of them could only be compared by size, since their store keeps no hashes
+verify-remote-skipped = Skipped { $count } remote copies of backups without a manifest
+
+## Secrets
+
+secret-set = Stored secret { $name }
+secret-deleted = Deleted secret { $name }
+secret-missing = No secret named { $name }
+watch-paused = Paused automatic backups of { $name }
+watch-paused-later = Paused automatic backups of { $name } (no watcher is running)
+watch-resumed = Resumed automatic backups of { $name }
+watch-resumed-later = Resumed automatic backups of { $name } (no watcher is running)
+
+## History
+
+history-empty = No operations recorded
+
+## Explorer context menu
+
+menu-backup = Back up with savefile ({ $profile })
+menu-restore = Restore latest savefile backup ({ $profile })
+
+## Tray menu
+
+tray-watch = Watch
+tray-backup-now = Back up now
+tray-quit = Quit
+
+## Errors
+
+# $category is one of the error categories listed in the README's exit codes.
+error = { $category ->
+    [no-such-profile] Unknown profile
+    [no-backups] No backups
+    [io] File error
+    [database] Database error
+    [invalid-profile] Invalid profile
+    [invalid-config] Invalid configuration
+    [locked] Profile locked
+    [limit-exceeded] Size limit exceeded
+    [not-interactive] Confirmation required
+    [validation-failed] Validation failed
+   *[other] Error
+}: { $message }
@@ -0,0 +1,1523 @@
+use std::{
+    collections::HashSet,
+    io::IsTerminal,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+};
+
+use savefile::{
+    archive,
+    audit::{self, Actor},
+    config::Prompt,
+    control, coop,
+    database::relocate_metadata,
+    delete_all_backups, delete_backups,
+    diff::{self, diff_backup, FileDiff, FileStatus},
+    doctor,
+    error::{BackupError, IoResultExt, ProfileError, Result},
+    file_history,
+    filesystem::{
+        config_path, event_log_path, hash_file, profile_path, profile_save_dir_of, profiles_dir,
+    },
+    ipc::{self, Request, Response},
+    list_profile_names, list_profiles, lock, migrate, move_backups, prune_backups, remap, search,
+    selection::Selection,
+    size::ByteSize,
+    store::{self, DeleteOutcome},
+    Backup, BackupCause, BackupId, BackupRef, BackupStatus, Config, Database, Include, Profile,
+    ProfileName,
+};
+
+use crate::cli::{
+    display::{
+        paint, paint_err, BackupList, BackupRecord, BackupRecords, CompareList, DeleteReport,
+        FileHistoryList, FileList, Format, HistoryList, ProfileHealth, ProfileHealthList,
+        ProfileList, ProfileStatus, Records, RemoteCopyList, SearchList, StatusList, Tone,
+    },
+    util::path_str,
+};
+
+pub use self::{display::set_color, util::set_assume_yes};
+
+#[macro_use]
+mod i18n;
+pub mod args;
+mod completions;
+mod display;
+mod prompt;
+mod signal;
+#[cfg(feature = "tray")]
+mod tray;
+mod util;
+mod wizard;
+
+/// How often `events tail` checks the event log for new events.
+const EVENTS_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// Config key holding the database encryption key, which is never printed.
+const DATABASE_KEY: &str = "database_key";
+
+/// Shown in place of secret values.
+const REDACTED: &str = "***";
+
+/// Print a list of installed profiles.
+///
+/// If `prefix` is given, only profiles with names starting with `prefix` will be listed.
+/// With `health`, each profile's backup statistics are listed as well.
+pub fn print_profiles(prefix: Option<String>, health: bool) -> Result<()> {
+    let profiles = find_profile(prefix.as_deref())?;
+    if profiles.is_empty() {
+        println!("No profiles found");
+    } else if health {
+        let health = profiles
+            .iter()
+            .filter_map(|(path, profile)| {
+                let name = path.file_stem()?.to_str()?;
+                Some((ProfileName::new(name).ok()?, profile))
+            })
+            .map(|(name, profile)| profile_health(name, profile))
+            .collect::<Result<Vec<_>>>()?;
+        println!("{}", ProfileHealthList(health));
+    } else {
+        let paths = profiles.into_iter().map(|(path, _)| path).collect();
+        println!("{}", ProfileList(paths));
+    }
+    Ok(())
+}
+
+/// Gather the backup statistics of a profile and check its base directory.
+fn profile_health(name: ProfileName, profile: &Profile) -> Result<ProfileHealth> {
+    let db = Database::open_for_profile(&name)?;
+    let (backups, latest, size) = match db.row_count(&name)? {
+        Some(count) if count > 0 => {
+            let backup_table = db.backup_table(&name)?;
+            let latest = backup_table.latest()?.map(|backup| backup.timestamp());
+            (count, latest, ByteSize(backup_table.total_size()?))
+        }
+        _ => (0, None, ByteSize(0)),
+    };
+    // the base of an sftp profile can't be checked without connecting
+    let base_exists = match profile.remote() {
+        Some(_) => None,
+        None => Some(profile.base().is_dir()),
+    };
+    Ok(ProfileHealth {
+        name,
+        backups,
+        latest,
+        size,
+        base_exists,
+    })
+}
+
+/// Open the directory where profiles are stored using the default program.
+pub fn open_profiles_dir() -> Result<()> {
+    let dir = profiles_dir()?;
+    open::that(dir).expect("failed to open profiles directory");
+    Ok(())
+}
+
+/// Open the profile with the given name using the default program.
+pub fn edit_profile(name: &ProfileName) -> Result<()> {
+    let path = profile_path(name)?;
+    if !path.exists() {
+        Err(ProfileError::unknown_name(name))?;
+    }
+    open::that(path).expect("failed to open profile");
+    Ok(())
+}
+
+/// Create a new profile with the given name.
+///
+/// With `wizard`, the base directory and includes are asked for interactively.
+/// Otherwise the base directory is the current directory.
+pub fn create_profile(name: &ProfileName, edit: bool, wizard: bool) -> Result<()> {
+    let path = profile_path(name)?;
+    match Profile::open(&path) {
+        Ok(_) => Err(ProfileError::AlreadyExists)?,
+        Err(_) => {
+            let profile = if wizard {
+                match wizard::run()? {
+                    Some(profile) => profile,
+                    None => return Ok(()),
+                }
+            } else {
+                let base = std::env::current_dir()
+                    .context(|| "failed reading current directory".to_owned())?;
+                Profile::new(base)
+            };
+            profile.save(&path)?;
+            println!("created profile {} at {:?}", name, path);
+            warn_overlaps(name, &profile)?;
+            if edit {
+                open::that(path).expect("failed to open profile");
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Warn about other profiles that include the same files as `profile`.
+fn warn_overlaps(name: &ProfileName, profile: &Profile) -> Result<()> {
+    for overlap in doctor::overlapping_profiles(name, profile)? {
+        println!(
+            "warning: {} file(s) are also included by profile {}",
+            overlap.files, overlap.profile
+        );
+    }
+    Ok(())
+}
+
+/// Delete the profile with the given name.
+pub fn delete_profile(name: &ProfileName) -> Result<()> {
+    let profile_path = profile_path(name)?;
+    if !profile_path.exists() {
+        Err(ProfileError::unknown_name(name))?;
+    }
+    if prompt::confirm(
+        Prompt::DeleteProfile,
+        Some(name),
+        &t!("prompt-delete-profile"),
+    )? {
+        let db = Database::open_for_profile(name)?;
+        delete_all_backups(&db, name)?;
+        std::fs::remove_file(&profile_path).with_path("removing profile", &profile_path)?;
+    }
+    Ok(())
+}
+
+/// Add an include to a profile and report how many files it matches.
+pub fn add_include(name: &ProfileName, include: Include) -> Result<()> {
+    let mut profile = Profile::open_named(name)?;
+    let files = profile
+        .expand_include(&include, false)?
+        .into_iter()
+        .filter(|path| path.is_file())
+        .count();
+    let pattern = include.glob.clone();
+    if !profile.add_include(include)? {
+        println!("{} already includes {:?}", name, pattern);
+        return Ok(());
+    }
+    profile.save(profile_path(name)?)?;
+    println!("added {:?} to {}", pattern, name);
+    warn_overlaps(name, &profile)?;
+    if files == 0 {
+        println!(
+            "{}",
+            paint(
+                Tone::Warn,
+                "warning: the pattern doesn't match any files yet"
+            )
+        );
+    } else {
+        println!("the pattern currently matches {} file(s)", files);
+    }
+    Ok(())
+}
+
+/// Remove an include from a profile.
+pub fn remove_include(name: &ProfileName, pattern: &str) -> Result<()> {
+    let mut profile = Profile::open_named(name)?;
+    if !profile.remove_include(pattern) {
+        println!("{} doesn't include {:?}", name, pattern);
+        return Ok(());
+    }
+    profile.save(profile_path(name)?)?;
+    println!("removed {:?} from {}", pattern, name);
+    Ok(())
+}
+
+/// Print a single field of a profile.
+///
+/// Strings are printed as they are, other values as JSON.
+pub fn print_profile_field(name: &ProfileName, key: &str) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    match profile.field(key)? {
+        serde_json::Value::String(s) => println!("{}", s),
+        value => println!(
+            "{}",
+            serde_json::to_string_pretty(&value).expect("value is always serializable")
+        ),
+    }
+    Ok(())
+}
+
+/// Set a single field of a profile.
+///
+/// The profile is validated before it is saved.
+///
+/// Changing `storage_dir` moves the profile's backups to the new directory,
+/// while its watcher (if any) is paused. The profile is saved first, and if
+/// moving fails, the backups moved so far are moved back and the previous
+/// profile is restored.
+pub fn set_profile_field(name: &ProfileName, key: &str, value: &str) -> Result<()> {
+    let old = Profile::open_named(name)?;
+    let profile = old.with_field(key, value)?;
+    let from = profile_save_dir_of(name, &old)?;
+    let to = profile_save_dir_of(name, &profile)?;
+    // so that the watcher doesn't back up into the old directory meanwhile
+    let _paused = match from != to {
+        true => Some(control::pause_during(name)?),
+        false => None,
+    };
+    let path = profile_path(name)?;
+    profile.save(&path)?;
+    if from != to {
+        println!("moving backups of {} to {}", name, to.display());
+        match move_backups(&from, &to) {
+            Ok(moved) => println!("moved {} backup(s)", moved),
+            Err(e) => {
+                println!("moving backups of {} back to {}", name, from.display());
+                if let Err(e) = move_backups(&to, &from) {
+                    let msg = format!("failed moving backups of {} back: {}", name, e);
+                    println!("{}", paint(Tone::Warn, msg));
+                }
+                old.save(&path)?;
+                Err(e)?
+            }
+        }
+    }
+    println!("set {} = {}", key, value);
+    Ok(())
+}
+
+/// Point a profile at a new base directory and report how many files it matches.
+pub fn remap_profile(name: &ProfileName, base: &Path, force: bool) -> Result<()> {
+    let old = Profile::open_named(name)?;
+    let files = remap::remap_base(name, base, force)?;
+    println!(
+        "remapped {} from {} to {}",
+        name,
+        path_str(old.base()),
+        path_str(base)
+    );
+    println!("the includes match {} file(s) in the new base", files);
+    Ok(())
+}
+
+/// Find all profiles with names starting with `prefix`.
+///
+/// If `prefix` is `None`, all profiles will be returned.
+pub fn find_profile(prefix: Option<&str>) -> Result<Vec<(PathBuf, Profile)>> {
+    let mut profiles = list_profiles()?;
+    if let Some(prefix) = prefix {
+        let file_stem = |p: &Path| {
+            p.file_stem()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_owned())
+                .unwrap()
+        };
+        profiles.retain(|(path, _)| file_stem(path).starts_with(prefix));
+    }
+    Ok(profiles)
+}
+
+/// Immediately create a backup for the given profile.
+///
+/// If a watcher is running for the profile, it is asked to create the backup.
+/// The backup is recorded as made because of `cause`.
+pub fn create_backup(name: &ProfileName, cause: BackupCause) -> Result<()> {
+    if let Some(id) = control::request_backup(name, None, cause)? {
+        let msg = format!("running watcher created backup {} for profile {}", id, name);
+        println!("{}", paint(Tone::Success, msg));
+        return Ok(());
+    }
+    let db = Database::open_for_profile(name)?;
+    let profile = Profile::open_named(name)?;
+    let id = savefile::backup(&db, &profile, name, &[], None, cause)?;
+    let backup = db
+        .backup_table(name)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let msg = format!("created backup {} for profile {}", id, name);
+    println!("{}", paint(Tone::Success, msg));
+    println!(
+        "saved to {:?}",
+        path_str(archive::local_path(name, &backup)?)
+    );
+    Ok(())
+}
+
+/// Restore the given backup, or the latest backup if `id` is `None`.
+///
+/// With `last_good`, the latest backup whose files passed validation is restored.
+/// Lists the files that will be overwritten before prompting for confirmation.
+/// With `snapshot`, the live files are backed up first.
+pub fn restore_backup(
+    name: &ProfileName,
+    id: Option<BackupRef>,
+    last_good: bool,
+    snapshot: bool,
+) -> Result<()> {
+    let db = Database::open_for_profile(name)?;
+    let id = match id {
+        Some(backup) => db.backup_table(name)?.select_ref(backup)?.id(),
+        None if last_good => db
+            .backup_table(name)?
+            .latest_valid()?
+            .ok_or(BackupError::NoValidBackups)?
+            .id(),
+        None => db
+            .backup_table(name)?
+            .latest()?
+            .ok_or(BackupError::BackupsEmpty)?
+            .id(),
+    };
+    let profile = Profile::open_named(name)?;
+    print_version_change(&db, &profile, name, id)?;
+    let diffs = diff_backup(&db, &profile, name, id)?;
+    if !confirm_restore(name, &diffs, &t!("restore-up-to-date", id = id.get()))? {
+        return Ok(());
+    }
+    if snapshot {
+        create_backup(name, BackupCause::PreRestore)?;
+    }
+    savefile::restore_backup(&db, name, id)
+}
+
+/// List the backups holding each version of a file.
+pub fn print_file_history(name: &ProfileName, path: &Path) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    let path = file_history::relative_path(&profile, path)?;
+    let db = Database::open_for_profile(name)?;
+    let versions = file_history::file_history(&db, name, &path)?;
+    if versions.is_empty() {
+        Err(BackupError::FileNeverBackedUp(path.clone()))?
+    }
+    let live = profile.base().join(&path);
+    let live_hash = match live.is_file() {
+        true => Some(hash_file(&live)?),
+        false => None,
+    };
+    let count = versions.len();
+    println!("{}", FileHistoryList::new(versions, live_hash));
+    println!(
+        "{}",
+        t!("file-versions", count = count, path = path_str(&path))
+    );
+    Ok(())
+}
+
+/// Print the files in a profile's backups containing `query`.
+///
+/// With `reindex`, the content index is rebuilt from all backups first.
+pub fn search_backups(
+    name: &ProfileName,
+    query: &str,
+    ignore_case: bool,
+    reindex: bool,
+) -> Result<()> {
+    // open profile for validation only
+    let _ = Profile::open_named(name)?;
+    let db = Database::open_for_profile(name)?;
+    if reindex {
+        let indexed = search::rebuild_index(&db, name)?;
+        println!("{}", t!("search-indexed", count = indexed));
+    }
+    let matches = search::search(&db, name, query, ignore_case)?;
+    if matches.is_empty() {
+        println!("{}", t!("search-no-matches", query = query));
+        return Ok(());
+    }
+    let backups = matches
+        .iter()
+        .map(|found| found.backup.id())
+        .collect::<HashSet<_>>()
+        .len();
+    let count = matches.len();
+    println!("{}", SearchList(matches));
+    println!("{}", t!("search-matches", count = count, backups = backups));
+    Ok(())
+}
+
+/// Restore a single file from the given backup.
+///
+/// Like [`restore_backup`], but other live files are left untouched.
+pub fn restore_file(
+    name: &ProfileName,
+    path: &Path,
+    backup: BackupRef,
+    snapshot: bool,
+) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    let path = file_history::relative_path(&profile, path)?;
+    let db = Database::open_for_profile(name)?;
+    let id = db.backup_table(name)?.select_ref(backup)?.id();
+    let diffs = diff_backup(&db, &profile, name, id)?
+        .into_iter()
+        .filter(|diff| diff.path == path)
+        .collect::<Vec<_>>();
+    if diffs.is_empty() {
+        Err(BackupError::FileNotInBackup {
+            id,
+            path: path.clone(),
+        })?
+    }
+    let up_to_date = t!(
+        "restore-file-up-to-date",
+        path = path_str(&path),
+        id = id.get()
+    );
+    if !confirm_restore(name, &diffs, &up_to_date)? {
+        return Ok(());
+    }
+    if snapshot {
+        create_backup(name, BackupCause::PreRestore)?;
+    }
+    file_history::restore_file(&db, name, &path, id)
+}
+
+/// Restore the files of a backup archive without importing it.
+///
+/// The archive is checked against the profile's includes first, then
+/// restored like a backup, see [`restore_backup`].
+pub fn restore_archive(name: &ProfileName, path: &Path, snapshot: bool) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    let extracted = archive::open_archive(name, path)?;
+    let diffs = diff::diff_dir(&profile, extracted.dir())?;
+    let up_to_date = t!("restore-archive-up-to-date", path = path_str(path));
+    if !confirm_restore(name, &diffs, &up_to_date)? {
+        return Ok(());
+    }
+    if snapshot {
+        create_backup(name, BackupCause::PreRestore)?;
+    }
+    savefile::restore_archive(name, &extracted)
+}
+
+/// Write a backup into a zip archive at `out`, by default the latest backup.
+pub fn pack_backup(name: &ProfileName, id: Option<BackupRef>, out: &Path) -> Result<()> {
+    // open profile for validation only
+    let _ = Profile::open_named(name)?;
+    let db = Database::open_for_profile(name)?;
+    let backup_table = db.backup_table(name)?;
+    let backup = match id {
+        Some(backup) => backup_table.select_ref(backup)?,
+        None => backup_table.latest()?.ok_or(BackupError::BackupsEmpty)?,
+    };
+    if out.exists() && !util::confirm(&t!("prompt-overwrite-file", path = path_str(out)))? {
+        return Ok(());
+    }
+    archive::export_backup(name, &backup, out)?;
+    let msg = t!(
+        "backup-packed",
+        id = backup.id().get(),
+        path = path_str(out)
+    );
+    println!("{}", paint(Tone::Success, msg));
+    Ok(())
+}
+
+/// List the files a restore will overwrite or create, and ask for confirmation.
+///
+/// Returns `false` without asking if there is nothing to restore, in which
+/// case `up_to_date` is printed.
+fn confirm_restore(name: &ProfileName, diffs: &[FileDiff], up_to_date: &str) -> Result<bool> {
+    let modified = diffs
+        .iter()
+        .filter(|diff| diff.status == FileStatus::Modified)
+        .collect::<Vec<_>>();
+    let missing = diffs
+        .iter()
+        .filter(|diff| diff.status == FileStatus::Missing)
+        .collect::<Vec<_>>();
+    let unchanged = diffs.len() - modified.len() - missing.len();
+    if modified.is_empty() && missing.is_empty() {
+        println!("{}", up_to_date);
+        return Ok(false);
+    }
+    if !modified.is_empty() {
+        let size = modified.iter().map(|diff| diff.size).sum::<u64>();
+        let msg = t!(
+            "restore-overwritten",
+            count = modified.len(),
+            size = ByteSize(size).to_string(),
+        );
+        println!("{}", msg);
+        for diff in &modified {
+            println!("  {}", diff.path.display());
+        }
+    }
+    if !missing.is_empty() {
+        println!("{}", t!("restore-created", count = missing.len()));
+        for diff in &missing {
+            println!("  {}", diff.path.display());
+        }
+    }
+    if unchanged > 0 {
+        println!("{}", t!("restore-unchanged", count = unchanged));
+    }
+    prompt::confirm(Prompt::Restore, Some(name), &t!("prompt-overwrite"))
+}
+
+/// Undo the most recent restore by restoring the snapshot taken before it.
+///
+/// The restore and its snapshot are looked up in the audit log. With
+/// `snapshot`, the live files are backed up first, so the undo can be undone.
+pub fn undo_restore(name: &ProfileName, snapshot: bool) -> Result<()> {
+    // fails for unknown profiles
+    Profile::open_named(name)?;
+    let restore = audit::last_restore(name)?.ok_or(BackupError::NoRestoreToUndo)?;
+    let snapshot_id = restore
+        .snapshot
+        .ok_or(BackupError::NoRestoreSnapshot(restore.restored))?;
+    let msg = t!(
+        "undo-restore",
+        id = restore.restored.get(),
+        time = restore.time.to_string(),
+        snapshot = snapshot_id.get(),
+    );
+    println!("{}", msg);
+    restore_backup(name, Some(snapshot_id.into()), false, snapshot)
+}
+
+/// Print the version a backup was made with, and whether it differs from the current one.
+fn print_version_change(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    id: BackupId,
+) -> Result<()> {
+    let backup = db
+        .backup_table(name)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let Some(version) = backup.version() else {
+        return Ok(());
+    };
+    let current = profile
+        .version()
+        .and_then(|source| source.capture(profile.base()).ok().flatten());
+    match current {
+        Some(current) if current != version => println!(
+            "{}",
+            t!(
+                "restore-version-changed",
+                id = id.get(),
+                version = version,
+                current = current,
+            )
+        ),
+        _ => println!(
+            "{}",
+            t!("restore-version", id = id.get(), version = version)
+        ),
+    }
+    Ok(())
+}
+
+/// Delete the selected backups for the given profile.
+///
+/// First prompts the user for confirmation.
+///
+/// If the selection has no criteria, all backups for the given profile will be
+/// deleted. Otherwise, the matching backups are deleted in one batch.
+///
+/// Copies of the backups in the profile's remotes are deleted in two steps:
+/// with `remote` but no `token`, the copies are listed with the token that
+/// confirms them. With the token, the copies are deleted, and the local backups
+/// only if all copies are gone. With `keep_remote`, only local backups are deleted.
+pub fn delete_backup(
+    profile_name: &ProfileName,
+    selection: &Selection,
+    remote: bool,
+    keep_remote: bool,
+    token: Option<&str>,
+) -> Result<()> {
+    let profile = Profile::open_named(profile_name)?;
+    let db = Database::open_for_profile(profile_name)?;
+    let ids = selection.resolve(&db.backup_table(profile_name)?.select_all()?);
+    if ids.is_empty() && !selection.is_all() {
+        Err(BackupError::NoneSelected)?
+    }
+    let plan = store::plan_delete(&db, &profile, profile_name, &ids)?;
+    if !plan.is_empty() && !keep_remote {
+        if !remote {
+            let mut ids = plan
+                .copies
+                .iter()
+                .map(|copy| copy.backup.id())
+                .collect::<Vec<_>>();
+            ids.dedup();
+            Err(BackupError::RemoteCopies(ids))?
+        }
+        let Some(token) = token else {
+            println!("{}", RemoteCopyList(plan.copies.clone()));
+            println!("{}", t!("delete-remote-token", token = plan.token()));
+            return Ok(());
+        };
+        if token != plan.token() {
+            Err(BackupError::StaleDeletePlan)?
+        }
+        let msg = t!("prompt-delete-remote", count = plan.copies.len());
+        if !prompt::confirm(Prompt::DeleteRemote, Some(profile_name), &msg)? {
+            return Ok(());
+        }
+        let report = store::delete_remote_copies(&profile, profile_name, &plan);
+        let failed = report
+            .iter()
+            .filter(|(_, outcome)| !matches!(outcome, DeleteOutcome::Deleted))
+            .count();
+        println!("{}", DeleteReport(report));
+        if failed > 0 {
+            Err(BackupError::RemoteDeleteFailed(failed))?
+        }
+    } else if !prompt::confirm(
+        Prompt::DeleteBackups,
+        Some(profile_name),
+        &t!("prompt-delete-backups"),
+    )? {
+        return Ok(());
+    }
+    if selection.is_all() {
+        delete_all_backups(&db, profile_name)
+    } else {
+        delete_backups(&db, profile_name, &ids)
+    }
+}
+
+/// Print a table of backups for the given profile.
+///
+/// With `cause`, only backups made for that reason are listed. With
+/// [`Format::Csv`], the backups are printed as CSV without a summary.
+pub fn print_backups(
+    profile_name: &ProfileName,
+    count: Option<usize>,
+    cause: Option<BackupCause>,
+    host: Option<&str>,
+    format: Format,
+) -> Result<()> {
+    // open profile for validation only
+    let _ = Profile::open_named(profile_name)?;
+
+    let db = Database::open_for_profile(profile_name)?;
+    let backup_table = db.backup_table(profile_name)?;
+    let filtered = cause.is_some() || host.is_some();
+    let matching = backup_table.iter().filter(|backup| match backup {
+        Ok(backup) => {
+            cause.is_none_or(|cause| backup.cause() == cause)
+                && host.is_none_or(|host| {
+                    backup
+                        .environment()
+                        .is_some_and(|env| env.host.eq_ignore_ascii_case(host))
+                })
+        }
+        Err(_) => true,
+    });
+    // without a filter, only the displayed backups need to be fetched
+    let (backups, total) = match filtered {
+        true => {
+            let mut backups = matching.collect::<Result<Vec<_>>>()?;
+            let total = backups.len();
+            backups.truncate(count.unwrap_or(total));
+            (backups, total)
+        }
+        false => {
+            let total = backup_table.count()?;
+            let count = count.unwrap_or(total);
+            (matching.take(count).collect::<Result<Vec<_>>>()?, total)
+        }
+    };
+    if format == Format::Csv {
+        let records = backup_records(&db, profile_name, backups)?;
+        print!("{}", BackupRecords(records).to_csv());
+        return Ok(());
+    }
+    if total == 0 {
+        match (cause, host) {
+            (Some(cause), _) => println!("No {} backups for profile {}", cause, profile_name),
+            (None, Some(host)) => {
+                println!("No backups from {} for profile {}", host, profile_name)
+            }
+            (None, None) => println!("No backups yet for profile {}", profile_name),
+        }
+    } else {
+        let count = backups.len();
+        let latest = backup_table.latest()?.map(|backup| backup.id());
+        let table = BackupList::new(profile_name, backups, latest).to_string();
+        println!("{}", table);
+        println!("Displayed {} of {} backups", count, total);
+        if !filtered {
+            let size = ByteSize(backup_table.total_size()?);
+            println!("{} of files in all backups", size);
+        }
+    }
+    Ok(())
+}
+
+/// Export the backups of all profiles as CSV to `output`, or print them.
+pub fn export_all_backups(output: Option<&Path>) -> Result<()> {
+    let mut records = Vec::new();
+    for name in list_profile_names()? {
+        let db = Database::open_for_profile(&name)?;
+        if db.row_count(&name)?.is_none() {
+            continue;
+        }
+        let mut backups = db.backup_table(&name)?.select_all()?;
+        backups.sort_by_key(|backup| backup.id());
+        records.extend(backup_records(&db, &name, backups)?);
+    }
+    let count = records.len();
+    let csv = BackupRecords(records).to_csv();
+    match output {
+        Some(path) => {
+            std::fs::write(path, csv).with_path("writing", path)?;
+            println!("exported {} backup(s) to {}", count, path_str(path));
+        }
+        None => print!("{}", csv),
+    }
+    Ok(())
+}
+
+/// Pair backups with their profile and the total size of their files.
+fn backup_records(
+    db: &Database,
+    profile_name: &ProfileName,
+    backups: Vec<Backup>,
+) -> Result<Vec<BackupRecord>> {
+    let file_table = db.file_table(profile_name)?;
+    backups
+        .into_iter()
+        .map(|backup| {
+            let files = file_table.select_backup(backup.id())?;
+            Ok(BackupRecord {
+                profile: profile_name.clone(),
+                size: files.iter().map(|file| file.size).sum(),
+                backup,
+            })
+        })
+        .collect()
+}
+
+/// Print which backups match the live files and which match the cloud copy in `cloud`.
+pub fn compare_backups(name: &ProfileName, cloud: &Path) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    if !cloud.is_dir() {
+        Err(std::io::Error::from(std::io::ErrorKind::NotFound))
+            .with_path("finding cloud directory", cloud)?
+    }
+    let db = Database::open_for_profile(name)?;
+    let comparison = diff::compare_backups(&db, &profile, name, cloud)?;
+    if comparison.backups.is_empty() {
+        println!("No backups yet for profile {}", name);
+        return Ok(());
+    }
+    let time = |time: Option<std::time::SystemTime>| match time {
+        Some(time) => chrono::DateTime::<chrono::Local>::from(time)
+            .format("%F %T")
+            .to_string(),
+        None => t!("value-no-files"),
+    };
+    println!(
+        "{}",
+        t!(
+            "compare-live-modified",
+            time = time(comparison.live_modified)
+        )
+    );
+    println!(
+        "{}",
+        t!(
+            "compare-cloud-modified",
+            time = time(comparison.other_modified)
+        )
+    );
+    println!("{}", CompareList(comparison.backups.clone()));
+    let latest = |f: fn(&diff::BackupMatch) -> bool| {
+        comparison
+            .backups
+            .iter()
+            .rev()
+            .find(|m| f(m))
+            .map(|m| m.backup.id())
+    };
+    match latest(diff::BackupMatch::matches_live) {
+        Some(id) => println!("{}", t!("compare-live-backup", id = id.get())),
+        None => println!("{}", t!("compare-live-unsaved")),
+    }
+    match latest(diff::BackupMatch::matches_other) {
+        Some(id) => println!("{}", t!("compare-cloud-backup", id = id.get())),
+        None => println!("{}", t!("compare-cloud-unsaved")),
+    }
+    Ok(())
+}
+
+/// Print the files contained in a backup, or in the latest backup if `id` is `None`.
+pub fn print_backup_files(profile_name: &ProfileName, id: Option<BackupRef>) -> Result<()> {
+    let db = Database::open_for_profile(profile_name)?;
+    let backup_table = db.backup_table(profile_name)?;
+    let backup = match id {
+        Some(backup) => backup_table.select_ref(backup)?,
+        None => backup_table.latest()?.ok_or(BackupError::BackupsEmpty)?,
+    };
+    let files = db.file_table(profile_name)?.select_backup(backup.id())?;
+    println!("Backup {} of {}", backup.id(), profile_name);
+    if let Some(uid) = backup.uid() {
+        println!("Unique ID: {}", uid);
+    }
+    println!("Created: {}", backup.timestamp());
+    if let Some(version) = backup.version() {
+        println!("Version: {}", version);
+    }
+    if let Some(tag) = backup.tag() {
+        println!("Tag: {}", tag);
+    }
+    if backup.status() == BackupStatus::Valid {
+        println!("Passed validation");
+    }
+    if files.is_empty() {
+        println!("No files recorded");
+    } else {
+        let count = files.len();
+        let changed = files.iter().filter(|f| f.changed).count();
+        let triggered = files.iter().filter(|f| f.triggered).count();
+        println!("{}", FileList(files));
+        println!(
+            "{} file(s), {} changed, {} triggered the backup",
+            count, changed, triggered
+        );
+    }
+    Ok(())
+}
+
+/// Check the remote copies of a profile's backups without downloading them,
+/// see [`store::verify_remotes`].
+pub fn verify_remotes(name: &ProfileName, id: Option<BackupRef>) -> Result<()> {
+    let db = Database::open_for_profile(name)?;
+    let id = match id {
+        Some(backup) => Some(db.backup_table(name)?.select_ref(backup)?.id()),
+        None => None,
+    };
+    let profile = Profile::open_named(name)?;
+    if profile.remotes().is_empty() {
+        println!("{}", t!("verify-remote-none"));
+        return Ok(());
+    }
+    let verification = store::verify_remotes(&db, &profile, name, id)?;
+    for problem in &verification.problems {
+        println!("{}", paint(Tone::Warn, problem));
+    }
+    let msg = t!(
+        "verify-remote-summary",
+        checked = verification.checked,
+        problems = verification.problems.len(),
+    );
+    println!("{}", msg);
+    if verification.size_only > 0 {
+        println!(
+            "{}",
+            t!("verify-remote-size-only", count = verification.size_only)
+        );
+    }
+    if verification.skipped > 0 {
+        println!(
+            "{}",
+            t!("verify-remote-skipped", count = verification.skipped)
+        );
+    }
+    Ok(())
+}
+
+/// Delete all but the most recent `count` backups for the given profile.
+pub fn retain_backups(profile_name: &ProfileName, count: usize) -> Result<()> {
+    let msg = t!("prompt-retain", count = count);
+    if !prompt::confirm(Prompt::Retain, Some(profile_name), &msg)? {
+        return Ok(());
+    }
+    let db = Database::open_for_profile(profile_name)?;
+    let backup_table = db.backup_table(profile_name)?;
+    let mut backups = backup_table.select_all()?;
+    backups.sort_by_key(|b| b.timestamp());
+    backups.reverse();
+    let to_delete = backups
+        .iter()
+        .skip(count)
+        .map(|b| b.id())
+        .collect::<Vec<_>>();
+    let profile = Profile::open_named(profile_name)?;
+    let plan = store::plan_delete(&db, &profile, profile_name, &to_delete)?;
+    if !plan.is_empty() {
+        println!("{}", t!("retain-remote-kept", count = plan.copies.len()));
+    }
+    if to_delete.is_empty() {
+        println!("No backups to delete");
+    } else {
+        println!("Deleting {} backup(s)", to_delete.len());
+        prune_backups(&db, profile_name, &to_delete)?;
+    }
+    Ok(())
+}
+
+/// Delete backups within the `window` so that only every `keep_every`th remains.
+///
+/// The most recent backup in the window is always kept.
+pub fn thin_backups(
+    profile_name: &ProfileName,
+    keep_every: NonZeroUsize,
+    window: &Selection,
+) -> Result<()> {
+    let db = Database::open_for_profile(profile_name)?;
+    let backups = db.backup_table(profile_name)?.select_all()?;
+    let to_delete = window.thin(&backups, keep_every);
+    if to_delete.is_empty() {
+        println!("No backups to delete");
+        return Ok(());
+    }
+    let msg = t!(
+        "prompt-thin",
+        count = to_delete.len(),
+        every = keep_every.get()
+    );
+    if !prompt::confirm(Prompt::Thin, Some(profile_name), &msg)? {
+        return Ok(());
+    }
+    let profile = Profile::open_named(profile_name)?;
+    let plan = store::plan_delete(&db, &profile, profile_name, &to_delete)?;
+    if !plan.is_empty() {
+        println!("{}", t!("retain-remote-kept", count = plan.copies.len()));
+    }
+    println!("Deleting {} backup(s)", to_delete.len());
+    prune_backups(&db, profile_name, &to_delete)
+}
+
+/// Print the audit log of one or all profiles.
+///
+/// With `count`, only the most recent entries are printed.
+pub fn print_history(name: Option<ProfileName>, count: Option<usize>) -> Result<()> {
+    if let Some(name) = &name {
+        // fails for unknown profiles
+        Profile::open_named(name)?;
+    }
+    let mut entries = audit::read(name.as_ref())?;
+    if let Some(count) = count {
+        entries.drain(..entries.len().saturating_sub(count));
+    }
+    if entries.is_empty() {
+        println!("{}", t!("history-empty"));
+    } else {
+        println!("{}", HistoryList(entries));
+    }
+    Ok(())
+}
+
+/// Print which player holds the turn of a co-op profile.
+pub fn print_turn(name: &ProfileName) -> Result<()> {
+    match coop::current_turn(name)? {
+        Some(turn) => println!(
+            "{}",
+            t!(
+                "coop-turn-held",
+                name = name.to_string(),
+                player = turn.player,
+                since = turn.since.to_string()
+            )
+        ),
+        None => println!("{}", t!("coop-turn-free", name = name.to_string())),
+    }
+    Ok(())
+}
+
+/// Take the turn of a co-op profile and restore the shared save.
+///
+/// The turn stays taken if the restore isn't confirmed.
+pub fn take_turn(name: &ProfileName, steal: bool, snapshot: bool) -> Result<()> {
+    let profile = Profile::open_named(name)?;
+    coop::take_turn(name, steal)?;
+    println!(
+        "{}",
+        paint(
+            Tone::Success,
+            t!("coop-turn-taken", name = name.to_string())
+        )
+    );
+    let Some(save_dir) = coop::shared_save(name)? else {
+        println!("{}", t!("coop-no-save"));
+        return Ok(());
+    };
+    let diffs = diff::diff_dir(&profile, &save_dir)?;
+    if !confirm_restore(name, &diffs, &t!("coop-up-to-date"))? {
+        return Ok(());
+    }
+    if snapshot {
+        create_backup(name, BackupCause::PreRestore)?;
+    }
+    coop::pull(name)
+}
+
+/// Share the live files of a co-op profile, unless `push` is unset, and pass
+/// the turn on.
+pub fn pass_turn(name: &ProfileName, push: bool) -> Result<()> {
+    let pushed = coop::pass_turn(name, push)?;
+    let msg = match push {
+        true => t!("coop-passed", name = name.to_string(), count = pushed),
+        false => t!("coop-released", name = name.to_string()),
+    };
+    println!("{}", paint(Tone::Success, msg));
+    Ok(())
+}
+
+/// Print the last `lines` events of a profile's event log, then follow it,
+/// printing events as they are appended.
+pub fn tail_events(name: &ProfileName, lines: usize) -> Result<()> {
+    use std::io::{Read, Seek, SeekFrom, Write};
+
+    // fails for unknown profiles
+    Profile::open_named(name)?;
+    let path = event_log_path(name)?;
+    let mut text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(e) => Err(e).with_path("reading", &path)?,
+    };
+    let mut pos = text.len() as u64;
+    // a line being written is printed once it's complete
+    let complete = text.rfind('\n').map_or(0, |i| i + 1);
+    let mut partial = text.split_off(complete);
+    let past = text.lines().collect::<Vec<_>>();
+    for line in &past[past.len().saturating_sub(lines)..] {
+        println!("{}", line);
+    }
+    loop {
+        std::thread::sleep(EVENTS_POLL_INTERVAL);
+        let Ok(mut file) = std::fs::File::open(&path) else {
+            continue;
+        };
+        let len = file.metadata().with_path("reading", &path)?.len();
+        if len < pos {
+            // the log was removed and started over
+            pos = 0;
+            partial.clear();
+        }
+        let mut new = String::new();
+        file.seek(SeekFrom::Start(pos))
+            .and_then(|_| file.read_to_string(&mut new))
+            .with_path("reading", &path)?;
+        pos += new.len() as u64;
+        partial.push_str(&new);
+        let complete = partial.rfind('\n').map_or(0, |i| i + 1);
+        let rest = partial.split_off(complete);
+        print!("{}", partial);
+        std::io::stdout()
+            .flush()
+            .context(|| "failed printing events".to_owned())?;
+        partial = rest;
+    }
+}
+
+/// Print the watcher and backup status of one or all profiles.
+pub fn print_status(name: Option<ProfileName>) -> Result<()> {
+    let names = match name {
+        Some(name) => vec![name],
+        None => list_profile_names()?,
+    };
+    if names.is_empty() {
+        println!("No profiles found");
+        return Ok(());
+    }
+    let statuses = names
+        .into_iter()
+        .map(|name| {
+            // a running watcher knows best, and already has the database open
+            if let Some(Response::Status { state, latest }) = ipc::request(&name, &Request::Status)?
+            {
+                return Ok(ProfileStatus {
+                    name,
+                    watcher: Some(state),
+                    latest: latest.map(|latest| *latest),
+                });
+            }
+            let db = Database::open_for_profile(&name)?;
+            Ok(ProfileStatus {
+                watcher: lock::read_state(&name)?,
+                latest: db.backup_table(&name)?.latest()?,
+                name,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    println!("{}", StatusList(statuses));
+    Ok(())
+}
+
+/// Check one or all profiles for problems and print a report.
+pub fn run_doctor(name: Option<ProfileName>) -> Result<()> {
+    let names = match name {
+        Some(name) => vec![name],
+        None => list_profile_names()?,
+    };
+    let mut count = 0;
+    for name in names {
+        let db = Database::open_for_profile(&name)?;
+        for issue in doctor::diagnose(&db, &name)? {
+            println!("{}: {}", issue.profile, issue.message);
+            count += 1;
+        }
+    }
+    if count == 0 {
+        println!("{}", paint(Tone::Success, "No problems found"));
+    } else {
+        println!(
+            "{}",
+            paint(Tone::Warn, format!("Found {} problem(s)", count))
+        );
+    }
+    Ok(())
+}
+
+/// Report and apply pending migrations for all profiles.
+///
+/// If `dry_run` is set, only the report is printed.
+pub fn run_migrations(dry_run: bool) -> Result<()> {
+    let names = list_profile_names()?;
+    let mut pending = 0;
+    for migration in migrate::migrations() {
+        for name in &names {
+            for change in migration.plan(name)? {
+                println!("[{}] {}: {}", migration.description(), name, change);
+                pending += 1;
+            }
+        }
+    }
+    if pending == 0 {
+        println!("Everything is up to date");
+        return Ok(());
+    }
+    if dry_run
+        || !prompt::confirm(
+            Prompt::Migrate,
+            None,
+            &t!("prompt-migrate", count = pending),
+        )?
+    {
+        return Ok(());
+    }
+    for migration in migrate::migrations() {
+        for name in &names {
+            // plan again, since earlier migrations may have changed the outcome
+            if !migration.plan(name)?.is_empty() {
+                migration.apply(name)?;
+            }
+        }
+    }
+    println!("Migration complete");
+    Ok(())
+}
+
+/// Print the current configuration.
+///
+/// The `database_key` is redacted.
+pub fn print_config() -> Result<()> {
+    let config = Config::load()?;
+    let mut json = serde_json::to_value(&config).expect("config is always serializable");
+    if let Some(key) = json.get_mut(DATABASE_KEY).filter(|key| !key.is_null()) {
+        *key = REDACTED.into();
+    }
+    let ser = serde_json::to_string_pretty(&json).expect("config is always serializable");
+    println!("{}", ser);
+    Ok(())
+}
+
+/// Set a single configuration value.
+///
+/// Changing `per_profile_database` moves the backup metadata of all profiles
+/// to their new location. The config is only saved once all metadata was
+/// moved; if moving fails, the profiles moved so far are moved back.
+pub fn set_config(key: &str, value: &str) -> Result<()> {
+    // leave values from environment variables out of the saved file
+    let old = Config::load_from(config_path()?)?;
+    let new = old.with_field(key, value)?;
+    if old.per_profile_database != new.per_profile_database {
+        let mut moved = Vec::new();
+        for name in list_profile_names()? {
+            println!("moving backup metadata for {}", name);
+            let result = relocate_metadata(&name, new.per_profile_database);
+            // a failed move may have copied part of the metadata already
+            moved.push(name);
+            if let Err(e) = result {
+                for name in moved.iter().rev() {
+                    println!("moving backup metadata for {} back", name);
+                    if let Err(e) = relocate_metadata(name, old.per_profile_database) {
+                        let msg = format!("failed moving backup metadata for {} back: {}", name, e);
+                        println!("{}", paint(Tone::Warn, msg));
+                    }
+                }
+                Err(e)?
+            }
+        }
+    }
+    new.save()?;
+    let value = match key {
+        DATABASE_KEY => REDACTED,
+        _ => value,
+    };
+    println!("set {} = {}", key, value);
+    Ok(())
+}
+
+/// Store a secret in the OS keyring.
+///
+/// The value is asked for on the terminal, or read from stdin otherwise.
+pub fn set_secret(name: &str) -> Result<()> {
+    let value = if std::io::stdin().is_terminal() {
+        dialoguer::Password::new()
+            .with_prompt(t!("prompt-secret", name = name))
+            .interact()
+            .context(|| "failed reading secret".to_owned())?
+    } else {
+        let mut line = String::new();
+        std::io::stdin()
+            .read_line(&mut line)
+            .context(|| "failed reading secret from stdin".to_owned())?;
+        line.trim_end_matches(['\r', '\n']).to_owned()
+    };
+    if value.is_empty() {
+        Err(savefile::error::Error::Secret {
+            name: name.to_owned(),
+            reason: "empty value".to_owned(),
+        })?
+    }
+    savefile::secret::set(name, &value)?;
+    println!("{}", t!("secret-set", name = name));
+    Ok(())
+}
+
+/// Delete a secret from the OS keyring.
+pub fn delete_secret(name: &str) -> Result<()> {
+    if savefile::secret::delete(name)? {
+        println!("{}", t!("secret-deleted", name = name));
+    } else {
+        println!("{}", t!("secret-missing", name = name));
+    }
+    Ok(())
+}
+
+/// Run the filesystem watcher for the given profile.
+///
+/// This will watch the profile's base directory for changes and automatically
+/// create a backup when a change to the requested files is detected.
+pub fn run_watcher(profile_name: &ProfileName) -> Result<()> {
+    let profile = Profile::open_named(profile_name)?;
+    let db = Database::open_for_profile(profile_name)?;
+    savefile::watch(&db, &profile, profile_name)
+}
+
+/// Run the filesystem watchers for the given profiles, or for all profiles.
+///
+/// A single profile is watched on the current thread. Several profiles are
+/// watched under a supervisor that restarts failed watchers, until all of
+/// them have stopped.
+///
+/// In the `foreground`, termination signals stop the process once running
+/// backups have finished.
+pub fn run_watchers(profile_names: Vec<ProfileName>, all: bool, foreground: bool) -> Result<()> {
+    let profile_names = if all {
+        list_profile_names()?
+    } else {
+        profile_names
+    };
+    if profile_names.is_empty() {
+        Err(ProfileError::NoProfiles)?
+    }
+    if foreground {
+        // before any other threads start, so that they don't receive the signals
+        signal::stop_on_termination(profile_names.clone());
+        audit::set_actor(Actor::Daemon);
+    } else {
+        audit::set_actor(Actor::Watcher);
+    }
+    start_servers()?;
+    match profile_names.as_slice() {
+        [profile_name] => run_watcher(profile_name),
+        _ => {
+            savefile::watch_all(&profile_names);
+            Ok(())
+        }
+    }
+}
+
+/// Pause or resume the automatic backups of a profile.
+pub fn set_paused(name: &ProfileName, paused: bool) -> Result<()> {
+    // fails for unknown profiles
+    Profile::open_named(name)?;
+    let running = control::set_paused(name, paused)?;
+    let name = name.to_string();
+    let msg = match (paused, running) {
+        (true, true) => t!("watch-paused", name = name),
+        (true, false) => t!("watch-paused-later", name = name),
+        (false, true) => t!("watch-resumed", name = name),
+        (false, false) => t!("watch-resumed-later", name = name),
+    };
+    println!("{}", msg);
+    Ok(())
+}
+
+/// Start the servers enabled in the configuration, which run in the background
+/// while watching.
+fn start_servers() -> Result<()> {
+    let config = Config::load()?;
+    if let Some(addr) = config.events_listen {
+        savefile::sse::serve(addr)?;
+        println!("serving events on http://{}/events", addr);
+    }
+    if let Some(addr) = config.grpc_listen {
+        #[cfg(feature = "grpc")]
+        {
+            savefile::grpc::serve(addr)?;
+            println!("serving gRPC on {}", addr);
+        }
+        #[cfg(not(feature = "grpc"))]
+        eprintln!(
+            "{}",
+            paint_err(
+                Tone::Warn,
+                format!(
+                    "warning: not serving gRPC on {}: built without the `grpc` feature",
+                    addr
+                )
+            )
+        );
+    }
+    Ok(())
+}
+
+/// Mount the backups of the given profile as a read-only filesystem.
+///
+/// Blocks until the filesystem is unmounted.
+#[cfg(feature = "mount")]
+pub fn mount_backups(profile_name: &ProfileName, mountpoint: &Path) -> Result<()> {
+    // open profile for validation only
+    let _ = Profile::open_named(profile_name)?;
+
+    let db = Database::open_for_profile(profile_name)?;
+    println!("mounting backups for {} at {:?}", profile_name, path_str(mountpoint));
+    savefile::mount::mount(&db, profile_name, mountpoint)
+}
+
+/// Add context-menu entries to the base directory of every profile.
+///
+/// Profiles that fail to open are skipped with a warning.
+pub fn install_shell_integration() -> Result<()> {
+    use savefile::shell_integration::{install, MenuAction, MenuEntry};
+
+    let exe = std::env::current_exe().context(|| "failed locating executable".to_owned())?;
+    let mut entries = vec![];
+    for name in list_profile_names()? {
+        let profile = match Profile::open_named(&name) {
+            Ok(profile) => profile,
+            Err(e) => {
+                eprintln!("skipping {}: {}", name, e);
+                continue;
+            }
+        };
+        for action in MenuAction::ALL {
+            let label = match action {
+                MenuAction::Backup => t!("menu-backup", profile = name.as_str()),
+                MenuAction::RestoreLatest => t!("menu-restore", profile = name.as_str()),
+            };
+            entries.push(MenuEntry {
+                profile: name.clone(),
+                folder: profile.base().to_owned(),
+                action,
+                label,
+            });
+        }
+    }
+    install(&exe, &entries)?;
+    println!("added {} context-menu entries", entries.len());
+    Ok(())
+}
+
+/// Remove all context-menu entries.
+pub fn uninstall_shell_integration() -> Result<()> {
+    let removed = savefile::shell_integration::uninstall()?;
+    println!("removed {} context-menu entries", removed);
+    Ok(())
+}
+
+/// Show the system tray icon until it is closed.
+#[cfg(feature = "tray")]
+pub fn run_tray() -> Result<()> {
+    tray::run()
+}
+
+/// Print a completion script for the given shell.
+///
+/// Where the shell allows it, the script also completes `--name` with the
+/// names of existing profiles.
+pub fn print_completions(shell: clap_complete::Shell) -> Result<()> {
+    use clap::CommandFactory;
+
+    let mut cmd = args::Args::command();
+    let mut out = std::io::stdout();
+    clap_complete::generate(shell, &mut cmd, "savefile", &mut out);
+    if let Some(hook) = completions::profile_name_hook(shell) {
+        println!("{}", hook);
+    }
+    Ok(())
+}
+
+/// Print the long help of a command and all of its subcommands.
+///
+/// `path` names the command to start from, e.g. `["backup", "restore"]`.
+/// All commands are printed if it is empty.
+pub fn print_docs(path: &[String]) -> Result<()> {
+    use clap::CommandFactory;
+
+    let mut root = args::Args::command();
+    root.build();
+    let mut cmd = &mut root;
+    for name in path {
+        cmd = match cmd.find_subcommand_mut(name) {
+            Some(sub) => sub,
+            None => args::Args::command()
+                .error(
+                    clap::error::ErrorKind::InvalidSubcommand,
+                    format!("no command named {:?}", path.join(" ")),
+                )
+                .exit(),
+        };
+    }
+    print_command_docs(cmd);
+    Ok(())
+}
+
+/// Print the long help of `cmd`, followed by that of its visible subcommands.
+fn print_command_docs(cmd: &mut clap::Command) {
+    println!("{}", cmd.render_long_help());
+    for sub in cmd
+        .get_subcommands_mut()
+        .filter(|sub| !sub.is_hide_set() && sub.get_name() != "help")
+    {
+        println!("{}", "-".repeat(50));
+        print_command_docs(sub);
+    }
+}
+
+/// Print the names of all profiles, one per line.
+pub fn print_profile_names() -> Result<()> {
+    for name in list_profile_names()? {
+        println!("{}", name);
+    }
+    Ok(())
+}
+
+/// Returns the localized message to print for an error.
+pub fn error_message(err: &savefile::error::Error) -> String {
+    use savefile::error::ErrorCategory;
+
+    let category = match err.category() {
+        ErrorCategory::NoSuchProfile => "no-such-profile",
+        ErrorCategory::NoBackups => "no-backups",
+        ErrorCategory::Io => "io",
+        ErrorCategory::Database => "database",
+        ErrorCategory::InvalidProfile => "invalid-profile",
+        ErrorCategory::InvalidConfig => "invalid-config",
+        ErrorCategory::Locked => "locked",
+        ErrorCategory::LimitExceeded => "limit-exceeded",
+        ErrorCategory::NotInteractive => "not-interactive",
+        ErrorCategory::ValidationFailed => "validation-failed",
+        ErrorCategory::Other => "other",
+    };
+    let msg = t!("error", category = category, message = err.to_string());
+    paint_err(Tone::Error, msg)
+}
@@ -0,0 +1,42 @@
+/// Confirmation of operations following the `confirm` policies of the
+/// configuration, shared by all commands that ask before changing anything.
+use dialoguer::Input;
+use savefile::{
+    config::{ConfirmPolicy, Prompt},
+    error::{IoResultExt, Result},
+    Config, ProfileName,
+};
+
+use super::util::{self, assume_yes, require_terminal};
+
+/// Confirm an operation, asking `msg` as the policy for `prompt` requires.
+///
+/// `name` is the profile the operation concerns, if it concerns a single one.
+/// Returns `true` if the operation may go ahead.
+pub fn confirm(prompt: Prompt, name: Option<&ProfileName>, msg: &str) -> Result<bool> {
+    match Config::load()?.confirm_policy(prompt) {
+        ConfirmPolicy::Ask => util::confirm(msg),
+        ConfirmPolicy::Always => util::ask(msg),
+        ConfirmPolicy::Never => Ok(true),
+        ConfirmPolicy::TypeName => match name {
+            Some(name) if !assume_yes() => type_name(name, msg),
+            _ => util::confirm(msg),
+        },
+    }
+}
+
+/// Ask to type the profile's name to confirm `msg`.
+fn type_name(name: &ProfileName, msg: &str) -> Result<bool> {
+    let prompt = t!("prompt-type-name", question = msg, name = name.as_str());
+    require_terminal(&prompt)?;
+    let input: String = Input::new()
+        .with_prompt(prompt)
+        .allow_empty(true)
+        .interact_text()
+        .context(|| "failed reading confirmation".to_owned())?;
+    if input.trim() == name.as_str() {
+        return Ok(true);
+    }
+    println!("{}", t!("prompt-name-mismatch"));
+    Ok(false)
+}
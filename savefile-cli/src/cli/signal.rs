@@ -0,0 +1,53 @@
+use savefile::ProfileName;
+#[cfg(unix)]
+use savefile::{filesystem::lock_path, lock, scheduler::Scheduler};
+
+/// Stop the process cleanly when it receives SIGTERM or SIGINT.
+///
+/// Running backups are allowed to finish, and the lock files of the given
+/// profiles are removed before exiting. This matters when running as the main
+/// process of a container, which gets no default handling of SIGTERM.
+///
+/// Must be called before any other threads are started, since the signals
+/// are blocked in every thread except the one waiting for them.
+#[cfg(unix)]
+pub fn stop_on_termination(profile_names: Vec<ProfileName>) {
+    // SAFETY: the signal set is initialized by sigemptyset before use
+    let set = unsafe {
+        let mut set: libc::sigset_t = std::mem::zeroed();
+        libc::sigemptyset(&mut set);
+        libc::sigaddset(&mut set, libc::SIGTERM);
+        libc::sigaddset(&mut set, libc::SIGINT);
+        libc::pthread_sigmask(libc::SIG_BLOCK, &set, std::ptr::null_mut());
+        set
+    };
+    std::thread::spawn(move || {
+        let mut signal = 0;
+        // SAFETY: the set was initialized above
+        unsafe { libc::sigwait(&set, &mut signal) };
+        println!("stopping after running backups finish");
+        Scheduler::global().drain();
+        remove_locks(&profile_names);
+        std::process::exit(0);
+    });
+}
+
+/// Stop the process cleanly when it receives SIGTERM or SIGINT.
+///
+/// Only Unix signals are handled, so this does nothing on other platforms.
+#[cfg(not(unix))]
+pub fn stop_on_termination(_profile_names: Vec<ProfileName>) {}
+
+/// Remove the lock files held by this process, which exits without dropping them.
+#[cfg(unix)]
+fn remove_locks(profile_names: &[ProfileName]) {
+    for name in profile_names {
+        let held = lock::read_state(name)
+            .ok()
+            .flatten()
+            .is_some_and(|state| state.pid == std::process::id());
+        if let (true, Ok(path)) = (held, lock_path(name)) {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
@@ -0,0 +1,161 @@
+/// System tray companion.
+///
+/// Shows a tray icon with a submenu per profile for toggling its watcher and
+/// creating a backup. Watchers are started as separate `savefile watch`
+/// processes, so they keep running after the tray exits.
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use savefile::{
+    control,
+    error::{IoResultExt, Result},
+    list_profile_names, lock, ProfileName,
+};
+use tao::event_loop::{ControlFlow, EventLoopBuilder};
+use tray_icon::{
+    menu::{CheckMenuItem, Menu, MenuEvent, MenuId, MenuItem, PredefinedMenuItem, Submenu},
+    Icon, TrayIconBuilder,
+};
+
+/// How often the watcher toggles are refreshed from the lock files.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Size of the generated tray icon in pixels.
+const ICON_SIZE: u32 = 16;
+
+/// Action bound to a menu item.
+enum Action {
+    ToggleWatcher(ProfileName),
+    BackupNow(ProfileName),
+    Quit,
+}
+
+/// Menu items of a single profile that reflect its state.
+struct ProfileItems {
+    name: ProfileName,
+    watcher: CheckMenuItem,
+}
+
+/// Show the tray icon and handle its menu until "Quit" is chosen.
+pub fn run() -> Result<()> {
+    let exe = std::env::current_exe().context(|| "failed locating executable".to_owned())?;
+    let menu = Menu::new();
+    let mut actions = HashMap::new();
+    let mut profiles = vec![];
+    for name in list_profile_names()? {
+        let submenu = Submenu::new(&name, true);
+        let watcher = CheckMenuItem::new(t!("tray-watch"), true, false, None);
+        let backup = MenuItem::new(t!("tray-backup-now"), true, None);
+        submenu
+            .append_items(&[&watcher, &backup])
+            .map_err(tray_error)?;
+        menu.append(&submenu).map_err(tray_error)?;
+        actions.insert(watcher.id().clone(), Action::ToggleWatcher(name.clone()));
+        actions.insert(backup.id().clone(), Action::BackupNow(name.clone()));
+        profiles.push(ProfileItems { name, watcher });
+    }
+    let quit = MenuItem::new(t!("tray-quit"), true, None);
+    menu.append_items(&[&PredefinedMenuItem::separator(), &quit])
+        .map_err(tray_error)?;
+    actions.insert(quit.id().clone(), Action::Quit);
+    refresh(&profiles);
+
+    let event_loop = EventLoopBuilder::new().build();
+    let _tray = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("savefile")
+        .with_icon(icon()?)
+        .build()
+        .map_err(tray_error)?;
+    let menu_events = MenuEvent::receiver();
+    event_loop.run(move |_, _, control_flow| {
+        *control_flow = ControlFlow::WaitUntil(Instant::now() + REFRESH_INTERVAL);
+        while let Ok(event) = menu_events.try_recv() {
+            if let Err(e) = handle(&exe, &actions, &event.id, control_flow) {
+                eprintln!("{}", super::error_message(&e));
+            }
+        }
+        refresh(&profiles);
+    })
+}
+
+/// Perform the action bound to the chosen menu item.
+fn handle(
+    exe: &Path,
+    actions: &HashMap<MenuId, Action>,
+    id: &MenuId,
+    control_flow: &mut ControlFlow,
+) -> Result<()> {
+    match actions.get(id) {
+        Some(Action::ToggleWatcher(name)) => toggle_watcher(exe, name),
+        Some(Action::BackupNow(name)) => spawn(exe, &["backup", "create", "--name", name.as_str()]),
+        Some(Action::Quit) => {
+            *control_flow = ControlFlow::Exit;
+            Ok(())
+        }
+        None => Ok(()),
+    }
+}
+
+/// Update the watcher toggles to match the running watchers.
+fn refresh(profiles: &[ProfileItems]) {
+    for profile in profiles {
+        let running = matches!(lock::read_state(&profile.name), Ok(Some(_)));
+        profile.watcher.set_checked(running);
+    }
+}
+
+/// Stop the profile's watcher if it is running, or start one otherwise.
+fn toggle_watcher(exe: &Path, name: &ProfileName) -> Result<()> {
+    match lock::read_state(name)? {
+        Some(_) => {
+            // the watcher releases its lock on the way out
+            control::stop_watcher(name)?;
+            Ok(())
+        }
+        None => spawn(exe, &["watch", "--name", name.as_str()]),
+    }
+}
+
+/// Run savefile in the background without a console window.
+fn spawn(exe: &Path, args: &[&str]) -> Result<()> {
+    let mut cmd = Command::new(exe);
+    cmd.args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+        cmd.creation_flags(CREATE_NO_WINDOW);
+    }
+    cmd.spawn()
+        .context(|| format!("failed running savefile {}", args.join(" ")))?;
+    Ok(())
+}
+
+/// Create the tray icon: a filled square with a darker border.
+fn icon() -> Result<Icon> {
+    let rgba = (0..ICON_SIZE * ICON_SIZE)
+        .flat_map(|i| {
+            let (x, y) = (i % ICON_SIZE, i / ICON_SIZE);
+            let edge = x == 0 || y == 0 || x == ICON_SIZE - 1 || y == ICON_SIZE - 1;
+            if edge {
+                [0x1f, 0x4e, 0x79, 0xff]
+            } else {
+                [0x3c, 0x8d, 0xd6, 0xff]
+            }
+        })
+        .collect();
+    Ok(Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE).map_err(tray_error)?)
+}
+
+/// Convert an error from the tray or menu libraries.
+fn tray_error(e: impl std::fmt::Display) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, e.to_string())
+}
@@ -0,0 +1,59 @@
+use std::{
+    io::IsTerminal,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use savefile::error::{Error, IoResultExt, Result};
+
+/// Whether prompts are answered with "yes" without asking.
+static ASSUME_YES: AtomicBool = AtomicBool::new(false);
+
+/// Answer all following prompts with "yes" without asking.
+pub fn set_assume_yes(yes: bool) {
+    ASSUME_YES.store(yes, Ordering::Relaxed);
+}
+
+/// Returns `true` if prompts are answered with "yes" without asking.
+pub fn assume_yes() -> bool {
+    ASSUME_YES.load(Ordering::Relaxed)
+}
+
+/// Prompts the user to confirm an action.
+///
+/// Returns `true` if the user confirms, `false` otherwise. If `--yes` was given,
+/// returns `true` without asking. Fails instead of blocking if stdin is not a
+/// terminal, e.g. when run from a script.
+///
+/// Operations covered by the `confirm` policies of the configuration are
+/// confirmed with [`super::prompt::confirm`] instead.
+pub fn confirm(msg: &str) -> Result<bool> {
+    if assume_yes() {
+        return Ok(true);
+    }
+    ask(msg)
+}
+
+/// Prompts the user to confirm an action, even if `--yes` was given.
+pub fn ask(msg: &str) -> Result<bool> {
+    use dialoguer::Confirm;
+
+    require_terminal(msg)?;
+    Confirm::new()
+        .with_prompt(msg)
+        .interact()
+        .context(|| "failed reading confirmation".to_owned())
+}
+
+/// Fails if stdin is not a terminal, so the question `msg` can't be asked.
+pub fn require_terminal(msg: &str) -> Result<()> {
+    if !std::io::stdin().is_terminal() {
+        Err(Error::NotInteractive(msg.to_owned()))?
+    }
+    Ok(())
+}
+
+/// Returns the path as a string, with backslashes replaced with forward slashes.
+pub fn path_str(path: impl AsRef<Path>) -> String {
+    path.as_ref().display().to_string().replace("\\", "/")
+}
@@ -0,0 +1,111 @@
+use std::path::{Path, PathBuf};
+
+use dialoguer::{Input, MultiSelect};
+use savefile::{
+    error::{IoResultExt, Result},
+    Include, Profile,
+};
+
+use super::util::{confirm, path_str, require_terminal};
+
+/// Number of matched files listed in the preview.
+const PREVIEW_LEN: usize = 10;
+
+/// Interactively set up a new profile.
+///
+/// Returns `None` if the user doesn't want to save the result.
+pub fn run() -> Result<Option<Profile>> {
+    let base = ask_base()?;
+    let mut profile = Profile::new(&base);
+    for include in ask_includes(&base)? {
+        profile.add_include(include)?;
+    }
+    preview(&profile)?;
+    if !confirm(&t!("prompt-save-profile"))? {
+        println!("{}", t!("wizard-cancelled"));
+        return Ok(None);
+    }
+    Ok(Some(profile))
+}
+
+/// Ask for the base directory until an existing directory is given.
+fn ask_base() -> Result<PathBuf> {
+    let prompt = t!("wizard-base");
+    require_terminal(&prompt)?;
+    let base: String = Input::new()
+        .with_prompt(prompt)
+        .validate_with(|input: &String| -> Result<(), String> {
+            if Path::new(input.trim()).is_dir() {
+                Ok(())
+            } else {
+                Err(t!("wizard-base-invalid"))
+            }
+        })
+        .interact_text()
+        .context(|| "failed reading base directory".to_owned())?;
+    Ok(PathBuf::from(base.trim()))
+}
+
+/// Let the user pick files and folders directly inside `base` to include.
+///
+/// Folders are included with everything below them.
+fn ask_includes(base: &Path) -> Result<Vec<Include>> {
+    let mut entries = std::fs::read_dir(base)
+        .with_path("listing", base)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            (name, entry.path().is_dir())
+        })
+        .collect::<Vec<_>>();
+    if entries.is_empty() {
+        println!("{}", t!("wizard-base-empty"));
+        return Ok(vec![]);
+    }
+    entries.sort();
+    let labels = entries
+        .iter()
+        .map(|(name, is_dir)| {
+            if *is_dir {
+                format!("{}/", name)
+            } else {
+                name.clone()
+            }
+        })
+        .collect::<Vec<_>>();
+    let selected = MultiSelect::new()
+        .with_prompt(t!("wizard-includes"))
+        .items(&labels)
+        .interact()
+        .context(|| "failed reading selection".to_owned())?;
+    let includes = selected
+        .into_iter()
+        .map(|i| {
+            let (name, is_dir) = &entries[i];
+            let name = glob::Pattern::escape(name);
+            Include::new(if *is_dir {
+                format!("{}/**", name)
+            } else {
+                name
+            })
+        })
+        .collect();
+    Ok(includes)
+}
+
+/// Print the files currently matched by the profile.
+fn preview(profile: &Profile) -> Result<()> {
+    let files = profile
+        .expand_includes(true)?
+        .into_iter()
+        .filter(|path| profile.base().join(path).is_file())
+        .collect::<Vec<_>>();
+    println!("{}", t!("wizard-matches", count = files.len()));
+    for path in files.iter().take(PREVIEW_LEN) {
+        println!("  {}", path_str(path));
+    }
+    if files.len() > PREVIEW_LEN {
+        println!("{}", t!("wizard-more", count = files.len() - PREVIEW_LEN));
+    }
+    Ok(())
+}
@@ -0,0 +1,226 @@
+use clap::Parser;
+use cli::args::{
+    Args, BackupCmd, ConfigCmd, CoopCmd, EventsCmd, FileCmd, ProfileCmd, SecretCmd,
+    ShellIntegrationCmd, SubCmd, WatchCmd,
+};
+use savefile::{
+    audit::{self, Actor},
+    error::Result,
+    filesystem::create_required_dirs,
+    selection::{IdRanges, Selection},
+    BackupCause, Include,
+};
+
+mod cli;
+
+fn main() {
+    create_required_dirs().expect("failed to create required directories");
+    let args = Args::parse();
+    audit::set_actor(Actor::Cli);
+    cli::set_assume_yes(args.yes);
+    cli::set_color(!args.no_color);
+    let res = match args.cmd {
+        SubCmd::Profile(cmd) => profile_cmd(cmd),
+        SubCmd::Watch { cmd: Some(cmd), .. } => watch_cmd(cmd),
+        SubCmd::Watch {
+            cmd: None,
+            name,
+            all,
+            foreground,
+        } => cli::run_watchers(name, all, foreground),
+        SubCmd::Backup(cmd) => backup_cmd(cmd),
+        SubCmd::Config(cmd) => config_cmd(cmd),
+        SubCmd::Secret(cmd) => secret_cmd(cmd),
+        SubCmd::Events(cmd) => events_cmd(cmd),
+        SubCmd::File(cmd) => file_cmd(cmd),
+        SubCmd::Coop(cmd) => coop_cmd(cmd),
+        SubCmd::Status { name } => cli::print_status(name),
+        SubCmd::History { name, count } => cli::print_history(name, count),
+        SubCmd::Doctor { name } => cli::run_doctor(name),
+        SubCmd::Search {
+            name,
+            query,
+            ignore_case,
+            reindex,
+        } => cli::search_backups(&name, &query, ignore_case, reindex),
+        SubCmd::Migrate { dry_run } => cli::run_migrations(dry_run),
+        SubCmd::ShellIntegration(cmd) => shell_integration_cmd(cmd),
+        #[cfg(feature = "tray")]
+        SubCmd::Tray => cli::run_tray(),
+        SubCmd::Completions { shell } => cli::print_completions(shell),
+        SubCmd::Docs { command } => cli::print_docs(&command),
+        SubCmd::ProfileNames => cli::print_profile_names(),
+        #[cfg(feature = "mount")]
+        SubCmd::Mount { name, path } => cli::mount_backups(&name, &path),
+    };
+    if let Err(err) = res {
+        eprintln!("{}", cli::error_message(&err));
+        std::process::exit(err.exit_code());
+    }
+}
+
+/// Handle the "profile" subcommand.
+pub fn profile_cmd(cmd: ProfileCmd) -> Result<()> {
+    match cmd {
+        ProfileCmd::List { prefix, health } => cli::print_profiles(prefix, health),
+        ProfileCmd::Browse => cli::open_profiles_dir(),
+        ProfileCmd::Edit { name } => cli::edit_profile(&name),
+        ProfileCmd::Create { name, edit, wizard } => cli::create_profile(&name, edit, wizard),
+        ProfileCmd::Delete { name } => cli::delete_profile(&name),
+        ProfileCmd::AddInclude {
+            name,
+            pattern,
+            max_size,
+            case_insensitive,
+            no_follow_symlinks,
+        } => {
+            let include = Include {
+                glob: pattern,
+                follow_symlinks: !no_follow_symlinks,
+                max_size,
+                case_insensitive,
+            };
+            cli::add_include(&name, include)
+        }
+        ProfileCmd::RemoveInclude { name, pattern } => cli::remove_include(&name, &pattern),
+        ProfileCmd::Get { name, key } => cli::print_profile_field(&name, &key),
+        ProfileCmd::Set { name, key, value } => cli::set_profile_field(&name, &key, &value),
+        ProfileCmd::Remap { name, base, force } => cli::remap_profile(&name, &base, force),
+    }
+}
+
+/// Handle the "backup" subcommand.
+pub fn backup_cmd(cmd: BackupCmd) -> Result<()> {
+    match cmd {
+        BackupCmd::Create { name, scheduled } => {
+            let cause = match scheduled {
+                true => BackupCause::Scheduled,
+                false => BackupCause::Manual,
+            };
+            cli::create_backup(&name, cause)
+        }
+        BackupCmd::Delete {
+            name,
+            id,
+            ids,
+            before,
+            remote,
+            keep_remote,
+            token,
+        } => {
+            let selection = Selection {
+                ids: id.map(IdRanges::from).or(ids),
+                before,
+                after: None,
+            };
+            cli::delete_backup(&name, &selection, remote, keep_remote, token.as_deref())
+        }
+        BackupCmd::List {
+            name,
+            count,
+            cause,
+            host,
+            format,
+        } => cli::print_backups(&name, count, cause, host.as_deref(), format),
+        BackupCmd::ExportAll { output } => cli::export_all_backups(output.as_deref()),
+        BackupCmd::Compare { name, cloud } => cli::compare_backups(&name, &cloud),
+        BackupCmd::Show { name, id } => cli::print_backup_files(&name, id),
+        BackupCmd::Restore {
+            name,
+            id,
+            last_good,
+            from_file,
+            snapshot,
+        } => match from_file {
+            Some(path) => cli::restore_archive(&name, &path, snapshot),
+            None => cli::restore_backup(&name, id, last_good, snapshot),
+        },
+        BackupCmd::UndoRestore { name, snapshot } => cli::undo_restore(&name, snapshot),
+        BackupCmd::Pack { name, id, out } => cli::pack_backup(&name, id, &out),
+        BackupCmd::Unpack {
+            name,
+            file,
+            snapshot,
+        } => cli::restore_archive(&name, &file, snapshot),
+        BackupCmd::VerifyRemote { name, id } => cli::verify_remotes(&name, id),
+        BackupCmd::Retain { name, count } => cli::retain_backups(&name, count),
+        BackupCmd::Thin {
+            name,
+            keep_every,
+            after,
+            before,
+        } => {
+            let window = Selection {
+                ids: None,
+                before,
+                after,
+            };
+            cli::thin_backups(&name, keep_every, &window)
+        }
+    }
+}
+
+/// Handle the "config" subcommand.
+pub fn config_cmd(cmd: ConfigCmd) -> Result<()> {
+    match cmd {
+        ConfigCmd::Show => cli::print_config(),
+        ConfigCmd::Set { key, value } => cli::set_config(&key, &value),
+    }
+}
+
+/// Handle the "events" subcommand.
+pub fn events_cmd(cmd: EventsCmd) -> Result<()> {
+    match cmd {
+        EventsCmd::Tail { name, lines } => cli::tail_events(&name, lines),
+    }
+}
+
+/// Handle the "file" subcommand.
+pub fn file_cmd(cmd: FileCmd) -> Result<()> {
+    match cmd {
+        FileCmd::History { name, path } => cli::print_file_history(&name, &path),
+        FileCmd::Restore {
+            name,
+            path,
+            id,
+            snapshot,
+        } => cli::restore_file(&name, &path, id, snapshot),
+    }
+}
+
+/// Handle the "coop" subcommand.
+pub fn coop_cmd(cmd: CoopCmd) -> Result<()> {
+    match cmd {
+        CoopCmd::Status { name } => cli::print_turn(&name),
+        CoopCmd::Take {
+            name,
+            steal,
+            snapshot,
+        } => cli::take_turn(&name, steal, snapshot),
+        CoopCmd::Pass { name, no_push } => cli::pass_turn(&name, !no_push),
+    }
+}
+
+/// Handle the "secret" subcommand.
+pub fn secret_cmd(cmd: SecretCmd) -> Result<()> {
+    match cmd {
+        SecretCmd::Set { name } => cli::set_secret(&name),
+        SecretCmd::Delete { name } => cli::delete_secret(&name),
+    }
+}
+
+/// Handle the "watch" subcommands.
+pub fn watch_cmd(cmd: WatchCmd) -> Result<()> {
+    match cmd {
+        WatchCmd::Pause { name } => cli::set_paused(&name, true),
+        WatchCmd::Resume { name } => cli::set_paused(&name, false),
+    }
+}
+
+/// Handle the "shell-integration" subcommand.
+pub fn shell_integration_cmd(cmd: ShellIntegrationCmd) -> Result<()> {
+    match cmd {
+        ShellIntegrationCmd::Install => cli::install_shell_integration(),
+        ShellIntegrationCmd::Uninstall => cli::uninstall_shell_integration(),
+    }
+}
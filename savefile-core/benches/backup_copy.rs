@@ -0,0 +1,51 @@
+//! Throughput of copying files into new backups.
+use std::time::{Duration, Instant};
+
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use savefile_core::{
+    delete_one_backup,
+    testing::{self, SaveTree},
+    BackupCause, Database, Include, Profile, ProfileName,
+};
+
+fn backup_copy(c: &mut Criterion) {
+    let home = tempfile::tempdir().unwrap();
+    std::env::set_var("SAVEFILE_HOME", home.path());
+    let base = tempfile::tempdir().unwrap();
+    let tree = SaveTree {
+        dirs: 9,
+        files_per_dir: 100,
+        file_size: 64 * 1024,
+        ..SaveTree::default()
+    };
+    testing::generate_save_tree(base.path(), &tree).unwrap();
+
+    let name = ProfileName::new("bench").unwrap();
+    let profile = Profile::builder(base.path())
+        .include(Include::new("**"))
+        .build()
+        .unwrap();
+    let db = Database::open_in_memory().unwrap();
+
+    let mut group = c.benchmark_group("backup_copy");
+    group.sample_size(10);
+    group.throughput(Throughput::Bytes(tree.total_size()));
+    group.bench_function("backup", |b| {
+        b.iter_custom(|iters| {
+            let mut elapsed = Duration::ZERO;
+            for _ in 0..iters {
+                let start = Instant::now();
+                let id =
+                    savefile_core::backup(&db, &profile, &name, &[], None, BackupCause::Manual)
+                        .unwrap();
+                elapsed += start.elapsed();
+                delete_one_backup(&db, &name, id).unwrap();
+            }
+            elapsed
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, backup_copy);
+criterion_main!(benches);
@@ -0,0 +1,36 @@
+//! Expanding the includes of a profile over a synthetic save tree.
+use criterion::{criterion_group, criterion_main, Criterion};
+use savefile_core::{
+    testing::{self, SaveTree},
+    Include, Profile,
+};
+
+fn expand_includes(c: &mut Criterion) {
+    let dir = tempfile::tempdir().unwrap();
+    let tree = SaveTree::default();
+    testing::generate_save_tree(dir.path(), &tree).unwrap();
+
+    let profile = Profile::builder(dir.path())
+        .include(Include::new("**/*.sav"))
+        .include(Include::new("d1/**"))
+        .include(Include::new("d2/d2?/save1?.{dat,bak}"))
+        .include(Include::new("re:.*/save7\\.bak"))
+        .build()
+        .unwrap();
+
+    let mut group = c.benchmark_group("expand_includes");
+    group.sample_size(20);
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            testing::clear_include_cache();
+            profile.expand_includes(true).unwrap()
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| profile.expand_includes(true).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, expand_includes);
+criterion_main!(benches);
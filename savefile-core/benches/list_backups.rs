@@ -0,0 +1,23 @@
+//! Listing and summarizing a history of 10,000 backups.
+use criterion::{criterion_group, criterion_main, Criterion};
+use savefile_core::{testing, Database, ProfileName};
+
+fn list_backups(c: &mut Criterion) {
+    let name = ProfileName::new("bench").unwrap();
+    let db = Database::open_in_memory().unwrap();
+    testing::populate_backups(&db, &name, 10_000, 10).unwrap();
+    let table = db.backup_table(&name).unwrap();
+
+    let mut group = c.benchmark_group("list_backups");
+    group.bench_function("select_all", |b| b.iter(|| table.select_all().unwrap()));
+    group.bench_function("iter", |b| {
+        b.iter(|| table.iter().collect::<Result<Vec<_>, _>>().unwrap())
+    });
+    group.bench_function("count", |b| b.iter(|| table.count().unwrap()));
+    group.bench_function("latest", |b| b.iter(|| table.latest().unwrap()));
+    group.bench_function("total_size", |b| b.iter(|| table.total_size().unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, list_backups);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    {
+        // use a bundled protoc, so that building doesn't require installing one
+        let protoc = protoc_bin_vendored::protoc_bin_path().expect("no bundled protoc");
+        std::env::set_var("PROTOC", protoc);
+        tonic_build::compile_protos("proto/savefile.proto").expect("failed compiling protos");
+    }
+}
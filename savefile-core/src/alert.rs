@@ -0,0 +1,220 @@
+/// Alerts about watchers whose backups keep failing.
+///
+/// A single failed backup is only logged, but silently failing backups can go
+/// unnoticed for days. Alert rules send an email or post to a webhook once a
+/// profile's backups have failed a number of times in a row.
+#[cfg(feature = "watch")]
+use std::{collections::HashMap, sync::Mutex};
+
+use lettre::{
+    message::{Mailbox, MessageBuilder},
+    transport::smtp::{authentication::Credentials, SmtpTransport},
+    Message, Transport,
+};
+
+#[cfg(feature = "watch")]
+use crate::{config::Config, profile::ProfileName};
+use crate::{secret, webhook::Webhook};
+
+/// Consecutive failed backups of each profile watched by this process.
+#[cfg(feature = "watch")]
+static FAILURES: Mutex<Option<HashMap<ProfileName, u32>>> = Mutex::new(None);
+
+/// When and where to send an alert.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AlertRule {
+    /// Number of consecutive failed backups that trigger the alert.
+    pub consecutive_failures: u32,
+    /// Profiles the rule applies to, or empty for all.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profiles: Vec<String>,
+    /// Address to email, using the configured SMTP server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Webhook URL to post to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<String>,
+}
+
+/// How to connect to the mail server used for alerts.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SmtpSettings {
+    /// Host name of the server.
+    pub host: String,
+    /// Port of the server, if not the default for `security`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    /// How the connection is encrypted.
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    /// User name to log in with, if the server requires it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Password to log in with, or `None` to use the `smtp-password` secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// Sender address, e.g. `savefile <me@example.com>`.
+    pub from: String,
+}
+
+/// Encryption of the connection to the mail server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// TLS from the start (usually port 465).
+    #[default]
+    Tls,
+    /// Plain connection upgraded with STARTTLS (usually port 587).
+    StartTls,
+    /// No encryption, for local relays only.
+    None,
+}
+
+impl AlertRule {
+    /// Check the rule against the rest of the configuration.
+    pub(crate) fn validate(&self, smtp: Option<&SmtpSettings>) -> Result<(), String> {
+        if self.consecutive_failures == 0 {
+            Err("alert consecutive_failures must be at least 1")?
+        }
+        if self.email.is_none() && self.webhook.is_none() {
+            Err("alerts need an email address or a webhook")?
+        }
+        if let Some(url) = &self.webhook {
+            Webhook::new(url)
+                .validate()
+                .map_err(|e| format!("alert webhook: {}", e))?;
+        }
+        if let Some(email) = &self.email {
+            email
+                .parse::<Mailbox>()
+                .map_err(|e| format!("invalid alert email {:?}: {}", email, e))?;
+            if smtp.is_none() {
+                Err("email alerts require smtp settings")?
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the rule applies to the given profile.
+    #[cfg(feature = "watch")]
+    fn applies_to(&self, name: &ProfileName) -> bool {
+        self.profiles.is_empty() || self.profiles.iter().any(|profile| profile == name.as_str())
+    }
+}
+
+impl SmtpSettings {
+    /// Send an email with the given subject and body.
+    #[cfg(feature = "watch")]
+    pub(crate) fn send(&self, to: &str, subject: &str, body: String) -> Result<(), String> {
+        let message = self
+            .message(to, subject)?
+            .body(body)
+            .map_err(|e| e.to_string())?;
+        self.deliver(&message)
+    }
+
+    /// Start an email from the configured sender.
+    pub(crate) fn message(&self, to: &str, subject: &str) -> Result<MessageBuilder, String> {
+        Ok(Message::builder()
+            .from(
+                self.from
+                    .parse()
+                    .map_err(|e| format!("invalid sender: {}", e))?,
+            )
+            .to(to
+                .parse()
+                .map_err(|e| format!("invalid recipient: {}", e))?)
+            .subject(subject))
+    }
+
+    /// Send an email through the server.
+    pub(crate) fn deliver(&self, message: &Message) -> Result<(), String> {
+        let builder = match self.security {
+            SmtpSecurity::Tls => SmtpTransport::relay(&self.host).map_err(|e| e.to_string())?,
+            SmtpSecurity::StartTls => {
+                SmtpTransport::starttls_relay(&self.host).map_err(|e| e.to_string())?
+            }
+            SmtpSecurity::None => SmtpTransport::builder_dangerous(&self.host),
+        };
+        let builder = match self.port {
+            Some(port) => builder.port(port),
+            None => builder,
+        };
+        let builder = match &self.username {
+            Some(username) => builder.credentials(Credentials::new(
+                username.clone(),
+                self.password
+                    .clone()
+                    .or_else(|| secret::lookup(secret::SMTP_PASSWORD))
+                    .unwrap_or_default(),
+            )),
+            None => builder,
+        };
+        builder
+            .build()
+            .send(message)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Record the outcome of a watcher's backup, sending alerts whose threshold
+/// of consecutive failures was just reached.
+///
+/// `error` is the error of a failed backup, or `None` after a successful one.
+#[cfg(feature = "watch")]
+pub(crate) fn record(name: &ProfileName, error: Option<&str>) {
+    let failures = {
+        let mut counts = FAILURES.lock().expect("failure counts poisoned");
+        let counts = counts.get_or_insert_with(HashMap::new);
+        match error {
+            Some(_) => {
+                let count = counts.entry(name.clone()).or_default();
+                *count += 1;
+                *count
+            }
+            None => {
+                counts.remove(name);
+                return;
+            }
+        }
+    };
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("warning: not sending alerts: {}", e);
+            return;
+        }
+    };
+    let subject = format!("savefile: backups of {} are failing", name);
+    let body = format!(
+        "The last {} backups of {} have failed.\n\nLast error: {}\n",
+        failures,
+        name,
+        error.unwrap_or_default()
+    );
+    let rules = config
+        .alerts
+        .iter()
+        .filter(|rule| rule.consecutive_failures == failures && rule.applies_to(name));
+    for rule in rules {
+        if let (Some(to), Some(smtp)) = (&rule.email, &config.smtp) {
+            if let Err(e) = smtp.send(to, &subject, body.clone()) {
+                eprintln!("warning: failed sending alert to {}: {}", to, e);
+            }
+        }
+        if let Some(url) = &rule.webhook {
+            if let Err(e) = Webhook::new(url).post(
+                &body,
+                serde_json::json!({
+                    "profile": name.as_str(),
+                    "alert": "consecutive_failures",
+                    "failures": failures,
+                    "error": error,
+                }),
+            ) {
+                eprintln!("warning: failed posting alert: {}", e);
+            }
+        }
+    }
+}
@@ -0,0 +1,322 @@
+/// Backups stored as standard zip archives.
+///
+/// With a profile's `storage` set to `zip`, each new backup is compressed into
+/// a single `<backup directory>.zip` once it is made, and its directory is
+/// removed. The archives are plain zip files, so they can be extracted with any
+/// zip tool (e.g. Explorer) where savefile isn't available. Cold storage uses
+/// the same format, see [`crate::cold`].
+use std::{
+    collections::HashSet,
+    fs::File,
+    io,
+    path::{Path, PathBuf},
+};
+
+use zip::{write::FileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{
+    backup::remove_backup_dir,
+    cold,
+    error::{BackupError, IoResultExt, Result},
+    filesystem::{backup_dir, profile_save_dir, set_readonly, staging_dir, walk_dirs, walk_files},
+    profile::{Profile, ProfileName},
+    Backup,
+};
+
+/// Extension of backups stored as zip archives.
+pub(crate) const EXTENSION: &str = "zip";
+
+/// How a profile's new backups are stored.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Storage {
+    /// A directory of read-only files.
+    #[default]
+    Dir,
+    /// A single zip archive.
+    Zip,
+}
+
+impl Storage {
+    /// Returns `true` for the default storage.
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Returns the path of a backup's zip archive, whether or not it exists.
+pub fn archive_path(profile: &ProfileName, backup: &Backup) -> Result<PathBuf> {
+    let name = format!("{}.{}", backup.dir_name(), EXTENSION);
+    Ok(profile_save_dir(profile)?.join(name))
+}
+
+/// Returns `true` if the backup is stored as a zip archive.
+pub fn is_zipped(profile: &ProfileName, backup: &Backup) -> Result<bool> {
+    Ok(archive_path(profile, backup)?.is_file())
+}
+
+/// Returns where a backup is stored locally: its zip archive if it has one,
+/// and its directory otherwise.
+pub fn local_path(profile: &ProfileName, backup: &Backup) -> Result<PathBuf> {
+    let path = archive_path(profile, backup)?;
+    match path.is_file() {
+        true => Ok(path),
+        false => backup_dir(profile, backup),
+    }
+}
+
+/// Compress a finished backup's directory into its archive, and remove the
+/// directory.
+pub(crate) fn pack(profile: &ProfileName, backup: &Backup, dir: &Path) -> Result<()> {
+    let path = archive_path(profile, backup)?;
+    // an interrupted backup must not leave a truncated archive behind
+    write_archive_via_partial(dir, &path)?;
+    set_readonly(&path, true)?;
+    remove_backup_dir(dir)
+}
+
+/// Delete a backup's archive.
+pub(crate) fn remove(profile: &ProfileName, backup: &Backup) -> Result<()> {
+    let path = archive_path(profile, backup)?;
+    set_readonly(&path, false)?;
+    std::fs::remove_file(&path).with_path("removing", &path)?;
+    Ok(())
+}
+
+/// Files of a backup, extracted to a staging directory which is removed when
+/// dropped.
+pub struct Extracted {
+    dir: PathBuf,
+}
+
+impl Extracted {
+    /// Prepare an empty staging directory for the backup's files.
+    pub(crate) fn create(profile: &ProfileName, backup: &Backup) -> Result<Self> {
+        Self::create_in(staging_dir(profile)?.join(backup.dir_name()))
+    }
+
+    /// Prepare an empty staging directory at `dir`.
+    pub(crate) fn create_in(dir: PathBuf) -> Result<Self> {
+        let extracted = Self { dir };
+        if extracted.dir.exists() {
+            remove_backup_dir(&extracted.dir)?;
+        }
+        Ok(extracted)
+    }
+
+    /// Returns the directory holding the backup's files.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for Extracted {
+    fn drop(&mut self) {
+        // leftovers are replaced the next time the backup is extracted
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Extract the files of a backup without a directory, i.e. one stored as a zip
+/// archive or in cold storage.
+///
+/// Returns `None` if the backup has a directory, or no files to extract.
+pub fn extract_backup(profile: &ProfileName, backup: &Backup) -> Result<Option<Extracted>> {
+    if backup_dir(profile, backup)?.is_dir() {
+        return Ok(None);
+    }
+    if is_zipped(profile, backup)? {
+        let extracted = Extracted::create(profile, backup)?;
+        extract(&archive_path(profile, backup)?, extracted.dir())?;
+        return Ok(Some(extracted));
+    }
+    if cold::is_cold(profile, backup)? {
+        return cold::thaw(profile, backup).map(Some);
+    }
+    Ok(None)
+}
+
+/// Write a backup's files into a zip archive at `out`, e.g. to share a save
+/// with a friend, who can restore it with [`open_archive`].
+///
+/// Backups stored as zip archives or in cold storage are extracted first.
+pub fn export_backup(profile: &ProfileName, backup: &Backup, out: &Path) -> Result<()> {
+    let extracted = extract_backup(profile, backup)?;
+    let dir = match &extracted {
+        Some(extracted) => extracted.dir().to_owned(),
+        None => backup_dir(profile, backup)?,
+    };
+    // a failed export must not leave a truncated archive behind
+    write_archive_via_partial(&dir, out)
+}
+
+/// Extract a backup archive from outside the profile's backups, e.g. a copy of
+/// a zip backup on a USB stick, so that it can be restored without importing it.
+///
+/// Before anything is extracted, every entry of the archive must have a safe
+/// relative path. The archive must hold at least one file, and all of its
+/// files must be matched by the profile's includes.
+pub fn open_archive(profile: &ProfileName, path: &Path) -> Result<Extracted> {
+    let opened = Profile::open_named(profile)?;
+    check_entry_paths(path)?;
+    let extracted = Extracted::create_in(staging_dir(profile)?.join("archive"))?;
+    extract(path, extracted.dir())?;
+    let files = walk_files(extracted.dir())?;
+    if files.is_empty() {
+        Err(BackupError::EmptyArchive(path.to_owned()))?
+    }
+    let included = opened
+        .with_base(extracted.dir())
+        .expand_includes(true)?
+        .into_iter()
+        .collect::<HashSet<_>>();
+    let unmatched = files
+        .into_iter()
+        .map(|file| {
+            file.strip_prefix(extracted.dir())
+                .expect("walked inside archive")
+                .to_owned()
+        })
+        // files inside an included directory count as included
+        .filter(|rel| !rel.ancestors().any(|path| included.contains(path)))
+        .collect::<Vec<_>>();
+    if !unmatched.is_empty() {
+        Err(BackupError::ArchiveMismatch {
+            archive: path.to_owned(),
+            profile: profile.to_string(),
+            files: unmatched,
+        })?
+    }
+    Ok(extracted)
+}
+
+/// Check that no entry of a zip archive would be written outside the directory
+/// it is extracted to, e.g. `../../autostart.bat` or an absolute path.
+fn check_entry_paths(path: &Path) -> Result<()> {
+    let file = File::open(path).with_path("opening", path)?;
+    let mut zip = ZipArchive::new(file)
+        .map_err(zip_error)
+        .with_path("reading", path)?;
+    for i in 0..zip.len() {
+        let entry = zip
+            .by_index(i)
+            .map_err(zip_error)
+            .with_path("reading", path)?;
+        if entry.enclosed_name().is_none() {
+            Err(BackupError::UnsafeArchivePath {
+                archive: path.to_owned(),
+                entry: entry.name().to_owned(),
+            })?
+        }
+    }
+    Ok(())
+}
+
+/// Compress the files and directories of a backup directory into a zip archive.
+pub(crate) fn write_archive(dir: &Path, path: &Path) -> Result<()> {
+    let file = File::create(path).with_path("creating", path)?;
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    // so that empty directories are restored too
+    for sub in walk_dirs(dir)? {
+        let name = sub.strip_prefix(dir).expect("walked inside backup");
+        let name = name.to_string_lossy().replace('\\', "/");
+        zip.add_directory(name, options)
+            .map_err(zip_error)
+            .with_path("writing", path)?;
+    }
+    for file in walk_files(dir)? {
+        let name = file.strip_prefix(dir).expect("walked inside backup");
+        let name = name.to_string_lossy().replace('\\', "/");
+        zip.start_file(name, options)
+            .map_err(zip_error)
+            .with_path("writing", path)?;
+        let mut reader = File::open(&file).with_path("opening", &file)?;
+        io::copy(&mut reader, &mut zip).with_path("compressing", &file)?;
+    }
+    zip.finish().map_err(zip_error).with_path("writing", path)?;
+    Ok(())
+}
+
+/// Write the files of `dir` into a zip archive at `path`, which is only created
+/// once the archive is complete. The partial archive is removed on failure.
+fn write_archive_via_partial(dir: &Path, path: &Path) -> Result<()> {
+    let partial = path.with_extension("zip.partial");
+    if let Err(e) = write_archive(dir, &partial) {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::rename(&partial, path).with_path("renaming", &partial)?;
+    Ok(())
+}
+
+/// Extract the files and directories of a zip archive into `dest`, replacing
+/// existing files.
+pub(crate) fn extract(path: &Path, dest: &Path) -> Result<()> {
+    std::fs::create_dir_all(dest).with_path("creating", dest)?;
+    let file = File::open(path).with_path("opening", path)?;
+    let mut zip = ZipArchive::new(file)
+        .map_err(zip_error)
+        .with_path("reading", path)?;
+    for i in 0..zip.len() {
+        let mut entry = zip
+            .by_index(i)
+            .map_err(zip_error)
+            .with_path("reading", path)?;
+        // names that would escape the destination are skipped
+        let Some(rel) = entry.enclosed_name().map(Path::to_owned) else {
+            continue;
+        };
+        if entry.is_dir() {
+            let dir = dest.join(rel);
+            std::fs::create_dir_all(&dir).with_path("creating", &dir)?;
+            continue;
+        }
+        let file = dest.join(rel);
+        let parent = file.parent().expect("extracted file has a parent");
+        std::fs::create_dir_all(parent).with_path("creating", parent)?;
+        if file.exists() {
+            set_readonly(&file, false)?;
+        }
+        let mut writer = File::create(&file).with_path("creating", &file)?;
+        io::copy(&mut entry, &mut writer).with_path("extracting", &file)?;
+    }
+    Ok(())
+}
+
+/// Convert an archive error for [`IoResultExt`].
+fn zip_error(e: zip::result::ZipError) -> io::Error {
+    io::Error::other(e)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{
+        database::Database,
+        filesystem::{profile_path, use_test_home},
+    };
+
+    #[test]
+    fn failed_exports_leave_no_partial_archive() {
+        let home = use_test_home();
+        let profile = ProfileName::new("archive-export").unwrap();
+        let settings = Profile::builder(home).build().unwrap();
+        settings.save(profile_path(&profile).unwrap()).unwrap();
+        let db = Database::open_for_profile(&profile).unwrap();
+        // the backup's directory is missing, so writing the archive fails
+        let backup = db
+            .backup_table(&profile)
+            .unwrap()
+            .insert("", &Utc::now())
+            .unwrap();
+
+        let out = tempfile::tempdir().unwrap();
+        let path = out.path().join("save.zip");
+        assert!(export_backup(&profile, &backup, &path).is_err());
+        assert!(!path.exists());
+        assert!(!path.with_extension("zip.partial").exists());
+    }
+}
@@ -0,0 +1,263 @@
+/// Append-only log of the operations that change backups.
+///
+/// Every backup, restore, delete and prune is recorded in `audit.jsonl` in the
+/// install directory, one JSON object per line, along with the process that
+/// did it and whether it succeeded. Entries are only ever appended, so the log
+/// shows who touched a save set even after its backups are gone.
+use std::{io::Write, sync::Mutex};
+
+use chrono::Utc;
+
+use crate::{
+    backup::{BackupCause, BackupId, Timestamp},
+    error::{IoResultExt, Result},
+    filesystem::audit_log_path,
+    profile::ProfileName,
+};
+
+/// The kind of process recorded as doing the operations of this process.
+static ACTOR: Mutex<Actor> = Mutex::new(Actor::Library);
+
+/// The kind of process that did an operation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Actor {
+    /// A program using this library, which didn't call [`set_actor`].
+    #[default]
+    Library,
+    /// A command run by the user.
+    Cli,
+    /// A watcher started by the user, see [`crate::watcher`].
+    Watcher,
+    /// Watchers running as a service, e.g. in a container.
+    Daemon,
+}
+
+impl Actor {
+    /// Returns the value stored in the log and shown to users.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Actor::Library => "library",
+            Actor::Cli => "cli",
+            Actor::Watcher => "watcher",
+            Actor::Daemon => "daemon",
+        }
+    }
+}
+
+impl std::fmt::Display for Actor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// An operation recorded in the log.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Operation {
+    /// A backup was made, see [`crate::backup()`].
+    Create,
+    /// A backup was restored, see [`crate::restore_backup`].
+    Restore,
+    /// Backups were deleted on request.
+    Delete,
+    /// Old backups were deleted to keep only the most recent ones.
+    Prune,
+}
+
+impl Operation {
+    /// Returns the value stored in the log and shown to users.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Operation::Create => "create",
+            Operation::Restore => "restore",
+            Operation::Delete => "delete",
+            Operation::Prune => "prune",
+        }
+    }
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A line of the log.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AuditEntry {
+    /// When the operation finished.
+    pub time: Timestamp,
+    /// Name of the profile whose backups were changed.
+    pub profile: String,
+    /// What was done to the profile's backups.
+    pub operation: Operation,
+    /// The kind of process that did the operation, see [`set_actor`].
+    pub actor: Actor,
+    /// The backups created, restored or deleted. Empty if a backup failed
+    /// before it got an ID.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backups: Vec<BackupId>,
+    /// Why the backup was made, for [`Operation::Create`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<BackupCause>,
+    /// Why the operation failed, or `None` if it succeeded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditEntry {
+    /// Returns `true` if the operation succeeded.
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Set the kind of process recorded as doing the operations of this process.
+///
+/// Frontends call this once at startup; the default is [`Actor::Library`].
+pub fn set_actor(actor: Actor) {
+    *ACTOR.lock().unwrap_or_else(|e| e.into_inner()) = actor;
+}
+
+/// Returns the kind of process recorded as doing the operations of this process.
+pub fn actor() -> Actor {
+    *ACTOR.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Record the outcome of an operation.
+///
+/// The operation itself already happened, so failing to write the log is only
+/// reported.
+pub(crate) fn record<T>(
+    profile: &ProfileName,
+    operation: Operation,
+    backups: &[BackupId],
+    cause: Option<BackupCause>,
+    result: &Result<T>,
+) {
+    let entry = AuditEntry {
+        time: Utc::now(),
+        profile: profile.to_string(),
+        operation,
+        actor: actor(),
+        backups: backups.to_vec(),
+        cause,
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Err(e) = append(&entry) {
+        eprintln!("warning: failed writing audit log: {}", e);
+    }
+}
+
+/// Append an entry to the log.
+fn append(entry: &AuditEntry) -> Result<()> {
+    let path = audit_log_path()?;
+    let mut line = serde_json::to_string(entry).expect("entries are always serializable");
+    line.push('\n');
+    // a single write of the whole line, so that entries of concurrent
+    // processes don't interleave
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_path("writing", &path)
+}
+
+/// Read the log, oldest entries first.
+///
+/// With `profile`, only the entries of that profile are returned. Lines that
+/// can't be read, such as one cut short by a crash, are skipped.
+pub fn read(profile: Option<&ProfileName>) -> Result<Vec<AuditEntry>> {
+    let path = audit_log_path()?;
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => Err(e).with_path("reading", &path)?,
+    };
+    Ok(text
+        .lines()
+        .filter_map(|line| serde_json::from_str::<AuditEntry>(line).ok())
+        .filter(|entry| profile.is_none_or(|p| entry.profile == **p))
+        .collect())
+}
+
+/// A restore found in the log, see [`last_restore`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Restore {
+    /// The restored backup.
+    pub restored: BackupId,
+    /// When the restore finished.
+    pub time: Timestamp,
+    /// The backup of the live files made right before the restore, if any.
+    pub snapshot: Option<BackupId>,
+}
+
+/// Find the most recent successful restore of a profile in the log.
+///
+/// Its snapshot is the last successful [`BackupCause::PreRestore`] backup
+/// logged since the restore before it.
+pub fn last_restore(profile: &ProfileName) -> Result<Option<Restore>> {
+    let entries = read(Some(profile))?;
+    let Some(index) = entries
+        .iter()
+        .rposition(|e| e.operation == Operation::Restore && e.succeeded())
+    else {
+        return Ok(None);
+    };
+    let entry = &entries[index];
+    let Some(&restored) = entry.backups.first() else {
+        return Ok(None);
+    };
+    let snapshot = entries[..index]
+        .iter()
+        .rev()
+        .take_while(|e| !(e.operation == Operation::Restore && e.succeeded()))
+        .find(|e| {
+            e.operation == Operation::Create
+                && e.cause == Some(BackupCause::PreRestore)
+                && e.succeeded()
+        })
+        .and_then(|e| e.backups.first().copied());
+    Ok(Some(Restore {
+        restored,
+        time: entry.time,
+        snapshot,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{error::BackupError, filesystem::use_test_home};
+
+    #[test]
+    fn last_restore_finds_its_snapshot() {
+        use_test_home();
+        let profile = ProfileName::new("audit-restore").unwrap();
+        let id = |id| BackupId::new(id).unwrap();
+        let ok: Result<()> = Ok(());
+        let create =
+            |backup, cause| record(&profile, Operation::Create, &[id(backup)], Some(cause), &ok);
+        let restore = |backup, result: Result<()>| {
+            record(&profile, Operation::Restore, &[id(backup)], None, &result)
+        };
+        assert_eq!(last_restore(&profile).unwrap(), None);
+
+        create(1, BackupCause::Manual);
+        create(2, BackupCause::PreRestore);
+        restore(1, Ok(()));
+        let first = last_restore(&profile).unwrap().unwrap();
+        assert_eq!((first.restored, first.snapshot), (id(1), Some(id(2))));
+
+        // failed restores are skipped
+        restore(2, Err(BackupError::NoSuchBackup(id(2)).into()));
+        assert_eq!(last_restore(&profile).unwrap(), Some(first));
+
+        // snapshots made before the previous restore don't count
+        restore(2, Ok(()));
+        let second = last_restore(&profile).unwrap().unwrap();
+        assert_eq!((second.restored, second.snapshot), (id(2), None));
+    }
+}
@@ -0,0 +1,1158 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use ulid::Ulid;
+
+use crate::{
+    archive::{self, Storage},
+    audit::{self, Operation},
+    cold,
+    config::Config,
+    control,
+    copy::{create_dirs, CopyEngine, Overwrite},
+    database::{BackupField, Database},
+    environment::Environment,
+    error::{BackupError, IoResultExt, Result},
+    events::{self, EventKind},
+    filesystem::{
+        backup_dir, hash_file, profile_save_dir, profile_save_dir_of, remote_mirror_dir,
+        set_readonly, walk_dirs, walk_files,
+    },
+    mirror, onedrive,
+    plugin::{BackupContext, Pipeline},
+    profile::{EmptyDirs, LimitAction, Profile, ProfileName},
+    remote, search,
+    size::ByteSize,
+    status_file, store,
+    validate::run_validators,
+    webhook,
+};
+
+/// Timestamps are stored in UTC with an explicit offset.
+///
+/// Older installs stored naive timestamps, which are interpreted as UTC
+/// when read and rewritten by `savefile migrate`.
+pub type Timestamp = chrono::DateTime<Utc>;
+
+/// Tag stored for backups without a tag.
+pub(crate) const UNTAGGED: &str = "unused";
+
+/// ID of a backup.
+///
+/// IDs are unique within a profile and start at 1.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(try_from = "u32", into = "u32")]
+pub struct BackupId(u32);
+
+impl BackupId {
+    /// Validate the given ID.
+    pub fn new(id: u32) -> Result<Self, BackupError> {
+        if id == 0 {
+            Err(BackupError::InvalidId(id.to_string()))
+        } else {
+            Ok(Self(id))
+        }
+    }
+
+    /// Returns the ID as a number.
+    pub fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl std::fmt::Display for BackupId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for BackupId {
+    type Err = BackupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let id = s
+            .trim()
+            .parse()
+            .map_err(|_| BackupError::InvalidId(s.to_owned()))?;
+        Self::new(id)
+    }
+}
+
+impl TryFrom<u32> for BackupId {
+    type Error = BackupError;
+
+    fn try_from(id: u32) -> Result<Self, Self::Error> {
+        Self::new(id)
+    }
+}
+
+impl From<BackupId> for u32 {
+    fn from(id: BackupId) -> Self {
+        id.0
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for BackupId {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.0))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for BackupId {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        let id = u32::column_result(value)?;
+        Self::new(id).map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// Globally unique ID of a backup, as a [ULID](https://github.com/ulid/spec).
+///
+/// Unlike [`BackupId`], which is a short index for display and commands,
+/// these never collide between profiles or machines, so backups keep their
+/// identity when histories are merged or exports are imported again.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub struct BackupUid(Ulid);
+
+impl BackupUid {
+    /// Generate a new ID for a backup made at the given time.
+    pub fn new(timestamp: &Timestamp) -> Self {
+        Self(Ulid::from_datetime((*timestamp).into()))
+    }
+}
+
+impl std::fmt::Display for BackupUid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::str::FromStr for BackupUid {
+    type Err = BackupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let uid = Ulid::from_string(s.trim()).map_err(|_| BackupError::InvalidId(s.to_owned()))?;
+        Ok(Self(uid))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for BackupUid {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.to_string()))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for BackupUid {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        value
+            .as_str()?
+            .parse()
+            .map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// A backup given by either its [`BackupId`] or its [`BackupUid`].
+///
+/// Parses from either form, so that commands accept both.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BackupRef {
+    /// The backup's ID within its profile.
+    Id(BackupId),
+    /// The backup's globally unique ID.
+    Uid(BackupUid),
+}
+
+impl BackupRef {
+    /// Returns whether this refers to the given backup.
+    pub fn matches(&self, backup: &Backup) -> bool {
+        match self {
+            Self::Id(id) => backup.id() == *id,
+            Self::Uid(uid) => backup.uid() == Some(*uid),
+        }
+    }
+}
+
+impl From<BackupId> for BackupRef {
+    fn from(id: BackupId) -> Self {
+        Self::Id(id)
+    }
+}
+
+impl From<BackupUid> for BackupRef {
+    fn from(uid: BackupUid) -> Self {
+        Self::Uid(uid)
+    }
+}
+
+impl std::fmt::Display for BackupRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Id(id) => id.fmt(f),
+            Self::Uid(uid) => uid.fmt(f),
+        }
+    }
+}
+
+impl std::str::FromStr for BackupRef {
+    type Err = BackupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.parse()
+            .map(Self::Id)
+            .or_else(|_| s.parse().map(Self::Uid))
+    }
+}
+
+/// Whether a backup's files passed the profile's validators.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupStatus {
+    /// The profile had no validators when the backup was made.
+    #[default]
+    Unchecked,
+    /// The files passed all validators, so the backup is known to be good.
+    Valid,
+}
+
+impl BackupStatus {
+    /// Returns the value stored in the database.
+    #[cfg(feature = "sqlite")]
+    fn as_str(self) -> &'static str {
+        match self {
+            BackupStatus::Unchecked => "unchecked",
+            BackupStatus::Valid => "valid",
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for BackupStatus {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.as_str()))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for BackupStatus {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        // rows written before validation existed have no status
+        match value {
+            rusqlite::types::ValueRef::Null => Ok(BackupStatus::Unchecked),
+            value => match value.as_str()? {
+                "valid" => Ok(BackupStatus::Valid),
+                _ => Ok(BackupStatus::Unchecked),
+            },
+        }
+    }
+}
+
+/// Why a backup was made.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackupCause {
+    /// The backup was made before causes were recorded.
+    #[default]
+    Unknown,
+    /// The watcher saw the included files change.
+    Watcher,
+    /// Requested by the user, e.g. with `savefile backup create`.
+    Manual,
+    /// Started by a scheduler, with `savefile backup create --scheduled`.
+    Scheduled,
+    /// Snapshot of the live files before a backup was restored over them.
+    PreRestore,
+    /// The watcher detected a game update.
+    PreUpdate,
+}
+
+impl BackupCause {
+    /// Returns the value stored in the database and shown to users.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BackupCause::Unknown => "unknown",
+            BackupCause::Watcher => "watcher",
+            BackupCause::Manual => "manual",
+            BackupCause::Scheduled => "scheduled",
+            BackupCause::PreRestore => "pre-restore",
+            BackupCause::PreUpdate => "pre-update",
+        }
+    }
+}
+
+impl std::fmt::Display for BackupCause {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for BackupCause {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [
+            BackupCause::Unknown,
+            BackupCause::Watcher,
+            BackupCause::Manual,
+            BackupCause::Scheduled,
+            BackupCause::PreRestore,
+            BackupCause::PreUpdate,
+        ]
+        .into_iter()
+        .find(|cause| cause.as_str() == s)
+        .ok_or_else(|| {
+            format!("unknown cause {:?} (expected watcher, manual, scheduled, pre-restore, pre-update or unknown)", s)
+        })
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for BackupCause {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        Ok(rusqlite::types::ToSqlOutput::from(self.as_str()))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for BackupCause {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        // rows written before causes existed have none
+        match value {
+            rusqlite::types::ValueRef::Null => Ok(BackupCause::Unknown),
+            value => Ok(value.as_str()?.parse().unwrap_or_default()),
+        }
+    }
+}
+
+/// Lightweight representation of a single backup.
+///
+/// Note: The ID of each backup is unique to the profile,
+/// meaning that two different profiles can have backups
+/// with the same ID. The [`BackupUid`] is unique everywhere.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Backup {
+    /// The backup's ID.
+    id: BackupId,
+    /// The backup's tag, or [`UNTAGGED`].
+    tag: String,
+    /// The backup's time of creation.
+    timestamp: Timestamp,
+    /// Name of the backup's directory, if it isn't the bare ID.
+    #[serde(default)]
+    dir: Option<String>,
+    /// Version of the game or app at the time of the backup, if captured.
+    #[serde(default)]
+    version: Option<String>,
+    /// Whether the backup's files passed validation.
+    #[serde(default)]
+    status: BackupStatus,
+    /// Why the backup was made.
+    #[serde(default)]
+    cause: BackupCause,
+    /// Number of files that differ from the previous backup, if recorded.
+    #[serde(default)]
+    changed: Option<u32>,
+    /// The machine the backup was made on, if captured.
+    #[serde(default)]
+    env: Option<Environment>,
+    /// Globally unique ID, if assigned.
+    #[serde(default)]
+    uid: Option<BackupUid>,
+}
+
+impl Backup {
+    /// Create a new backup representation.
+    ///
+    /// This function is for internal use only.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        id: BackupId,
+        tag: String,
+        timestamp: Timestamp,
+        dir: Option<String>,
+        version: Option<String>,
+        status: BackupStatus,
+        cause: BackupCause,
+        changed: Option<u32>,
+        env: Option<Environment>,
+        uid: Option<BackupUid>,
+    ) -> Self {
+        Self {
+            id,
+            tag,
+            timestamp,
+            dir,
+            version,
+            status,
+            cause,
+            changed,
+            env,
+            uid,
+        }
+    }
+
+    /// Returns the backup's ID.
+    ///
+    /// This ID is unique to the profile, not the entire database, and may be
+    /// given to a new backup once this one is deleted. See [`Backup::uid`]
+    /// for an ID that is unique everywhere.
+    pub fn id(&self) -> BackupId {
+        self.id
+    }
+
+    /// Returns the backup's globally unique ID.
+    ///
+    /// This is `None` for backups made before unique IDs were assigned, until
+    /// `savefile migrate` assigns them.
+    pub fn uid(&self) -> Option<BackupUid> {
+        self.uid
+    }
+
+    /// Returns the backup with its ID replaced, e.g. to merge it into another
+    /// history whose backup with that ID is a different one.
+    ///
+    /// The directory name is kept, even for backups whose directory is named
+    /// after their old ID.
+    pub(crate) fn with_id(self, id: BackupId) -> Self {
+        let dir = Some(self.dir_name());
+        Self { id, dir, ..self }
+    }
+
+    /// Returns the name of the backup's directory.
+    ///
+    /// Backups created before directory names became configurable use their ID.
+    pub fn dir_name(&self) -> String {
+        self.dir.clone().unwrap_or_else(|| self.id.to_string())
+    }
+
+    /// Returns the backup's tag, if it has one.
+    ///
+    /// Tags mark backups made for a particular reason, such as `pre-update`
+    /// for backups made when the game was about to be updated.
+    pub fn tag(&self) -> Option<&str> {
+        Some(self.tag.as_str()).filter(|tag| *tag != UNTAGGED)
+    }
+
+    /// Returns the backup's timestamp.
+    ///
+    /// This is the time at which the backup was created.
+    pub fn timestamp(&self) -> Timestamp {
+        self.timestamp
+    }
+
+    /// Returns the version of the game or app the backup was made with, if known.
+    ///
+    /// See [`Profile::version`].
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    /// Returns whether the backup's files passed validation.
+    pub fn status(&self) -> BackupStatus {
+        self.status
+    }
+
+    /// Returns why the backup was made.
+    pub fn cause(&self) -> BackupCause {
+        self.cause
+    }
+
+    /// Returns how many files differ from the previous backup.
+    ///
+    /// This is `None` for backups made before the count was recorded.
+    pub fn changed_files(&self) -> Option<u32> {
+        self.changed
+    }
+
+    /// Returns the machine the backup was made on, if captured.
+    ///
+    /// See [`Profile::capture_env`].
+    pub fn environment(&self) -> Option<&Environment> {
+        self.env.as_ref()
+    }
+
+    /// Returns the tag as stored, which is [`UNTAGGED`] for untagged backups.
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn raw_tag(&self) -> &str {
+        &self.tag
+    }
+
+    /// Returns the directory name as stored, which is `None` if it is the bare ID.
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn raw_dir(&self) -> Option<&str> {
+        self.dir.as_deref()
+    }
+
+    /// Update one of the backup's fields.
+    pub(crate) fn set(&mut self, field: BackupField<'_>) {
+        match field {
+            BackupField::Dir(dir) => self.dir = Some(dir.to_owned()),
+            BackupField::Version(version) => self.version = Some(version.to_owned()),
+            BackupField::Status(status) => self.status = status,
+            BackupField::Cause(cause) => self.cause = cause,
+            BackupField::Changed(count) => self.changed = Some(count),
+            BackupField::Environment(env) => self.env = Some(env.clone()),
+            BackupField::Uid(uid) => self.uid = Some(uid),
+        }
+    }
+}
+
+/// A single file contained in a backup.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct FileRecord {
+    /// Path of the file, relative to the profile's base directory.
+    pub path: PathBuf,
+    /// SHA-256 hash of the file's contents, hex-encoded.
+    pub hash: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Whether the file is new or differs from the previous backup.
+    pub changed: bool,
+    /// Whether a change to this file triggered the backup.
+    pub triggered: bool,
+}
+
+/// Create a backup of the given profile.
+///
+/// This function will create a new backup entry in the database and copy all
+/// files specified by the profile into the backup directory.
+///
+/// `triggers` are the absolute paths of the files whose changes caused the
+/// backup (empty for manual backups). They are recorded in the backup's manifest
+/// along with which files changed since the previous backup.
+///
+/// `tag` marks why the backup was made, see [`Backup::tag`], and `cause`
+/// records what started it.
+///
+/// Files of profiles with an `sftp://` base are first pulled into a local
+/// mirror, see [`crate::remote`].
+///
+/// With the profile's `storage` set to `zip`, the finished backup is
+/// compressed into a zip archive, see [`crate::archive`].
+///
+/// Files that are only available online are left out unless the profile's
+/// `cloud_files` is `hydrate`, see [`crate::onedrive`].
+///
+/// The profile's webhooks are notified of the outcome, which is also recorded
+/// in the audit log, see [`crate::audit`].
+pub fn backup(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    triggers: &[PathBuf],
+    tag: Option<&str>,
+    cause: BackupCause,
+) -> Result<BackupId> {
+    let result = create_backup(db, profile, name, triggers, tag, cause);
+    webhook::notify(profile.webhooks(), db, name, &result);
+    let ids = result.as_ref().map_or(Vec::new(), |id| vec![*id]);
+    audit::record(name, Operation::Create, &ids, Some(cause), &result);
+    result
+}
+
+/// Create a backup of the given profile, see [`backup`].
+fn create_backup(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    triggers: &[PathBuf],
+    tag: Option<&str>,
+    cause: BackupCause,
+) -> Result<BackupId> {
+    let mirrored;
+    let profile = match profile.remote() {
+        Some(remote) => {
+            let mirror = remote_mirror_dir(name)?;
+            remote::pull(&remote, &mirror)?;
+            mirrored = profile.with_base(mirror);
+            &mirrored
+        }
+        None => profile,
+    };
+    let paths = apply_empty_dirs(profile, profile.expand_includes(true)?)?;
+    let paths = onedrive::apply_cloud_files(profile, paths);
+    let paths = apply_limits(profile, paths)?;
+    let config = Config::load()?;
+    let pipeline = Pipeline::build(profile)?;
+    let backup_table = db.backup_table(name)?;
+    // validators can't look at a previous backup in cold storage
+    let previous = match backup_table.latest()? {
+        Some(latest) => Some(backup_dir(name, &latest)?).filter(|dir| dir.is_dir()),
+        None => None,
+    };
+    let problems = run_validators(
+        profile.validators(),
+        profile.base(),
+        &paths,
+        previous.as_deref(),
+    )?;
+    if !problems.is_empty() {
+        Err(BackupError::ValidationFailed {
+            problems,
+            last_good: backup_table.latest_valid()?.map(|backup| backup.id()),
+        })?
+    }
+    let timestamp = Utc::now();
+    let id = backup_table
+        .insert(tag.unwrap_or(UNTAGGED), &timestamp)?
+        .id();
+    let uid = BackupUid::new(&timestamp);
+    backup_table.set_uid(id, uid)?;
+    let dir = config.backup_dir_name(id, uid, &timestamp);
+    backup_table.set_dir(id, &dir)?;
+    backup_table.set_cause(id, cause)?;
+    if let Some(version) = capture_version(profile) {
+        backup_table.set_version(id, &version)?;
+    }
+    if profile.capture_env() {
+        backup_table.set_environment(id, &Environment::current())?;
+    }
+    if !profile.validators().is_empty() {
+        backup_table.set_status(id, BackupStatus::Valid)?;
+    }
+    let backup_dir = profile_save_dir_of(name, profile)?.join(dir);
+    std::fs::create_dir_all(&backup_dir).with_path("creating backup directory", &backup_dir)?;
+    CopyEngine::new()
+        .overwrite(Overwrite::Never)
+        .io_limit(profile.io_limit().or(config.io_limit))
+        .ntfs_metadata(profile.ntfs_metadata())
+        .dest_name(format!("backup {}", id))
+        .copy_paths(profile.base(), &backup_dir, &paths)?;
+    let triggers = triggers
+        .iter()
+        .filter_map(|path| path.strip_prefix(profile.base()).ok())
+        .collect::<HashSet<_>>();
+    let file_table = db.file_table(name)?;
+    let previous = match backup_table.previous_id(id)? {
+        Some(prev) => file_table.select_backup(prev)?,
+        None => Vec::new(),
+    };
+    let manifest = build_manifest(&previous, &backup_dir, &triggers)?;
+    file_table.insert(id, &manifest)?;
+    backup_table.set_changed(id, count_changes(&manifest, &previous))?;
+    if profile.content_index() {
+        // a failed index shouldn't fail the backup, which can be reindexed later
+        if let Err(e) = search::index_backup(name, &backup_dir, &manifest) {
+            eprintln!("warning: failed indexing file contents: {}", e);
+        }
+    }
+    // protect the finished backup against accidental modification
+    set_tree_readonly(&backup_dir, true)?;
+    let backup = backup_table
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    store::put_remote_copies(profile, name, &backup, &backup_dir);
+    mirror::mirror_backup(profile, &backup, &backup_dir);
+    if let Some(status) = &config.status_file {
+        if let Err(e) = status_file::write(status, name, &backup, paths.len()) {
+            eprintln!("warning: failed writing status file: {}", e);
+        }
+    }
+    if !pipeline.is_empty() {
+        pipeline.run(BackupContext {
+            profile_name: name.clone(),
+            profile: profile.clone(),
+            id,
+            timestamp,
+            tag: tag.map(str::to_owned),
+            artifacts: vec![backup_dir.clone()],
+            dir: backup_dir.clone(),
+            files: paths,
+        })?;
+    }
+    if profile.storage() == Storage::Zip {
+        archive::pack(name, &backup, &backup_dir)?;
+    }
+    Ok(id)
+}
+
+/// Read the profile's current version, if it specifies where to find it.
+///
+/// A failure is reported as a warning, since it shouldn't prevent the backup.
+fn capture_version(profile: &Profile) -> Option<String> {
+    match profile.version()?.capture(profile.base()) {
+        Ok(version) => version,
+        Err(e) => {
+            eprintln!("warning: failed capturing version: {}", e);
+            None
+        }
+    }
+}
+
+/// Apply the profile's [`EmptyDirs`] setting to the given paths (relative to the base).
+fn apply_empty_dirs(profile: &Profile, mut paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    match profile.empty_dirs() {
+        EmptyDirs::Matched => {}
+        // directories holding included files are created along with them
+        EmptyDirs::Skip => paths.retain(|rel| !profile.base().join(rel).is_dir()),
+        EmptyDirs::Preserve if profile.base().is_dir() => {
+            for dir in walk_dirs(profile.base())? {
+                let rel = dir
+                    .strip_prefix(profile.base())
+                    .expect("walked inside base");
+                paths.push(rel.to_owned());
+            }
+            paths.sort();
+            paths.dedup();
+        }
+        EmptyDirs::Preserve => {}
+    }
+    Ok(paths)
+}
+
+/// Check the given paths (relative to the base) against the profile's size limits.
+///
+/// Depending on the profile's [`LimitAction`], offending files are either left out
+/// with a warning, or the whole backup fails.
+fn apply_limits(profile: &Profile, paths: Vec<PathBuf>) -> Result<Vec<PathBuf>> {
+    let limits = profile.limits();
+    if limits.is_empty() {
+        return Ok(paths);
+    }
+    let mut total = 0;
+    let mut kept = Vec::with_capacity(paths.len());
+    for rel in paths {
+        let abs = profile.base().join(&rel);
+        let size = match std::fs::metadata(&abs) {
+            Ok(meta) if meta.is_file() => meta.len(),
+            _ => {
+                kept.push(rel);
+                continue;
+            }
+        };
+        let exceeded = if let Some(limit) = limits.max_file_size.filter(|l| size > l.0) {
+            BackupError::FileTooLarge {
+                path: abs,
+                size: ByteSize(size),
+                limit,
+            }
+        } else if let Some(limit) = limits.max_total_size.filter(|l| total + size > l.0) {
+            BackupError::BackupTooLarge {
+                size: ByteSize(total + size),
+                limit,
+            }
+        } else {
+            total += size;
+            kept.push(rel);
+            continue;
+        };
+        match limits.on_exceed {
+            LimitAction::Abort => Err(exceeded)?,
+            LimitAction::Skip => eprintln!("warning: skipping {}: {}", rel.display(), exceeded),
+        }
+    }
+    Ok(kept)
+}
+
+/// Build the file manifest of a freshly copied backup.
+///
+/// Files are compared by hash against the manifest of the previous backup.
+fn build_manifest(
+    previous: &[FileRecord],
+    backup_dir: &Path,
+    triggers: &HashSet<&Path>,
+) -> Result<Vec<FileRecord>> {
+    let previous = previous
+        .iter()
+        .map(|file| (&file.path, &file.hash))
+        .collect::<HashMap<_, _>>();
+    walk_files(backup_dir)?
+        .into_iter()
+        .map(|file| {
+            let path = file
+                .strip_prefix(backup_dir)
+                .expect("walked inside backup directory")
+                .to_owned();
+            let hash = hash_file(&file)?;
+            let size = std::fs::metadata(&file)
+                .with_path("reading metadata of", &file)?
+                .len();
+            Ok(FileRecord {
+                changed: previous.get(&path) != Some(&&hash),
+                triggered: triggers.contains(path.as_path()),
+                path,
+                hash,
+                size,
+            })
+        })
+        .collect()
+}
+
+/// Count the files of a backup that differ from the previous backup.
+///
+/// This includes changed and added files, as well as files the previous backup
+/// had that are now gone.
+pub(crate) fn count_changes(manifest: &[FileRecord], previous: &[FileRecord]) -> u32 {
+    let current = manifest
+        .iter()
+        .map(|file| file.path.as_path())
+        .collect::<HashSet<_>>();
+    let changed = manifest.iter().filter(|file| file.changed).count();
+    let removed = previous
+        .iter()
+        .filter(|file| !current.contains(file.path.as_path()))
+        .count();
+    (changed + removed) as u32
+}
+
+/// Delete the backup with the given ID.
+///
+/// This removes the backup from the database and deletes the backup's directory,
+/// its zip archive, or its archive in cold storage, and its copy in the profile's mirror
+/// directory.
+pub fn delete_one_backup(db: &Database, profile: &ProfileName, id: BackupId) -> Result<()> {
+    delete_backups(db, profile, &[id])
+}
+
+/// Delete the backups with the given IDs.
+///
+/// The directories, archives and mirrored copies of the backups are deleted on
+/// several threads, and the backups are then removed from the database in one
+/// go, like with [`delete_one_backup`]. Backups whose files couldn't all be
+/// deleted stay in the database, so that deleting them can be retried.
+pub fn delete_backups(db: &Database, profile: &ProfileName, ids: &[BackupId]) -> Result<()> {
+    let result = remove_backups(db, profile, ids);
+    audit::record(profile, Operation::Delete, ids, None, &result);
+    result
+}
+
+/// Delete old backups to keep only the most recent ones.
+///
+/// This is the same as [`delete_backups`], but recorded as a prune in the
+/// audit log.
+pub fn prune_backups(db: &Database, profile: &ProfileName, ids: &[BackupId]) -> Result<()> {
+    let result = remove_backups(db, profile, ids);
+    audit::record(profile, Operation::Prune, ids, None, &result);
+    if result.is_ok() && !ids.is_empty() {
+        let ids = ids.to_vec();
+        events::publish(profile, EventKind::Pruned { ids });
+    }
+    result
+}
+
+/// Delete the backups with the given IDs, see [`delete_backups`].
+fn remove_backups(db: &Database, profile: &ProfileName, ids: &[BackupId]) -> Result<()> {
+    let backup_table = db.backup_table(profile)?;
+    let backups = ids
+        .iter()
+        .map(|id| {
+            let backup = backup_table
+                .select_id(*id)?
+                .ok_or(BackupError::NoSuchBackup(*id))?;
+            let was_cold = cold::is_cold(profile, &backup)?;
+            let was_zipped = archive::is_zipped(profile, &backup)?;
+            Ok((backup, was_cold, was_zipped))
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    // the mirror can't be found without the profile
+    let opened = Profile::open_named(profile).ok();
+    let remove = |(backup, was_cold, was_zipped): &(Backup, bool, bool)| -> Result<()> {
+        let backup_dir = backup_dir(profile, backup)?;
+        if *was_cold {
+            cold::remove(profile, backup)?;
+        }
+        if *was_zipped {
+            archive::remove(profile, backup)?;
+        }
+        // may be gone already if an earlier attempt failed later on
+        if backup_dir.is_dir() {
+            remove_backup_dir(&backup_dir)?;
+        }
+        if let Some(opened) = &opened {
+            mirror::remove_mirrored(opened, backup)?;
+        }
+        Ok(())
+    };
+    let remove = &remove;
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let chunk_size = backups.len().div_ceil(threads).max(1);
+    let results = std::thread::scope(|scope| {
+        backups
+            .chunks(chunk_size)
+            .map(|chunk| {
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|backup| (backup.0.id(), remove(backup)))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("backup cleanup panicked"))
+            .collect::<Vec<_>>()
+    });
+    let removed = results
+        .iter()
+        .filter(|(_, result)| result.is_ok())
+        .map(|(id, _)| *id)
+        .collect::<Vec<_>>();
+    backup_table.delete_many(&removed)?;
+    results.into_iter().try_for_each(|(_, result)| result)
+}
+
+/// Delete all backups with the given ID.
+///
+/// This removes all backups from the database and deletes all backup directories,
+/// as well as the zip archives, the archives of backups in cold storage and the copies in the
+/// profile's mirror directory.
+pub fn delete_all_backups(db: &Database, profile: &ProfileName) -> Result<()> {
+    let ids = db
+        .backup_table(profile)?
+        .iter()
+        .map(|backup| backup.map(|b| b.id()))
+        .collect::<Result<Vec<_>>>()?;
+    let result = remove_all_backups(db, profile);
+    audit::record(profile, Operation::Delete, &ids, None, &result);
+    result
+}
+
+/// Delete all backups of a profile, see [`delete_all_backups`].
+fn remove_all_backups(db: &Database, profile: &ProfileName) -> Result<()> {
+    let backup_table = db.backup_table(profile)?;
+    let profile_dir = profile_save_dir(profile)?;
+    let opened = Profile::open_named(profile).ok();
+    for backup in backup_table.iter() {
+        let backup = backup?;
+        if cold::is_cold(profile, &backup)? {
+            cold::remove(profile, &backup)?;
+        }
+        if archive::is_zipped(profile, &backup)? {
+            archive::remove(profile, &backup)?;
+        }
+        if let Some(opened) = &opened {
+            mirror::remove_mirrored(opened, &backup)?;
+        }
+    }
+    backup_table.drop()?;
+    db.file_table(profile)?.drop()?;
+    if !profile_dir.exists() {
+        return Ok(());
+    }
+    // only remove the backup directories, since the profile's
+    // own database (if any) may live alongside them
+    for entry in std::fs::read_dir(&profile_dir).with_path("reading", &profile_dir)? {
+        let path = entry.with_path("reading", &profile_dir)?.path();
+        if path.is_dir() {
+            remove_backup_dir(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Restore the backup with the given ID.
+///
+/// This function will copy all files from the backup directory into the profile's
+/// base directory, replacing existing files. Use [`crate::diff::diff_backup`]
+/// to find out beforehand which files will be replaced.
+///
+/// Backups of profiles with an `sftp://` base are restored to the local mirror
+/// and uploaded to the server.
+///
+/// Backups stored as zip archives or in cold storage are extracted first, see
+/// [`archive::extract_backup`].
+///
+/// Empty directories in the backup are recreated unless the profile's
+/// `empty_dirs` setting is [`EmptyDirs::Skip`].
+///
+/// A running watcher of the profile is paused during the restore, see
+/// [`control::pause_during`].
+///
+/// The outcome is recorded in the audit log, see [`crate::audit`].
+pub fn restore_backup(db: &Database, profile: &ProfileName, id: BackupId) -> Result<()> {
+    let result = restore_files(db, profile, id);
+    audit::record(profile, Operation::Restore, &[id], None, &result);
+    result
+}
+
+/// Restore the backup with the given ID, see [`restore_backup`].
+fn restore_files(db: &Database, profile: &ProfileName, id: BackupId) -> Result<()> {
+    let backup = db
+        .backup_table(profile)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let extracted = archive::extract_backup(profile, &backup)?;
+    let src_dir = match &extracted {
+        Some(extracted) => extracted.dir().to_owned(),
+        None => backup_dir(profile, &backup)?,
+    };
+    restore_dir(profile, &src_dir)
+}
+
+/// Restore the files of an archive opened with [`archive::open_archive`],
+/// without importing it into the profile's backups.
+///
+/// Otherwise like [`restore_backup`]. The outcome is recorded in the audit log
+/// without a backup ID, so the restore can't be undone with its snapshot.
+pub fn restore_archive(profile: &ProfileName, extracted: &archive::Extracted) -> Result<()> {
+    let result = restore_dir(profile, extracted.dir());
+    audit::record(profile, Operation::Restore, &[], None, &result);
+    result
+}
+
+/// Copy the files in `src_dir` over the live files of a profile.
+pub(crate) fn restore_dir(profile: &ProfileName, src_dir: &Path) -> Result<()> {
+    let opened = Profile::open_named(profile)?;
+    // otherwise the watcher would back up the restored files
+    let _paused = control::pause_during(profile)?;
+    let engine = CopyEngine::new().ntfs_metadata(opened.ntfs_metadata());
+    let restore = |dest: &Path| match opened.empty_dirs() {
+        EmptyDirs::Skip => engine.copy_files(src_dir, dest),
+        EmptyDirs::Matched | EmptyDirs::Preserve => engine.copy_tree(src_dir, dest),
+    };
+    match opened.remote() {
+        Some(remote) => {
+            restore(&remote_mirror_dir(profile)?)?;
+            remote::push(&remote, src_dir)?;
+        }
+        None => restore(opened.base())?,
+    }
+    Ok(())
+}
+
+/// Move the backups of a profile from one directory to another, e.g. after
+/// its `storage_dir` changed. See [`crate::filesystem::profile_save_dir`].
+///
+/// Backups are renamed, or copied and then removed if `to` is on another
+/// drive. The per-profile database stays where it is. Returns the number of
+/// backups moved.
+pub fn move_backups(from: &Path, to: &Path) -> Result<usize> {
+    if !from.is_dir() {
+        return Ok(0);
+    }
+    create_dirs(to)?;
+    let mut moved = 0;
+    for entry in std::fs::read_dir(from).with_path("reading", from)? {
+        let src = entry.with_path("reading", from)?.path();
+        let dest = to.join(src.file_name().expect("entries have names"));
+        let is_backup = src.is_dir()
+            || matches!(
+                src.extension().and_then(|ext| ext.to_str()),
+                Some(archive::EXTENSION | cold::STUB_EXTENSION)
+            );
+        if !is_backup {
+            continue;
+        }
+        if std::fs::rename(&src, &dest).is_err() {
+            if src.is_dir() {
+                CopyEngine::new().copy_tree(&src, &dest)?;
+                set_tree_readonly(&dest, true)?;
+                remove_backup_dir(&src)?;
+            } else {
+                let readonly = std::fs::metadata(&src)
+                    .with_path("reading metadata of", &src)?
+                    .permissions()
+                    .readonly();
+                CopyEngine::new().copy_file(&src, &dest)?;
+                set_readonly(&dest, readonly)?;
+                set_readonly(&src, false)?;
+                std::fs::remove_file(&src).with_path("removing", &src)?;
+            }
+        }
+        moved += 1;
+    }
+    Ok(moved)
+}
+
+/// Set or clear the read-only flag on every file in a directory tree.
+pub(crate) fn set_tree_readonly(dir: &Path, readonly: bool) -> Result<()> {
+    walk_files(dir)?
+        .into_iter()
+        .try_for_each(|file| set_readonly(file, readonly))
+}
+
+/// Remove a backup directory, clearing read-only flags first.
+pub(crate) fn remove_backup_dir(dir: &Path) -> Result<()> {
+    set_tree_readonly(dir, false)?;
+    std::fs::remove_dir_all(dir).with_path("removing backup directory", dir)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_refs_parse_ids_and_uids() {
+        let id = BackupId::new(7).unwrap();
+        assert_eq!("7".parse::<BackupRef>().unwrap(), BackupRef::Id(id));
+        let uid = BackupUid::new(&Utc::now());
+        assert_eq!(
+            uid.to_string().parse::<BackupRef>().unwrap(),
+            BackupRef::Uid(uid)
+        );
+        assert!("0".parse::<BackupRef>().is_err());
+        assert!("latest".parse::<BackupRef>().is_err());
+    }
+
+    fn file(path: &str, changed: bool) -> FileRecord {
+        FileRecord {
+            path: path.into(),
+            hash: String::new(),
+            size: 0,
+            changed,
+            triggered: false,
+        }
+    }
+
+    #[test]
+    fn changes_count_changed_and_added_files() {
+        let previous = [file("a", true), file("b", true)];
+        let manifest = [file("a", false), file("b", true), file("c", true)];
+        assert_eq!(count_changes(&manifest, &previous), 2);
+    }
+
+    #[test]
+    fn changes_count_removed_files() {
+        let previous = [file("a", true), file("b", false), file("c", false)];
+        let manifest = [file("a", false)];
+        assert_eq!(count_changes(&manifest, &previous), 2);
+    }
+
+    #[test]
+    fn changes_of_first_backup_are_its_files() {
+        let manifest = [file("a", true), file("b", true)];
+        assert_eq!(count_changes(&manifest, &[]), 2);
+        assert_eq!(count_changes(&[], &[]), 0);
+    }
+
+    #[test]
+    fn unchanged_backups_have_no_changes() {
+        let previous = [file("a", true), file("b", true)];
+        let manifest = [file("b", false), file("a", false)];
+        assert_eq!(count_changes(&manifest, &previous), 0);
+    }
+
+    #[test]
+    fn failed_cleanup_keeps_backups_in_database() {
+        crate::filesystem::use_test_home();
+        let db = Database::from_store(crate::json_store::JsonStore::in_memory());
+        let profile = ProfileName::new("remove-failure").unwrap();
+        let table = db.backup_table(&profile).unwrap();
+        let removed = table.insert("", &Utc::now()).unwrap();
+        let failing = table.insert("", &Utc::now()).unwrap();
+        let dir = profile_save_dir(&profile).unwrap();
+        std::fs::create_dir_all(dir.join(removed.dir_name())).unwrap();
+        std::fs::write(dir.join(removed.dir_name()).join("a.sav"), "save").unwrap();
+        // removing the backup from cold storage fails on an unreadable stub
+        let stub = dir.join(format!("{}.{}", failing.dir_name(), cold::STUB_EXTENSION));
+        std::fs::write(&stub, "not a stub").unwrap();
+
+        assert!(remove_backups(&db, &profile, &[removed.id(), failing.id()]).is_err());
+
+        assert!(!dir.join(removed.dir_name()).exists());
+        assert!(table.select_id(removed.id()).unwrap().is_none());
+        assert!(table.select_id(failing.id()).unwrap().is_some());
+        assert!(stub.is_file());
+    }
+}
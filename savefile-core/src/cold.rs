@@ -0,0 +1,172 @@
+/// Moving old backups to cheaper storage, such as a NAS or a slow drive.
+///
+/// A profile's `cold_storage` rule moves backups older than some number of
+/// days to a store: each backup is compressed into a zip archive which is put
+/// into the store, and its local directory is replaced by a small stub file
+/// recording where the archive went. Watchers apply the rule periodically.
+/// Restoring a cold backup fetches and extracts its archive transparently.
+use std::{io, path::PathBuf};
+
+use chrono::Utc;
+
+use crate::{
+    archive::{self, extract, write_archive, Extracted},
+    backup::remove_backup_dir,
+    copy::CopyEngine,
+    database::Database,
+    error::{IoResultExt, ProfileError, Result},
+    filesystem::{backup_dir, profile_save_dir, staging_dir},
+    profile::{Profile, ProfileName},
+    store::{Remote, RemoteStore},
+    Backup,
+};
+
+/// Extension of the stub files left in place of cold backups.
+pub(crate) const STUB_EXTENSION: &str = "cold";
+/// Name of the archive in a cold backup's directory in the store.
+const ARCHIVE_NAME: &str = "backup.zip";
+
+/// A rule moving old backups to cold storage.
+///
+/// In profiles, the rule is written as e.g.
+/// `{ "after_days": 30, "target": { "type": "dir", "path": "//nas/cold" } }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ColdStorage {
+    /// Age in days after which backups are moved.
+    pub after_days: u32,
+    /// Store the compressed backups are moved to.
+    pub target: Remote,
+}
+
+impl ColdStorage {
+    /// Check the rule against the profile's other stores.
+    pub(crate) fn validate(&self, remotes: &[Remote]) -> Result<(), ProfileError> {
+        let invalid = |reason: &str| ProfileError::InvalidField {
+            key: "cold_storage".to_owned(),
+            reason: reason.to_owned(),
+        };
+        if self.after_days == 0 {
+            Err(invalid("after_days must be at least 1"))?
+        }
+        // both would store their copies under the same names
+        if remotes.contains(&self.target) {
+            Err(invalid("the target must not also be one of the remotes"))?
+        }
+        Ok(())
+    }
+}
+
+/// Stub left in place of a cold backup's directory.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Stub {
+    /// Store holding the backup's archive.
+    store: Remote,
+}
+
+/// Returns `true` if the backup was moved to cold storage.
+pub fn is_cold(profile: &ProfileName, backup: &Backup) -> Result<bool> {
+    Ok(stub_path(profile, backup)?.is_file())
+}
+
+/// Returns the store holding a cold backup, or `None` if the backup is
+/// stored locally.
+pub fn store(profile: &ProfileName, backup: &Backup) -> Result<Option<Box<dyn RemoteStore>>> {
+    if !is_cold(profile, backup)? {
+        return Ok(None);
+    }
+    Ok(Some(read_stub(profile, backup)?.store.open()))
+}
+
+/// Move the profile's backups that are older than its `cold_storage` rule
+/// allows to cold storage.
+///
+/// Returns the number of backups moved.
+pub fn apply_rule(db: &Database, profile: &Profile, name: &ProfileName) -> Result<usize> {
+    let Some(rule) = profile.cold_storage() else {
+        return Ok(0);
+    };
+    let cutoff = Utc::now() - chrono::Duration::days(rule.after_days.into());
+    let mut moved = 0;
+    for backup in db.backup_table(name)?.iter() {
+        let backup = backup?;
+        let local = backup_dir(name, &backup)?.is_dir() || archive::is_zipped(name, &backup)?;
+        if backup.timestamp() < cutoff && local {
+            freeze(name, &backup, &rule.target)?;
+            moved += 1;
+        }
+    }
+    Ok(moved)
+}
+
+/// Compress a backup into the store, and replace its directory (or its zip
+/// archive) with a stub.
+fn freeze(profile: &ProfileName, backup: &Backup, target: &Remote) -> Result<()> {
+    let dir = backup_dir(profile, backup)?;
+    let zipped = archive::is_zipped(profile, backup)?;
+    let stub = stub_path(profile, backup)?;
+    // with a stub already in place, an earlier move was interrupted after
+    // the archive was stored
+    if !stub.is_file() {
+        let packed = staging_dir(profile)?.join(format!("{}.pack", backup.dir_name()));
+        std::fs::create_dir_all(&packed).with_path("creating", &packed)?;
+        let dest = packed.join(ARCHIVE_NAME);
+        let written = match zipped {
+            // zipped backups already are in the right format
+            true => archive::archive_path(profile, backup)
+                .and_then(|path| CopyEngine::new().copy_file(&path, &dest)),
+            false => write_archive(&dir, &dest),
+        };
+        let result = written.and_then(|()| target.open().put(profile, backup, &packed));
+        remove_backup_dir(&packed)?;
+        result?;
+        let contents = serde_json::to_vec_pretty(&Stub {
+            store: target.clone(),
+        })
+        .expect("stub is always serializable");
+        std::fs::write(&stub, contents).with_path("writing", &stub)?;
+    }
+    match zipped {
+        true => archive::remove(profile, backup),
+        false => remove_backup_dir(&dir),
+    }
+}
+
+/// Fetch a cold backup's archive from its store, and extract its files.
+pub fn thaw(profile: &ProfileName, backup: &Backup) -> Result<Extracted> {
+    let stub = read_stub(profile, backup)?;
+    let fetched = staging_dir(profile)?.join(format!("{}.fetch", backup.dir_name()));
+    let thawed = Extracted::create(profile, backup)?;
+    std::fs::create_dir_all(&fetched).with_path("creating", &fetched)?;
+    let result = stub
+        .store
+        .open()
+        .fetch(profile, backup, &fetched)
+        .and_then(|()| extract(&fetched.join(ARCHIVE_NAME), thawed.dir()));
+    remove_backup_dir(&fetched)?;
+    result?;
+    Ok(thawed)
+}
+
+/// Delete a cold backup's archive from its store, and then its stub.
+pub(crate) fn remove(profile: &ProfileName, backup: &Backup) -> Result<()> {
+    let stub = read_stub(profile, backup)?;
+    stub.store.open().delete(profile, backup)?;
+    let path = stub_path(profile, backup)?;
+    std::fs::remove_file(&path).with_path("removing", &path)?;
+    Ok(())
+}
+
+/// Returns the path of the stub left in place of a cold backup.
+fn stub_path(profile: &ProfileName, backup: &Backup) -> Result<PathBuf> {
+    let name = format!("{}.{}", backup.dir_name(), STUB_EXTENSION);
+    Ok(profile_save_dir(profile)?.join(name))
+}
+
+/// Read the stub of a cold backup.
+fn read_stub(profile: &ProfileName, backup: &Backup) -> Result<Stub> {
+    let path = stub_path(profile, backup)?;
+    let contents = std::fs::read(&path).with_path("reading", &path)?;
+    serde_json::from_slice(&contents)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+        .with_path("parsing", &path)
+}
@@ -0,0 +1,356 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    alert::{AlertRule, SmtpSettings},
+    error::{Error, IoResultExt, Result},
+    filesystem::config_path,
+    size::ByteSize,
+    status_file::validate_template,
+    BackupId, BackupUid, Timestamp,
+};
+
+/// Prefix of environment variables that override config values, e.g. `SAVEFILE_IO_LIMIT`.
+const ENV_PREFIX: &str = "SAVEFILE_";
+/// Placeholders available in [`Config::backup_dir_template`].
+const TEMPLATE_PLACEHOLDERS: [&str; 3] = ["{id}", "{ulid}", "{timestamp}"];
+
+/// Global program configuration.
+///
+/// The configuration is stored as a JSON file in the install directory.
+/// Missing fields take their default values, so an absent or partial file is valid.
+/// Each value may be overridden by an environment variable named after its key,
+/// such as `SAVEFILE_MAX_CONCURRENT_BACKUPS`.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Store each profile's backup metadata in its own database file
+    /// (`saves/<profile>/index.db`) instead of the shared database.
+    pub per_profile_database: bool,
+    /// Name of new backup directories, e.g. `{timestamp}_id{id}`.
+    ///
+    /// `{id}` is replaced with the backup's ID and `{ulid}` with its globally
+    /// unique ID, and one of them must be present to keep names unique.
+    /// `{ulid}`, the default, also keeps names unique across machines and over
+    /// time, since the ID of a deleted backup can be given to a new one whose
+    /// name would then collide with copies of the deleted backup in remotes or
+    /// mirrors. `{timestamp}` is replaced with the creation time, such as
+    /// `2024-06-01T20-33-05`. Existing backups keep their names.
+    pub backup_dir_template: String,
+    /// Maximum number of backups that run at the same time when watching
+    /// several profiles in one process, or 0 for no limit.
+    pub max_concurrent_backups: usize,
+    /// Maximum rate at which backups copy files, per second, or `None` for no limit.
+    ///
+    /// Profiles may override this with their own `io_limit`.
+    pub io_limit: Option<ByteSize>,
+    /// Run backups made by watchers at background IO and CPU priority.
+    pub background_priority: bool,
+    /// Address on which `savefile watch` serves the gRPC service, if any
+    /// (requires the `grpc` feature).
+    pub grpc_listen: Option<SocketAddr>,
+    /// Address on which `savefile watch` serves a stream of server-sent events, if any.
+    pub events_listen: Option<SocketAddr>,
+    /// File updated after each backup, e.g. for display in OBS.
+    pub status_file: Option<StatusFile>,
+    /// Key the metadata databases are encrypted with (requires the `sqlcipher` feature).
+    ///
+    /// Best given through the `SAVEFILE_DATABASE_KEY` environment variable
+    /// rather than stored in the file.
+    pub database_key: Option<String>,
+    /// Rules for alerting about watched profiles whose backups keep failing.
+    pub alerts: Vec<AlertRule>,
+    /// Mail server used by email alerts.
+    pub smtp: Option<SmtpSettings>,
+    /// How frontends confirm each kind of operation, where it differs from
+    /// [`ConfirmPolicy::Ask`].
+    pub confirm: BTreeMap<Prompt, ConfirmPolicy>,
+}
+
+/// An operation that frontends ask the user to confirm.
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Prompt {
+    /// Overwriting the live files with a backup.
+    Restore,
+    /// Deleting backups.
+    DeleteBackups,
+    /// Deleting the remote copies of backups.
+    DeleteRemote,
+    /// Deleting a profile and its backups.
+    DeleteProfile,
+    /// Deleting all but the most recent backups.
+    Retain,
+    /// Deleting all but every Nth backup.
+    Thin,
+    /// Applying migrations to all profiles.
+    Migrate,
+}
+
+impl Prompt {
+    /// Returns `true` if the operation concerns a single profile, whose name
+    /// can be asked for with [`ConfirmPolicy::TypeName`].
+    pub fn has_profile(self) -> bool {
+        self != Prompt::Migrate
+    }
+}
+
+/// How an operation is confirmed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfirmPolicy {
+    /// Ask, unless `--yes` was given.
+    #[default]
+    Ask,
+    /// Ask even if `--yes` was given.
+    Always,
+    /// Never ask.
+    Never,
+    /// Ask to type the profile's name, unless `--yes` was given.
+    TypeName,
+}
+
+/// Set the field `key` of a serialized struct to `value` and deserialize it.
+///
+/// `value` is parsed as JSON, or taken as a plain string if it isn't JSON or
+/// the parsed value doesn't fit the field, so that string fields accept
+/// values like `123456` or `true` as they are.
+pub(crate) fn with_json_field<T: serde::de::DeserializeOwned>(
+    mut json: serde_json::Value,
+    key: &str,
+    value: &str,
+) -> serde_json::Result<T> {
+    let plain = serde_json::Value::String(value.to_owned());
+    let Ok(parsed) = serde_json::from_str(value) else {
+        json[key] = plain;
+        return serde_json::from_value(json);
+    };
+    json[key] = parsed;
+    serde_json::from_value(json.clone()).or_else(|e| {
+        json[key] = plain;
+        // report why the parsed value didn't fit if the string doesn't either
+        serde_json::from_value(json).map_err(|_| e)
+    })
+}
+
+/// A file describing the latest backup, rewritten after each backup.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct StatusFile {
+    /// Where to write the file.
+    pub path: PathBuf,
+    /// Text of the file, with placeholders such as `{profile}` and `{time:%H:%M}`.
+    ///
+    /// If `None`, the file is a JSON object with the same fields.
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            per_profile_database: false,
+            backup_dir_template: "{ulid}".to_owned(),
+            max_concurrent_backups: 2,
+            io_limit: None,
+            background_priority: true,
+            grpc_listen: None,
+            events_listen: None,
+            status_file: None,
+            database_key: None,
+            alerts: Vec::new(),
+            smtp: None,
+            confirm: BTreeMap::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Load the configuration from the default location, with values
+    /// overridden by environment variables.
+    ///
+    /// Returns the default configuration if no configuration file exists.
+    pub fn load() -> Result<Self> {
+        Self::load_from(config_path()?)?.with_env()
+    }
+
+    /// Load the configuration from the given path.
+    ///
+    /// Returns the default configuration if the file does not exist.
+    pub fn load_from(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = std::fs::read(path).with_path("reading config", path)?;
+        let config: Config =
+            serde_json::from_slice(&contents).map_err(|e| Error::InvalidConfig {
+                path: path.to_owned(),
+                reason: e.to_string(),
+            })?;
+        config.validate().map_err(|reason| Error::InvalidConfig {
+            path: path.to_owned(),
+            reason,
+        })?;
+        Ok(config)
+    }
+
+    /// Check the values that deserialization alone doesn't validate.
+    fn validate(&self) -> Result<(), String> {
+        let template = &self.backup_dir_template;
+        if !template.contains("{id}") && !template.contains("{ulid}") {
+            Err("backup_dir_template must contain {id} or {ulid}")?
+        }
+        let mut rest = template.clone();
+        for placeholder in TEMPLATE_PLACEHOLDERS {
+            rest = rest.replace(placeholder, "");
+        }
+        if rest.contains(['{', '}']) {
+            Err(format!(
+                "backup_dir_template may only contain the placeholders {}",
+                TEMPLATE_PLACEHOLDERS.join(", ")
+            ))?
+        }
+        if rest.contains(['/', '\\', ':', '*', '?', '"', '<', '>', '|']) {
+            Err("backup_dir_template contains characters not allowed in directory names")?
+        }
+        if let Some(template) = self.status_file.as_ref().and_then(|s| s.template.as_ref()) {
+            validate_template(template).map_err(|reason| format!("status_file: {}", reason))?;
+        }
+        #[cfg(not(feature = "sqlcipher"))]
+        if self.database_key.is_some() {
+            Err("database_key requires a build with the `sqlcipher` feature")?
+        }
+        if self.database_key.as_deref() == Some("") {
+            Err("database_key must not be empty")?
+        }
+        for rule in &self.alerts {
+            rule.validate(self.smtp.as_ref())?;
+        }
+        for (prompt, policy) in &self.confirm {
+            if *policy == ConfirmPolicy::TypeName && !prompt.has_profile() {
+                Err(format!(
+                    "confirm: {} doesn't concern a single profile, so it can't be type_name",
+                    serde_json::to_string(prompt).expect("prompts are always serializable")
+                ))?
+            }
+        }
+        Ok(())
+    }
+
+    /// Override values with `SAVEFILE_<KEY>` environment variables, which are
+    /// parsed like values given to [`Config::with_field`].
+    fn with_env(self) -> Result<Self> {
+        let json = serde_json::to_value(&self).expect("config is always serializable");
+        let keys = json.as_object().expect("config is an object").keys();
+        let mut config = self;
+        for key in keys {
+            let var = format!("{}{}", ENV_PREFIX, key.to_uppercase());
+            let Ok(value) = std::env::var(&var) else {
+                continue;
+            };
+            config = config.with_field(key, &value).map_err(|e| match e {
+                Error::InvalidConfig { path, reason } => Error::InvalidConfig {
+                    path,
+                    reason: format!("{} (from {})", reason, var),
+                },
+                e => e,
+            })?;
+        }
+        Ok(config)
+    }
+
+    /// Returns how the given operation is confirmed.
+    pub fn confirm_policy(&self, prompt: Prompt) -> ConfirmPolicy {
+        self.confirm.get(&prompt).copied().unwrap_or_default()
+    }
+
+    /// Returns the directory name for a new backup, following `backup_dir_template`.
+    pub fn backup_dir_name(&self, id: BackupId, uid: BackupUid, timestamp: &Timestamp) -> String {
+        self.backup_dir_template
+            .replace("{id}", &id.to_string())
+            .replace("{ulid}", &uid.to_string())
+            .replace(
+                "{timestamp}",
+                &timestamp.format("%Y-%m-%dT%H-%M-%S").to_string(),
+            )
+    }
+
+    /// Save the configuration to the default location.
+    pub fn save(&self) -> Result<()> {
+        let path = config_path()?;
+        let ser = serde_json::to_string_pretty(self).expect("config is always serializable");
+        std::fs::write(&path, ser).with_path("writing config", &path)?;
+        Ok(())
+    }
+
+    /// Set a single field by name, parsing `value` as JSON (or as a plain string).
+    ///
+    /// Returns the updated configuration without saving it.
+    pub fn with_field(&self, key: &str, value: &str) -> Result<Self> {
+        let invalid = |reason: String| Error::InvalidConfig {
+            path: config_path().unwrap_or_default(),
+            reason,
+        };
+        let json = serde_json::to_value(self).expect("config is always serializable");
+        if !json
+            .as_object()
+            .expect("config is an object")
+            .contains_key(key)
+        {
+            Err(invalid(format!("unknown key {:?}", key)))?
+        }
+        let config: Config =
+            with_json_field(json, key, value).map_err(|e| invalid(e.to_string()))?;
+        config.validate().map_err(invalid)?;
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(key: &str, value: &str) -> serde_json::Result<Config> {
+        with_json_field(serde_json::to_value(Config::default()).unwrap(), key, value)
+    }
+
+    #[test]
+    fn json_values_are_parsed() {
+        assert_eq!(
+            set("max_concurrent_backups", "4")
+                .unwrap()
+                .max_concurrent_backups,
+            4
+        );
+        assert!(
+            !set("background_priority", "false")
+                .unwrap()
+                .background_priority
+        );
+        assert_eq!(set("database_key", "null").unwrap().database_key, None);
+    }
+
+    #[test]
+    fn string_fields_accept_json_lookalikes() {
+        for value in ["123456", "true", "1.5", "hunter2", "\"quoted\""] {
+            let expected = match value {
+                "\"quoted\"" => "quoted",
+                value => value,
+            };
+            let config = set("database_key", value).unwrap();
+            assert_eq!(config.database_key.as_deref(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn mismatched_values_report_the_parsed_type() {
+        let e = set("max_concurrent_backups", "-1").unwrap_err();
+        assert!(e.to_string().contains("integer `-1`"), "{}", e);
+        assert!(set("max_concurrent_backups", "many").is_err());
+    }
+}
@@ -0,0 +1,140 @@
+/// Having running watchers act on a caller's behalf, and pausing their
+/// automatic backups.
+///
+/// These work whether or not a watcher is running for the profile, and don't
+/// need the `watch` feature: without a watcher, backups are made directly and
+/// pauses are recorded for watchers started later.
+use crate::{
+    backup::{backup, BackupCause, BackupId},
+    database::Database,
+    error::{IoResultExt, Result, WatcherError},
+    filesystem::pause_path,
+    ipc::{self, Request, Response},
+    lock,
+    profile::{Profile, ProfileName},
+};
+
+/// Create a backup of a profile right away.
+///
+/// If a watcher is running for the profile, it is asked to create the backup,
+/// since it holds the profile's database open. Otherwise, the backup is made
+/// directly. Either way, it is tagged with `tag`.
+pub fn backup_now(name: &ProfileName, tag: Option<&str>, cause: BackupCause) -> Result<BackupId> {
+    if let Some(id) = request_backup(name, tag, cause)? {
+        return Ok(id);
+    }
+    let db = Database::open_for_profile(name)?;
+    let profile = Profile::open_named(name)?;
+    backup(&db, &profile, name, &[], tag, cause)
+}
+
+/// Ask the watcher running for a profile to create a backup, tagged with `tag`.
+///
+/// Returns `None` if no watcher is running, so the caller can make the backup
+/// itself.
+pub fn request_backup(
+    name: &ProfileName,
+    tag: Option<&str>,
+    cause: BackupCause,
+) -> Result<Option<BackupId>> {
+    let request = Request::Backup {
+        cause: Some(cause),
+        tag: tag.map(str::to_owned),
+    };
+    match ipc::request(name, &request)? {
+        Some(Response::BackedUp { id }) => Ok(Some(id)),
+        Some(Response::Error { message }) => Err(WatcherError::RequestFailed(message))?,
+        Some(_) => Err(WatcherError::RequestFailed(
+            "unexpected response".to_owned(),
+        ))?,
+        None => Ok(None),
+    }
+}
+
+/// Pause or resume the automatic backups of a profile.
+///
+/// The pause is recorded in a file next to the profile's lock, so it also
+/// applies to watchers started later. A running watcher is told right away;
+/// returns `true` if one acknowledged the change.
+pub fn set_paused(name: &ProfileName, paused: bool) -> Result<bool> {
+    let request = if paused {
+        Request::Pause
+    } else {
+        Request::Resume
+    };
+    match ipc::request(name, &request)? {
+        Some(Response::Paused { .. }) => Ok(true),
+        Some(Response::Error { message }) => Err(WatcherError::RequestFailed(message))?,
+        // a watcher that can't be reached picks up the file when it wakes up
+        _ => {
+            write_pause_file(name, paused)?;
+            Ok(false)
+        }
+    }
+}
+
+/// Ask the watcher running for a profile to stop, releasing its lock.
+///
+/// Returns `true` if a watcher acknowledged the request, and `false` if none
+/// is running.
+pub fn stop_watcher(name: &ProfileName) -> Result<bool> {
+    match ipc::request(name, &Request::Stop)? {
+        Some(Response::Stopped) => Ok(true),
+        Some(Response::Error { message }) => Err(WatcherError::RequestFailed(message))?,
+        Some(_) => Err(WatcherError::RequestFailed(
+            "unexpected response".to_owned(),
+        ))?,
+        None => Ok(false),
+    }
+}
+
+/// Returns `true` if the automatic backups of a profile are paused.
+pub fn is_paused(name: &ProfileName) -> Result<bool> {
+    Ok(pause_path(name)?.exists())
+}
+
+/// Create or remove the file marking a profile as paused.
+pub(crate) fn write_pause_file(name: &ProfileName, paused: bool) -> Result<()> {
+    let path = pause_path(name)?;
+    if paused {
+        std::fs::write(&path, "").with_path("writing", &path)?;
+    } else if path.exists() {
+        std::fs::remove_file(&path).with_path("removing", &path)?;
+    }
+    Ok(())
+}
+
+/// Pauses a running watcher while the base directory is being written to,
+/// see [`pause_during`].
+pub struct PauseGuard {
+    name: ProfileName,
+    /// Whether the watcher was paused by the guard, so it must be resumed.
+    resume: bool,
+}
+
+impl Drop for PauseGuard {
+    fn drop(&mut self) {
+        if self.resume {
+            if let Err(e) = set_paused(&self.name, false) {
+                eprintln!("warning: failed resuming watcher of {:?}: {}", self.name, e);
+            }
+        }
+    }
+}
+
+/// Pause the profile's running watcher, if any, until the returned guard is
+/// dropped, e.g. for the duration of a restore.
+///
+/// Changes reported shortly after resuming are ignored as well, since they
+/// were caused by the paused operation. A watcher that was already paused is
+/// left paused.
+pub fn pause_during(name: &ProfileName) -> Result<PauseGuard> {
+    let resume = lock::read_state(name)?.is_some() && !is_paused(name)?;
+    if resume {
+        set_paused(name, true)?;
+    }
+    Ok(PauseGuard {
+        name: name.clone(),
+        resume,
+    })
+}
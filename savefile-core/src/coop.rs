@@ -0,0 +1,302 @@
+/// Pass-the-save co-op, where players take turns on one save kept in a shared
+/// directory, e.g. hotseat games played by sharing the save file.
+///
+/// The shared directory holds the current save and a turn token. Only the
+/// player holding the token may pull the save into their live files or push
+/// their live files back, so two players can't clobber each other's progress.
+/// Taking the token fails while another player holds it, and passing the turn
+/// pushes the save before releasing the token.
+///
+/// The token is taken by creating it exclusively, so the shared directory must
+/// be a single filesystem all players write to, e.g. a network share. Synced
+/// folders copy files between machines after the fact, so two players could
+/// both take the turn before either sees the other's token.
+use std::{
+    fs::OpenOptions,
+    io::{self, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+
+use crate::{
+    audit::{self, Operation},
+    backup::{remove_backup_dir, restore_dir},
+    copy::CopyEngine,
+    error::{CoopError, Error, IoResultExt, ProfileError, Result},
+    filesystem::{remote_mirror_dir, walk_files},
+    profile::{Profile, ProfileName},
+    remote, Timestamp,
+};
+
+/// Co-op settings of a profile.
+///
+/// In profiles, written as e.g. `{ "shared": "//nas/heroes", "player": "alice" }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Coop {
+    /// Directory shared by all players, e.g. a network share.
+    pub shared: PathBuf,
+    /// Name of the local player, which must differ between players.
+    pub player: String,
+}
+
+impl Coop {
+    /// Check the settings.
+    pub(crate) fn validate(&self) -> Result<(), ProfileError> {
+        let invalid = |reason: &str| ProfileError::InvalidField {
+            key: "coop".to_owned(),
+            reason: reason.to_owned(),
+        };
+        if !self.shared.is_absolute() {
+            Err(invalid("shared must be an absolute path"))?
+        }
+        if self.player.trim().is_empty() {
+            Err(invalid("player must not be empty"))?
+        }
+        Ok(())
+    }
+
+    /// Returns the profile's directory in the shared directory.
+    fn dir(&self, profile: &ProfileName) -> PathBuf {
+        self.shared.join(profile)
+    }
+
+    /// Returns the path of the turn token.
+    fn token_path(&self, profile: &ProfileName) -> PathBuf {
+        self.dir(profile).join("turn.json")
+    }
+
+    /// Returns the directory holding the shared save.
+    fn save_dir(&self, profile: &ProfileName) -> PathBuf {
+        self.dir(profile).join("save")
+    }
+}
+
+/// The turn token, kept in the shared directory while a player holds the turn.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Turn {
+    /// The player holding the turn.
+    pub player: String,
+    /// When the turn was taken.
+    pub since: Timestamp,
+}
+
+/// Returns the co-op settings of a profile, failing if it has none.
+fn coop_of(name: &ProfileName) -> Result<(Profile, Coop)> {
+    let profile = Profile::open_named(name)?;
+    let coop = profile
+        .coop()
+        .cloned()
+        .ok_or_else(|| CoopError::NotCoop(name.to_string()))?;
+    Ok((profile, coop))
+}
+
+/// Returns who holds the turn of a co-op profile, if anyone.
+pub fn current_turn(name: &ProfileName) -> Result<Option<Turn>> {
+    let (_, coop) = coop_of(name)?;
+    read_turn(&coop.token_path(name))
+}
+
+/// Read a turn token, or `None` if there is none.
+fn read_turn(path: &Path) -> Result<Option<Turn>> {
+    let contents = match std::fs::read(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        other => other.with_path("reading turn token", path)?,
+    };
+    let turn = serde_json::from_slice(&contents).map_err(|_| Error::IoContext {
+        context: format!("failed parsing turn token {}", path.display()),
+        source: io::ErrorKind::InvalidData.into(),
+    })?;
+    Ok(Some(turn))
+}
+
+/// Take the turn of a co-op profile for the local player.
+///
+/// Fails if another player holds the turn, unless `steal` is set, e.g. when a
+/// player forgot to pass it. Taking a turn the local player already holds
+/// keeps it.
+pub fn take_turn(name: &ProfileName, steal: bool) -> Result<Turn> {
+    let (_, coop) = coop_of(name)?;
+    let dir = coop.dir(name);
+    std::fs::create_dir_all(&dir).with_path("creating", &dir)?;
+    let path = coop.token_path(name);
+    let turn = Turn {
+        player: coop.player.clone(),
+        since: Utc::now(),
+    };
+    let contents = serde_json::to_vec_pretty(&turn).expect("turn is always serializable");
+    // creating the token fails if it exists, so only one player can take it
+    match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(mut file) => {
+            file.write_all(&contents)
+                .with_path("writing turn token", &path)?;
+            return Ok(turn);
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(e) => Err(e).with_path("creating turn token", &path)?,
+    }
+    match read_turn(&path)? {
+        Some(held) if held.player == coop.player => Ok(held),
+        Some(held) if !steal => Err(CoopError::TurnHeld {
+            player: held.player,
+            since: held.since,
+        })?,
+        _ => {
+            std::fs::write(&path, contents).with_path("writing turn token", &path)?;
+            Ok(turn)
+        }
+    }
+}
+
+/// Fails unless the local player holds the turn of a co-op profile.
+fn require_turn(name: &ProfileName, coop: &Coop) -> Result<()> {
+    match read_turn(&coop.token_path(name))? {
+        Some(turn) if turn.player == coop.player => Ok(()),
+        turn => Err(CoopError::NotYourTurn(turn.map(|turn| turn.player)))?,
+    }
+}
+
+/// Returns the directory holding the shared save of a co-op profile, or
+/// `None` if no save was pushed yet.
+pub fn shared_save(name: &ProfileName) -> Result<Option<PathBuf>> {
+    let (_, coop) = coop_of(name)?;
+    let dir = coop.save_dir(name);
+    Ok(Some(dir).filter(|dir| dir.is_dir()))
+}
+
+/// Copy the shared save over the live files of a co-op profile.
+///
+/// The local player must hold the turn. The restore is recorded in the audit
+/// log without a backup ID, like restores of archives.
+pub fn pull(name: &ProfileName) -> Result<()> {
+    let (_, coop) = coop_of(name)?;
+    require_turn(name, &coop)?;
+    let save_dir = coop.save_dir(name);
+    if !save_dir.is_dir() {
+        Err(CoopError::NoSharedSave(name.to_string()))?
+    }
+    let result = restore_dir(name, &save_dir);
+    audit::record(name, Operation::Restore, &[], None, &result);
+    result
+}
+
+/// Pass the turn of a co-op profile on to the next player.
+///
+/// The local player must hold the turn. With `push`, the included live files
+/// replace the shared save first, and the number of files pushed is returned.
+pub fn pass_turn(name: &ProfileName, push: bool) -> Result<usize> {
+    let (profile, coop) = coop_of(name)?;
+    require_turn(name, &coop)?;
+    let pushed = match push {
+        true => push_save(name, &profile, &coop)?,
+        false => 0,
+    };
+    let path = coop.token_path(name);
+    std::fs::remove_file(&path).with_path("removing turn token", &path)?;
+    Ok(pushed)
+}
+
+/// Replace the shared save with the included live files.
+fn push_save(name: &ProfileName, profile: &Profile, coop: &Coop) -> Result<usize> {
+    let mirrored;
+    let profile = match profile.remote() {
+        Some(remote) => {
+            let mirror = remote_mirror_dir(name)?;
+            remote::pull(&remote, &mirror)?;
+            mirrored = profile.with_base(mirror);
+            &mirrored
+        }
+        None => profile,
+    };
+    let save_dir = coop.save_dir(name);
+    // other players must never see a partially pushed save
+    let partial = save_dir.with_extension("partial");
+    if partial.exists() {
+        remove_backup_dir(&partial)?;
+    }
+    std::fs::create_dir_all(&partial).with_path("creating", &partial)?;
+    let paths = profile.expand_includes(true)?;
+    CopyEngine::new().copy_paths(profile.base(), &partial, &paths)?;
+    if save_dir.exists() {
+        remove_backup_dir(&save_dir)?;
+    }
+    std::fs::rename(&partial, &save_dir).with_path("renaming", &partial)?;
+    Ok(walk_files(&save_dir)?.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filesystem::{profile_path, use_test_home};
+
+    /// Save a co-op profile for `player`, replacing any earlier one.
+    fn set_player(name: &ProfileName, shared: &Path, player: &str) {
+        let coop = Coop {
+            shared: shared.to_owned(),
+            player: player.to_owned(),
+        };
+        let profile = Profile::builder(use_test_home())
+            .coop(coop)
+            .build()
+            .unwrap();
+        profile.save(profile_path(name).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn only_one_player_holds_the_turn() {
+        let shared = tempfile::tempdir().unwrap();
+        let name = ProfileName::new("coop-turns").unwrap();
+        set_player(&name, shared.path(), "alice");
+        assert_eq!(current_turn(&name).unwrap(), None);
+        let taken = take_turn(&name, false).unwrap();
+        assert_eq!(taken.player, "alice");
+        // taking the turn again keeps it
+        assert_eq!(take_turn(&name, false).unwrap(), taken);
+
+        set_player(&name, shared.path(), "bob");
+        assert!(matches!(
+            take_turn(&name, false),
+            Err(Error::CoopError(CoopError::TurnHeld { player, .. })) if player == "alice"
+        ));
+        assert!(matches!(
+            pass_turn(&name, false),
+            Err(Error::CoopError(CoopError::NotYourTurn(Some(player)))) if player == "alice"
+        ));
+        assert_eq!(current_turn(&name).unwrap(), Some(taken));
+    }
+
+    #[test]
+    fn stealing_takes_the_turn() {
+        let shared = tempfile::tempdir().unwrap();
+        let name = ProfileName::new("coop-steal").unwrap();
+        set_player(&name, shared.path(), "alice");
+        take_turn(&name, false).unwrap();
+
+        set_player(&name, shared.path(), "bob");
+        assert_eq!(take_turn(&name, true).unwrap().player, "bob");
+        assert_eq!(current_turn(&name).unwrap().unwrap().player, "bob");
+
+        set_player(&name, shared.path(), "alice");
+        assert!(matches!(
+            pass_turn(&name, false),
+            Err(Error::CoopError(CoopError::NotYourTurn(Some(player)))) if player == "bob"
+        ));
+    }
+
+    #[test]
+    fn passing_releases_the_turn() {
+        let shared = tempfile::tempdir().unwrap();
+        let name = ProfileName::new("coop-pass").unwrap();
+        set_player(&name, shared.path(), "alice");
+        take_turn(&name, false).unwrap();
+        assert_eq!(pass_turn(&name, false).unwrap(), 0);
+        assert_eq!(current_turn(&name).unwrap(), None);
+        assert!(matches!(
+            pass_turn(&name, false),
+            Err(Error::CoopError(CoopError::NotYourTurn(None)))
+        ));
+
+        set_player(&name, shared.path(), "bob");
+        assert_eq!(take_turn(&name, false).unwrap().player, "bob");
+    }
+}
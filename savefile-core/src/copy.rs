@@ -0,0 +1,206 @@
+/// Copying files and directory trees, for backups, restores, mirrors and stores.
+///
+/// A [`CopyEngine`] carries the options of a copy: whether existing files are
+/// replaced, the rate limit, and whether NTFS streams and attributes are kept.
+/// Features that change how files are copied belong here, so that every copy
+/// gets them.
+use std::{
+    cell::RefCell,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{IoResultExt, Result},
+    filesystem::{set_readonly, walk_dirs, walk_files},
+    ntfs,
+    size::ByteSize,
+    throttle::Throttle,
+};
+
+/// What to do with files that already exist at the destination.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Overwrite {
+    /// Replace them, e.g. when restoring.
+    #[default]
+    Always,
+    /// Keep them.
+    Never,
+}
+
+/// Copies files with a set of options.
+///
+/// By default, existing files are replaced, files are copied at full speed,
+/// and NTFS streams and attributes are not kept. Files are copied one at a
+/// time.
+pub struct CopyEngine {
+    overwrite: Overwrite,
+    throttle: RefCell<Throttle>,
+    ntfs_metadata: bool,
+    dest_name: Option<String>,
+}
+
+impl Default for CopyEngine {
+    fn default() -> Self {
+        Self {
+            overwrite: Overwrite::default(),
+            throttle: RefCell::new(Throttle::new(None)),
+            ntfs_metadata: false,
+            dest_name: None,
+        }
+    }
+}
+
+impl CopyEngine {
+    /// Create an engine with the default options.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set what to do with files that already exist at the destination.
+    pub fn overwrite(mut self, overwrite: Overwrite) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+
+    /// Limit the rate of the copy to `limit` bytes per second, or not at all if `None`.
+    pub fn io_limit(mut self, limit: Option<ByteSize>) -> Self {
+        self.throttle = RefCell::new(Throttle::new(limit));
+        self
+    }
+
+    /// Set whether alternate data streams and attributes are copied too, see
+    /// [`crate::ntfs`].
+    pub fn ntfs_metadata(mut self, ntfs_metadata: bool) -> Self {
+        self.ntfs_metadata = ntfs_metadata;
+        self
+    }
+
+    /// Name the destination as `name` in errors, e.g. `backup 3`, rather than
+    /// by the path of each copy.
+    pub fn dest_name(mut self, name: impl Into<String>) -> Self {
+        self.dest_name = Some(name.into());
+        self
+    }
+
+    /// Copy the given paths, relative to `src`, to the same paths under `dest`.
+    ///
+    /// Directories among the paths are created (without their contents) before
+    /// any files are copied.
+    pub fn copy_paths(&self, src: &Path, dest: &Path, paths: &[PathBuf]) -> Result<()> {
+        let (mut dirs, files): (Vec<_>, Vec<_>) =
+            paths.iter().partition(|rel| src.join(rel).is_dir());
+        // parents first, so that their attributes are set before their children's
+        dirs.sort();
+        for rel in dirs.into_iter().chain(files) {
+            self.copy(&src.join(rel), &dest.join(rel))?;
+        }
+        Ok(())
+    }
+
+    /// Copy the contents of a directory recursively from `src` to `dest`,
+    /// including empty directories.
+    pub fn copy_tree(&self, src: &Path, dest: &Path) -> Result<()> {
+        create_dirs(dest)?;
+        let mut paths = walk_dirs(src)?;
+        paths.extend(walk_files(src)?);
+        self.copy_paths(src, dest, &relative_to(src, paths))
+    }
+
+    /// Copy the files of a directory recursively from `src` to `dest`, but
+    /// without recreating empty directories.
+    pub fn copy_files(&self, src: &Path, dest: &Path) -> Result<()> {
+        self.copy_paths(src, dest, &relative_to(src, walk_files(src)?))
+    }
+
+    /// Copy a single file to `dest`, creating its parent directories.
+    pub fn copy_file(&self, src: &Path, dest: &Path) -> Result<()> {
+        self.copy(src, dest)
+    }
+
+    /// Copy a single file, or create a directory, at `dest`.
+    fn copy(&self, src: &Path, dest: &Path) -> Result<()> {
+        if src.is_dir() {
+            create_dirs(dest)?;
+        } else if self.overwrite == Overwrite::Always || !dest.exists() {
+            create_dirs(dest.parent().expect("copied files have a parent"))?;
+            if dest.exists() {
+                set_readonly(dest, false)?;
+            }
+            self.throttle
+                .borrow_mut()
+                .copy(src, dest)
+                .context(|| format!("failed copying {} to {}", src.display(), self.name(dest)))?;
+            // copies inherit the source's permissions, but copied files must be
+            // writable (e.g. restored files coming from a read-only backup)
+            set_readonly(dest, false)?;
+        } else {
+            return Ok(());
+        }
+        if self.ntfs_metadata {
+            ntfs::copy_metadata(src, dest).context(|| {
+                format!(
+                    "failed copying streams and attributes of {} to {}",
+                    src.display(),
+                    self.name(dest)
+                )
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Returns how errors name the destination `dest`.
+    fn name(&self, dest: &Path) -> String {
+        match &self.dest_name {
+            Some(name) => name.clone(),
+            None => dest.display().to_string(),
+        }
+    }
+}
+
+/// Returns the given paths under `base`, relative to it.
+fn relative_to(base: &Path, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    paths
+        .into_iter()
+        .map(|path| {
+            path.strip_prefix(base)
+                .expect("walked inside the directory")
+                .to_owned()
+        })
+        .collect()
+}
+
+/// Create all missing directories (if any) in the given path.
+pub(crate) fn create_dirs(path: &Path) -> Result<()> {
+    match std::fs::create_dir_all(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => {
+            Err(e).with_path("creating directory", path)?
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::Error;
+
+    #[test]
+    fn errors_use_the_destination_name() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = PathBuf::from("missing.sav");
+        let result = CopyEngine::new().dest_name("backup 3").copy_paths(
+            dir.path(),
+            &dir.path().join("3"),
+            &[missing],
+        );
+        let Err(Error::IoContext { context, .. }) = result else {
+            panic!("copying a missing file succeeded");
+        };
+        let expected = format!(
+            "failed copying {} to backup 3",
+            dir.path().join("missing.sav").display()
+        );
+        assert_eq!(context, expected);
+    }
+}
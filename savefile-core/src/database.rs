@@ -0,0 +1,717 @@
+/// Storage of backup metadata.
+///
+/// Metadata lives in a [`MetadataStore`]. Builds with the `sqlite` feature (the
+/// default) use an [`SqliteStore`](crate::sqlite::SqliteStore), while builds
+/// without it fall back to the pure-Rust
+/// [`JsonStore`](crate::json_store::JsonStore). [`Database`] and its table
+/// proxies work the same with either.
+use std::{collections::VecDeque, path::Path};
+
+#[cfg(not(feature = "sqlite"))]
+use crate::json_store::JsonStore;
+#[cfg(feature = "sqlite")]
+use crate::sqlite::SqliteStore;
+use crate::{
+    backup::{
+        Backup, BackupCause, BackupId, BackupRef, BackupStatus, BackupUid, FileRecord, Timestamp,
+    },
+    config::Config,
+    environment::Environment,
+    error::{BackupError, Result},
+    filesystem::{database_path, profile_database_path},
+    profile::ProfileName,
+};
+
+/// A backend holding the backup metadata of any number of profiles.
+///
+/// Each profile has its own backups, whose IDs are unique within the profile,
+/// and a file manifest for each backup. Methods other than
+/// [`MetadataStore::backup_count`] may assume that
+/// [`MetadataStore::create_tables`] was called for the profile.
+///
+/// Callers go through [`Database`], [`BackupTable`] and [`FileTable`], so a new
+/// backend only needs to implement this trait and be passed to
+/// [`Database::from_store`]. The provided methods are built on
+/// [`MetadataStore::select_page`]; backends that can answer them directly,
+/// like SQLite, should override them.
+pub trait MetadataStore: Send {
+    /// Returns the number of backups of a profile, or `None` if the store has
+    /// no tables for it.
+    fn backup_count(&self, profile: &str) -> Result<Option<usize>>;
+
+    /// Create the tables of a profile if they do not exist.
+    fn create_tables(&self, profile: &str) -> Result<()>;
+
+    /// Insert a new backup with the next free ID.
+    fn insert_backup(&self, profile: &str, tag: &str, timestamp: &Timestamp) -> Result<Backup>;
+
+    /// Insert a backup with its own ID, replacing any backup with that ID.
+    fn put_backup(&self, profile: &str, backup: &Backup) -> Result<()>;
+
+    /// Set one field of a backup.
+    fn update_backup(&self, profile: &str, id: BackupId, field: BackupField<'_>) -> Result<()>;
+
+    /// Select a backup with the given ID.
+    fn select_backup(&self, profile: &str, id: BackupId) -> Result<Option<Backup>>;
+
+    /// Select up to `limit` backups with IDs greater than `after`, in order of their IDs.
+    fn select_page(
+        &self,
+        profile: &str,
+        after: Option<BackupId>,
+        limit: usize,
+    ) -> Result<Vec<Backup>>;
+
+    /// Select all backups.
+    fn select_all(&self, profile: &str) -> Result<Vec<Backup>> {
+        let mut backups: Vec<Backup> = Vec::new();
+        loop {
+            let page = self.select_page(profile, backups.last().map(Backup::id), PAGE_SIZE)?;
+            let done = page.len() < PAGE_SIZE;
+            backups.extend(page);
+            if done {
+                return Ok(backups);
+            }
+        }
+    }
+
+    /// Returns the ID of the backup created before the given one, if any.
+    fn previous_id(&self, profile: &str, id: BackupId) -> Result<Option<BackupId>> {
+        let backups = self.select_all(profile)?;
+        Ok(backups
+            .iter()
+            .map(Backup::id)
+            .filter(|prev| *prev < id)
+            .max())
+    }
+
+    /// Returns the most recent backup whose files passed validation, if any.
+    fn latest_valid(&self, profile: &str) -> Result<Option<Backup>> {
+        let backups = self.select_all(profile)?;
+        Ok(backups
+            .into_iter()
+            .filter(|b| b.status() == BackupStatus::Valid)
+            .max_by_key(|b| (b.timestamp(), b.id())))
+    }
+
+    /// Returns the most recent backup, if any.
+    fn latest(&self, profile: &str) -> Result<Option<Backup>> {
+        let backups = self.select_all(profile)?;
+        Ok(backups.into_iter().max_by_key(|b| (b.timestamp(), b.id())))
+    }
+
+    /// Returns the total size of the files in the manifests of all backups.
+    fn total_size(&self, profile: &str) -> Result<u64> {
+        self.select_all(profile)?
+            .iter()
+            .try_fold(0, |total, backup| {
+                let files = self.select_files(profile, backup.id())?;
+                Ok(total + files.iter().map(|f| f.size).sum::<u64>())
+            })
+    }
+
+    /// Remove a backup with the given ID.
+    fn remove_backup(&self, profile: &str, id: BackupId) -> Result<()>;
+
+    /// Insert backups with their own IDs, replacing any backups with those IDs.
+    ///
+    /// Backends with transactions insert all of them or none.
+    fn put_backups(&self, profile: &str, backups: &[Backup]) -> Result<()> {
+        backups
+            .iter()
+            .try_for_each(|backup| self.put_backup(profile, backup))
+    }
+
+    /// Remove the backups with the given IDs along with their manifests.
+    ///
+    /// Backends with transactions remove all of them or none.
+    fn remove_backups(&self, profile: &str, ids: &[BackupId]) -> Result<()> {
+        ids.iter().try_for_each(|id| {
+            self.remove_backup(profile, *id)?;
+            self.remove_files(profile, *id)
+        })
+    }
+
+    /// Remove all backups of a profile.
+    fn drop_backups(&self, profile: &str) -> Result<()>;
+
+    /// Insert the manifest of a backup, replacing records with the same path.
+    fn insert_files(&self, profile: &str, backup_id: BackupId, files: &[FileRecord]) -> Result<()>;
+
+    /// Insert backups with their own IDs along with their manifests, replacing
+    /// any backups with those IDs.
+    ///
+    /// Backends with transactions insert all of them or none.
+    fn put_backups_with_files(
+        &self,
+        profile: &str,
+        backups: &[(Backup, Vec<FileRecord>)],
+    ) -> Result<()> {
+        backups.iter().try_for_each(|(backup, files)| {
+            self.put_backup(profile, backup)?;
+            self.insert_files(profile, backup.id(), files)
+        })
+    }
+
+    /// Select the manifest of a backup, sorted by path.
+    fn select_files(&self, profile: &str, backup_id: BackupId) -> Result<Vec<FileRecord>>;
+
+    /// Select the records of one file in every backup that holds it, along
+    /// with those backups, oldest first.
+    ///
+    /// Backups are ordered by time rather than ID, as IDs may be reused.
+    /// Manifests left behind by deleted backups are skipped.
+    fn select_file_history(&self, profile: &str, path: &Path) -> Result<Vec<(Backup, FileRecord)>> {
+        let mut history = Vec::new();
+        for backup in self.select_all(profile)? {
+            let files = self.select_files(profile, backup.id())?;
+            history.extend(
+                files
+                    .into_iter()
+                    .filter(|file| file.path == path)
+                    .map(|file| (backup.clone(), file)),
+            );
+        }
+        history.sort_by_key(|(backup, _)| (backup.timestamp(), backup.id()));
+        Ok(history)
+    }
+
+    /// Remove the manifest of a backup.
+    fn remove_files(&self, profile: &str, backup_id: BackupId) -> Result<()>;
+
+    /// Remove the manifests of all backups of a profile.
+    fn drop_files(&self, profile: &str) -> Result<()>;
+
+    /// Rewrite timestamps stored without an offset by older versions as UTC
+    /// timestamps, returning how many there are.
+    ///
+    /// If `dry_run` is set, the timestamps are only counted. Stores which never
+    /// held such timestamps need not implement this.
+    fn rewrite_naive_timestamps(&self, _profile: &str, _dry_run: bool) -> Result<usize> {
+        Ok(0)
+    }
+
+    /// Returns the SQLite connection of stores kept in SQLite.
+    #[cfg(feature = "sqlite")]
+    fn connection(&self) -> Option<&rusqlite::Connection> {
+        None
+    }
+}
+
+/// A field of a backup which can be changed after it is inserted.
+#[derive(Clone, Copy, Debug)]
+pub enum BackupField<'a> {
+    /// Name of the backup's directory.
+    Dir(&'a str),
+    /// Version of the game or app the backup was made with.
+    Version(&'a str),
+    /// Whether the backup's files passed validation.
+    Status(BackupStatus),
+    /// Why the backup was made.
+    Cause(BackupCause),
+    /// Number of files that differ from the previous backup.
+    Changed(u32),
+    /// The machine the backup was made on.
+    Environment(&'a Environment),
+    /// Globally unique ID of the backup.
+    Uid(BackupUid),
+}
+
+/// Abstraction over the metadata store.
+pub struct Database(Box<dyn MetadataStore>);
+
+impl Database {
+    /// Create a database backed by the given store.
+    ///
+    /// This is how other backends, or an in-memory fake such as
+    /// [`JsonStore::in_memory`](crate::json_store::JsonStore::in_memory) in
+    /// tests, are used in place of the one the program was built with.
+    pub fn from_store(store: impl MetadataStore + 'static) -> Self {
+        Self(Box::new(store))
+    }
+
+    /// Returns the underlying store.
+    pub fn store(&self) -> &dyn MetadataStore {
+        self.0.as_ref()
+    }
+
+    /// Open a new in-memory database.
+    pub fn open_in_memory() -> Result<Self> {
+        #[cfg(feature = "sqlite")]
+        let store = SqliteStore::new(rusqlite::Connection::open_in_memory()?);
+        #[cfg(not(feature = "sqlite"))]
+        let store = JsonStore::in_memory();
+        Ok(Self::from_store(store))
+    }
+
+    /// Open a database at the given path.
+    ///
+    /// This will create the database if it does not exist. With the `sqlite`
+    /// feature, the database may be encrypted, see [`SqliteStore::open`].
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self> {
+        let path = path.as_ref();
+        #[cfg(feature = "sqlite")]
+        let store = SqliteStore::open(path)?;
+        #[cfg(not(feature = "sqlite"))]
+        let store = JsonStore::open(path)?;
+        Ok(Self::from_store(store))
+    }
+
+    /// Open the default database.
+    pub fn open_default() -> Result<Self> {
+        Self::open(database_path()?)
+    }
+
+    /// Open the database holding the backup metadata of the given profile.
+    ///
+    /// Depending on the `per_profile_database` config flag, this is either the
+    /// default database or the profile's own index.
+    pub fn open_for_profile(profile: &ProfileName) -> Result<Self> {
+        if Config::load()?.per_profile_database {
+            Self::open(profile_database_path(profile)?)
+        } else {
+            Self::open_default()
+        }
+    }
+
+    /// Open a database with the given SQLite connection.
+    #[cfg(feature = "sqlite")]
+    pub fn with_connection(connection: rusqlite::Connection) -> Result<Self> {
+        Ok(Self::from_store(SqliteStore::new(connection)))
+    }
+
+    /// Returns the underlying connection.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the database was created with [`Database::from_store`] from a
+    /// store that isn't kept in SQLite.
+    #[cfg(feature = "sqlite")]
+    #[deprecated(note = "metadata may be kept in other stores, use `Database::store` instead")]
+    pub fn connection(&self) -> &rusqlite::Connection {
+        self.0.connection().expect("database is not kept in SQLite")
+    }
+
+    /// Returns the number of backups of the given profile, or `None` if the
+    /// database has no table for it.
+    ///
+    /// Unlike [`Database::backup_table`], this does not create the table.
+    pub fn row_count(&self, profile: &ProfileName) -> Result<Option<usize>> {
+        self.0.backup_count(profile)
+    }
+
+    /// Returns a proxy to the backup table.
+    pub fn backup_table<'a>(&'a self, profile: &ProfileName) -> Result<BackupTable<'a>> {
+        BackupTable::open(self.0.as_ref(), profile)
+    }
+
+    /// Returns a proxy to the file manifest table.
+    pub fn file_table<'a>(&'a self, profile: &ProfileName) -> Result<FileTable<'a>> {
+        FileTable::open(self.0.as_ref(), profile)
+    }
+}
+
+/// Proxy to the backup table for some profile.
+pub struct BackupTable<'a> {
+    /// The underlying store.
+    store: &'a dyn MetadataStore,
+    /// The name of the profile this table is for.
+    profile: String,
+}
+
+impl<'a> BackupTable<'a> {
+    /// Open the backup table, or create it if necessary.
+    fn open(store: &'a dyn MetadataStore, profile: &str) -> Result<Self> {
+        store.create_tables(profile)?;
+        Ok(Self {
+            store,
+            profile: profile.to_owned(),
+        })
+    }
+
+    /// Drop the backup table.
+    pub fn drop(self) -> Result<()> {
+        self.store.drop_backups(&self.profile)
+    }
+
+    /// Insert a new backup into the table.
+    pub fn insert(&self, tag: &str, timestamp: &Timestamp) -> Result<Backup> {
+        self.store.insert_backup(&self.profile, tag, timestamp)
+    }
+
+    /// Insert a backup with its own ID, replacing any backup with that ID.
+    pub fn put(&self, backup: &Backup) -> Result<()> {
+        self.store.put_backup(&self.profile, backup)
+    }
+
+    /// Insert backups with their own IDs in one go, replacing any backups with
+    /// those IDs.
+    pub fn insert_many(&self, backups: &[Backup]) -> Result<()> {
+        self.store.put_backups(&self.profile, backups)
+    }
+
+    /// Set the name of a backup's directory.
+    pub fn set_dir(&self, id: BackupId, dir: &str) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Dir(dir))
+    }
+
+    /// Set the version of the game or app a backup was made with.
+    pub fn set_version(&self, id: BackupId, version: &str) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Version(version))
+    }
+
+    /// Set whether a backup's files passed validation.
+    pub fn set_status(&self, id: BackupId, status: BackupStatus) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Status(status))
+    }
+
+    /// Set why a backup was made.
+    pub fn set_cause(&self, id: BackupId, cause: BackupCause) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Cause(cause))
+    }
+
+    /// Set how many files of a backup differ from the previous backup.
+    pub fn set_changed(&self, id: BackupId, count: u32) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Changed(count))
+    }
+
+    /// Set the machine a backup was made on.
+    pub fn set_environment(&self, id: BackupId, env: &Environment) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Environment(env))
+    }
+
+    /// Set the globally unique ID of a backup.
+    pub fn set_uid(&self, id: BackupId, uid: BackupUid) -> Result<()> {
+        self.store
+            .update_backup(&self.profile, id, BackupField::Uid(uid))
+    }
+
+    /// Select a backup with the given ID
+    pub fn select_id(&self, id: BackupId) -> Result<Option<Backup>> {
+        self.store.select_backup(&self.profile, id)
+    }
+
+    /// Select the backup with the given ID or unique ID, failing if there is none.
+    pub fn select_ref(&self, backup: BackupRef) -> Result<Backup> {
+        let found = match backup {
+            BackupRef::Id(id) => self.select_id(id)?,
+            BackupRef::Uid(_) => self
+                .select_all()?
+                .into_iter()
+                .find(|candidate| backup.matches(candidate)),
+        };
+        let missing = match backup {
+            BackupRef::Id(id) => BackupError::NoSuchBackup(id),
+            BackupRef::Uid(uid) => BackupError::NoSuchUid(uid),
+        };
+        Ok(found.ok_or(missing)?)
+    }
+
+    /// Retrieve all backups.
+    pub fn select_all(&self) -> Result<Vec<Backup>> {
+        self.store.select_all(&self.profile)
+    }
+
+    /// Iterate over all backups in order of their IDs.
+    ///
+    /// Backups are fetched from the database in pages as the iterator advances,
+    /// so large histories can be processed without loading them all at once.
+    pub fn iter(&self) -> BackupIter<'_, 'a> {
+        BackupIter {
+            table: self,
+            after: None,
+            page: VecDeque::new(),
+            done: false,
+        }
+    }
+
+    /// Remove a backup with the given ID.
+    pub fn remove(&self, id: BackupId) -> Result<()> {
+        self.store.remove_backup(&self.profile, id)
+    }
+
+    /// Remove the backups with the given IDs in one go, along with their file
+    /// manifests.
+    pub fn delete_many(&self, ids: &[BackupId]) -> Result<()> {
+        self.store.remove_backups(&self.profile, ids)
+    }
+
+    /// Returns the ID of the backup created before the given one, if any.
+    pub fn previous_id(&self, id: BackupId) -> Result<Option<BackupId>> {
+        self.store.previous_id(&self.profile, id)
+    }
+
+    /// Returns the most recent backup whose files passed validation, if any.
+    pub fn latest_valid(&self) -> Result<Option<Backup>> {
+        self.store.latest_valid(&self.profile)
+    }
+
+    /// Returns the most recent backup, if any.
+    pub fn latest(&self) -> Result<Option<Backup>> {
+        self.store.latest(&self.profile)
+    }
+
+    /// Returns the number of backups.
+    pub fn count(&self) -> Result<usize> {
+        Ok(self.store.backup_count(&self.profile)?.unwrap_or(0))
+    }
+
+    /// Returns the total size of the files in the manifests of all backups,
+    /// in bytes.
+    ///
+    /// Files unchanged between backups are counted once per backup, so this is
+    /// the size of all backups if none were stored as archives.
+    pub fn total_size(&self) -> Result<u64> {
+        self.store.total_size(&self.profile)
+    }
+}
+
+/// Number of backups fetched at a time by [`BackupIter`].
+const PAGE_SIZE: usize = 256;
+
+/// Iterator over the backups in a [`BackupTable`], see [`BackupTable::iter`].
+pub struct BackupIter<'t, 'a> {
+    table: &'t BackupTable<'a>,
+    /// ID of the last backup fetched so far.
+    after: Option<BackupId>,
+    /// Fetched backups not yet returned.
+    page: VecDeque<Backup>,
+    /// Whether the last page has been fetched.
+    done: bool,
+}
+
+impl Iterator for BackupIter<'_, '_> {
+    type Item = Result<Backup>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.page.is_empty() && !self.done {
+            match self
+                .table
+                .store
+                .select_page(&self.table.profile, self.after, PAGE_SIZE)
+            {
+                Ok(page) => {
+                    self.done = page.len() < PAGE_SIZE;
+                    self.after = page.last().map(|b| b.id()).or(self.after);
+                    self.page = page.into();
+                }
+                Err(e) => {
+                    self.done = true;
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.page.pop_front().map(Ok)
+    }
+}
+
+/// Proxy to the file manifest table for some profile.
+///
+/// The manifest records every file contained in each backup, along with its
+/// hash and whether it changed or triggered the backup.
+pub struct FileTable<'a> {
+    /// The underlying store.
+    store: &'a dyn MetadataStore,
+    /// The name of the profile this table is for.
+    profile: String,
+}
+
+impl<'a> FileTable<'a> {
+    /// Open the file table, or create it if necessary.
+    fn open(store: &'a dyn MetadataStore, profile: &str) -> Result<Self> {
+        store.create_tables(profile)?;
+        Ok(Self {
+            store,
+            profile: profile.to_owned(),
+        })
+    }
+
+    /// Drop the file table.
+    pub fn drop(self) -> Result<()> {
+        self.store.drop_files(&self.profile)
+    }
+
+    /// Insert the manifest of a backup.
+    pub fn insert(&self, backup_id: BackupId, files: &[FileRecord]) -> Result<()> {
+        self.store.insert_files(&self.profile, backup_id, files)
+    }
+
+    /// Retrieve the manifest of a backup, sorted by path.
+    pub fn select_backup(&self, backup_id: BackupId) -> Result<Vec<FileRecord>> {
+        self.store.select_files(&self.profile, backup_id)
+    }
+
+    /// Retrieve the records of one file in every backup that holds it, along
+    /// with those backups, oldest first.
+    pub fn select_path(&self, path: &Path) -> Result<Vec<(Backup, FileRecord)>> {
+        self.store.select_file_history(&self.profile, path)
+    }
+
+    /// Remove the manifest of a backup.
+    pub fn remove_backup(&self, backup_id: BackupId) -> Result<()> {
+        self.store.remove_files(&self.profile, backup_id)
+    }
+}
+
+/// Rewrite naive timestamps in the given profile's backup table as UTC
+/// timestamps with an explicit offset.
+///
+/// Returns the number of rewritten rows. If `dry_run` is set, the rows are
+/// only counted.
+pub(crate) fn rewrite_naive_timestamps(
+    db: &Database,
+    profile: &str,
+    dry_run: bool,
+) -> Result<usize> {
+    db.0.rewrite_naive_timestamps(profile, dry_run)
+}
+
+/// Move the backup metadata of a profile between the shared database and
+/// its per-profile database.
+///
+/// If `per_profile` is `true`, metadata is moved from the shared database into
+/// the profile's own index in `saves/<profile>`, otherwise the other way
+/// around.
+///
+/// Backups already in the destination, recognized by their unique ID, are
+/// skipped. Backups whose ID is taken by a different backup in the
+/// destination get the next free ID. Backups without a unique ID replace any
+/// backup with the same ID, as they can't be told apart.
+pub fn relocate_metadata(profile: &ProfileName, per_profile: bool) -> Result<()> {
+    let shared = database_path()?;
+    let own = profile_database_path(profile)?;
+    let (src, dest) = if per_profile {
+        (shared, own)
+    } else {
+        (own, shared)
+    };
+    if !src.exists() {
+        return Ok(());
+    }
+
+    // the destination takes all of the metadata or none of it, and the source
+    // is only cleared once it has, so that a failure never splits the metadata
+    // between the two
+    let db = Database::open(&src)?;
+    merge_metadata(&db, &Database::open(&dest)?, profile)?;
+    db.file_table(profile)?.drop()?;
+    db.backup_table(profile)?.drop()?;
+    Ok(())
+}
+
+/// Copy the backups of a profile and their manifests from `src` into `dest`,
+/// see [`relocate_metadata`].
+///
+/// With a `dest` that has transactions, all of them are copied or none.
+fn merge_metadata(src: &Database, dest: &Database, profile: &ProfileName) -> Result<()> {
+    let (backups, files) = (src.backup_table(profile)?, src.file_table(profile)?);
+    let dest_backups = dest.backup_table(profile)?;
+    let existing = dest_backups.select_all()?;
+    let source = backups.select_all()?;
+    let mut next_id = existing
+        .iter()
+        .chain(&source)
+        .map(|b| b.id().get())
+        .max()
+        .unwrap_or(0)
+        + 1;
+    let mut merged = Vec::new();
+    for backup in source {
+        if backup.uid().is_some() && existing.iter().any(|b| b.uid() == backup.uid()) {
+            continue;
+        }
+        let taken = existing
+            .iter()
+            .any(|b| b.id() == backup.id() && (b.uid().is_some() || backup.uid().is_some()));
+        let files = files.select_backup(backup.id())?;
+        let backup = if taken {
+            let id = BackupId::new(next_id)?;
+            next_id += 1;
+            backup.with_id(id)
+        } else {
+            backup
+        };
+        merged.push((backup, files));
+    }
+    dest.store().put_backups_with_files(profile, &merged)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::json_store::JsonStore;
+
+    /// Insert backups with the given unique IDs, returning their IDs.
+    fn insert(db: &Database, profile: &ProfileName, uids: &[BackupUid]) -> Vec<BackupId> {
+        let table = db.backup_table(profile).unwrap();
+        uids.iter()
+            .map(|uid| {
+                let id = table.insert("", &Utc::now()).unwrap().id();
+                table.set_uid(id, *uid).unwrap();
+                id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn merge_skips_known_backups_and_renumbers_conflicts() {
+        let src = Database::from_store(JsonStore::in_memory());
+        let dest = Database::from_store(JsonStore::in_memory());
+        let profile = ProfileName::new("game").unwrap();
+        let uid = |days| BackupUid::new(&(Utc::now() - Duration::days(days)));
+        let (shared, local, moved) = (uid(3), uid(2), uid(1));
+        insert(&src, &profile, &[shared, moved]);
+        insert(&dest, &profile, &[shared, local]);
+
+        merge_metadata(&src, &dest, &profile).unwrap();
+
+        let mut merged = dest
+            .backup_table(&profile)
+            .unwrap()
+            .select_all()
+            .unwrap()
+            .iter()
+            .map(|backup| (backup.id().get(), backup.uid().unwrap()))
+            .collect::<Vec<_>>();
+        merged.sort();
+        assert_eq!(merged, [(1, shared), (2, local), (3, moved)]);
+    }
+
+    #[test]
+    fn renumbered_backups_keep_their_directories() {
+        let src = Database::from_store(JsonStore::in_memory());
+        let dest = Database::from_store(JsonStore::in_memory());
+        let profile = ProfileName::new("game").unwrap();
+        let legacy = BackupUid::new(&(Utc::now() - Duration::days(2)));
+        insert(&src, &profile, &[legacy]);
+        insert(&dest, &profile, &[BackupUid::new(&Utc::now())]);
+        let id = BackupId::new(1).unwrap();
+        assert_eq!(
+            src.backup_table(&profile)
+                .unwrap()
+                .select_id(id)
+                .unwrap()
+                .unwrap()
+                .dir_name(),
+            "1"
+        );
+
+        merge_metadata(&src, &dest, &profile).unwrap();
+
+        let moved = dest
+            .backup_table(&profile)
+            .unwrap()
+            .select_ref(BackupRef::Uid(legacy))
+            .unwrap();
+        assert_eq!(moved.id().get(), 2);
+        assert_eq!(moved.dir_name(), "1");
+    }
+}
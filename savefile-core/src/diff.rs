@@ -0,0 +1,262 @@
+/// Comparison of backups against the live files in a profile's base directory.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{
+    archive, cold,
+    database::Database,
+    error::{BackupError, Result},
+    filesystem::{backup_dir, hash_file, walk_files},
+    profile::{Profile, ProfileName},
+    Backup, BackupId,
+};
+
+/// How a file in a backup relates to the live file at the same path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FileStatus {
+    /// The live file has the same contents as the backup.
+    Unchanged,
+    /// The live file differs from the backup.
+    Modified,
+    /// There is no live file at this path.
+    Missing,
+}
+
+/// Difference between a single backed up file and its live counterpart.
+#[derive(Clone, Debug)]
+pub struct FileDiff {
+    /// Path relative to the base directory.
+    pub path: PathBuf,
+    /// Status of the live file.
+    pub status: FileStatus,
+    /// Size of the backed up file in bytes.
+    pub size: u64,
+}
+
+/// Compare every file in a backup against the live files of the profile.
+///
+/// Hashes recorded in the backup's manifest are used where available.
+/// Live files that are not part of the backup are not reported, since a
+/// restore leaves them untouched.
+///
+/// Backups stored as zip archives or in cold storage are compared by their
+/// manifest, and only extracted if they have none.
+pub fn diff_backup(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    id: BackupId,
+) -> Result<Vec<FileDiff>> {
+    let manifest = db.file_table(name)?.select_backup(id)?;
+    let backup = db
+        .backup_table(name)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let dir = backup_dir(name, &backup)?;
+    let archived = archive::is_zipped(name, &backup)? || cold::is_cold(name, &backup)?;
+    if !dir.is_dir() && archived && !manifest.is_empty() {
+        let mut diffs = manifest
+            .into_iter()
+            .map(|file| {
+                let live = profile.base().join(&file.path);
+                let status = live_status(&live, || Ok(file.hash))?;
+                Ok(FileDiff {
+                    path: file.path,
+                    status,
+                    size: file.size,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        diffs.sort_by(|a, b| a.path.cmp(&b.path));
+        return Ok(diffs);
+    }
+    let extracted = archive::extract_backup(name, &backup)?;
+    let dir = match &extracted {
+        Some(extracted) => extracted.dir().to_owned(),
+        None => dir,
+    };
+    let recorded = manifest
+        .into_iter()
+        .map(|file| (file.path, file.hash))
+        .collect::<HashMap<_, _>>();
+    diff_files(profile, &dir, &recorded)
+}
+
+/// Compare every file in a directory, such as an archive opened with
+/// [`archive::open_archive`], against the live files of the profile.
+pub fn diff_dir(profile: &Profile, dir: &Path) -> Result<Vec<FileDiff>> {
+    diff_files(profile, dir, &HashMap::new())
+}
+
+/// Compare the files in `dir` against the live files, using the `recorded`
+/// hashes of files where available.
+fn diff_files(
+    profile: &Profile,
+    dir: &Path,
+    recorded: &HashMap<PathBuf, String>,
+) -> Result<Vec<FileDiff>> {
+    let mut diffs = walk_files(dir)?
+        .into_iter()
+        .map(|file| {
+            let path = file
+                .strip_prefix(dir)
+                .expect("walked inside backup directory")
+                .to_owned();
+            let size = std::fs::metadata(&file).map(|m| m.len()).unwrap_or(0);
+            let live = profile.base().join(&path);
+            let status = live_status(&live, || match recorded.get(&path) {
+                Some(hash) => Ok(hash.clone()),
+                None => hash_file(&file),
+            })?;
+            Ok(FileDiff { path, status, size })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    diffs.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(diffs)
+}
+
+/// Compare a live file against the hash of its backed up copy.
+///
+/// The hash is only computed if the live file exists.
+fn live_status(live: &Path, backup_hash: impl FnOnce() -> Result<String>) -> Result<FileStatus> {
+    if !live.is_file() {
+        return Ok(FileStatus::Missing);
+    }
+    Ok(if hash_file(live)? == backup_hash()? {
+        FileStatus::Unchanged
+    } else {
+        FileStatus::Modified
+    })
+}
+
+/// How well a backup matches each of two copies of the saves.
+#[derive(Clone, Debug)]
+pub struct BackupMatch {
+    /// The compared backup.
+    pub backup: Backup,
+    /// Number of files in the backup.
+    pub files: usize,
+    /// Number of files with the same contents in the live directory.
+    pub live: usize,
+    /// Number of files with the same contents in the other directory.
+    pub other: usize,
+}
+
+impl BackupMatch {
+    /// Returns `true` if every file of the backup is identical in the live directory.
+    pub fn matches_live(&self) -> bool {
+        self.files > 0 && self.live == self.files
+    }
+
+    /// Returns `true` if every file of the backup is identical in the other directory.
+    pub fn matches_other(&self) -> bool {
+        self.files > 0 && self.other == self.files
+    }
+}
+
+/// Result of comparing a profile's backups against two copies of its saves.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    /// Most recent modification time among the live files of any backup.
+    pub live_modified: Option<SystemTime>,
+    /// Most recent modification time among the other directory's files of any backup.
+    pub other_modified: Option<SystemTime>,
+    /// One entry per backup, in order of their IDs.
+    pub backups: Vec<BackupMatch>,
+}
+
+/// Compare every backup of a profile against both the live files and another
+/// copy of them, such as the local Steam Cloud cache.
+///
+/// `other` must mirror the layout of the base directory, i.e. a backed up file
+/// `saves/1.sav` is looked up at `other/saves/1.sav`.
+pub fn compare_backups(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    other: &Path,
+) -> Result<Comparison> {
+    let file_table = db.file_table(name)?;
+    let mut live = HashedDir::new(profile.base());
+    let mut copy = HashedDir::new(other);
+    let mut backups = Vec::new();
+    for backup in db.backup_table(name)?.iter() {
+        let backup = backup?;
+        let mut hashes = file_table
+            .select_backup(backup.id())?
+            .into_iter()
+            .map(|file| (file.path, file.hash))
+            .collect::<Vec<_>>();
+        let dir = backup_dir(name, &backup)?;
+        // backups made before manifests were recorded, unless they were
+        // archived since
+        if hashes.is_empty() && dir.is_dir() {
+            for file in walk_files(&dir)? {
+                let path = file
+                    .strip_prefix(&dir)
+                    .expect("walked inside backup directory")
+                    .to_owned();
+                hashes.push((path, hash_file(&file)?));
+            }
+        }
+        let count = |side: &mut HashedDir| -> Result<usize> {
+            let mut count = 0;
+            for (path, hash) in &hashes {
+                if side.hash(path)?.as_ref() == Some(hash) {
+                    count += 1;
+                }
+            }
+            Ok(count)
+        };
+        backups.push(BackupMatch {
+            files: hashes.len(),
+            live: count(&mut live)?,
+            other: count(&mut copy)?,
+            backup,
+        });
+    }
+    Ok(Comparison {
+        live_modified: live.newest,
+        other_modified: copy.newest,
+        backups,
+    })
+}
+
+/// A directory whose files are hashed on demand, at most once each.
+struct HashedDir<'a> {
+    root: &'a Path,
+    /// Hashes by relative path, `None` if the file doesn't exist.
+    hashes: HashMap<PathBuf, Option<String>>,
+    /// Most recent modification time among the hashed files.
+    newest: Option<SystemTime>,
+}
+
+impl<'a> HashedDir<'a> {
+    fn new(root: &'a Path) -> Self {
+        Self {
+            root,
+            hashes: HashMap::new(),
+            newest: None,
+        }
+    }
+
+    /// Returns the hash of the file at `path`, relative to the root.
+    fn hash(&mut self, path: &Path) -> Result<Option<String>> {
+        if let Some(hash) = self.hashes.get(path) {
+            return Ok(hash.clone());
+        }
+        let abs = self.root.join(path);
+        let hash = if abs.is_file() {
+            let modified = std::fs::metadata(&abs).and_then(|m| m.modified()).ok();
+            self.newest = self.newest.max(modified);
+            Some(hash_file(&abs)?)
+        } else {
+            None
+        };
+        self.hashes.insert(path.to_owned(), hash.clone());
+        Ok(hash)
+    }
+}
@@ -0,0 +1,211 @@
+/// Consistency checks for profiles and their backups.
+use std::{collections::HashSet, path::PathBuf};
+
+use crate::{
+    archive, cold,
+    database::Database,
+    error::Result,
+    filesystem::{backup_dir, walk_files},
+    mirror,
+    onedrive::{self, CloudFiles},
+    profile::{list_profile_names, Profile, ProfileName},
+    remap, Backup,
+};
+
+/// A problem found while checking a profile.
+#[derive(Clone, Debug)]
+pub struct Issue {
+    /// Name of the affected profile.
+    pub profile: ProfileName,
+    /// Human-readable description of the problem.
+    pub message: String,
+}
+
+/// Check the given profile and its backups for problems.
+///
+/// The following checks are performed:
+/// - the profile can be opened
+/// - the base directory exists, or else where it may have moved to
+/// - no other profile includes the same files
+/// - every backup in the database has a backup directory, a zip archive, or an
+///   archive in cold storage
+/// - every file in a backup, and every zip archive, is marked read-only
+/// - the mirror directory, if any, matches the backups
+/// - no included files in a OneDrive folder are skipped for being cloud-only
+pub fn diagnose(db: &Database, profile: &ProfileName) -> Result<Vec<Issue>> {
+    let mut issues = Vec::new();
+    let mut report = |message: String| {
+        issues.push(Issue {
+            profile: profile.clone(),
+            message,
+        })
+    };
+
+    match Profile::open_named(profile) {
+        Ok(opened) => {
+            if opened.remote().is_none() && !opened.base().is_dir() {
+                report(missing_base(profile, &opened)?);
+            }
+            for overlap in overlapping_profiles(profile, &opened)? {
+                report(format!(
+                    "{} file(s) are also included by profile {}",
+                    overlap.files, overlap.profile
+                ));
+            }
+            for divergence in mirror::divergences(db, &opened, profile)? {
+                report(divergence);
+            }
+            if let Some(issue) = check_onedrive(&opened)? {
+                report(issue);
+            }
+        }
+        Err(e) => report(format!("profile cannot be opened: {}", e)),
+    }
+
+    for backup in db.backup_table(profile)?.iter() {
+        let backup = backup?;
+        let dir = backup_dir(profile, &backup)?;
+        if !dir.is_dir() && archive::is_zipped(profile, &backup)? {
+            let path = archive::archive_path(profile, &backup)?;
+            let readonly = std::fs::metadata(&path)
+                .map(|meta| meta.permissions().readonly())
+                .unwrap_or(true);
+            if !readonly {
+                report(format!(
+                    "backup {} has an archive that is not read-only",
+                    backup.id()
+                ));
+            }
+            continue;
+        }
+        if !dir.is_dir() && cold::is_cold(profile, &backup)? {
+            if let Some(issue) = check_cold(profile, &backup)? {
+                report(issue);
+            }
+            continue;
+        }
+        if !dir.is_dir() {
+            report(format!("backup {} has no directory at {}", backup.id(), dir.display()));
+            continue;
+        }
+        let writable = walk_files(&dir)?
+            .into_iter()
+            .filter(|file| {
+                std::fs::metadata(file)
+                    .map(|meta| !meta.permissions().readonly())
+                    .unwrap_or(false)
+            })
+            .count();
+        if writable > 0 {
+            report(format!(
+                "backup {} has {} file(s) that are not read-only",
+                backup.id(),
+                writable
+            ));
+        }
+    }
+
+    Ok(issues)
+}
+
+/// Describe a missing base directory, suggesting where it may have moved.
+fn missing_base(name: &ProfileName, profile: &Profile) -> Result<String> {
+    let missing = format!("base directory {} doesn't exist", profile.base().display());
+    Ok(match remap::find_moved_base(profile)?.first() {
+        Some(moved) => format!(
+            "{}, but it may have moved to {} (run `savefile profile remap --name {} --base {:?}`)",
+            missing,
+            moved.display(),
+            name,
+            moved
+        ),
+        None => missing,
+    })
+}
+
+/// Check whether backups of a profile in a OneDrive folder skip cloud-only files.
+fn check_onedrive(profile: &Profile) -> Result<Option<String>> {
+    let Some(root) = onedrive::onedrive_root(profile.base()) else {
+        return Ok(None);
+    };
+    if profile.cloud_files() == CloudFiles::Hydrate {
+        return Ok(None);
+    }
+    let cloud_only = profile
+        .expand_includes(false)?
+        .into_iter()
+        .filter(|path| onedrive::is_cloud_only(path))
+        .count();
+    if cloud_only == 0 {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "base directory is in OneDrive ({}), and {} included file(s) are only available online, so backups skip them (set cloud_files to hydrate to download them)",
+        root.display(),
+        cloud_only
+    )))
+}
+
+/// Check that a cold backup's archive is still in its store.
+fn check_cold(profile: &ProfileName, backup: &Backup) -> Result<Option<String>> {
+    let Some(store) = cold::store(profile, backup)? else {
+        return Ok(None);
+    };
+    if store.contains(profile, backup)? {
+        return Ok(None);
+    }
+    Ok(Some(format!(
+        "backup {} is in cold storage, but {} has no archive of it",
+        backup.id(),
+        store.describe()
+    )))
+}
+
+/// Another profile that includes some of the same files.
+#[derive(Clone, Debug)]
+pub struct Overlap {
+    /// Name of the other profile.
+    pub profile: ProfileName,
+    /// Number of files included by both profiles.
+    pub files: usize,
+}
+
+/// Find the other profiles that include any of the files included by `profile`.
+///
+/// Two watchers backing up the same files store them twice and may race each
+/// other. Profiles that fail to open are skipped.
+pub fn overlapping_profiles(name: &ProfileName, profile: &Profile) -> Result<Vec<Overlap>> {
+    let files = included_files(profile)?;
+    let mut overlaps = Vec::new();
+    if files.is_empty() {
+        return Ok(overlaps);
+    }
+    for other_name in list_profile_names()? {
+        if &other_name == name {
+            continue;
+        }
+        let other = match Profile::open_named(&other_name) {
+            Ok(other) => other,
+            Err(_) => continue,
+        };
+        let shared = included_files(&other)?.intersection(&files).count();
+        if shared > 0 {
+            overlaps.push(Overlap {
+                profile: other_name,
+                files: shared,
+            });
+        }
+    }
+    Ok(overlaps)
+}
+
+/// Returns the canonical paths of the files included by a profile.
+fn included_files(profile: &Profile) -> Result<HashSet<PathBuf>> {
+    let files = profile
+        .expand_includes(false)?
+        .into_iter()
+        .filter(|path| path.is_file())
+        .map(|path| std::fs::canonicalize(&path).unwrap_or(path))
+        .collect();
+    Ok(files)
+}
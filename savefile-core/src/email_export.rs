@@ -0,0 +1,186 @@
+/// The built-in `email` pipeline plugin, which mails each backup as a zip
+/// archive, e.g. as a simple offsite copy of small saves.
+///
+/// In profiles, the step is written as
+/// `{ "plugin": "email", "options": { "to": "me@example.com", "max_size": "10MB" } }`.
+/// Mail is sent through the `smtp` server from the configuration. Archives
+/// larger than `max_size` are not sent.
+use std::{
+    fs::File,
+    io::{self, Cursor, Seek, SeekFrom, Write},
+    path::PathBuf,
+};
+
+use lettre::message::{header::ContentType, Attachment, MultiPart, SinglePart};
+use zip::{result::ZipError, write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    config::Config,
+    filesystem::walk_files,
+    plugin::{BackupContext, Plugin, PluginError},
+    size::ByteSize,
+};
+
+/// Name the plugin is registered with.
+pub(crate) const NAME: &str = "email";
+
+/// Largest archive sent if the step sets no `max_size`.
+const DEFAULT_MAX_SIZE: ByteSize = ByteSize(10 << 20);
+
+/// Options of the `email` step.
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct EmailExport {
+    /// Address to send the archives to.
+    to: String,
+    /// Largest archive to send.
+    #[serde(default = "default_max_size")]
+    max_size: ByteSize,
+}
+
+/// Default of [`EmailExport::max_size`].
+fn default_max_size() -> ByteSize {
+    DEFAULT_MAX_SIZE
+}
+
+/// Create the plugin from a step's options.
+pub(crate) fn factory(options: &serde_json::Value) -> Result<Box<dyn Plugin>, PluginError> {
+    let export: EmailExport = serde_json::from_value(options.clone())?;
+    export
+        .to
+        .parse::<lettre::message::Mailbox>()
+        .map_err(|e| format!("invalid address {:?}: {}", export.to, e))?;
+    Ok(Box::new(export))
+}
+
+impl Plugin for EmailExport {
+    fn run(&self, backup: &mut BackupContext) -> Result<(), PluginError> {
+        let smtp = Config::load()?
+            .smtp
+            .ok_or("the email plugin requires smtp settings in the configuration")?;
+        let Some(archive) = archive(&backup.artifacts, self.max_size)? else {
+            eprintln!(
+                "warning: not emailing backup {}: archive is larger than {}",
+                backup.id, self.max_size
+            );
+            return Ok(());
+        };
+        let file_name = format!("{}-{}.zip", backup.profile_name, backup.id);
+        let text = format!(
+            "Backup {} of {}, made {}.\n",
+            backup.id,
+            backup.profile_name,
+            crate::template::format_time(&backup.timestamp, None)
+        );
+        let zip = ContentType::parse("application/zip").expect("valid content type");
+        let message = smtp
+            .message(
+                &self.to,
+                &format!("savefile: backup {} of {}", backup.id, backup.profile_name),
+            )?
+            .multipart(
+                MultiPart::mixed()
+                    .singlepart(SinglePart::plain(text))
+                    .singlepart(Attachment::new(file_name).body(archive, zip)),
+            )?;
+        smtp.deliver(&message)?;
+        Ok(())
+    }
+}
+
+/// Compress the given files and directories into a zip archive in memory.
+///
+/// Directories are stored with their contents at the top level, files by name.
+/// Returns `None` as soon as the archive grows larger than `max_size`, so
+/// large backups are never held in memory.
+fn archive(artifacts: &[PathBuf], max_size: ByteSize) -> Result<Option<Vec<u8>>, PluginError> {
+    let mut files = Vec::new();
+    for artifact in artifacts {
+        if artifact.is_dir() {
+            for file in walk_files(artifact)? {
+                let name = file.strip_prefix(artifact).expect("walked inside artifact");
+                let name = name.to_string_lossy().replace('\\', "/");
+                files.push((file, name));
+            }
+        } else {
+            let name = artifact.file_name().unwrap_or_default();
+            files.push((artifact.to_owned(), name.to_string_lossy().into_owned()));
+        }
+    }
+    let mut zip = ZipWriter::new(Capped {
+        buf: Cursor::new(Vec::new()),
+        max_size: max_size.0,
+    });
+    match write_files(&mut zip, &files).and_then(|()| zip.finish()) {
+        Ok(capped) => Ok(Some(capped.buf.into_inner())),
+        Err(ZipError::Io(e)) if e.get_ref().is_some_and(|e| e.is::<TooLarge>()) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Add files to a zip archive under the given names.
+fn write_files<W: Write + Seek>(
+    zip: &mut ZipWriter<W>,
+    files: &[(PathBuf, String)],
+) -> zip::result::ZipResult<()> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    for (file, name) in files {
+        zip.start_file(name, options)?;
+        io::copy(&mut File::open(file)?, zip)?;
+    }
+    Ok(())
+}
+
+/// Error of writes past the end of a [`Capped`] buffer.
+#[derive(Debug, thiserror::Error)]
+#[error("archive is too large")]
+struct TooLarge;
+
+/// An in-memory buffer refusing to grow larger than `max_size` bytes.
+struct Capped {
+    buf: Cursor<Vec<u8>>,
+    max_size: u64,
+}
+
+impl Write for Capped {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        if self.buf.position() + data.len() as u64 > self.max_size {
+            return Err(io::Error::other(TooLarge));
+        }
+        self.buf.write(data)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.buf.flush()
+    }
+}
+
+impl Seek for Capped {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.buf.seek(pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archives_stop_at_the_size_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let small = dir.path().join("small");
+        std::fs::create_dir(&small).unwrap();
+        std::fs::write(small.join("a.sav"), "save").unwrap();
+        let zipped = archive(&[small], ByteSize(1 << 10)).unwrap().unwrap();
+        assert!(!zipped.is_empty());
+
+        let large = dir.path().join("large");
+        std::fs::create_dir(&large).unwrap();
+        // random-looking contents that don't compress below the limit
+        let contents = (0..64u32 << 10)
+            .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+            .collect::<Vec<_>>();
+        std::fs::write(large.join("b.sav"), contents).unwrap();
+        assert!(archive(&[large], ByteSize(1 << 10)).unwrap().is_none());
+    }
+}
@@ -0,0 +1,61 @@
+/// Capturing the machine a backup is made on.
+///
+/// Profiles whose saves are backed up from several machines can record the
+/// environment with each backup, so that a shared history shows which machine
+/// each backup came from.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Environment {
+    /// Name of the machine.
+    pub host: String,
+    /// Operating system, e.g. `windows` or `linux`.
+    pub os: String,
+    /// Version of savefile that made the backup.
+    pub savefile_version: String,
+}
+
+impl Environment {
+    /// Returns the environment of the running process.
+    pub fn current() -> Self {
+        Self {
+            host: hostname().unwrap_or_default(),
+            os: std::env::consts::OS.to_owned(),
+            savefile_version: env!("CARGO_PKG_VERSION").to_owned(),
+        }
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::ToSql for Environment {
+    fn to_sql(&self) -> rusqlite::Result<rusqlite::types::ToSqlOutput<'_>> {
+        let json = serde_json::to_string(self).expect("environment is always serializable");
+        Ok(rusqlite::types::ToSqlOutput::from(json))
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl rusqlite::types::FromSql for Environment {
+    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
+        serde_json::from_str(value.as_str()?)
+            .map_err(|e| rusqlite::types::FromSqlError::Other(Box::new(e)))
+    }
+}
+
+/// Returns the name of the machine, if it can be determined.
+#[cfg(unix)]
+fn hostname() -> Option<String> {
+    let mut buf = [0u8; 256];
+    let result = unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) };
+    if result != 0 {
+        return None;
+    }
+    let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    Some(String::from_utf8_lossy(&buf[..len]).into_owned()).filter(|name| !name.is_empty())
+}
+
+/// Returns the name of the machine, if it can be determined.
+#[cfg(not(unix))]
+fn hostname() -> Option<String> {
+    std::env::var("COMPUTERNAME")
+        .ok()
+        .filter(|name| !name.is_empty())
+}
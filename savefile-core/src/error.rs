@@ -0,0 +1,381 @@
+use std::path::{Path, PathBuf};
+
+use crate::{size::ByteSize, BackupId, BackupUid, Timestamp};
+
+#[cfg(feature = "sqlite")]
+type SqliteError = rusqlite::Error;
+type IoError = std::io::Error;
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[cfg(feature = "sqlite")]
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] SqliteError),
+    #[error("invalid metadata file {path}: {reason}")]
+    Metadata { path: PathBuf, reason: String },
+    #[error("io error: {0}")]
+    Io(#[from] IoError),
+    #[error("{context}: {source}")]
+    IoContext {
+        /// Description of the operation that failed, including any paths involved.
+        context: String,
+        source: IoError,
+    },
+    #[error("invalid config {path}: {reason}")]
+    InvalidConfig { path: PathBuf, reason: String },
+    #[error("{0}")]
+    ProfileError(#[from] ProfileError),
+    #[error("{0}")]
+    BackupError(#[from] BackupError),
+    #[error("{0}")]
+    WatcherError(#[from] WatcherError),
+    #[error("{0}")]
+    CoopError(#[from] CoopError),
+    #[error("{url}: {reason}")]
+    Remote { url: String, reason: String },
+    #[error("keyring error for secret {name:?}: {reason}")]
+    Secret { name: String, reason: String },
+    #[error("cannot ask {0:?}: stdin is not a terminal (pass --yes to confirm)")]
+    NotInteractive(String),
+}
+
+impl Error {
+    /// Returns the broad category of this error.
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            #[cfg(feature = "sqlite")]
+            Error::Sqlite(_) => ErrorCategory::Database,
+            Error::Metadata { .. } => ErrorCategory::Database,
+            Error::Io(_) | Error::IoContext { .. } => ErrorCategory::Io,
+            Error::InvalidConfig { .. } => ErrorCategory::InvalidConfig,
+            Error::ProfileError(ProfileError::NoSuchProfile(_))
+            | Error::ProfileError(ProfileError::NoProfiles)
+            | Error::ProfileError(ProfileError::UnknownName { .. }) => ErrorCategory::NoSuchProfile,
+            Error::ProfileError(_) => ErrorCategory::InvalidProfile,
+            Error::BackupError(BackupError::BackupsEmpty)
+            | Error::BackupError(BackupError::NoValidBackups)
+            | Error::BackupError(BackupError::NoSuchBackup(_))
+            | Error::BackupError(BackupError::NoSuchUid(_))
+            | Error::BackupError(BackupError::NoneSelected)
+            | Error::BackupError(BackupError::InvalidId(_))
+            | Error::BackupError(BackupError::NoRestoreToUndo)
+            | Error::BackupError(BackupError::NoRestoreSnapshot(_))
+            | Error::BackupError(BackupError::EmptyArchive(_))
+            | Error::BackupError(BackupError::FileNeverBackedUp(_))
+            | Error::BackupError(BackupError::NoContentIndex(_))
+            | Error::BackupError(BackupError::FileNotInBackup { .. }) => ErrorCategory::NoBackups,
+            Error::BackupError(BackupError::ContentIndexEncrypted(_)) => {
+                ErrorCategory::InvalidConfig
+            }
+            Error::BackupError(BackupError::FileTooLarge { .. })
+            | Error::BackupError(BackupError::BackupTooLarge { .. }) => {
+                ErrorCategory::LimitExceeded
+            }
+            Error::BackupError(BackupError::ValidationFailed { .. }) => {
+                ErrorCategory::ValidationFailed
+            }
+            Error::BackupError(BackupError::PluginFailed { .. })
+            | Error::BackupError(BackupError::ArchiveMismatch { .. })
+            | Error::BackupError(BackupError::UnsafeArchivePath { .. })
+            | Error::BackupError(BackupError::RemoteCopies(_))
+            | Error::BackupError(BackupError::StaleDeletePlan)
+            | Error::BackupError(BackupError::RemoteDeleteFailed(_)) => ErrorCategory::Other,
+            Error::WatcherError(WatcherError::AlreadyWatched { .. }) => ErrorCategory::Locked,
+            Error::WatcherError(WatcherError::RequestFailed(_)) => ErrorCategory::Other,
+            Error::CoopError(CoopError::NotCoop(_)) => ErrorCategory::InvalidProfile,
+            Error::CoopError(CoopError::NoSharedSave(_)) => ErrorCategory::NoBackups,
+            Error::CoopError(CoopError::TurnHeld { .. })
+            | Error::CoopError(CoopError::NotYourTurn(_)) => ErrorCategory::Locked,
+            Error::Remote { .. } => ErrorCategory::Io,
+            Error::Secret { .. } => ErrorCategory::Other,
+            Error::NotInteractive(_) => ErrorCategory::NotInteractive,
+        }
+    }
+
+    /// Returns the process exit code associated with this error.
+    ///
+    /// See [`ErrorCategory::exit_code`] for the list of codes.
+    pub fn exit_code(&self) -> i32 {
+        self.category().exit_code()
+    }
+}
+
+/// Broad categories of errors, used by scripts to branch on failure causes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    /// The requested profile does not exist.
+    NoSuchProfile,
+    /// The profile has no backups.
+    NoBackups,
+    /// A filesystem operation failed.
+    Io,
+    /// The metadata database could not be read or written.
+    Database,
+    /// The profile exists but is malformed or otherwise unusable.
+    InvalidProfile,
+    /// The global configuration file is malformed.
+    InvalidConfig,
+    /// The profile is locked by a running watcher.
+    Locked,
+    /// A backup exceeded the profile's size limits.
+    LimitExceeded,
+    /// Confirmation was required, but no terminal was available to ask.
+    NotInteractive,
+    /// The live files failed the profile's validators.
+    ValidationFailed,
+    /// Any other failure.
+    Other,
+}
+
+impl ErrorCategory {
+    /// Returns the stable exit code for this category.
+    ///
+    /// | Code | Category         |
+    /// |------|------------------|
+    /// | 1    | (unspecified)    |
+    /// | 2    | no such profile  |
+    /// | 3    | no backups       |
+    /// | 4    | IO error         |
+    /// | 5    | database error   |
+    /// | 6    | invalid profile  |
+    /// | 7    | invalid config   |
+    /// | 8    | profile locked   |
+    /// | 9    | limit exceeded   |
+    /// | 10   | not interactive  |
+    /// | 11   | validation failed |
+    ///
+    /// These values are part of the public interface and must not change.
+    pub fn exit_code(self) -> i32 {
+        match self {
+            ErrorCategory::Other => 1,
+            ErrorCategory::NoSuchProfile => 2,
+            ErrorCategory::NoBackups => 3,
+            ErrorCategory::Io => 4,
+            ErrorCategory::Database => 5,
+            ErrorCategory::InvalidProfile => 6,
+            ErrorCategory::InvalidConfig => 7,
+            ErrorCategory::Locked => 8,
+            ErrorCategory::LimitExceeded => 9,
+            ErrorCategory::NotInteractive => 10,
+            ErrorCategory::ValidationFailed => 11,
+        }
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ProfileError {
+    #[error("invalid profile format: {0}")]
+    InvalidFormat(PathBuf),
+    #[error("no profile at: {0}")]
+    NoSuchProfile(PathBuf),
+    #[error("no profile named {name:?}{}", suggestion_hint(.suggestions))]
+    UnknownName {
+        name: String,
+        /// Names of existing profiles that are close to `name`.
+        suggestions: Vec<String>,
+    },
+    #[error("no profiles exist")]
+    NoProfiles,
+    #[error("profile already exists")]
+    AlreadyExists,
+    #[error("invalid profile name {0:?}: {1}")]
+    InvalidName(String, &'static str),
+    #[error("invalid base directory: {0}")]
+    InvalidBase(PathBuf),
+    #[error("the profile's includes match no files in {0} (pass --force to use it anyway)")]
+    RemapNoFiles(PathBuf),
+    #[error(
+        "{0} is on a remote server, so it cannot be watched (schedule `savefile backup` instead)"
+    )]
+    RemoteNotWatchable(String),
+    #[error("invalid delay: {0}")]
+    InvalidDelay(f32),
+    #[error("invalid glob pattern: {0}")]
+    InvalidGlob(String),
+    #[error("invalid regular expression {0:?}: {1}")]
+    InvalidRegex(String, String),
+    #[error("include matches a path outside the base directory: {0}")]
+    IncludeOutsideBase(PathBuf),
+    #[error("{} is not inside the base directory", .0.display())]
+    PathOutsideBase(PathBuf),
+    #[error("unknown profile field {0:?}")]
+    UnknownField(String),
+    #[error("invalid value for profile field {key:?}: {reason}")]
+    InvalidField { key: String, reason: String },
+    #[error("invalid validator: {0}")]
+    InvalidValidator(String),
+    #[error("no plugin named {0:?} is available")]
+    UnknownPlugin(String),
+    #[error("invalid options for plugin {plugin:?}: {reason}")]
+    InvalidPlugin { plugin: String, reason: String },
+    #[error("invalid webhook: {0}")]
+    InvalidWebhook(String),
+}
+
+impl ProfileError {
+    /// Create an error for a missing profile, suggesting similarly named profiles.
+    pub fn unknown_name(name: &str) -> Self {
+        Self::UnknownName {
+            name: name.to_owned(),
+            suggestions: crate::profile::similar_profile_names(name),
+        }
+    }
+}
+
+/// Formats the "did you mean" part of an unknown profile error.
+fn suggestion_hint(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [one] => format!(" (did you mean {:?}?)", one),
+        many => format!(" (did you mean one of: {}?)", many.join(", ")),
+    }
+}
+
+/// Extension trait for attaching context to IO results.
+pub trait IoResultExt<T> {
+    /// Attach a description of the failed operation to the error.
+    fn context(self, f: impl FnOnce() -> String) -> Result<T>;
+
+    /// Attach the operation and offending path to the error,
+    /// e.g. `failed reading C:/save.dat`.
+    fn with_path(self, op: &str, path: impl AsRef<Path>) -> Result<T>;
+}
+
+impl<T> IoResultExt<T> for std::result::Result<T, IoError> {
+    fn context(self, f: impl FnOnce() -> String) -> Result<T> {
+        self.map_err(|source| Error::IoContext {
+            context: f(),
+            source,
+        })
+    }
+
+    fn with_path(self, op: &str, path: impl AsRef<Path>) -> Result<T> {
+        self.context(|| format!("failed {} {}", op, path.as_ref().display()))
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum BackupError {
+    #[error("backups database is empty")]
+    BackupsEmpty,
+    #[error("no backup has passed validation")]
+    NoValidBackups,
+    #[error("no backup with ID {0}")]
+    NoSuchBackup(BackupId),
+    #[error("no backup with unique ID {0}")]
+    NoSuchUid(BackupUid),
+    #[error("no backups match the selection")]
+    NoneSelected,
+    #[error("invalid backup ID {0:?}: IDs are positive integers or ULIDs")]
+    InvalidId(String),
+    #[error("{} is {size}, which exceeds the file size limit of {limit}", .path.display())]
+    FileTooLarge {
+        path: PathBuf,
+        size: ByteSize,
+        limit: ByteSize,
+    },
+    #[error("backup would be {size}, which exceeds the total size limit of {limit}")]
+    BackupTooLarge { size: ByteSize, limit: ByteSize },
+    #[error("files failed validation: {}{}", .problems.join("; "), last_good_hint(.last_good))]
+    ValidationFailed {
+        /// Description of each problem found.
+        problems: Vec<String>,
+        /// The most recent backup whose files passed validation.
+        last_good: Option<BackupId>,
+    },
+    #[error("backup(s) {} also exist in remote stores (pass --remote to delete the copies too, or --keep-remote to leave them)", join_ids(.0))]
+    RemoteCopies(Vec<BackupId>),
+    #[error("the remote copies changed since the token was issued, review them again")]
+    StaleDeletePlan,
+    #[error("failed deleting {0} remote copies, so the local backups were kept")]
+    RemoteDeleteFailed(usize),
+    #[error("no restore to undo")]
+    NoRestoreToUndo,
+    #[error("the restore of backup {0} was run without --snapshot, so it can't be undone")]
+    NoRestoreSnapshot(BackupId),
+    #[error("{} holds no files", .0.display())]
+    EmptyArchive(PathBuf),
+    #[error("no backup holds {}", .0.display())]
+    FileNeverBackedUp(PathBuf),
+    #[error("profile {0:?} has no content index (set its \"content_index\" field, then run `savefile search --reindex`)")]
+    NoContentIndex(String),
+    #[error("profile {0:?} can't keep a content index while the metadata databases are encrypted, since the index holds the text of the saves unencrypted (unset its \"content_index\" field)")]
+    ContentIndexEncrypted(String),
+    #[error("backup {id} holds no file {}", .path.display())]
+    FileNotInBackup { id: BackupId, path: PathBuf },
+    #[error("{} is unsafe to extract: {entry:?} would be written outside the target directory", .archive.display())]
+    UnsafeArchivePath { archive: PathBuf, entry: String },
+    #[error("{} is not a backup of profile {profile:?}: {} is not matched by its includes{}", .archive.display(), .files[0].display(), more_files(.files))]
+    ArchiveMismatch {
+        archive: PathBuf,
+        profile: String,
+        files: Vec<PathBuf>,
+    },
+    #[error("backup {id} was saved, but plugin {plugin:?} failed: {reason}")]
+    PluginFailed {
+        id: BackupId,
+        plugin: String,
+        reason: String,
+    },
+}
+
+/// Formats a list of backup IDs, e.g. `1, 2, 5`.
+fn join_ids(ids: &[BackupId]) -> String {
+    ids.iter()
+        .map(BackupId::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Formats the count of further unmatched files of an archive.
+fn more_files(files: &[PathBuf]) -> String {
+    match files.len() {
+        0 | 1 => String::new(),
+        n => format!(" (nor are {} other files)", n - 1),
+    }
+}
+
+/// Formats the last known good backup part of a validation error.
+fn last_good_hint(last_good: &Option<BackupId>) -> String {
+    match last_good {
+        Some(id) => format!(" (last known good backup: {})", id),
+        None => String::new(),
+    }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum WatcherError {
+    #[error("profile {profile:?} is already being watched{}", pid_hint(.pid))]
+    AlreadyWatched { profile: String, pid: Option<u32> },
+    #[error("running watcher failed the request: {0}")]
+    RequestFailed(String),
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum CoopError {
+    #[error("profile {0:?} has no coop settings")]
+    NotCoop(String),
+    #[error("no save of profile {0:?} has been shared yet")]
+    NoSharedSave(String),
+    #[error("it is {player}'s turn since {since} (pass --steal to take it anyway)")]
+    TurnHeld { player: String, since: Timestamp },
+    #[error("it is not your turn{} (take it with `savefile coop take` first)", turn_hint(.0))]
+    NotYourTurn(Option<String>),
+}
+
+/// Formats the holder part of a co-op turn error.
+fn turn_hint(player: &Option<String>) -> String {
+    match player {
+        Some(player) => format!(", {} holds it", player),
+        None => String::new(),
+    }
+}
+
+/// Formats the process ID part of a watcher error.
+fn pid_hint(pid: &Option<u32>) -> String {
+    match pid {
+        Some(pid) => format!(" (pid {})", pid),
+        None => String::new(),
+    }
+}
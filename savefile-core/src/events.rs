@@ -0,0 +1,106 @@
+/// Events reported by running watchers.
+///
+/// Watchers publish what they do, and any number of listeners in the same
+/// process (such as the gRPC server) may subscribe to the stream. Every event
+/// is also appended to its profile's event log, `events/<profile>.jsonl` in the
+/// install directory, one JSON object per line, for other tools to follow.
+use std::{
+    io::Write,
+    path::PathBuf,
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        Mutex,
+    },
+};
+
+use chrono::Utc;
+
+use crate::{
+    error::{IoResultExt, Result},
+    filesystem::event_log_path,
+    profile::ProfileName,
+    BackupCause, BackupId, Timestamp,
+};
+
+/// Senders of the current subscribers.
+static SUBSCRIBERS: Mutex<Vec<Sender<WatchEvent>>> = Mutex::new(Vec::new());
+
+/// Something a watcher did.
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+pub struct WatchEvent {
+    /// When it happened.
+    pub time: Timestamp,
+    /// Name of the watched profile.
+    pub profile: String,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened in a [`WatchEvent`].
+#[derive(Clone, Debug, PartialEq, serde::Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// The watcher started.
+    Started,
+    /// Included files changed; a backup follows after the profile's delay.
+    Changed { files: Vec<PathBuf> },
+    /// A backup is starting.
+    BackupStarted { cause: BackupCause },
+    /// A backup was created.
+    BackedUp { id: BackupId, tag: Option<String> },
+    /// A backup failed.
+    BackupFailed { error: String },
+    /// Old backups were deleted to keep only the most recent ones.
+    Pruned { ids: Vec<BackupId> },
+    /// The base directory became unavailable, so watching paused.
+    Unavailable,
+    /// The base directory is available again, so watching resumed.
+    Available,
+    /// Automatic backups were paused.
+    Paused,
+    /// Automatic backups were resumed.
+    Resumed,
+}
+
+/// Receive all events published from now on.
+///
+/// Dropping the receiver unsubscribes.
+pub fn subscribe() -> Receiver<WatchEvent> {
+    let (tx, rx) = mpsc::channel();
+    SUBSCRIBERS
+        .lock()
+        .expect("event subscribers poisoned")
+        .push(tx);
+    rx
+}
+
+/// Send an event about the given profile to all subscribers, and append it
+/// to the profile's event log.
+pub(crate) fn publish(profile: &ProfileName, kind: EventKind) {
+    let event = WatchEvent {
+        time: Utc::now(),
+        profile: profile.to_string(),
+        kind,
+    };
+    if let Err(e) = append(profile, &event) {
+        eprintln!("warning: failed writing event log: {}", e);
+    }
+    SUBSCRIBERS
+        .lock()
+        .expect("event subscribers poisoned")
+        .retain(|tx| tx.send(event.clone()).is_ok());
+}
+
+/// Append an event to the profile's event log.
+fn append(profile: &ProfileName, event: &WatchEvent) -> Result<()> {
+    let path = event_log_path(profile)?;
+    let mut line = serde_json::to_string(event).expect("events are always serializable");
+    line.push('\n');
+    // a single write of the whole line, so that readers never see half of it
+    std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .and_then(|mut file| file.write_all(line.as_bytes()))
+        .with_path("writing", &path)
+}
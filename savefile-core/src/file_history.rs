@@ -0,0 +1,166 @@
+/// Following a single file through a profile's backups, and restoring one of
+/// its old versions without touching the other live files.
+///
+/// Versions are told apart by the hashes in the backups' manifests, so backups
+/// made before manifests were recorded are not considered.
+use std::path::{Component, Path, PathBuf};
+
+use crate::{
+    archive::{self, Extracted},
+    audit::{self, Operation},
+    backup::restore_dir,
+    copy::CopyEngine,
+    database::Database,
+    error::{BackupError, ProfileError, Result},
+    filesystem::{backup_dir, staging_dir},
+    profile::{Profile, ProfileName},
+    Backup, BackupId, FileRecord,
+};
+
+/// A version of a file, along with the first backup holding it.
+#[derive(Clone, Debug)]
+pub struct FileVersion {
+    /// The first backup holding this version.
+    pub backup: Backup,
+    /// The file's record in that backup's manifest.
+    pub file: FileRecord,
+}
+
+/// Returns a path given on the command line as a path relative to the
+/// profile's base directory.
+///
+/// Absolute paths must be inside the base directory, and relative paths must
+/// not leave it.
+pub fn relative_path(profile: &Profile, path: &Path) -> Result<PathBuf> {
+    let rel = match path.is_absolute() {
+        true => path
+            .strip_prefix(profile.base())
+            .map_err(|_| ProfileError::PathOutsideBase(path.to_owned()))?,
+        false => path,
+    };
+    let parts = rel
+        .components()
+        .filter(|component| *component != Component::CurDir)
+        .collect::<Vec<_>>();
+    let escapes = parts
+        .iter()
+        .any(|component| !matches!(component, Component::Normal(_)));
+    if parts.is_empty() || escapes {
+        Err(ProfileError::PathOutsideBase(path.to_owned()))?
+    }
+    Ok(parts.iter().collect())
+}
+
+/// Returns every version of a file, oldest first.
+///
+/// Backups are ordered by time, as IDs of deleted backups may be reused, and
+/// manifests left behind by deleted backups are ignored. `path` is relative to the profile's base directory. A backup is listed if
+/// the file's hash differs from the previous backup holding it, so a file that
+/// was deleted and later restored unchanged is listed once.
+pub fn file_history(db: &Database, profile: &ProfileName, path: &Path) -> Result<Vec<FileVersion>> {
+    let mut versions: Vec<FileVersion> = Vec::new();
+    for (backup, file) in db.file_table(profile)?.select_path(path)? {
+        if versions
+            .last()
+            .is_none_or(|last| last.file.hash != file.hash)
+        {
+            versions.push(FileVersion { backup, file });
+        }
+    }
+    Ok(versions)
+}
+
+/// Restore a single file from the backup with the given ID, leaving the other
+/// live files untouched.
+///
+/// `path` is relative to the profile's base directory. Like
+/// [`crate::restore_backup`], a running watcher is paused and the outcome is
+/// recorded in the audit log.
+pub fn restore_file(db: &Database, profile: &ProfileName, path: &Path, id: BackupId) -> Result<()> {
+    let result = restore_one(db, profile, path, id);
+    audit::record(profile, Operation::Restore, &[id], None, &result);
+    result
+}
+
+/// Restore a single file, see [`restore_file`].
+fn restore_one(db: &Database, profile: &ProfileName, path: &Path, id: BackupId) -> Result<()> {
+    let backup = db
+        .backup_table(profile)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let extracted = archive::extract_backup(profile, &backup)?;
+    let src_dir = match &extracted {
+        Some(extracted) => extracted.dir().to_owned(),
+        None => backup_dir(profile, &backup)?,
+    };
+    if !src_dir.join(path).is_file() {
+        Err(BackupError::FileNotInBackup {
+            id,
+            path: path.to_owned(),
+        })?
+    }
+    // staging the file alone lets the regular restore copy nothing else
+    let staged =
+        Extracted::create_in(staging_dir(profile)?.join(format!("{}-file", backup.dir_name())))?;
+    CopyEngine::new().copy_paths(&src_dir, staged.dir(), &[path.to_owned()])?;
+    restore_dir(profile, staged.dir())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use super::*;
+    use crate::json_store::JsonStore;
+
+    fn record(hash: &str) -> FileRecord {
+        FileRecord {
+            path: "a.sav".into(),
+            hash: hash.to_owned(),
+            size: 4,
+            changed: true,
+            triggered: false,
+        }
+    }
+
+    /// Check that the history of a file in `db` is ordered by time.
+    fn check_history_order(db: &Database) {
+        let profile = ProfileName::new("history").unwrap();
+        let backups = db.backup_table(&profile).unwrap();
+        let files = db.file_table(&profile).unwrap();
+        let now = Utc::now();
+        // a reused ID can be lower than the IDs of older backups
+        let newer = backups.insert("", &now).unwrap();
+        let older = backups.insert("", &(now - Duration::hours(1))).unwrap();
+        files.insert(newer.id(), &[record("new")]).unwrap();
+        files.insert(older.id(), &[record("old")]).unwrap();
+        // manifests of deleted backups are ignored
+        files
+            .insert(
+                BackupId::new(older.id().get() + 1).unwrap(),
+                &[record("gone")],
+            )
+            .unwrap();
+
+        let history = file_history(db, &profile, Path::new("a.sav")).unwrap();
+        let versions = history
+            .iter()
+            .map(|version| (version.backup.id(), version.file.hash.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(versions, [(older.id(), "old"), (newer.id(), "new")]);
+    }
+
+    #[test]
+    fn history_is_ordered_by_time() {
+        check_history_order(&Database::from_store(JsonStore::in_memory()));
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_history_is_ordered_by_time() {
+        crate::filesystem::use_test_home();
+        let dir = tempfile::tempdir().unwrap();
+        let store = crate::sqlite::SqliteStore::open(&dir.path().join("database.db")).unwrap();
+        check_history_order(&Database::from_store(store));
+    }
+}
@@ -0,0 +1,343 @@
+/// Functions for retrieving and creating directories used by this program.
+///
+/// Here is the structure of the directories used by this program:
+///
+/// ```text
+/// %LOCALAPPDATA%\savefile
+/// ├── audit.jsonl
+/// ├── config.json
+/// ├── database.db (database.json without the `sqlite` feature)
+/// ├── locks
+/// │   ├── profile1.lock (while watched)
+/// │   ├── profile1.paused (while automatic backups are paused)
+/// │   └── ...
+/// ├── profiles
+/// │   ├── profile1.json
+/// │   └── ...
+/// ├── remote
+/// │   ├── profile1 (mirror of an `sftp://` base directory)
+/// │   └── ...
+/// ├── saves
+/// │   ├── profile1
+/// │   │   ├── index.db (if `per_profile_database` is set)
+/// │   │   ├── content.jsonl (if `content_index` is set)
+/// │   │   ├── 1 (id)
+/// │   │   │   └── <files>
+/// │   │   ├── 2.cold (stub of a backup in cold storage)
+/// │   │   ├── 3.zip (backup stored as a zip archive)
+/// │   │   └── ...
+/// │   ├── profile2 (backups in `<storage_dir>/profile2` if set)
+/// │   └── ...
+/// └── staging
+///    ├── profile1 (archives being extracted, or moving to cold storage)
+///    └── ...
+/// ```
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    error::{IoResultExt, ProfileError, Result},
+    profile::{Profile, ProfileName},
+    Backup,
+};
+
+/// Environment variable overriding [`install_dir`].
+const HOME_VAR: &str = "SAVEFILE_HOME";
+
+/// File next to the executable which switches to portable mode, see [`portable_dir`].
+const PORTABLE_FLAG: &str = "portable.flag";
+
+/// Creates the required directories for this program if they do not exist.
+pub fn create_required_dirs() -> Result<()> {
+    create_if_nonexistent(install_dir()?)?;
+    create_if_nonexistent(profiles_dir()?)?;
+    create_if_nonexistent(save_dir()?)?;
+    create_if_nonexistent(locks_dir()?)?;
+    Ok(())
+}
+
+/// Returns the directory where profiles are stored.
+///
+/// This is the directory named by the `SAVEFILE_HOME` environment variable if it
+/// is set, e.g. a mounted volume in a container. Otherwise it is the
+/// [`portable_dir`] if there is one, or `%LOCALAPPDATA%\savefile` on Windows.
+///
+/// This function will create the directory if it does not exist.
+pub fn install_dir() -> Result<PathBuf> {
+    let dir = std::env::var_os(HOME_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(portable_dir);
+    if let Some(dir) = dir {
+        std::fs::create_dir_all(&dir).with_path("creating", &dir)?;
+        return Ok(dir);
+    }
+    let dir = dirs::data_local_dir()
+        .ok_or(io::Error::new(
+            io::ErrorKind::NotFound,
+            "could not find local data directory (set SAVEFILE_HOME)",
+        ))?
+        .join("savefile");
+    create_if_nonexistent(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the install directory of portable mode, or `None` if there is no
+/// `portable.flag` file next to the executable.
+///
+/// In portable mode, all data lives next to the executable, e.g. on a USB
+/// stick, or in the directory named by the first line of the flag file. A
+/// relative directory is relative to the executable's directory.
+pub fn portable_dir() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let exe_dir = exe.parent()?;
+    let flag = std::fs::read_to_string(exe_dir.join(PORTABLE_FLAG)).ok()?;
+    let dir = match flag.lines().next().map(str::trim) {
+        Some(dir) if !dir.is_empty() => exe_dir.join(dir),
+        _ => exe_dir.to_owned(),
+    };
+    Some(dir)
+}
+
+/// Extension of metadata database files, which depends on the metadata store
+/// the program was built with.
+#[cfg(feature = "sqlite")]
+const DATABASE_EXTENSION: &str = "db";
+#[cfg(not(feature = "sqlite"))]
+const DATABASE_EXTENSION: &str = "json";
+
+/// Returns the path to the database.
+pub fn database_path() -> Result<PathBuf> {
+    Ok(install_dir()?
+        .join("database")
+        .with_extension(DATABASE_EXTENSION))
+}
+
+/// Returns the path to the audit log, see [`crate::audit`].
+pub fn audit_log_path() -> Result<PathBuf> {
+    Ok(install_dir()?.join("audit.jsonl"))
+}
+
+/// Returns the path to the configuration file.
+pub fn config_path() -> Result<PathBuf> {
+    Ok(install_dir()?.join("config.json"))
+}
+
+/// Returns the path to the per-profile database for the given profile.
+///
+/// This function will create the profile's save directory if it does not exist.
+pub fn profile_database_path(profile: &ProfileName) -> Result<PathBuf> {
+    let dir = save_dir()?.join(profile);
+    create_if_nonexistent(&dir)?;
+    Ok(dir.join("index").with_extension(DATABASE_EXTENSION))
+}
+
+/// Returns the path to the content index of the given profile, see
+/// [`crate::search`].
+///
+/// This function will create the profile's save directory if it does not exist.
+pub fn content_index_path(profile: &ProfileName) -> Result<PathBuf> {
+    let dir = save_dir()?.join(profile);
+    create_if_nonexistent(&dir)?;
+    Ok(dir.join("content.jsonl"))
+}
+
+/// Returns the directory where profiles are stored.
+pub fn profiles_dir() -> Result<PathBuf> {
+    let dir = install_dir()?.join("profiles");
+    create_if_nonexistent(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the path to a profile with the given name.
+pub fn profile_path(name: impl AsRef<str>) -> Result<PathBuf> {
+    Ok(profiles_dir()?.join(format!("{}.json", name.as_ref())))
+}
+
+/// Returns the directory where watcher lock files are stored.
+pub fn locks_dir() -> Result<PathBuf> {
+    let dir = install_dir()?.join("locks");
+    create_if_nonexistent(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the path to the event log of the given profile, see [`crate::events`].
+pub fn event_log_path(profile: &ProfileName) -> Result<PathBuf> {
+    let dir = install_dir()?.join("events");
+    create_if_nonexistent(&dir)?;
+    Ok(dir.join(format!("{}.jsonl", profile)))
+}
+
+/// Returns the path to the watcher lock file for the given profile.
+pub fn lock_path(profile: &ProfileName) -> Result<PathBuf> {
+    Ok(locks_dir()?.join(format!("{}.lock", profile)))
+}
+
+/// Returns the path to the file marking the given profile's automatic backups
+/// as paused.
+pub fn pause_path(profile: &ProfileName) -> Result<PathBuf> {
+    Ok(locks_dir()?.join(format!("{}.paused", profile)))
+}
+
+/// Returns the local mirror of the given profile's remote base directory.
+///
+/// See [`crate::remote`].
+pub fn remote_mirror_dir(profile: &ProfileName) -> Result<PathBuf> {
+    let dir = install_dir()?.join("remote");
+    create_if_nonexistent(&dir)?;
+    Ok(dir.join(profile))
+}
+
+/// Returns the directory where the given profile's backups are packed into
+/// cold storage archives, and extracted from zip or cold storage archives.
+///
+/// See [`crate::archive`] and [`crate::cold`].
+pub fn staging_dir(profile: &ProfileName) -> Result<PathBuf> {
+    let dir = install_dir()?.join("staging");
+    create_if_nonexistent(&dir)?;
+    Ok(dir.join(profile))
+}
+
+/// Returns the directory where save files are stored.
+pub fn save_dir() -> Result<PathBuf> {
+    let dir = install_dir()?.join("saves");
+    create_if_nonexistent(&dir)?;
+    Ok(dir)
+}
+
+/// Returns the directory holding the backups of the given profile.
+///
+/// This is a directory named after the profile, in the profile's `storage_dir`
+/// if it has one and in [`save_dir`] otherwise. Profiles that no longer exist
+/// use the latter.
+///
+/// Only `storage_dir` is read from the profile file, so that the backups of a
+/// profile with otherwise invalid settings can still be found, e.g. to delete
+/// them. Fails only if the file isn't JSON at all.
+pub fn profile_save_dir(profile: &ProfileName) -> Result<PathBuf> {
+    let path = profile_path(profile)?;
+    let contents = match std::fs::read(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(save_dir()?.join(profile)),
+        other => other.with_path("reading profile", &path)?,
+    };
+    let value = serde_json::from_slice::<serde_json::Value>(&contents)
+        .map_err(|_| ProfileError::InvalidFormat(path))?;
+    match value.get("storage_dir").and_then(|dir| dir.as_str()) {
+        Some(dir) => Ok(Path::new(dir).join(profile)),
+        None => Ok(save_dir()?.join(profile)),
+    }
+}
+
+/// Returns the directory holding the backups of the given profile, according
+/// to its (possibly unsaved) settings. See [`profile_save_dir`].
+pub fn profile_save_dir_of(name: &ProfileName, profile: &Profile) -> Result<PathBuf> {
+    match profile.storage_dir() {
+        Some(dir) => Ok(dir.join(name)),
+        None => Ok(save_dir()?.join(name)),
+    }
+}
+
+/// Returns the path to the directory of the given backup.
+pub fn backup_dir(profile: &ProfileName, backup: &Backup) -> Result<PathBuf> {
+    Ok(profile_save_dir(profile)?.join(backup.dir_name()))
+}
+
+/// Expand the given glob pattern.
+pub fn match_glob(pattern: &str) -> Result<Vec<PathBuf>> {
+    let paths = glob::glob(pattern).expect("invalid glob pattern");
+    let mut paths: Vec<PathBuf> = paths.filter_map(|p| p.ok()).collect();
+    paths.sort();
+    Ok(paths)
+}
+
+/// Recursively list all files (not directories) under `dir`.
+pub fn walk_files(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir).with_path("reading directory", dir)? {
+        let path = entry.with_path("reading directory", dir)?.path();
+        if path.is_dir() {
+            files.extend(walk_files(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+/// Recursively list all directories under `dir`, without following symbolic links.
+pub fn walk_dirs(dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    let dir = dir.as_ref();
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(dir).with_path("reading directory", dir)? {
+        let entry = entry.with_path("reading directory", dir)?;
+        let file_type = entry.file_type().with_path("reading directory", dir)?;
+        if file_type.is_dir() {
+            let path = entry.path();
+            dirs.extend(walk_dirs(&path)?);
+            dirs.push(path);
+        }
+    }
+    Ok(dirs)
+}
+
+/// Compute the SHA-256 hash of a file's contents, hex-encoded.
+pub fn hash_file(path: impl AsRef<Path>) -> Result<String> {
+    use sha2::{Digest, Sha256};
+
+    let path = path.as_ref();
+    let mut file = std::fs::File::open(path).with_path("opening", path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher).with_path("hashing", path)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// Set or clear the read-only flag of a single file.
+///
+/// On Unix, clearing the flag only restores the owner's write permission.
+pub fn set_readonly(path: impl AsRef<Path>, readonly: bool) -> Result<()> {
+    let path = path.as_ref();
+    let mut perms = std::fs::metadata(path)
+        .with_path("reading metadata of", path)?
+        .permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = if readonly {
+            perms.mode() & !0o222
+        } else {
+            perms.mode() | 0o200
+        };
+        perms.set_mode(mode);
+    }
+    #[cfg(not(unix))]
+    perms.set_readonly(readonly);
+    std::fs::set_permissions(path, perms).with_path("setting permissions of", path)?;
+    Ok(())
+}
+
+/// Create a directory if it does not exist.
+fn create_if_nonexistent(dir: impl AsRef<Path>) -> Result<()> {
+    match std::fs::create_dir(dir.as_ref()) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        other => other,
+    }?;
+    Ok(())
+}
+
+/// Point the install directory at a temporary directory shared by all tests,
+/// so that tests never touch the real one.
+///
+/// Tests using it should use profile names of their own.
+#[cfg(test)]
+pub(crate) fn use_test_home() -> &'static Path {
+    static HOME: std::sync::OnceLock<tempfile::TempDir> = std::sync::OnceLock::new();
+    HOME.get_or_init(|| {
+        let home = tempfile::tempdir().unwrap();
+        std::env::set_var(HOME_VAR, home.path());
+        home
+    })
+    .path()
+}
@@ -0,0 +1,184 @@
+/// gRPC service for controlling a running watcher process remotely.
+///
+/// The service is defined in `proto/savefile.proto`, so typed clients can be
+/// generated for other languages.
+use std::{collections::HashSet, net::SocketAddr, pin::Pin};
+
+use tokio_stream::{
+    wrappers::{ReceiverStream, TcpListenerStream},
+    Stream,
+};
+use tonic::{transport::Server, Request, Response, Status};
+
+use crate::{
+    control,
+    error::{Error, ErrorCategory, IoResultExt, Result},
+    events::{self, EventKind},
+    profile::{list_profile_names, Profile, ProfileName},
+    BackupCause,
+};
+
+mod proto {
+    tonic::include_proto!("savefile");
+}
+
+use proto::{
+    savefile_server::{Savefile, SavefileServer},
+    watch_event::Kind,
+};
+
+/// Start serving on the given address in the background.
+///
+/// Fails if the address can't be bound.
+pub fn serve(addr: SocketAddr) -> Result<()> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .context(|| "failed starting gRPC runtime".to_owned())?;
+    let listener = runtime
+        .block_on(tokio::net::TcpListener::bind(addr))
+        .context(|| format!("failed listening on {}", addr))?;
+    std::thread::spawn(move || {
+        runtime.block_on(async move {
+            let result = Server::builder()
+                .add_service(SavefileServer::new(Service))
+                .serve_with_incoming(TcpListenerStream::new(listener))
+                .await;
+            if let Err(e) = result {
+                eprintln!("gRPC server failed: {}", e);
+            }
+        })
+    });
+    Ok(())
+}
+
+/// Implementation of the `Savefile` service.
+struct Service;
+
+#[tonic::async_trait]
+impl Savefile for Service {
+    async fn list_profiles(
+        &self,
+        _request: Request<proto::ListProfilesRequest>,
+    ) -> Result<Response<proto::ListProfilesResponse>, Status> {
+        let profiles = blocking(|| {
+            Ok(list_profile_names()?
+                .into_iter()
+                .map(|name| proto::Profile {
+                    base: Profile::open_named(&name)
+                        .map(|profile| profile.base().display().to_string())
+                        .unwrap_or_default(),
+                    name: name.into(),
+                })
+                .collect())
+        })
+        .await?;
+        Ok(Response::new(proto::ListProfilesResponse { profiles }))
+    }
+
+    async fn create_backup(
+        &self,
+        request: Request<proto::CreateBackupRequest>,
+    ) -> Result<Response<proto::CreateBackupResponse>, Status> {
+        let request = request.into_inner();
+        let name = ProfileName::new(request.profile)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let id = blocking(move || {
+            let tag = request.tag.as_deref();
+            control::backup_now(&name, tag, BackupCause::Manual)
+        })
+        .await?;
+        Ok(Response::new(proto::CreateBackupResponse { id: id.get() }))
+    }
+
+    type StreamWatchEventsStream =
+        Pin<Box<dyn Stream<Item = Result<proto::WatchEvent, Status>> + Send>>;
+
+    async fn stream_watch_events(
+        &self,
+        request: Request<proto::StreamWatchEventsRequest>,
+    ) -> Result<Response<Self::StreamWatchEventsStream>, Status> {
+        let profiles = request
+            .into_inner()
+            .profiles
+            .into_iter()
+            .collect::<HashSet<_>>();
+        let events = events::subscribe();
+        let (tx, rx) = tokio::sync::mpsc::channel(64);
+        // stops at the first event after the client disconnects
+        std::thread::spawn(move || {
+            for event in events {
+                if !profiles.is_empty() && !profiles.contains(&event.profile) {
+                    continue;
+                }
+                if tx.blocking_send(Ok(event.into())).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Response::new(Box::pin(ReceiverStream::new(rx))))
+    }
+}
+
+impl From<events::WatchEvent> for proto::WatchEvent {
+    fn from(event: events::WatchEvent) -> Self {
+        let kind = match event.kind {
+            EventKind::Started => Kind::Started(proto::Started {}),
+            EventKind::Changed { files } => Kind::Changed(proto::Changed {
+                files: files
+                    .iter()
+                    .map(|path| path.display().to_string())
+                    .collect(),
+            }),
+            EventKind::BackupStarted { cause } => Kind::BackupStarted(proto::BackupStarted {
+                cause: cause.to_string(),
+            }),
+            EventKind::BackedUp { id, tag } => {
+                Kind::BackedUp(proto::BackedUp { id: id.get(), tag })
+            }
+            EventKind::BackupFailed { error } => Kind::BackupFailed(proto::BackupFailed { error }),
+            EventKind::Pruned { ids } => Kind::Pruned(proto::Pruned {
+                ids: ids.iter().map(|id| id.get()).collect(),
+            }),
+            EventKind::Unavailable => Kind::Unavailable(proto::Unavailable {}),
+            EventKind::Available => Kind::Available(proto::Available {}),
+            EventKind::Paused => Kind::Paused(proto::Paused {}),
+            EventKind::Resumed => Kind::Resumed(proto::Resumed {}),
+        };
+        Self {
+            profile: event.profile,
+            time: event.time.to_rfc3339(),
+            kind: Some(kind),
+        }
+    }
+}
+
+/// Run blocking library code off the async runtime.
+async fn blocking<T, F>(f: F) -> Result<T, Status>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?
+        .map_err(status)
+}
+
+/// Convert a library error into the closest gRPC status.
+fn status(e: Error) -> Status {
+    let message = e.to_string();
+    match e.category() {
+        ErrorCategory::NoSuchProfile | ErrorCategory::NoBackups => Status::not_found(message),
+        ErrorCategory::InvalidProfile | ErrorCategory::InvalidConfig => {
+            Status::invalid_argument(message)
+        }
+        ErrorCategory::Locked => Status::unavailable(message),
+        ErrorCategory::LimitExceeded
+        | ErrorCategory::ValidationFailed
+        | ErrorCategory::NotInteractive => Status::failed_precondition(message),
+        ErrorCategory::Io | ErrorCategory::Database | ErrorCategory::Other => {
+            Status::internal(message)
+        }
+    }
+}
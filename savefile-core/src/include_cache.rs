@@ -0,0 +1,165 @@
+/// Cache of expanded includes, so that profiles matching many files aren't
+/// globbed again by every backup, diff and watcher event.
+///
+/// Entries are keyed by a hash of the profile's contents and checked against
+/// a digest of every directory's listing under the profile's base, which
+/// changes whenever an entry is added to, removed from or renamed within the
+/// directory. Directory modification times would be cheaper to compare, but
+/// aren't reliably updated on network filesystems such as SMB shares.
+/// Expansions that could change without touching a directory are not cached:
+/// those of profiles with `max_size` includes, and of bases containing symbolic
+/// links to directories.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, OnceLock},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{error::Result, profile::Profile};
+
+/// Maximum number of cached expansions.
+const CAPACITY: usize = 32;
+
+/// A cached expansion.
+struct Entry {
+    /// Listing digest of the base and each directory under it.
+    dirs: Vec<(PathBuf, [u8; 32])>,
+    /// The expanded paths.
+    paths: Vec<PathBuf>,
+}
+
+impl Entry {
+    /// Returns `true` if no directory changed since the expansion.
+    fn is_fresh(&self) -> bool {
+        self.dirs
+            .iter()
+            .all(|(dir, digest)| listing(dir).is_some_and(|(d, _)| d == *digest))
+    }
+}
+
+/// Returns the cache, keyed by [`key`].
+fn cache() -> MutexGuard<'static, HashMap<[u8; 32], Entry>> {
+    static CACHE: OnceLock<Mutex<HashMap<[u8; 32], Entry>>> = OnceLock::new();
+    CACHE
+        .get_or_init(Mutex::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the cached expansion of the profile's includes, or runs `expand`
+/// and caches its result.
+pub(crate) fn expand(
+    profile: &Profile,
+    relative: bool,
+    expand: impl FnOnce() -> Result<Vec<PathBuf>>,
+) -> Result<Vec<PathBuf>> {
+    let Some(key) = key(profile, relative) else {
+        return expand();
+    };
+    if let Some(entry) = cache().get(&key).filter(|entry| entry.is_fresh()) {
+        return Ok(entry.paths.clone());
+    }
+    // directories are read first, so that changes during the expansion are noticed
+    let dirs = snapshot(profile.base());
+    let paths = expand()?;
+    if let Some(dirs) = dirs {
+        let mut cache = cache();
+        if cache.len() >= CAPACITY {
+            cache.clear();
+        }
+        cache.insert(
+            key,
+            Entry {
+                dirs,
+                paths: paths.clone(),
+            },
+        );
+    }
+    Ok(paths)
+}
+
+/// Forget all cached expansions.
+#[cfg(feature = "testing")]
+pub(crate) fn clear() {
+    cache().clear();
+}
+
+/// Returns the cache key of the profile's expansion, or `None` if it can't be cached.
+fn key(profile: &Profile, relative: bool) -> Option<[u8; 32]> {
+    if profile.includes().iter().any(|i| i.max_size.is_some()) {
+        return None;
+    }
+    let json = serde_json::to_vec(profile).ok()?;
+    let mut hasher = Sha256::new();
+    hasher.update(json);
+    hasher.update([u8::from(relative)]);
+    Some(hasher.finalize().into())
+}
+
+/// Returns the listing digests of `base` and every directory under it, or
+/// `None` if they can't all be read or a symbolic link points to a directory.
+fn snapshot(base: &Path) -> Option<Vec<(PathBuf, [u8; 32])>> {
+    let mut dirs = Vec::new();
+    let mut pending = vec![base.to_owned()];
+    while let Some(dir) = pending.pop() {
+        let (digest, subdirs) = listing(&dir)?;
+        pending.extend(subdirs);
+        dirs.push((dir, digest));
+    }
+    Some(dirs)
+}
+
+/// Returns a digest of the names and kinds of a directory's entries, and its
+/// subdirectories, or `None` if it can't be read or a symbolic link in it
+/// points to a directory.
+fn listing(dir: &Path) -> Option<([u8; 32], Vec<PathBuf>)> {
+    let mut entries = Vec::new();
+    let mut subdirs = Vec::new();
+    for entry in std::fs::read_dir(dir).ok()? {
+        let entry = entry.ok()?;
+        let file_type = entry.file_type().ok()?;
+        if file_type.is_dir() {
+            subdirs.push(entry.path());
+        } else if file_type.is_symlink() && entry.path().is_dir() {
+            return None;
+        }
+        entries.push((entry.file_name(), file_type.is_dir()));
+    }
+    // the order of entries depends on the filesystem
+    entries.sort();
+    let mut hasher = Sha256::new();
+    for (name, is_dir) in entries {
+        hasher.update(name.as_encoded_bytes());
+        hasher.update([0, u8::from(is_dir)]);
+    }
+    Some((hasher.finalize().into(), subdirs))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::profile::Include;
+
+    #[test]
+    fn listing_changes_invalidate_expansions() {
+        let base = tempfile::tempdir().unwrap();
+        let saves = base.path().join("saves");
+        std::fs::create_dir(&saves).unwrap();
+        std::fs::write(saves.join("slot1.sav"), "1").unwrap();
+        let profile = Profile::builder(base.path())
+            .include(Include::new("saves/*.sav"))
+            .build()
+            .unwrap();
+        let expand = || profile.expand_includes(true).unwrap();
+        assert_eq!(expand(), vec![PathBuf::from("saves/slot1.sav")]);
+
+        std::fs::write(saves.join("slot2.sav"), "2").unwrap();
+        assert_eq!(expand().len(), 2);
+        std::fs::rename(saves.join("slot2.sav"), saves.join("slot3.sav")).unwrap();
+        assert!(expand().contains(&PathBuf::from("saves/slot3.sav")));
+        std::fs::remove_file(saves.join("slot1.sav")).unwrap();
+        assert_eq!(expand(), vec![PathBuf::from("saves/slot3.sav")]);
+    }
+}
@@ -0,0 +1,154 @@
+/// Local control channel of running watchers.
+///
+/// Each watcher listens on a local socket (a named pipe on Windows, a Unix
+/// domain socket elsewhere) whose name is recorded in its lock file. Other
+/// commands use it to have the watcher act on their behalf instead of opening
+/// the database concurrently.
+///
+/// The protocol is line-based: clients send one JSON [`Request`] per line and
+/// receive one JSON [`Response`] line for each.
+use std::{
+    io::{BufRead, BufReader, Write},
+    sync::mpsc::{channel, Sender},
+};
+
+use interprocess::local_socket::{LocalSocketListener, LocalSocketStream};
+
+use crate::{
+    error::{IoResultExt, Result},
+    lock::{self, WatcherState},
+    profile::ProfileName,
+    Backup, BackupCause, BackupId,
+};
+
+/// Request sent to a watcher.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    /// Create a backup right away, recording `cause` (manual by default) and
+    /// `tag`.
+    Backup {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cause: Option<BackupCause>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tag: Option<String>,
+    },
+    /// Report the watcher's state.
+    Status,
+    /// Stop making automatic backups until resumed.
+    Pause,
+    /// Make automatic backups again.
+    Resume,
+    /// Stop watching and release the lock.
+    Stop,
+}
+
+/// Response sent by a watcher.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    /// A backup was created.
+    BackedUp { id: BackupId },
+    /// State of the watcher and its most recent backup.
+    Status {
+        state: WatcherState,
+        latest: Option<Box<Backup>>,
+    },
+    /// Automatic backups were paused or resumed.
+    Paused { paused: bool },
+    /// The watcher is stopping.
+    Stopped,
+    /// The request failed.
+    Error { message: String },
+}
+
+/// A request received by the watcher, with a channel for the response.
+pub struct Control {
+    pub request: Request,
+    pub reply: Sender<Response>,
+}
+
+/// Returns the name of the control endpoint for the given profile.
+#[cfg(windows)]
+pub fn endpoint_name(profile: &ProfileName) -> Result<String> {
+    Ok(format!("@savefile-{}", profile))
+}
+
+/// Returns the name of the control endpoint for the given profile.
+#[cfg(not(windows))]
+pub fn endpoint_name(profile: &ProfileName) -> Result<String> {
+    let path = crate::filesystem::locks_dir()?.join(format!("{}.sock", profile));
+    Ok(path.display().to_string())
+}
+
+/// Listen for requests on the given endpoint in the background.
+///
+/// Each request is forwarded through `tx`. Must only be called while holding
+/// the profile's watcher lock.
+pub fn listen<T: From<Control> + Send + 'static>(endpoint: &str, tx: Sender<T>) -> Result<()> {
+    #[cfg(not(windows))]
+    {
+        // left behind by a watcher that didn't exit cleanly; we hold the lock
+        let _ = std::fs::remove_file(endpoint);
+    }
+    let listener = LocalSocketListener::bind(endpoint)
+        .context(|| format!("failed listening on {}", endpoint))?;
+    std::thread::spawn(move || {
+        for conn in listener.incoming().filter_map(|conn| conn.ok()) {
+            let tx = tx.clone();
+            std::thread::spawn(move || serve(conn, tx));
+        }
+    });
+    Ok(())
+}
+
+/// Answer requests on a single connection until the client disconnects.
+fn serve<T: From<Control>>(conn: LocalSocketStream, tx: Sender<T>) -> std::io::Result<()> {
+    let mut conn = BufReader::new(conn);
+    let mut line = String::new();
+    while conn.read_line(&mut line)? > 0 {
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let (reply, rx) = channel();
+                let _ = tx.send(Control { request, reply }.into());
+                rx.recv().unwrap_or_else(|_| Response::Error {
+                    message: "watcher stopped".to_owned(),
+                })
+            }
+            Err(e) => Response::Error {
+                message: format!("invalid request: {}", e),
+            },
+        };
+        let mut out = serde_json::to_string(&response).expect("response is always serializable");
+        out.push('\n');
+        conn.get_mut().write_all(out.as_bytes())?;
+        line.clear();
+    }
+    Ok(())
+}
+
+/// Send a request to the watcher of the given profile.
+///
+/// Returns `None` if no watcher is running or it can't be reached, in which
+/// case the caller should perform the action itself.
+pub fn request(profile: &ProfileName, request: &Request) -> Result<Option<Response>> {
+    let endpoint = match lock::read_state(profile)?.and_then(|state| state.endpoint) {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+    let conn = match LocalSocketStream::connect(endpoint.as_str()) {
+        Ok(conn) => conn,
+        Err(_) => return Ok(None),
+    };
+    let context = || format!("failed talking to watcher at {}", endpoint);
+    let mut conn = BufReader::new(conn);
+    let mut out = serde_json::to_string(request).expect("request is always serializable");
+    out.push('\n');
+    conn.get_mut().write_all(out.as_bytes()).context(context)?;
+    let mut line = String::new();
+    conn.read_line(&mut line).context(context)?;
+    let response = serde_json::from_str(&line)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        .context(context)?;
+    Ok(Some(response))
+}
@@ -0,0 +1,478 @@
+/// Pure-Rust metadata store keeping backup metadata in a JSON file.
+///
+/// This is the fallback for builds without the `sqlite` feature, for platforms
+/// where building SQLite is problematic. The file is read before every
+/// operation and rewritten after every change, which is fine for the histories
+/// of typical profiles but slower than SQLite for very large ones.
+///
+/// Changes are made while holding an advisory lock on `<file>.lock`, so that
+/// watchers and commands changing the metadata at the same time don't lose
+/// each other's changes.
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, MutexGuard,
+    },
+};
+
+use crate::{
+    backup::{Backup, BackupCause, BackupId, BackupStatus, FileRecord, Timestamp},
+    database::{BackupField, MetadataStore},
+    error::{Error, IoResultExt, Result},
+};
+
+/// Number of metadata files written by this process, making the names of
+/// partially written files unique.
+static PARTIAL_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The metadata of one profile.
+#[derive(Default, serde::Serialize, serde::Deserialize)]
+struct Tables {
+    /// Backups, in order of their IDs.
+    #[serde(default)]
+    backups: Vec<Backup>,
+    /// File manifests of the backups, each sorted by path.
+    #[serde(default)]
+    files: Vec<Manifest>,
+}
+
+/// The file manifest of one backup.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Manifest {
+    backup_id: BackupId,
+    files: Vec<FileRecord>,
+}
+
+impl Tables {
+    /// Returns the index of the backup with the given ID, or where it would be inserted.
+    fn position(&self, id: BackupId) -> std::result::Result<usize, usize> {
+        self.backups.binary_search_by_key(&id, Backup::id)
+    }
+
+    /// Insert a backup with its own ID, replacing any backup with that ID.
+    fn put_backup(&mut self, backup: &Backup) {
+        match self.position(backup.id()) {
+            Ok(index) => self.backups[index] = backup.clone(),
+            Err(index) => self.backups.insert(index, backup.clone()),
+        }
+    }
+
+    /// Insert records into the manifest of a backup, replacing records with
+    /// the same path.
+    fn insert_files(&mut self, backup_id: BackupId, files: &[FileRecord]) {
+        let manifest = self.manifest(backup_id);
+        for file in files {
+            match manifest.binary_search_by(|f| f.path.cmp(&file.path)) {
+                Ok(index) => manifest[index] = file.clone(),
+                Err(index) => manifest.insert(index, file.clone()),
+            }
+        }
+    }
+
+    /// Returns the manifest of a backup, creating an empty one if necessary.
+    fn manifest(&mut self, backup_id: BackupId) -> &mut Vec<FileRecord> {
+        let index = match self.files.iter().position(|m| m.backup_id == backup_id) {
+            Some(index) => index,
+            None => {
+                self.files.push(Manifest {
+                    backup_id,
+                    files: Vec::new(),
+                });
+                self.files.len() - 1
+            }
+        };
+        &mut self.files[index].files
+    }
+}
+
+/// A [`MetadataStore`] keeping backup metadata in a JSON file, or only in memory.
+///
+/// The file maps each profile name to its backups and file manifests.
+pub struct JsonStore {
+    /// The file holding the metadata, or `None` for an in-memory store.
+    path: Option<PathBuf>,
+    /// The metadata of each profile.
+    profiles: Mutex<BTreeMap<String, Tables>>,
+}
+
+impl JsonStore {
+    /// Open the store at the given path.
+    ///
+    /// The file is created by the first change.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let store = Self {
+            path: Some(path.as_ref().to_owned()),
+            profiles: Mutex::default(),
+        };
+        drop(store.lock()?);
+        Ok(store)
+    }
+
+    /// Create an empty store which is never written to disk.
+    ///
+    /// This is handy as a fake in tests, passed to
+    /// [`Database::from_store`](crate::Database::from_store).
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            profiles: Mutex::default(),
+        }
+    }
+
+    /// Lock the metadata, reloading it from the file first.
+    fn lock(&self) -> Result<MutexGuard<'_, BTreeMap<String, Tables>>> {
+        let mut profiles = self.profiles.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(path) = &self.path {
+            *profiles = match std::fs::read(path) {
+                Ok(bytes) => serde_json::from_slice(&bytes).map_err(|e| Error::Metadata {
+                    path: path.clone(),
+                    reason: e.to_string(),
+                })?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeMap::new(),
+                Err(e) => Err(e).with_path("reading", path)?,
+            };
+        }
+        Ok(profiles)
+    }
+
+    /// Read the metadata of a profile, if there is any.
+    fn read<T>(&self, profile: &str, f: impl FnOnce(Option<&Tables>) -> T) -> Result<T> {
+        Ok(f(self.lock()?.get(profile)))
+    }
+
+    /// Take the lock on the file for changing it, blocking until other
+    /// writers are done.
+    ///
+    /// The lock is released when the returned file is dropped. In-memory
+    /// stores don't need one.
+    fn lock_file(&self) -> Result<Option<File>> {
+        let Some(path) = &self.path else {
+            return Ok(None);
+        };
+        let lock_path = path.with_extension("json.lock");
+        let file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&lock_path)
+            .with_path("opening", &lock_path)?;
+        lock_exclusive(&file).with_path("locking", &lock_path)?;
+        Ok(Some(file))
+    }
+
+    /// Change the metadata of a profile and write the file.
+    fn write<T>(&self, profile: &str, f: impl FnOnce(&mut Tables) -> Result<T>) -> Result<T> {
+        let _file_lock = self.lock_file()?;
+        let mut profiles = self.lock()?;
+        let out = f(profiles.entry(profile.to_owned()).or_default())?;
+        self.save(&profiles)?;
+        Ok(out)
+    }
+
+    /// Clear part of the metadata of a profile and write the file.
+    ///
+    /// Profiles left without backups or manifests are removed entirely.
+    fn drop_table(&self, profile: &str, clear: impl FnOnce(&mut Tables)) -> Result<()> {
+        let _file_lock = self.lock_file()?;
+        let mut profiles = self.lock()?;
+        let Some(tables) = profiles.get_mut(profile) else {
+            return Ok(());
+        };
+        clear(tables);
+        if tables.backups.is_empty() && tables.files.is_empty() {
+            profiles.remove(profile);
+        }
+        self.save(&profiles)
+    }
+
+    /// Write the metadata to the file, replacing it in one step.
+    fn save(&self, profiles: &BTreeMap<String, Tables>) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let json = serde_json::to_vec(profiles).map_err(|e| Error::Metadata {
+            path: path.clone(),
+            reason: e.to_string(),
+        })?;
+        let partial = path.with_extension(format!(
+            "json.{}-{}.partial",
+            std::process::id(),
+            PARTIAL_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::write(&partial, json).with_path("writing", &partial)?;
+        std::fs::rename(&partial, path).with_path("replacing", path)?;
+        Ok(())
+    }
+}
+
+/// Block until an exclusive advisory lock on the file is taken.
+#[cfg(unix)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        // SAFETY: the descriptor is open for the lifetime of `file`
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        if error.kind() != io::ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+}
+
+/// Block until an exclusive lock on the file is taken.
+#[cfg(windows)]
+fn lock_exclusive(file: &File) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+
+    use windows_sys::Win32::{
+        Storage::FileSystem::{LockFileEx, LOCKFILE_EXCLUSIVE_LOCK},
+        System::IO::OVERLAPPED,
+    };
+
+    // SAFETY: an all-zero OVERLAPPED locks from the start of the file
+    let mut overlapped: OVERLAPPED = unsafe { std::mem::zeroed() };
+    // SAFETY: the handle is open for the lifetime of `file`, and `overlapped`
+    // outlives the call, which blocks until the lock is taken
+    let ok = unsafe {
+        LockFileEx(
+            file.as_raw_handle() as _,
+            LOCKFILE_EXCLUSIVE_LOCK,
+            0,
+            u32::MAX,
+            u32::MAX,
+            &mut overlapped,
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Take an exclusive lock on the file.
+///
+/// Files can't be locked on this platform, so concurrent writers aren't
+/// protected against.
+#[cfg(not(any(unix, windows)))]
+fn lock_exclusive(_file: &File) -> io::Result<()> {
+    Ok(())
+}
+
+impl MetadataStore for JsonStore {
+    fn backup_count(&self, profile: &str) -> Result<Option<usize>> {
+        self.read(profile, |tables| tables.map(|t| t.backups.len()))
+    }
+
+    fn create_tables(&self, _profile: &str) -> Result<()> {
+        // a profile's tables are created by its first change, so that reading
+        // never writes the file
+        Ok(())
+    }
+
+    fn insert_backup(&self, profile: &str, tag: &str, timestamp: &Timestamp) -> Result<Backup> {
+        self.write(profile, |tables| {
+            let id = tables.backups.last().map_or(0, |b| b.id().get()) + 1;
+            let backup = Backup::new(
+                BackupId::new(id)?,
+                tag.to_owned(),
+                timestamp.to_owned(),
+                None,
+                None,
+                BackupStatus::Unchecked,
+                BackupCause::Unknown,
+                None,
+                None,
+                None,
+            );
+            tables.backups.push(backup.clone());
+            Ok(backup)
+        })
+    }
+
+    fn put_backup(&self, profile: &str, backup: &Backup) -> Result<()> {
+        self.put_backups(profile, std::slice::from_ref(backup))
+    }
+
+    fn update_backup(&self, profile: &str, id: BackupId, field: BackupField<'_>) -> Result<()> {
+        self.write(profile, |tables| {
+            if let Ok(index) = tables.position(id) {
+                tables.backups[index].set(field);
+            }
+            Ok(())
+        })
+    }
+
+    fn select_backup(&self, profile: &str, id: BackupId) -> Result<Option<Backup>> {
+        self.read(profile, |tables| {
+            let tables = tables?;
+            let index = tables.position(id).ok()?;
+            Some(tables.backups[index].clone())
+        })
+    }
+
+    fn select_page(
+        &self,
+        profile: &str,
+        after: Option<BackupId>,
+        limit: usize,
+    ) -> Result<Vec<Backup>> {
+        self.read(profile, |tables| {
+            let Some(tables) = tables else {
+                return Vec::new();
+            };
+            let start = after.map_or(0, |id| match tables.position(id) {
+                Ok(index) => index + 1,
+                Err(index) => index,
+            });
+            tables
+                .backups
+                .iter()
+                .skip(start)
+                .take(limit)
+                .cloned()
+                .collect()
+        })
+    }
+
+    fn total_size(&self, profile: &str) -> Result<u64> {
+        self.read(profile, |tables| {
+            tables.map_or(0, |t| {
+                t.files.iter().flat_map(|m| &m.files).map(|f| f.size).sum()
+            })
+        })
+    }
+
+    fn remove_backup(&self, profile: &str, id: BackupId) -> Result<()> {
+        self.write(profile, |tables| {
+            tables.backups.retain(|b| b.id() != id);
+            Ok(())
+        })
+    }
+
+    fn put_backups(&self, profile: &str, backups: &[Backup]) -> Result<()> {
+        self.write(profile, |tables| {
+            backups.iter().for_each(|backup| tables.put_backup(backup));
+            Ok(())
+        })
+    }
+
+    fn remove_backups(&self, profile: &str, ids: &[BackupId]) -> Result<()> {
+        self.write(profile, |tables| {
+            tables.backups.retain(|b| !ids.contains(&b.id()));
+            tables.files.retain(|m| !ids.contains(&m.backup_id));
+            Ok(())
+        })
+    }
+
+    fn drop_backups(&self, profile: &str) -> Result<()> {
+        self.drop_table(profile, |tables| tables.backups.clear())
+    }
+
+    fn insert_files(&self, profile: &str, backup_id: BackupId, files: &[FileRecord]) -> Result<()> {
+        self.write(profile, |tables| {
+            tables.insert_files(backup_id, files);
+            Ok(())
+        })
+    }
+
+    fn put_backups_with_files(
+        &self,
+        profile: &str,
+        backups: &[(Backup, Vec<FileRecord>)],
+    ) -> Result<()> {
+        self.write(profile, |tables| {
+            for (backup, files) in backups {
+                tables.put_backup(backup);
+                tables.insert_files(backup.id(), files);
+            }
+            Ok(())
+        })
+    }
+
+    fn select_files(&self, profile: &str, backup_id: BackupId) -> Result<Vec<FileRecord>> {
+        self.read(profile, |tables| {
+            tables
+                .and_then(|t| t.files.iter().find(|m| m.backup_id == backup_id))
+                .map_or_else(Vec::new, |m| m.files.clone())
+        })
+    }
+
+    fn remove_files(&self, profile: &str, backup_id: BackupId) -> Result<()> {
+        self.write(profile, |tables| {
+            tables.files.retain(|m| m.backup_id != backup_id);
+            Ok(())
+        })
+    }
+
+    fn drop_files(&self, profile: &str) -> Result<()> {
+        self.drop_table(profile, |tables| tables.files.clear())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_writers_keep_all_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.json");
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let store = JsonStore::open(&path).unwrap();
+                std::thread::spawn(move || {
+                    for _ in 0..25 {
+                        store
+                            .insert_backup("profile", "tag", &chrono::Utc::now())
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let store = JsonStore::open(&path).unwrap();
+        assert_eq!(store.backup_count("profile").unwrap(), Some(100));
+        let leftovers = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter(|entry| {
+                entry
+                    .as_ref()
+                    .unwrap()
+                    .path()
+                    .to_string_lossy()
+                    .ends_with(".partial")
+            })
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[test]
+    fn reading_does_not_write_the_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.json");
+        let db = crate::Database::from_store(JsonStore::open(&path).unwrap());
+        let profile = crate::ProfileName::new("game").unwrap();
+        assert!(db
+            .backup_table(&profile)
+            .unwrap()
+            .select_all()
+            .unwrap()
+            .is_empty());
+        assert!(db
+            .file_table(&profile)
+            .unwrap()
+            .select_path(Path::new("a.sav"))
+            .unwrap()
+            .is_empty());
+        assert!(!path.exists());
+    }
+}
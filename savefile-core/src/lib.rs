@@ -0,0 +1,89 @@
+//! Backing up and restoring game saves.
+//!
+//! This is the library behind the `savefile` command, for frontends such as
+//! launchers or GUIs. A [`Profile`] (created with [`Profile::builder`] or opened
+//! with [`Profile::open_named`]) specifies which files to back up, and the
+//! functions re-exported here back them up, restore and delete backups, and
+//! watch for changes. Failures are reported as [`error::Error`], whose
+//! [`category`](error::Error::category) tells them apart.
+//!
+//! The items re-exported here, the public modules and the error types follow
+//! semantic versioning. The library never prompts; what to confirm with the
+//! user is up to the frontend.
+pub mod alert;
+pub mod archive;
+pub mod audit;
+mod backup;
+pub mod cold;
+pub mod config;
+pub mod control;
+pub mod coop;
+pub mod copy;
+pub mod database;
+pub mod diff;
+pub mod doctor;
+mod email_export;
+pub mod environment;
+pub mod error;
+pub mod events;
+pub mod file_history;
+pub mod filesystem;
+#[cfg(feature = "grpc")]
+pub mod grpc;
+mod include_cache;
+pub mod ipc;
+pub mod json_store;
+pub mod lock;
+pub mod migrate;
+pub mod mirror;
+#[cfg(feature = "mount")]
+pub mod mount;
+mod ntfs;
+pub mod onedrive;
+mod pattern;
+pub mod plugin;
+#[cfg(feature = "watch")]
+mod priority;
+mod process;
+mod profile;
+#[cfg(feature = "python")]
+mod python;
+pub mod remap;
+pub mod remote;
+pub mod scheduler;
+pub mod search;
+pub mod secret;
+pub mod selection;
+pub mod shell_integration;
+pub mod size;
+mod sparse;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+pub mod sse;
+mod status_file;
+pub mod store;
+mod template;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod throttle;
+pub mod validate;
+pub mod version;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "watch")]
+pub mod watcher;
+pub mod webhook;
+
+pub use backup::{
+    backup, delete_all_backups, delete_backups, delete_one_backup, move_backups, prune_backups,
+    restore_archive, restore_backup, Backup, BackupCause, BackupId, BackupRef, BackupStatus,
+    BackupUid, FileRecord, Timestamp,
+};
+pub use config::Config;
+pub use database::{Database, MetadataStore};
+pub use profile::{
+    list_profile_names, list_profiles, EmptyDirs, Include, LimitAction, Limits, MatchSettings,
+    Profile, ProfileBuilder, ProfileName,
+};
+#[cfg(feature = "watch")]
+pub use watcher::{watch, watch_all};
@@ -0,0 +1,252 @@
+/// Lock files held by running watchers.
+///
+/// While a watcher runs, it holds a lock file for its profile containing its
+/// process ID and runtime state. Other commands read the lock file to find out
+/// whether a watcher is active and what it has been doing.
+///
+/// Watchers refresh a heartbeat in their lock file from a background thread,
+/// so that it stays fresh during long backups. A lock whose heartbeat is too
+/// old was left behind by a crashed watcher and is removed by the next command
+/// that reads it.
+use std::{
+    path::PathBuf,
+    sync::{
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
+
+use chrono::Utc;
+
+use crate::{
+    backup::{BackupId, Timestamp},
+    error::{Error, IoResultExt, Result, WatcherError},
+    filesystem::lock_path,
+    profile::ProfileName,
+};
+
+/// Runtime state of a watcher, as stored in its lock file.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct WatcherState {
+    /// Process ID of the watcher.
+    pub pid: u32,
+    /// When the watcher was started.
+    pub started: Timestamp,
+    /// When the watcher last signalled that it is alive.
+    #[serde(default)]
+    pub heartbeat: Timestamp,
+    /// Outcome of the most recent backup made by the watcher.
+    pub last_backup: Option<BackupOutcome>,
+    /// When the next backup is scheduled, if changes are pending.
+    pub next_backup: Option<Timestamp>,
+    /// Name of the watcher's control endpoint, see [`crate::ipc`].
+    #[serde(default)]
+    pub endpoint: Option<String>,
+    /// Whether watching is paused because the base directory is unavailable,
+    /// e.g. on a disconnected drive.
+    #[serde(default)]
+    pub unavailable: bool,
+    /// Whether automatic backups are paused, see [`crate::control::set_paused`].
+    #[serde(default)]
+    pub paused: bool,
+}
+
+impl WatcherState {
+    /// Returns `true` if the heartbeat is too old for the watcher to still be running.
+    pub fn is_stale(&self) -> bool {
+        let age = Utc::now() - self.heartbeat;
+        age.to_std().is_ok_and(|age| age > STALE_AFTER)
+    }
+}
+
+/// How often a watcher refreshes its heartbeat.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How old a heartbeat may get before the lock is considered stale.
+pub const STALE_AFTER: Duration = Duration::from_secs(60);
+
+/// Outcome of a backup attempt.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct BackupOutcome {
+    /// When the backup was attempted.
+    pub time: Timestamp,
+    /// ID of the created backup, if it succeeded.
+    pub id: Option<BackupId>,
+    /// Error message, if it failed.
+    pub error: Option<String>,
+}
+
+/// Guard for a held watcher lock.
+///
+/// While the guard is alive, a background thread refreshes the heartbeat every
+/// [`HEARTBEAT_INTERVAL`]. The lock file is removed when the guard is dropped.
+pub struct WatchLock {
+    file: Arc<LockFile>,
+    /// Stops the heartbeat thread when dropped.
+    stop: Option<mpsc::Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+/// A lock file and the state written to it.
+struct LockFile {
+    path: PathBuf,
+    state: Mutex<WatcherState>,
+}
+
+impl LockFile {
+    /// Modify the state and write it to the lock file.
+    ///
+    /// The state stays locked while writing, so that writes don't interleave.
+    fn update(&self, f: impl FnOnce(&mut WatcherState)) -> Result<()> {
+        let mut state = self.state.lock().expect("lock state poisoned");
+        f(&mut state);
+        let ser = serde_json::to_string_pretty(&*state).expect("state is always serializable");
+        std::fs::write(&self.path, ser).with_path("writing lock file", &self.path)?;
+        Ok(())
+    }
+
+    /// Refresh the heartbeat if it is due.
+    fn heartbeat(&self) -> Result<()> {
+        let heartbeat = self.state.lock().expect("lock state poisoned").heartbeat;
+        let age = (Utc::now() - heartbeat).to_std().unwrap_or_default();
+        if age >= HEARTBEAT_INTERVAL {
+            self.update(|state| state.heartbeat = Utc::now())?;
+        }
+        Ok(())
+    }
+}
+
+impl WatchLock {
+    /// Acquire the watcher lock for the given profile.
+    ///
+    /// Fails if another watcher already holds the lock. Stale locks are removed first.
+    pub fn acquire(profile: &ProfileName) -> Result<Self> {
+        // removes a stale lock, if any
+        read_state(profile)?;
+        let path = lock_path(profile)?;
+        let file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path);
+        match file {
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                let pid = read_state(profile)?.map(|state| state.pid);
+                Err(WatcherError::AlreadyWatched {
+                    profile: profile.to_string(),
+                    pid,
+                })?
+            }
+            other => other.with_path("creating lock file", &path)?,
+        };
+        let file = Arc::new(LockFile {
+            path,
+            state: Mutex::new(WatcherState {
+                pid: std::process::id(),
+                started: Utc::now(),
+                heartbeat: Utc::now(),
+                last_backup: None,
+                next_backup: None,
+                endpoint: None,
+                unavailable: false,
+                paused: false,
+            }),
+        });
+        // the guard removes the lock file if writing it fails
+        let mut lock = Self {
+            file,
+            stop: None,
+            heartbeat: None,
+        };
+        lock.file.update(|_| {})?;
+        let (stop, stopped) = mpsc::channel();
+        let file = lock.file.clone();
+        let heartbeat = std::thread::spawn(move || loop {
+            match stopped.recv_timeout(HEARTBEAT_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => {
+                    if let Err(e) = file.heartbeat() {
+                        eprintln!("warning: failed refreshing watcher heartbeat: {}", e);
+                    }
+                }
+                _ => return,
+            }
+        });
+        lock.stop = Some(stop);
+        lock.heartbeat = Some(heartbeat);
+        Ok(lock)
+    }
+
+    /// Returns a copy of the stored state.
+    pub fn state(&self) -> WatcherState {
+        self.file.state.lock().expect("lock state poisoned").clone()
+    }
+
+    /// Modify the stored state and write it to the lock file.
+    pub fn update(&mut self, f: impl FnOnce(&mut WatcherState)) -> Result<()> {
+        self.file.update(f)
+    }
+
+    /// Refresh the heartbeat if it is due.
+    ///
+    /// The heartbeat is refreshed in the background while the lock is held, so
+    /// this is only needed to refresh it right away.
+    pub fn heartbeat(&mut self) -> Result<()> {
+        self.file.heartbeat()
+    }
+}
+
+impl Drop for WatchLock {
+    fn drop(&mut self) {
+        // stop the heartbeat before removing the file, so it isn't written again
+        drop(self.stop.take());
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+        let _ = std::fs::remove_file(&self.file.path);
+    }
+}
+
+/// Read the state of the watcher for the given profile.
+///
+/// Returns `None` if no watcher holds the lock. If the lock is stale, it is
+/// removed and `None` is returned.
+pub fn read_state(profile: &ProfileName) -> Result<Option<WatcherState>> {
+    let path = lock_path(profile)?;
+    let contents = match std::fs::read(&path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        other => other.with_path("reading lock file", &path)?,
+    };
+    let state: WatcherState = match serde_json::from_slice(&contents) {
+        Ok(state) => state,
+        // a watcher that crashed mid-write leaves a truncated lock file behind
+        Err(_) if modified_before(&path, STALE_AFTER) => {
+            std::fs::remove_file(&path).with_path("removing stale lock file", &path)?;
+            return Ok(None);
+        }
+        Err(_) => Err(Error::IoContext {
+            context: format!("failed parsing lock file {}", path.display()),
+            source: std::io::ErrorKind::InvalidData.into(),
+        })?,
+    };
+    Ok(Some(state).filter(|state| !remove_if_stale(state, &path)))
+}
+
+/// Remove the lock file if the watcher state is stale.
+///
+/// Returns `true` if the lock was removed.
+fn remove_if_stale(state: &WatcherState, path: &std::path::Path) -> bool {
+    if !state.is_stale() {
+        return false;
+    }
+    std::fs::remove_file(path).is_ok()
+}
+
+/// Returns `true` if the file was last modified more than `age` ago.
+fn modified_before(path: &std::path::Path, age: Duration) -> bool {
+    std::fs::metadata(path)
+        .and_then(|meta| meta.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|elapsed| elapsed > age)
+}
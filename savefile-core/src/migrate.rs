@@ -0,0 +1,277 @@
+/// Upgrades for existing installs whose storage layout is out of date.
+///
+/// Each [`Migration`] inspects a single profile and reports the changes it
+/// would make, so that a dry run can be shown before anything is modified.
+use std::path::PathBuf;
+
+use crate::{
+    archive::{self, Storage},
+    backup::{count_changes, Backup, BackupId, BackupUid},
+    config::Config,
+    database::{relocate_metadata, rewrite_naive_timestamps, Database},
+    error::Result,
+    filesystem::{backup_dir, database_path, profile_database_path},
+    profile::{Profile, ProfileName},
+};
+
+/// A single upgrade step for existing installs.
+pub trait Migration {
+    /// Short description of what the migration does.
+    fn description(&self) -> &'static str;
+
+    /// Returns a list of the changes this migration would make to the given profile.
+    ///
+    /// An empty list means the profile is already up to date.
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>>;
+
+    /// Apply the migration to the given profile.
+    fn apply(&self, profile: &ProfileName) -> Result<()>;
+}
+
+/// Returns all known migrations, in the order they should be applied.
+pub fn migrations() -> Vec<Box<dyn Migration>> {
+    vec![
+        Box::new(MetadataLocation),
+        Box::new(UtcTimestamps),
+        Box::new(ChangedCounts),
+        Box::new(UniqueIds),
+        Box::new(ZipBackups),
+    ]
+}
+
+/// Moves backup metadata into the database selected by the `per_profile_database` flag.
+///
+/// This picks up metadata left behind when the config file was edited by hand.
+struct MetadataLocation;
+
+impl Migration for MetadataLocation {
+    fn description(&self) -> &'static str {
+        "move backup metadata to the configured database"
+    }
+
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>> {
+        let per_profile = Config::load()?.per_profile_database;
+        let (src, dest) = if per_profile {
+            (database_path()?, profile_database_path(profile)?)
+        } else {
+            (profile_database_path(profile)?, database_path()?)
+        };
+        if !src.exists() {
+            return Ok(Vec::new());
+        }
+        match Database::open(&src)?.row_count(profile)? {
+            Some(count) if count > 0 => Ok(vec![format!(
+                "move {} backup record(s) from {} to {}",
+                count,
+                src.display(),
+                dest.display()
+            )]),
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    fn apply(&self, profile: &ProfileName) -> Result<()> {
+        relocate_metadata(profile, Config::load()?.per_profile_database)
+    }
+}
+
+/// Rewrites naive timestamps (written by older versions) as UTC timestamps.
+struct UtcTimestamps;
+
+impl Migration for UtcTimestamps {
+    fn description(&self) -> &'static str {
+        "store backup timestamps with an explicit UTC offset"
+    }
+
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>> {
+        let db = Database::open_for_profile(profile)?;
+        if db.row_count(profile)?.is_none() {
+            return Ok(Vec::new());
+        }
+        match rewrite_naive_timestamps(&db, profile, true)? {
+            0 => Ok(Vec::new()),
+            count => Ok(vec![format!("rewrite {} naive timestamp(s) as UTC", count)]),
+        }
+    }
+
+    fn apply(&self, profile: &ProfileName) -> Result<()> {
+        let db = Database::open_for_profile(profile)?;
+        rewrite_naive_timestamps(&db, profile, false)?;
+        Ok(())
+    }
+}
+
+/// Records how many files changed in backups made before the count was stored.
+///
+/// The counts are derived from the file manifests, so backups without one are skipped.
+struct ChangedCounts;
+
+impl Migration for ChangedCounts {
+    fn description(&self) -> &'static str {
+        "record how many files changed in existing backups"
+    }
+
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>> {
+        let db = Database::open_for_profile(profile)?;
+        match missing_changed_counts(&db, profile)?.len() {
+            0 => Ok(Vec::new()),
+            count => Ok(vec![format!(
+                "record the changed-file count of {} backup(s)",
+                count
+            )]),
+        }
+    }
+
+    fn apply(&self, profile: &ProfileName) -> Result<()> {
+        let db = Database::open_for_profile(profile)?;
+        let backup_table = db.backup_table(profile)?;
+        for (id, count) in missing_changed_counts(&db, profile)? {
+            backup_table.set_changed(id, count)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the changed-file counts of backups that have a manifest but no recorded count.
+fn missing_changed_counts(db: &Database, profile: &ProfileName) -> Result<Vec<(BackupId, u32)>> {
+    if db.row_count(profile)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut backups = db.backup_table(profile)?.select_all()?;
+    backups.sort_by_key(|backup| backup.id());
+    let file_table = db.file_table(profile)?;
+    let mut previous = Vec::new();
+    let mut counts = Vec::new();
+    for backup in backups {
+        let manifest = file_table.select_backup(backup.id())?;
+        if backup.changed_files().is_none() && !manifest.is_empty() {
+            counts.push((backup.id(), count_changes(&manifest, &previous)));
+        }
+        previous = manifest;
+    }
+    Ok(counts)
+}
+
+/// Assigns globally unique IDs to backups made before they were introduced.
+///
+/// The IDs are generated from the backups' timestamps, so they sort like the
+/// backups themselves.
+struct UniqueIds;
+
+impl Migration for UniqueIds {
+    fn description(&self) -> &'static str {
+        "assign globally unique IDs to existing backups"
+    }
+
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>> {
+        let db = Database::open_for_profile(profile)?;
+        match missing_uids(&db, profile)?.len() {
+            0 => Ok(Vec::new()),
+            count => Ok(vec![format!("assign unique IDs to {} backup(s)", count)]),
+        }
+    }
+
+    fn apply(&self, profile: &ProfileName) -> Result<()> {
+        let db = Database::open_for_profile(profile)?;
+        let backup_table = db.backup_table(profile)?;
+        for backup in missing_uids(&db, profile)? {
+            backup_table.set_uid(backup.id(), BackupUid::new(&backup.timestamp()))?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the backups that have no unique ID.
+fn missing_uids(db: &Database, profile: &ProfileName) -> Result<Vec<Backup>> {
+    if db.row_count(profile)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let backups = db.backup_table(profile)?.select_all()?;
+    Ok(backups
+        .into_iter()
+        .filter(|backup| backup.uid().is_none())
+        .collect())
+}
+
+/// Compresses the backup directories of profiles whose `storage` is `zip`.
+///
+/// Only new backups are compressed when they are made, so this picks up the
+/// backups made before the setting changed.
+struct ZipBackups;
+
+impl Migration for ZipBackups {
+    fn description(&self) -> &'static str {
+        "compress existing backups of profiles stored as zip archives"
+    }
+
+    fn plan(&self, profile: &ProfileName) -> Result<Vec<String>> {
+        match unpacked_backups(profile)?.len() {
+            0 => Ok(Vec::new()),
+            count => Ok(vec![format!(
+                "compress {} backup(s) into zip archives",
+                count
+            )]),
+        }
+    }
+
+    fn apply(&self, profile: &ProfileName) -> Result<()> {
+        for (backup, dir) in unpacked_backups(profile)? {
+            archive::pack(profile, &backup, &dir)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the backups still stored as directories, along with their
+/// directories, if the profile's `storage` is `zip`.
+fn unpacked_backups(profile: &ProfileName) -> Result<Vec<(Backup, PathBuf)>> {
+    if Profile::open_named(profile)?.storage() != Storage::Zip {
+        return Ok(Vec::new());
+    }
+    let db = Database::open_for_profile(profile)?;
+    if db.row_count(profile)?.is_none() {
+        return Ok(Vec::new());
+    }
+    let mut unpacked = Vec::new();
+    for backup in db.backup_table(profile)?.select_all()? {
+        let dir = backup_dir(profile, &backup)?;
+        if dir.is_dir() && !archive::is_zipped(profile, &backup)? {
+            unpacked.push((backup, dir));
+        }
+    }
+    Ok(unpacked)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::filesystem::{profile_path, use_test_home};
+
+    #[test]
+    fn zip_storage_compresses_existing_backups() {
+        let home = use_test_home();
+        let profile = ProfileName::new("migrate-zip").unwrap();
+        let settings = Profile::builder(home)
+            .storage(Storage::Zip)
+            .build()
+            .unwrap();
+        settings.save(profile_path(&profile).unwrap()).unwrap();
+        let db = Database::open_for_profile(&profile).unwrap();
+        let backup = db
+            .backup_table(&profile)
+            .unwrap()
+            .insert("", &Utc::now())
+            .unwrap();
+        let dir = backup_dir(&profile, &backup).unwrap();
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.sav"), "save").unwrap();
+
+        assert_eq!(ZipBackups.plan(&profile).unwrap().len(), 1);
+        ZipBackups.apply(&profile).unwrap();
+        assert!(!dir.exists());
+        assert!(archive::is_zipped(&profile, &backup).unwrap());
+        assert!(ZipBackups.plan(&profile).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,129 @@
+/// A second local copy of a profile's backups, e.g. on another drive or a
+/// mounted NAS share.
+///
+/// With the profile's `mirror_dir` set, each new backup is copied to
+/// `<mirror_dir>/<backup directory>` as soon as it is made, and deleting a
+/// backup deletes its mirrored copy too. [`divergences`] finds where the
+/// mirror no longer matches the backups.
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+};
+
+use crate::{
+    backup::{remove_backup_dir, set_tree_readonly},
+    copy::CopyEngine,
+    database::Database,
+    error::{IoResultExt, Result},
+    filesystem::{backup_dir, hash_file, walk_files},
+    profile::{Profile, ProfileName},
+    Backup,
+};
+
+/// Copy a new backup to the profile's mirror directory, if it has one.
+///
+/// Failures are reported as warnings, since the backup itself is saved.
+pub(crate) fn mirror_backup(profile: &Profile, backup: &Backup, dir: &Path) {
+    let Some(mirror) = profile.mirror_dir() else {
+        return;
+    };
+    let dest = mirror.join(backup.dir_name());
+    let result = CopyEngine::new()
+        .copy_tree(dir, &dest)
+        .and_then(|()| set_tree_readonly(&dest, true));
+    if let Err(e) = result {
+        eprintln!(
+            "warning: failed mirroring backup {} to {}: {}",
+            backup.id(),
+            mirror.display(),
+            e
+        );
+    }
+}
+
+/// Delete the mirrored copy of a backup, if there is one.
+pub(crate) fn remove_mirrored(profile: &Profile, backup: &Backup) -> Result<()> {
+    let Some(mirror) = profile.mirror_dir() else {
+        return Ok(());
+    };
+    let dest = mirror.join(backup.dir_name());
+    if dest.is_dir() {
+        remove_backup_dir(&dest)?;
+    }
+    Ok(())
+}
+
+/// Compare the profile's mirror directory against its backups.
+///
+/// Returns a description of each difference: backups missing from the mirror,
+/// mirrored copies whose files differ from the backup's manifest, and
+/// directories in the mirror that belong to no backup.
+pub fn divergences(db: &Database, profile: &Profile, name: &ProfileName) -> Result<Vec<String>> {
+    let Some(mirror) = profile.mirror_dir() else {
+        return Ok(Vec::new());
+    };
+    if !mirror.is_dir() {
+        return Ok(vec![format!(
+            "mirror directory {} does not exist",
+            mirror.display()
+        )]);
+    }
+    let file_table = db.file_table(name)?;
+    let mut found = Vec::new();
+    let mut known = HashSet::new();
+    for backup in db.backup_table(name)?.iter() {
+        let backup = backup?;
+        let copy = mirror.join(backup.dir_name());
+        known.insert(backup.dir_name());
+        if !copy.is_dir() {
+            found.push(format!("backup {} is missing from the mirror", backup.id()));
+            continue;
+        }
+        let mut expected = file_table
+            .select_backup(backup.id())?
+            .into_iter()
+            .map(|file| (file.path, file.hash))
+            .collect::<HashMap<_, _>>();
+        if expected.is_empty() {
+            // backups made before manifests were recorded
+            let dir = backup_dir(name, &backup)?;
+            if !dir.is_dir() {
+                continue;
+            }
+            for file in walk_files(&dir)? {
+                let path = file.strip_prefix(&dir).expect("walked inside backup");
+                expected.insert(path.to_owned(), hash_file(&file)?);
+            }
+        }
+        let (mut changed, mut extra) = (0, 0);
+        for file in walk_files(&copy)? {
+            let path = file.strip_prefix(&copy).expect("walked inside mirror");
+            match expected.remove(path) {
+                Some(hash) if hash != hash_file(&file)? => changed += 1,
+                Some(_) => {}
+                None => extra += 1,
+            }
+        }
+        let missing = expected.len();
+        if missing + changed + extra > 0 {
+            found.push(format!(
+                "backup {} differs in the mirror: {} missing, {} changed and {} extra file(s)",
+                backup.id(),
+                missing,
+                changed,
+                extra
+            ));
+        }
+    }
+    for entry in std::fs::read_dir(mirror).with_path("reading", mirror)? {
+        let path = entry.with_path("reading", mirror)?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() && !known.contains(name.as_ref()) {
+            found.push(format!(
+                "mirror has {}, which belongs to no backup",
+                path.display()
+            ));
+        }
+    }
+    Ok(found)
+}
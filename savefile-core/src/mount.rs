@@ -0,0 +1,297 @@
+/// Read-only virtual filesystem exposing a profile's backup history.
+///
+/// The root of the mounted filesystem contains one directory per backup,
+/// named `<id>_<timestamp>`. Each directory mirrors the contents of the
+/// corresponding backup directory.
+use std::path::Path;
+
+use crate::{database::Database, error::Result, profile::ProfileName};
+
+/// Mount the backup history of the given profile at `mountpoint`.
+///
+/// This function blocks until the filesystem is unmounted.
+#[cfg(unix)]
+pub fn mount(db: &Database, profile: &ProfileName, mountpoint: impl AsRef<Path>) -> Result<()> {
+    use fuser::MountOption;
+
+    let fs = fuse::BackupFs::new(db, profile)?;
+    let options = [
+        MountOption::RO,
+        MountOption::FSName("savefile".to_owned()),
+        MountOption::AutoUnmount,
+    ];
+    fuser::mount2(fs, mountpoint, &options)?;
+    Ok(())
+}
+
+/// Mount the backup history of the given profile at `mountpoint`.
+///
+/// Mounting is currently only supported on Unix-like systems.
+#[cfg(not(unix))]
+pub fn mount(_db: &Database, _profile: &ProfileName, _mountpoint: impl AsRef<Path>) -> Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "mounting backups is not supported on this platform",
+    ))?
+}
+
+/// Returns the directory name used for a backup in the mounted filesystem.
+fn entry_name(backup: &crate::Backup) -> String {
+    format!(
+        "{}_{}",
+        backup.id(),
+        backup.timestamp().format("%Y-%m-%dT%H-%M-%S")
+    )
+}
+
+#[cfg(unix)]
+mod fuse {
+    use std::{
+        collections::HashMap,
+        ffi::{OsStr, OsString},
+        fs::File,
+        os::unix::fs::{FileExt, MetadataExt},
+        path::PathBuf,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    use fuser::{
+        FileAttr, FileType, Filesystem, ReplyAttr, ReplyData, ReplyDirectory, ReplyEntry,
+        ReplyOpen, Request,
+    };
+    use libc::{EACCES, EIO, ENOENT, O_ACCMODE, O_RDONLY};
+
+    use crate::{database::Database, error::Result, filesystem::backup_dir, profile::ProfileName};
+
+    /// How long the kernel may cache attributes and entries.
+    const TTL: Duration = Duration::from_secs(1);
+    /// Inode of the root directory.
+    const ROOT_INO: u64 = 1;
+
+    /// A node in the mounted filesystem.
+    enum Node {
+        /// The virtual root directory listing all backups.
+        Root,
+        /// A real file or directory inside a backup.
+        Real(PathBuf),
+    }
+
+    /// Read-only filesystem over the backups of a single profile.
+    pub struct BackupFs {
+        /// Backup directories, keyed by their entry name in the root.
+        backups: Vec<(OsString, PathBuf)>,
+        /// Inode table.
+        nodes: HashMap<u64, Node>,
+        /// Reverse lookup for inodes of real paths.
+        inodes: HashMap<PathBuf, u64>,
+        /// Next inode to hand out.
+        next_ino: u64,
+    }
+
+    impl BackupFs {
+        /// Create a new filesystem for the given profile.
+        ///
+        /// The list of backups is captured once at mount time.
+        pub fn new(db: &Database, profile: &ProfileName) -> Result<Self> {
+            let mut backups = db
+                .backup_table(profile)?
+                .select_all()?
+                .into_iter()
+                .map(|backup| {
+                    let dir = backup_dir(profile, &backup)?;
+                    Ok((OsString::from(super::entry_name(&backup)), dir))
+                })
+                .collect::<Result<Vec<_>>>()?;
+            // backups stored as zip archives or in cold storage have no
+            // local files to show
+            backups.retain(|(_, dir)| dir.is_dir());
+            backups.sort();
+            let mut nodes = HashMap::new();
+            nodes.insert(ROOT_INO, Node::Root);
+            Ok(Self {
+                backups,
+                nodes,
+                inodes: HashMap::new(),
+                next_ino: ROOT_INO + 1,
+            })
+        }
+
+        /// Returns the inode for a real path, allocating one if necessary.
+        fn inode_of(&mut self, path: PathBuf) -> u64 {
+            if let Some(ino) = self.inodes.get(&path) {
+                return *ino;
+            }
+            let ino = self.next_ino;
+            self.next_ino += 1;
+            self.inodes.insert(path.clone(), ino);
+            self.nodes.insert(ino, Node::Real(path));
+            ino
+        }
+
+        /// Returns the real path behind an inode, if any.
+        fn path_of(&self, ino: u64) -> Option<&PathBuf> {
+            match self.nodes.get(&ino) {
+                Some(Node::Real(path)) => Some(path),
+                _ => None,
+            }
+        }
+
+        /// Returns the attributes of the given inode.
+        fn attr(&self, ino: u64) -> std::io::Result<FileAttr> {
+            match self.nodes.get(&ino) {
+                Some(Node::Root) => Ok(root_attr()),
+                Some(Node::Real(path)) => Ok(real_attr(ino, &std::fs::metadata(path)?)),
+                None => Err(std::io::ErrorKind::NotFound.into()),
+            }
+        }
+    }
+
+    impl Filesystem for BackupFs {
+        fn lookup(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: ReplyEntry) {
+            let path = match self.nodes.get(&parent) {
+                Some(Node::Root) => self
+                    .backups
+                    .iter()
+                    .find(|(entry, _)| entry == name)
+                    .map(|(_, dir)| dir.clone()),
+                Some(Node::Real(dir)) => Some(dir.join(name)).filter(|path| path.exists()),
+                None => None,
+            };
+            let Some(path) = path else {
+                return reply.error(ENOENT);
+            };
+            let ino = self.inode_of(path);
+            match self.attr(ino) {
+                Ok(attr) => reply.entry(&TTL, &attr, 0),
+                Err(_) => reply.error(ENOENT),
+            }
+        }
+
+        fn getattr(&mut self, _req: &Request<'_>, ino: u64, reply: ReplyAttr) {
+            match self.attr(ino) {
+                Ok(attr) => reply.attr(&TTL, &attr),
+                Err(_) => reply.error(ENOENT),
+            }
+        }
+
+        fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
+            if flags & O_ACCMODE != O_RDONLY {
+                return reply.error(EACCES);
+            }
+            match self.path_of(ino) {
+                Some(_) => reply.opened(0, 0),
+                None => reply.error(ENOENT),
+            }
+        }
+
+        fn read(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            size: u32,
+            _flags: i32,
+            _lock_owner: Option<u64>,
+            reply: ReplyData,
+        ) {
+            let Some(path) = self.path_of(ino) else {
+                return reply.error(ENOENT);
+            };
+            let mut buf = vec![0; size as usize];
+            let res = File::open(path).and_then(|file| file.read_at(&mut buf, offset as u64));
+            match res {
+                Ok(n) => reply.data(&buf[..n]),
+                Err(_) => reply.error(EIO),
+            }
+        }
+
+        fn readdir(
+            &mut self,
+            _req: &Request<'_>,
+            ino: u64,
+            _fh: u64,
+            offset: i64,
+            mut reply: ReplyDirectory,
+        ) {
+            let mut entries = vec![
+                (ino, FileType::Directory, OsString::from(".")),
+                (ROOT_INO, FileType::Directory, OsString::from("..")),
+            ];
+            match self.nodes.get(&ino) {
+                Some(Node::Root) => {
+                    for (name, dir) in self.backups.clone() {
+                        entries.push((self.inode_of(dir), FileType::Directory, name));
+                    }
+                }
+                Some(Node::Real(dir)) => {
+                    let Ok(read_dir) = std::fs::read_dir(dir.clone()) else {
+                        return reply.error(EIO);
+                    };
+                    for entry in read_dir.filter_map(|entry| entry.ok()) {
+                        let kind = match entry.file_type() {
+                            Ok(t) if t.is_dir() => FileType::Directory,
+                            _ => FileType::RegularFile,
+                        };
+                        entries.push((self.inode_of(entry.path()), kind, entry.file_name()));
+                    }
+                }
+                None => return reply.error(ENOENT),
+            }
+            for (i, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+                if reply.add(ino, (i + 1) as i64, kind, name) {
+                    break;
+                }
+            }
+            reply.ok();
+        }
+    }
+
+    /// Attributes of the virtual root directory.
+    fn root_attr() -> FileAttr {
+        FileAttr {
+            ino: ROOT_INO,
+            size: 0,
+            blocks: 0,
+            atime: UNIX_EPOCH,
+            mtime: UNIX_EPOCH,
+            ctime: UNIX_EPOCH,
+            crtime: UNIX_EPOCH,
+            kind: FileType::Directory,
+            perm: 0o555,
+            nlink: 2,
+            uid: unsafe { libc::getuid() },
+            gid: unsafe { libc::getgid() },
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+
+    /// Attributes of a real file or directory, with write permissions removed.
+    fn real_attr(ino: u64, meta: &std::fs::Metadata) -> FileAttr {
+        let kind = if meta.is_dir() {
+            FileType::Directory
+        } else {
+            FileType::RegularFile
+        };
+        let time = |t: std::io::Result<std::time::SystemTime>| t.unwrap_or(UNIX_EPOCH);
+        FileAttr {
+            ino,
+            size: meta.len(),
+            blocks: meta.blocks(),
+            atime: time(meta.accessed()),
+            mtime: time(meta.modified()),
+            ctime: time(meta.modified()),
+            crtime: time(meta.created()),
+            kind,
+            perm: (meta.mode() & 0o555) as u16,
+            nlink: meta.nlink() as u32,
+            uid: meta.uid(),
+            gid: meta.gid(),
+            rdev: 0,
+            blksize: 512,
+            flags: 0,
+        }
+    }
+}
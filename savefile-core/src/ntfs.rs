@@ -0,0 +1,131 @@
+/// Preserving NTFS alternate data streams and file attributes.
+///
+/// Some games keep metadata in alternate data streams of their saves, or rely
+/// on saves being hidden or system files. Plain copies drop both, so with the
+/// profile's `ntfs_metadata` set, backups and restores copy them as well.
+///
+/// Only the hidden, system, archive and not-content-indexed attributes are
+/// copied: backups are read-only regardless, and restored files writable.
+/// Zip archives and cold storage keep neither streams nor attributes.
+use std::{io, path::Path};
+
+/// Copy the alternate data streams and attributes of `src` to `dest`.
+///
+/// Does nothing on platforms other than Windows.
+pub(crate) fn copy_metadata(src: &Path, dest: &Path) -> io::Result<()> {
+    #[cfg(windows)]
+    {
+        for stream in windows::streams(src)? {
+            let mut src_stream = src.as_os_str().to_owned();
+            src_stream.push(&stream);
+            let mut dest_stream = dest.as_os_str().to_owned();
+            dest_stream.push(&stream);
+            let mut reader = std::fs::File::open(src_stream)?;
+            let mut writer = std::fs::File::create(dest_stream)?;
+            io::copy(&mut reader, &mut writer)?;
+        }
+        windows::copy_attributes(src, dest)?;
+    }
+    #[cfg(not(windows))]
+    let _ = (src, dest);
+    Ok(())
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::{
+        ffi::{c_void, OsString},
+        io,
+        os::windows::ffi::{OsStrExt, OsStringExt},
+        path::Path,
+    };
+
+    use windows_sys::Win32::{
+        Foundation::{ERROR_HANDLE_EOF, INVALID_HANDLE_VALUE},
+        Storage::FileSystem::{
+            FindClose, FindFirstStreamW, FindNextStreamW, FindStreamInfoStandard,
+            GetFileAttributesW, SetFileAttributesW, FILE_ATTRIBUTE_ARCHIVE, FILE_ATTRIBUTE_HIDDEN,
+            FILE_ATTRIBUTE_NOT_CONTENT_INDEXED, FILE_ATTRIBUTE_SYSTEM, INVALID_FILE_ATTRIBUTES,
+            WIN32_FIND_STREAM_DATA,
+        },
+    };
+
+    /// Attributes copied by [`copy_attributes`].
+    const COPIED_ATTRIBUTES: u32 = FILE_ATTRIBUTE_HIDDEN
+        | FILE_ATTRIBUTE_SYSTEM
+        | FILE_ATTRIBUTE_ARCHIVE
+        | FILE_ATTRIBUTE_NOT_CONTENT_INDEXED;
+
+    /// Name of the unnamed stream holding a file's contents.
+    const MAIN_STREAM: &str = "::$DATA";
+
+    /// Returns the path as a null-terminated wide string.
+    fn wide(path: &Path) -> Vec<u16> {
+        path.as_os_str().encode_wide().chain([0]).collect()
+    }
+
+    /// Returns true if the error means that there are no more streams.
+    fn is_eof(e: &io::Error) -> bool {
+        e.raw_os_error() == Some(ERROR_HANDLE_EOF as i32)
+    }
+
+    /// Returns the names of the alternate data streams of a file, such as
+    /// `:meta:$DATA`, which are appended to the path to open them.
+    pub(super) fn streams(path: &Path) -> io::Result<Vec<OsString>> {
+        let path = wide(path);
+        // SAFETY: the struct is plain data, for which zeroes are valid
+        let mut data: WIN32_FIND_STREAM_DATA = unsafe { std::mem::zeroed() };
+        let data_ptr = &mut data as *mut WIN32_FIND_STREAM_DATA as *mut c_void;
+        // SAFETY: the path is null-terminated and `data` matches the info level
+        let handle =
+            unsafe { FindFirstStreamW(path.as_ptr(), FindStreamInfoStandard, data_ptr, 0) };
+        if handle == INVALID_HANDLE_VALUE {
+            let e = io::Error::last_os_error();
+            // e.g. directories without streams
+            return if is_eof(&e) { Ok(Vec::new()) } else { Err(e) };
+        }
+        let mut names = Vec::new();
+        let result = loop {
+            let len = data
+                .cStreamName
+                .iter()
+                .position(|c| *c == 0)
+                .unwrap_or(data.cStreamName.len());
+            let name = OsString::from_wide(&data.cStreamName[..len]);
+            if name != MAIN_STREAM {
+                names.push(name);
+            }
+            // SAFETY: the handle is open and `data` matches the info level
+            if unsafe { FindNextStreamW(handle, data_ptr) } == 0 {
+                let e = io::Error::last_os_error();
+                break if is_eof(&e) { Ok(names) } else { Err(e) };
+            }
+        };
+        // SAFETY: the handle is open, and not used afterwards
+        unsafe { FindClose(handle) };
+        result
+    }
+
+    /// Copy the hidden, system, archive and not-content-indexed attributes of
+    /// `src` to `dest`, keeping the other attributes of `dest`.
+    pub(super) fn copy_attributes(src: &Path, dest: &Path) -> io::Result<()> {
+        let src = wide(src);
+        let dest = wide(dest);
+        // SAFETY: the paths are null-terminated
+        let (src_attributes, dest_attributes) = unsafe {
+            (
+                GetFileAttributesW(src.as_ptr()),
+                GetFileAttributesW(dest.as_ptr()),
+            )
+        };
+        if src_attributes == INVALID_FILE_ATTRIBUTES || dest_attributes == INVALID_FILE_ATTRIBUTES {
+            return Err(io::Error::last_os_error());
+        }
+        let attributes = dest_attributes & !COPIED_ATTRIBUTES | src_attributes & COPIED_ATTRIBUTES;
+        // SAFETY: the path is null-terminated
+        if unsafe { SetFileAttributesW(dest.as_ptr(), attributes) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
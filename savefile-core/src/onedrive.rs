@@ -0,0 +1,85 @@
+/// Awareness of OneDrive-synced folders on Windows.
+///
+/// OneDrive can redirect Documents, where many games keep their saves, into
+/// the synced folder. With Files On-Demand, files there may be cloud-only
+/// placeholders: reading one downloads it first, which is slow, fails while
+/// offline, and can pull gigabytes unexpectedly. Backups leave such files out
+/// unless the profile's `cloud_files` is set to [`CloudFiles::Hydrate`].
+use std::path::{Path, PathBuf};
+
+use crate::profile::Profile;
+
+/// Environment variables OneDrive sets to the roots of its synced folders.
+const ROOT_VARS: [&str; 3] = ["OneDrive", "OneDriveConsumer", "OneDriveCommercial"];
+
+/// How backups treat files that are only available online.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CloudFiles {
+    /// Leave the files out of backups, with a warning.
+    #[default]
+    Skip,
+    /// Download the files and back them up.
+    Hydrate,
+}
+
+impl CloudFiles {
+    /// Returns `true` for the default setting.
+    pub(crate) fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Returns the root of the OneDrive folder containing `path`, if any.
+pub fn onedrive_root(path: &Path) -> Option<PathBuf> {
+    ROOT_VARS
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .find(|root| !root.as_os_str().is_empty() && path.starts_with(root))
+}
+
+/// Returns `true` if the file is a placeholder whose contents are only
+/// available online, without downloading it.
+#[cfg(windows)]
+pub fn is_cloud_only(path: &Path) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_OFFLINE: u32 = 0x1000;
+    const FILE_ATTRIBUTE_RECALL_ON_OPEN: u32 = 0x40000;
+    const FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS: u32 = 0x400000;
+    const CLOUD_ONLY: u32 = FILE_ATTRIBUTE_OFFLINE
+        | FILE_ATTRIBUTE_RECALL_ON_OPEN
+        | FILE_ATTRIBUTE_RECALL_ON_DATA_ACCESS;
+    std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_attributes() & CLOUD_ONLY != 0)
+}
+
+/// Returns `true` if the file is a placeholder whose contents are only
+/// available online, without downloading it.
+///
+/// Placeholders only exist on Windows.
+#[cfg(not(windows))]
+pub fn is_cloud_only(_path: &Path) -> bool {
+    false
+}
+
+/// Leave cloud-only files out of the given paths (relative to the base),
+/// unless the profile's `cloud_files` is [`CloudFiles::Hydrate`].
+pub(crate) fn apply_cloud_files(profile: &Profile, paths: Vec<PathBuf>) -> Vec<PathBuf> {
+    if profile.cloud_files() == CloudFiles::Hydrate {
+        return paths;
+    }
+    paths
+        .into_iter()
+        .filter(|rel| {
+            let cloud_only = is_cloud_only(&profile.base().join(rel));
+            if cloud_only {
+                eprintln!(
+                    "warning: skipping {}: only available online (set the profile's cloud_files to hydrate to download it)",
+                    rel.display()
+                );
+            }
+            !cloud_only
+        })
+        .collect()
+}
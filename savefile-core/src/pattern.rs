@@ -0,0 +1,337 @@
+/// Helpers for the pattern syntax used in profiles.
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use regex::Regex;
+use walkdir::{DirEntry, WalkDir};
+
+use crate::{
+    error::ProfileError,
+    profile::{Include, MatchSettings},
+};
+
+/// Expand brace alternatives in a glob pattern.
+///
+/// `saves/*.{sav,bak}` expands to `saves/*.sav` and `saves/*.bak`. Braces may be
+/// nested, and braces without a comma (e.g. `{a}`) are left as they are.
+pub(crate) fn expand_braces(pattern: &str) -> Vec<String> {
+    let chars = pattern.chars().collect::<Vec<_>>();
+    let mut depth = 0;
+    let mut open = 0;
+    for (i, c) in chars.iter().enumerate() {
+        match c {
+            '{' => {
+                if depth == 0 {
+                    open = i;
+                }
+                depth += 1;
+            }
+            '}' if depth > 0 => {
+                depth -= 1;
+                if depth > 0 {
+                    continue;
+                }
+                let alternatives = split_top_level(&chars[open + 1..i]);
+                if alternatives.len() < 2 {
+                    continue;
+                }
+                let prefix = chars[..open].iter().collect::<String>();
+                let suffix = chars[i + 1..].iter().collect::<String>();
+                return alternatives
+                    .into_iter()
+                    .flat_map(|alt| expand_braces(&format!("{}{}{}", prefix, alt, suffix)))
+                    .collect();
+            }
+            _ => {}
+        }
+    }
+    vec![pattern.to_owned()]
+}
+
+/// Split the contents of a brace group at commas that are not nested in other braces.
+fn split_top_level(chars: &[char]) -> Vec<String> {
+    let mut parts = vec![String::new()];
+    let mut depth = 0;
+    for c in chars {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(String::new());
+                continue;
+            }
+            _ => {}
+        }
+        parts.last_mut().expect("parts is never empty").push(*c);
+    }
+    parts
+}
+
+/// Matches paths against a set of includes.
+///
+/// The globs of all includes are compiled into one [`GlobSet`], so that the
+/// base directory is walked once for all of them rather than once per pattern.
+pub(crate) struct IncludeMatcher {
+    /// The compiled globs.
+    globs: GlobSet,
+    /// For each compiled glob, the index of its include, and the pattern to
+    /// check again if leading dots must be matched literally.
+    glob_includes: Vec<(usize, Option<(glob::Pattern, glob::MatchOptions)>)>,
+    /// Regular expressions of `re:` includes, with the index of their include.
+    regexes: Vec<(usize, Regex)>,
+    /// Directories to walk, relative to the base, with how many levels below
+    /// them matches can be, or `None` for any depth.
+    roots: BTreeMap<PathBuf, Option<usize>>,
+}
+
+impl IncludeMatcher {
+    /// Compile the given includes.
+    pub(crate) fn new(
+        includes: &[Include],
+        settings: &MatchSettings,
+    ) -> Result<Self, ProfileError> {
+        let mut builder = GlobSetBuilder::new();
+        let mut glob_includes = Vec::new();
+        let mut regexes = Vec::new();
+        let mut roots = Vec::new();
+        for (index, include) in includes.iter().enumerate() {
+            if let Some(regex) = include.compile_regex()? {
+                regexes.push((index, regex));
+                roots.push((PathBuf::new(), None));
+                continue;
+            }
+            let invalid = || ProfileError::InvalidGlob(include.glob.clone());
+            let options = settings.options(Some(include));
+            for expanded in expand_braces(&include.glob) {
+                let components = expanded
+                    .split('/')
+                    .filter(|c| !c.is_empty() && *c != ".")
+                    .collect::<Vec<_>>();
+                if components.contains(&"..") {
+                    Err(ProfileError::IncludeOutsideBase(
+                        include.glob.clone().into(),
+                    ))?
+                }
+                let pattern = components.join("/");
+                let glob = GlobBuilder::new(&pattern)
+                    .case_insensitive(!options.case_sensitive)
+                    .literal_separator(true)
+                    .backslash_escape(cfg!(not(windows)))
+                    .build()
+                    .map_err(|_| invalid())?;
+                builder.add(glob);
+                let recheck = if options.require_literal_leading_dot {
+                    let pattern = glob::Pattern::new(&pattern).map_err(|_| invalid())?;
+                    Some((pattern, options))
+                } else {
+                    None
+                };
+                glob_includes.push((index, recheck));
+                // literal directories can't be trusted to exist as written
+                // when case is ignored
+                let (root, depth) = literal_root(&components);
+                if options.case_sensitive {
+                    roots.push((root, depth));
+                } else {
+                    let levels = root.components().count();
+                    roots.push((PathBuf::new(), depth.map(|d| d + levels)));
+                }
+            }
+        }
+        let globs = builder
+            .build()
+            .map_err(|e| ProfileError::InvalidGlob(e.to_string()))?;
+        Ok(Self {
+            globs,
+            glob_includes,
+            regexes,
+            roots: merge_roots(roots),
+        })
+    }
+
+    /// Walk the directories under `base` that matches can be in, following
+    /// symbolic links.
+    ///
+    /// Unreadable entries are skipped. Directories walked more than once yield
+    /// their entries more than once.
+    pub(crate) fn walk<'a>(&'a self, base: &'a Path) -> impl Iterator<Item = DirEntry> + 'a {
+        self.roots.iter().flat_map(move |(root, depth)| {
+            WalkDir::new(base.join(root))
+                .min_depth(1)
+                .max_depth(depth.unwrap_or(usize::MAX))
+                .follow_links(true)
+                .into_iter()
+                .filter_map(|entry| entry.ok())
+        })
+    }
+
+    /// Returns the indices of the includes matching a path relative to the base.
+    ///
+    /// Regular expressions only match files.
+    pub(crate) fn matches(&self, rel: &Path, is_dir: bool) -> Vec<usize> {
+        let globs = self.globs.matches(rel).into_iter().filter_map(|i| {
+            let (index, recheck) = &self.glob_includes[i];
+            match recheck {
+                Some((pattern, options)) if !pattern.matches_path_with(rel, *options) => None,
+                _ => Some(*index),
+            }
+        });
+        let text = rel.to_string_lossy().replace('\\', "/");
+        let regexes = self
+            .regexes
+            .iter()
+            .filter(|(_, regex)| !is_dir && regex.is_match(&text))
+            .map(|(index, _)| *index);
+        globs.chain(regexes).collect()
+    }
+}
+
+/// Returns the directory holding all matches of a glob, made of its leading
+/// literal components, and how many levels below it the matches are, or `None`
+/// if the glob contains `**`.
+fn literal_root(components: &[&str]) -> (PathBuf, Option<usize>) {
+    let literal = components[..components.len().saturating_sub(1)]
+        .iter()
+        .take_while(|c| !c.contains(['*', '?', '[', '{', '\\']))
+        .count();
+    let root = components[..literal].iter().collect::<PathBuf>();
+    if components.iter().any(|c| c.contains("**")) {
+        (root, None)
+    } else {
+        (root, Some(components.len() - literal))
+    }
+}
+
+/// Merge the roots of several globs, keeping the deepest walk of each root and
+/// dropping roots below one that is walked to any depth.
+fn merge_roots(roots: Vec<(PathBuf, Option<usize>)>) -> BTreeMap<PathBuf, Option<usize>> {
+    let mut merged = BTreeMap::<PathBuf, Option<usize>>::new();
+    for (root, depth) in roots {
+        merged
+            .entry(root)
+            .and_modify(|d| *d = d.zip(depth).map(|(a, b)| a.max(b)))
+            .or_insert(depth);
+    }
+    let unbounded = merged
+        .iter()
+        .filter(|(_, depth)| depth.is_none())
+        .map(|(root, _)| root.clone())
+        .collect::<Vec<_>>();
+    merged.retain(|root, _| {
+        !unbounded
+            .iter()
+            .any(|other| root != other && root.starts_with(other))
+    });
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn braces_expand_to_alternatives() {
+        assert_eq!(
+            expand_braces("saves/*.{sav,bak}"),
+            ["saves/*.sav", "saves/*.bak"]
+        );
+        assert_eq!(expand_braces("{a,b}{c,d}"), ["ac", "ad", "bc", "bd"]);
+        assert_eq!(expand_braces("slot{,1}"), ["slot", "slot1"]);
+        assert_eq!(expand_braces("saves/*.sav"), ["saves/*.sav"]);
+    }
+
+    #[test]
+    fn nested_braces_expand() {
+        assert_eq!(expand_braces("a{b,c{d,e}}f"), ["abf", "acdf", "acef"]);
+        assert_eq!(expand_braces("{x,{y,z}w}"), ["x", "yw", "zw"]);
+    }
+
+    #[test]
+    fn braces_without_comma_are_kept() {
+        assert_eq!(expand_braces("{a}"), ["{a}"]);
+        assert_eq!(expand_braces("{}"), ["{}"]);
+        assert_eq!(expand_braces("x{a}{b,c}"), ["x{a}b", "x{a}c"]);
+    }
+
+    #[test]
+    fn unbalanced_braces_are_kept() {
+        assert_eq!(expand_braces("saves/{a,b"), ["saves/{a,b"]);
+        assert_eq!(expand_braces("saves/a,b}"), ["saves/a,b}"]);
+        assert_eq!(expand_braces("{{a,b}"), ["{{a,b}"]);
+        assert_eq!(expand_braces("{a,b}}"), ["a}", "b}"]);
+    }
+
+    fn root(components: &[&str]) -> (String, Option<usize>) {
+        let (root, depth) = literal_root(components);
+        (root.to_string_lossy().replace('\\', "/"), depth)
+    }
+
+    #[test]
+    fn literal_root_stops_at_wildcards() {
+        assert_eq!(root(&["saves", "*.sav"]), ("saves".into(), Some(1)));
+        assert_eq!(
+            root(&["saves", "s*", "x", "*.sav"]),
+            ("saves".into(), Some(3))
+        );
+        assert_eq!(root(&["*.sav"]), ("".into(), Some(1)));
+        assert_eq!(root(&["sl[o]t", "a.sav"]), ("".into(), Some(2)));
+    }
+
+    #[test]
+    fn literal_root_excludes_last_component() {
+        assert_eq!(
+            root(&["saves", "slot1", "data.sav"]),
+            ("saves/slot1".into(), Some(1))
+        );
+        assert_eq!(root(&["data.sav"]), ("".into(), Some(1)));
+    }
+
+    #[test]
+    fn literal_root_is_unbounded_with_double_star() {
+        assert_eq!(root(&["saves", "**", "*.sav"]), ("saves".into(), None));
+        assert_eq!(root(&["saves", "**"]), ("saves".into(), None));
+    }
+
+    fn merged(roots: &[(&str, Option<usize>)]) -> Vec<(PathBuf, Option<usize>)> {
+        let roots = roots
+            .iter()
+            .map(|(root, depth)| (PathBuf::from(root), *depth))
+            .collect();
+        merge_roots(roots).into_iter().collect()
+    }
+
+    #[test]
+    fn merge_roots_keeps_deepest_walk() {
+        assert_eq!(
+            merged(&[("saves", Some(1)), ("saves", Some(3)), ("saves", Some(2))]),
+            [("saves".into(), Some(3))]
+        );
+        assert_eq!(
+            merged(&[("saves", Some(1)), ("saves", None)]),
+            [("saves".into(), None)]
+        );
+    }
+
+    #[test]
+    fn merge_roots_drops_roots_below_unbounded_ones() {
+        assert_eq!(
+            merged(&[
+                ("saves/slot1", Some(1)),
+                ("saves", None),
+                ("config", Some(1))
+            ]),
+            [("config".into(), Some(1)), ("saves".into(), None)]
+        );
+        assert_eq!(
+            merged(&[("saves/a", None), ("saves", Some(1)), ("saves/ab", Some(1))]),
+            [
+                ("saves".into(), Some(1)),
+                ("saves/a".into(), None),
+                ("saves/ab".into(), Some(1))
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+/// Post-processing steps run on each finished backup.
+///
+/// A profile's `pipeline` lists plugins by name, which run in order once a
+/// backup is complete, e.g. to compress, encrypt and then upload it. Plugins
+/// are provided by crates using savefile as a library, which [`register`] them
+/// before making backups. The built-in `email` plugin mails the backup as an
+/// archive.
+///
+/// A profile's `plugins` are external commands run after the pipeline, which
+/// receive a JSON description of the backup on standard input.
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::PathBuf,
+    process::Stdio,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{
+    email_export,
+    error::{BackupError, ProfileError, Result},
+    process::shell,
+    profile::{Profile, ProfileName},
+    BackupId, Timestamp,
+};
+
+/// Error type returned by plugins.
+pub type PluginError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Creates a plugin from the options given in a profile.
+type Factory = dyn Fn(&serde_json::Value) -> Result<Box<dyn Plugin>, PluginError> + Send + Sync;
+
+/// A post-processing step of a backup.
+pub trait Plugin: Send + Sync {
+    /// Process a finished backup.
+    ///
+    /// The backup directory is read-only. Steps that produce files, such as an
+    /// archive, write them elsewhere and replace [`BackupContext::artifacts`]
+    /// with them, so that later steps work on their output.
+    fn run(&self, backup: &mut BackupContext) -> Result<(), PluginError>;
+}
+
+/// A backup passed along a pipeline.
+#[derive(Clone, Debug)]
+pub struct BackupContext {
+    /// Name of the backed up profile.
+    pub profile_name: ProfileName,
+    /// The backed up profile.
+    pub profile: Profile,
+    /// ID of the new backup.
+    pub id: BackupId,
+    /// Creation time of the backup.
+    pub timestamp: Timestamp,
+    /// Why the backup was made, see [`Backup::tag`](crate::Backup::tag).
+    pub tag: Option<String>,
+    /// Absolute path of the backup directory.
+    pub dir: PathBuf,
+    /// Backed up files, relative to `dir`.
+    pub files: Vec<PathBuf>,
+    /// Output of the previous steps; initially just the backup directory.
+    pub artifacts: Vec<PathBuf>,
+}
+
+/// A pipeline step in a profile.
+///
+/// In profiles, steps are written as e.g. `{ "plugin": "zip", "options": { "level": 9 } }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Step {
+    /// Name the plugin was registered with.
+    pub plugin: String,
+    /// Plugin-specific options.
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub options: serde_json::Value,
+}
+
+/// Returns the registered plugin factories by name, starting with the built-in
+/// plugins.
+fn registry() -> &'static RwLock<HashMap<String, Arc<Factory>>> {
+    static REGISTRY: OnceLock<RwLock<HashMap<String, Arc<Factory>>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let mut builtins = HashMap::new();
+        builtins.insert(
+            email_export::NAME.to_owned(),
+            Arc::new(email_export::factory) as Arc<Factory>,
+        );
+        RwLock::new(builtins)
+    })
+}
+
+/// Make a plugin available to profile pipelines under the given name.
+///
+/// `factory` creates the plugin from the step's options when a backup starts,
+/// and should reject invalid options. Registering a name again replaces the
+/// previous plugin.
+pub fn register<F>(name: impl Into<String>, factory: F)
+where
+    F: Fn(&serde_json::Value) -> Result<Box<dyn Plugin>, PluginError> + Send + Sync + 'static,
+{
+    registry()
+        .write()
+        .expect("plugin registry poisoned")
+        .insert(name.into(), Arc::new(factory));
+}
+
+/// The plugins of a profile's pipeline, ready to run.
+pub(crate) struct Pipeline {
+    plugins: Vec<(String, Box<dyn Plugin>)>,
+}
+
+impl Pipeline {
+    /// Create the plugins of a profile's `pipeline`, followed by its external
+    /// `plugins`.
+    ///
+    /// This is done before a backup is made, so that a misconfigured pipeline
+    /// fails early.
+    pub(crate) fn build(profile: &Profile) -> Result<Self> {
+        let registry = registry().read().expect("plugin registry poisoned");
+        let mut plugins = profile
+            .pipeline()
+            .iter()
+            .map(|step| {
+                let factory = registry
+                    .get(&step.plugin)
+                    .ok_or_else(|| ProfileError::UnknownPlugin(step.plugin.clone()))?;
+                let plugin = factory(&step.options).map_err(|e| ProfileError::InvalidPlugin {
+                    plugin: step.plugin.clone(),
+                    reason: e.to_string(),
+                })?;
+                Ok((step.plugin.clone(), plugin))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        plugins.extend(profile.plugins().iter().map(|command| {
+            let plugin: Box<dyn Plugin> = Box::new(CommandPlugin {
+                command: command.clone(),
+            });
+            (command.clone(), plugin)
+        }));
+        Ok(Self { plugins })
+    }
+
+    /// Run the steps in order, stopping at the first failure.
+    pub(crate) fn run(&self, mut backup: BackupContext) -> Result<()> {
+        for (name, plugin) in &self.plugins {
+            plugin
+                .run(&mut backup)
+                .map_err(|e| BackupError::PluginFailed {
+                    id: backup.id,
+                    plugin: name.clone(),
+                    reason: e.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the pipeline has no steps.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.plugins.is_empty()
+    }
+}
+
+/// An external command, given the backup as JSON on standard input.
+///
+/// The command runs with the platform's shell in the profile's base directory,
+/// and must exit successfully. The JSON object has the fields `profile`, `id`,
+/// `timestamp` (RFC 3339), `tag` (or `null`), `base`, `dir`, `files` (relative
+/// to `dir`) and `artifacts`.
+struct CommandPlugin {
+    command: String,
+}
+
+impl Plugin for CommandPlugin {
+    fn run(&self, backup: &mut BackupContext) -> Result<(), PluginError> {
+        let input = serde_json::json!({
+            "profile": backup.profile_name.as_str(),
+            "id": backup.id.get(),
+            "timestamp": backup.timestamp.to_rfc3339(),
+            "tag": backup.tag,
+            "base": backup.profile.base(),
+            "dir": backup.dir,
+            "files": backup.files,
+            "artifacts": backup.artifacts,
+        })
+        .to_string();
+        let mut child = shell(&self.command)
+            .current_dir(backup.profile.base())
+            .stdin(Stdio::piped())
+            .spawn()?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        match stdin.write_all(input.as_bytes()) {
+            // commands don't have to read their input
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {}
+            result => result?,
+        }
+        // closing stdin signals the end of the input
+        drop(stdin);
+        let status = child.wait()?;
+        if !status.success() {
+            Err(format!("exited with {}", status))?
+        }
+        Ok(())
+    }
+}
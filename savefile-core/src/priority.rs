@@ -0,0 +1,228 @@
+/// Lowering the priority of background backups.
+///
+/// Backups made by a watcher usually run while the game is being played, so
+/// their disk and CPU use is given the lowest priority the platform offers.
+/// Priorities are changed for the current thread only, and restored afterwards.
+use std::io;
+
+/// Runs the current thread at background priority until dropped.
+pub(crate) struct Background {
+    saved: Option<platform::Saved>,
+}
+
+impl Background {
+    /// Lower the IO and CPU priority of the current thread.
+    ///
+    /// Failures are reported but otherwise ignored, as the backup can still run
+    /// at normal priority.
+    pub(crate) fn enter() -> Self {
+        Self {
+            saved: report("lower backup priority", platform::lower()),
+        }
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.take() {
+            report("restore thread priority", platform::restore(saved));
+        }
+    }
+}
+
+/// Print an error from changing priorities, which isn't worth failing a backup over.
+fn report<T>(action: &str, result: io::Result<T>) -> Option<T> {
+    result
+        .map_err(|e| eprintln!("failed to {}: {}", action, e))
+        .ok()
+}
+
+#[cfg(windows)]
+use self::windows as platform;
+
+#[cfg(target_os = "linux")]
+use self::linux as platform;
+
+#[cfg(not(any(windows, target_os = "linux")))]
+use self::other as platform;
+
+#[cfg(windows)]
+mod windows {
+    use std::io;
+
+    use windows_sys::Win32::System::Threading::{
+        GetCurrentThread, SetThreadPriority, THREAD_MODE_BACKGROUND_BEGIN,
+        THREAD_MODE_BACKGROUND_END,
+    };
+
+    /// Nothing needs saving, as leaving background mode restores the priorities.
+    pub(super) type Saved = ();
+
+    /// Enter background processing mode, which lowers both IO and CPU priority.
+    pub(super) fn lower() -> io::Result<Saved> {
+        set(THREAD_MODE_BACKGROUND_BEGIN)
+    }
+
+    /// Leave background processing mode.
+    pub(super) fn restore(_saved: Saved) -> io::Result<()> {
+        set(THREAD_MODE_BACKGROUND_END)
+    }
+
+    fn set(mode: i32) -> io::Result<()> {
+        // SAFETY: the pseudo handle of the current thread is always valid
+        if unsafe { SetThreadPriority(GetCurrentThread(), mode) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::io;
+
+    /// `IOPRIO_WHO_PROCESS`, which targets a single thread when given a thread ID.
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    /// `IOPRIO_CLASS_IDLE`, only given disk time when no one else needs it.
+    const IOPRIO_CLASS_IDLE: libc::c_int = 3;
+    /// Bit offset of the class in an IO priority value.
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    /// Niceness of background backups, like `nice` without arguments.
+    const BACKGROUND_NICE: libc::c_int = 10;
+
+    /// Priorities of the thread before they were lowered.
+    pub(super) struct Saved {
+        ioprio: libc::c_int,
+        /// The niceness before it was raised, or `None` if it was kept.
+        nice: Option<libc::c_int>,
+    }
+
+    /// Give the current thread the idle IO class and a higher niceness,
+    /// like `ionice -c3 nice`.
+    ///
+    /// Lowering the niceness again requires privileges or a high enough
+    /// `RLIMIT_NICE`, so the niceness is only raised if it can be restored
+    /// afterwards. Otherwise just the IO class is changed.
+    pub(super) fn lower() -> io::Result<Saved> {
+        let ioprio = ioprio_get()?;
+        let nice = get_nice()?;
+        ioprio_set(IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT)?;
+        let raise = nice < BACKGROUND_NICE && can_set_nice(nice)?;
+        if raise {
+            if let Err(e) = set_nice(BACKGROUND_NICE) {
+                ioprio_set(ioprio)?;
+                return Err(e);
+            }
+        }
+        let nice = raise.then_some(nice);
+        Ok(Saved { ioprio, nice })
+    }
+
+    /// Restore the priorities saved by [`lower`].
+    pub(super) fn restore(saved: Saved) -> io::Result<()> {
+        ioprio_set(saved.ioprio)?;
+        if let Some(nice) = saved.nice {
+            set_nice(nice)?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the current thread may lower its niceness to `nice`.
+    fn can_set_nice(nice: libc::c_int) -> io::Result<bool> {
+        // SAFETY: geteuid can't fail
+        if unsafe { libc::geteuid() } == 0 {
+            return Ok(true);
+        }
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: getrlimit only writes to the given struct
+        if unsafe { libc::getrlimit(libc::RLIMIT_NICE, &mut limit) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // the limit is given as 20 - niceness, see setrlimit(2)
+        let needed = (20 - nice) as libc::rlim_t;
+        Ok(limit.rlim_cur == libc::RLIM_INFINITY || needed <= limit.rlim_cur)
+    }
+
+    fn tid() -> libc::id_t {
+        // SAFETY: gettid takes no arguments and can't fail
+        unsafe { libc::syscall(libc::SYS_gettid) as libc::id_t }
+    }
+
+    pub(super) fn ioprio_get() -> io::Result<libc::c_int> {
+        // SAFETY: ioprio_get only reads its integer arguments
+        let prio = unsafe { libc::syscall(libc::SYS_ioprio_get, IOPRIO_WHO_PROCESS, 0) };
+        if prio < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(prio as libc::c_int)
+    }
+
+    fn ioprio_set(prio: libc::c_int) -> io::Result<()> {
+        // SAFETY: ioprio_set only reads its integer arguments
+        if unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, prio) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    pub(super) fn get_nice() -> io::Result<libc::c_int> {
+        // -1 is a valid niceness, so errors are told apart by errno
+        // SAFETY: errno is thread-local, and getpriority only reads its arguments
+        let nice = unsafe {
+            *libc::__errno_location() = 0;
+            libc::getpriority(libc::PRIO_PROCESS as _, tid())
+        };
+        match io::Error::last_os_error() {
+            e if nice == -1 && e.raw_os_error() != Some(0) => Err(e),
+            _ => Ok(nice),
+        }
+    }
+
+    fn set_nice(nice: libc::c_int) -> io::Result<()> {
+        // on Linux, PRIO_PROCESS with a thread ID only affects that thread
+        // SAFETY: setpriority only reads its arguments
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS as _, tid(), nice) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+mod other {
+    use std::io;
+
+    /// Priorities aren't changed on this platform.
+    pub(super) type Saved = ();
+
+    pub(super) fn lower() -> io::Result<Saved> {
+        Ok(())
+    }
+
+    pub(super) fn restore(_saved: Saved) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn priorities_are_restored() {
+        // on a thread of its own, so that other tests keep their priorities
+        std::thread::spawn(|| {
+            let before = (linux::ioprio_get().unwrap(), linux::get_nice().unwrap());
+            let background = Background::enter();
+            assert_ne!(linux::ioprio_get().unwrap(), before.0);
+            drop(background);
+            let after = (linux::ioprio_get().unwrap(), linux::get_nice().unwrap());
+            assert_eq!(after, before);
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,19 @@
+/// Helpers for running external commands given in profiles.
+use std::process::{Command, Stdio};
+
+/// Returns a command that runs `command` with the platform's shell.
+///
+/// Standard input is closed, so commands can't wait for input.
+pub(crate) fn shell(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.args(["/C", command]);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", command]);
+        cmd
+    };
+    cmd.stdin(Stdio::null());
+    cmd
+}
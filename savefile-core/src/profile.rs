@@ -0,0 +1,1100 @@
+use std::path::{Component, Path, PathBuf};
+
+use crate::{
+    archive::Storage,
+    cold::ColdStorage,
+    config::with_json_field,
+    coop::Coop,
+    error::{Error, IoResultExt, ProfileError, Result},
+    filesystem::{profile_path, profiles_dir},
+    include_cache,
+    onedrive::CloudFiles,
+    pattern::{expand_braces, IncludeMatcher},
+    plugin::Step,
+    remote::RemoteBase,
+    size::ByteSize,
+    store::Remote,
+    validate::Validator,
+    version::VersionSource,
+    webhook::Webhook,
+};
+
+/// Maximum length of a profile name.
+const MAX_NAME_LEN: usize = 64;
+
+/// A validated profile name.
+///
+/// Profile names are used in file names and database table names, so they are
+/// restricted to ASCII letters, digits, `-` and `_`, and may not start with `-`.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProfileName(String);
+
+impl ProfileName {
+    /// Validate the given name.
+    pub fn new(name: impl Into<String>) -> Result<Self, ProfileError> {
+        let name = name.into();
+        let reason = if name.is_empty() {
+            Some("name is empty")
+        } else if name.len() > MAX_NAME_LEN {
+            Some("name is longer than 64 characters")
+        } else if name.starts_with('-') {
+            Some("name may not start with '-'")
+        } else if !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            Some("only ASCII letters, digits, '-' and '_' are allowed")
+        } else {
+            None
+        };
+        match reason {
+            Some(reason) => Err(ProfileError::InvalidName(name, reason)),
+            None => Ok(Self(name)),
+        }
+    }
+
+    /// Returns the name as a string slice.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ProfileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::str::FromStr for ProfileName {
+    type Err = ProfileError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::new(s)
+    }
+}
+
+impl std::fmt::Debug for ProfileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::ops::Deref for ProfileName {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<str> for ProfileName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for ProfileName {
+    fn as_ref(&self) -> &Path {
+        Path::new(&self.0)
+    }
+}
+
+impl From<ProfileName> for String {
+    fn from(name: ProfileName) -> Self {
+        name.0
+    }
+}
+
+/// Prefix marking an include as a regular expression rather than a glob.
+const REGEX_PREFIX: &str = "re:";
+
+/// A single include pattern, with options.
+///
+/// Patterns prefixed with `re:` are regular expressions matched against
+/// paths relative to the base directory (see [`Include::regex`]).
+///
+/// In profiles, an include is either a plain glob string, or an object such as
+/// `{ "glob": "saves/**", "follow_symlinks": false, "max_size": "500MB" }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(from = "IncludeRepr", into = "IncludeRepr")]
+pub struct Include {
+    /// Glob pattern relative to the base directory, or a regular expression
+    /// prefixed with `re:`.
+    pub glob: String,
+    /// Whether to include paths reached through symbolic links.
+    pub follow_symlinks: bool,
+    /// Files larger than this are skipped.
+    pub max_size: Option<ByteSize>,
+    /// Whether the pattern matches regardless of case.
+    pub case_insensitive: bool,
+}
+
+impl Include {
+    /// Create an include for the given glob pattern with default options.
+    pub fn new(glob: impl Into<String>) -> Self {
+        Self {
+            glob: glob.into(),
+            follow_symlinks: true,
+            max_size: None,
+            case_insensitive: false,
+        }
+    }
+
+    /// Returns the regular expression of a `re:` include, or `None` for glob includes.
+    pub fn regex(&self) -> Option<&str> {
+        self.glob.strip_prefix(REGEX_PREFIX)
+    }
+
+    /// Compile the regular expression of a `re:` include.
+    ///
+    /// The expression must match the entire path relative to the base
+    /// directory, using `/` as the separator.
+    pub(crate) fn compile_regex(&self) -> Result<Option<regex::Regex>, ProfileError> {
+        let Some(expr) = self.regex() else {
+            return Ok(None);
+        };
+        regex::RegexBuilder::new(&format!("^(?:{})$", expr))
+            .case_insensitive(self.case_insensitive)
+            .build()
+            .map(Some)
+            .map_err(|e| ProfileError::InvalidRegex(expr.to_owned(), e.to_string()))
+    }
+
+    /// Check that the pattern is a valid glob or regular expression.
+    fn validate(&self) -> Result<(), ProfileError> {
+        if self.compile_regex()?.is_some() {
+            return Ok(());
+        }
+        for expanded in expand_braces(&self.glob) {
+            glob::Pattern::new(&expanded)
+                .map_err(|_| ProfileError::InvalidGlob(self.glob.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the include has no options set.
+    fn is_plain(&self) -> bool {
+        *self == Self::new(self.glob.clone())
+    }
+}
+
+impl std::fmt::Display for Include {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.glob)
+    }
+}
+
+/// Serialized form of [`Include`].
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum IncludeRepr {
+    Plain(String),
+    Detailed {
+        glob: String,
+        #[serde(default = "default_true")]
+        follow_symlinks: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_size: Option<ByteSize>,
+        #[serde(default)]
+        case_insensitive: bool,
+    },
+}
+
+fn default_true() -> bool {
+    true
+}
+
+impl From<IncludeRepr> for Include {
+    fn from(repr: IncludeRepr) -> Self {
+        match repr {
+            IncludeRepr::Plain(glob) => Include::new(glob),
+            IncludeRepr::Detailed {
+                glob,
+                follow_symlinks,
+                max_size,
+                case_insensitive,
+            } => Include {
+                glob,
+                follow_symlinks,
+                max_size,
+                case_insensitive,
+            },
+        }
+    }
+}
+
+impl From<Include> for IncludeRepr {
+    fn from(include: Include) -> Self {
+        if include.is_plain() {
+            return IncludeRepr::Plain(include.glob);
+        }
+        IncludeRepr::Detailed {
+            glob: include.glob,
+            follow_symlinks: include.follow_symlinks,
+            max_size: include.max_size,
+            case_insensitive: include.case_insensitive,
+        }
+    }
+}
+
+/// Options controlling how glob patterns in a profile are matched.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct MatchSettings {
+    /// Whether patterns match case-sensitively.
+    ///
+    /// Individual includes can still opt out with `case_insensitive`.
+    pub case_sensitive: bool,
+    /// Whether `*` and `?` must not match path separators.
+    pub require_literal_separator: bool,
+    /// Whether `*` and `?` must not match a leading `.` in file names.
+    pub require_literal_leading_dot: bool,
+}
+
+impl Default for MatchSettings {
+    fn default() -> Self {
+        Self {
+            case_sensitive: true,
+            require_literal_separator: false,
+            require_literal_leading_dot: false,
+        }
+    }
+}
+
+impl MatchSettings {
+    /// Returns `true` if all settings have their default values.
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Returns the glob options to use for the given include.
+    pub(crate) fn options(&self, include: Option<&Include>) -> glob::MatchOptions {
+        let case_insensitive = include.is_some_and(|include| include.case_insensitive);
+        glob::MatchOptions {
+            case_sensitive: self.case_sensitive && !case_insensitive,
+            require_literal_separator: self.require_literal_separator,
+            require_literal_leading_dot: self.require_literal_leading_dot,
+        }
+    }
+}
+
+/// Size limits applied to each backup.
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct Limits {
+    /// Largest size of any single file.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_file_size: Option<ByteSize>,
+    /// Largest total size of a backup.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_total_size: Option<ByteSize>,
+    /// What to do when a limit is exceeded.
+    pub on_exceed: LimitAction,
+}
+
+impl Limits {
+    /// Returns `true` if no limits are set.
+    pub fn is_empty(&self) -> bool {
+        self.max_file_size.is_none() && self.max_total_size.is_none()
+    }
+}
+
+/// What to do when a backup exceeds a size limit.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LimitAction {
+    /// Leave out the offending files, with a warning.
+    #[default]
+    Skip,
+    /// Fail the whole backup.
+    Abort,
+}
+
+/// How backups and restores treat empty directories.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmptyDirs {
+    /// Back up and restore the directories matched by `include`, even if empty.
+    #[default]
+    Matched,
+    /// Leave out empty directories, and don't restore those found in older backups.
+    Skip,
+    /// Back up every directory below the base directory, matched or not, so
+    /// that restores recreate the full directory structure.
+    Preserve,
+}
+
+impl EmptyDirs {
+    /// Returns `true` for the default setting.
+    fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// A profile is primarily a specification of which files to back up.
+///
+/// Files to back up are specified as glob patterns relative to the profile's base directory,
+/// optionally with per-pattern options (see [`Include`]).
+/// The `delay` field specifies the time to wait after a save file is modified before backing
+/// up everything.
+///
+/// The profile is stored as a JSON file in the profiles directory.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub struct Profile {
+    /// Root directory which includes are relative to
+    base: PathBuf,
+    /// Glob patterns for files to watch/include in the backup
+    include: Vec<Include>,
+    /// The time to wait after a save file is modified before backing up everything.
+    delay: f32,
+    /// Glob patterns for files whose changes should not trigger a backup,
+    /// even if they are included in it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    watch_ignore: Vec<String>,
+    /// Size limits for each backup.
+    #[serde(default, skip_serializing_if = "Limits::is_empty")]
+    limits: Limits,
+    /// Options controlling how `include` and `watch_ignore` patterns are matched.
+    #[serde(default, skip_serializing_if = "MatchSettings::is_default")]
+    match_options: MatchSettings,
+    /// Where to read the game or app version recorded with each backup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    version: Option<VersionSource>,
+    /// File (e.g. the game's executable or manifest) whose changes indicate a game
+    /// update, triggering a `pre-update` backup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    update_trigger: Option<PathBuf>,
+    /// Checks the live files must pass before they are backed up.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    validators: Vec<Validator>,
+    /// Maximum rate at which backups copy files, per second.
+    ///
+    /// Overrides the global `io_limit` config value.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    io_limit: Option<ByteSize>,
+    /// Plugins run in order on each finished backup.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pipeline: Vec<Step>,
+    /// Commands run after the pipeline, which are given the backup as JSON.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    plugins: Vec<String>,
+    /// Webhooks notified when a backup succeeds or fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    webhooks: Vec<Webhook>,
+    /// Stores each new backup is copied to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    remotes: Vec<Remote>,
+    /// Rule moving old backups to cold storage.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cold_storage: Option<ColdStorage>,
+    /// Local directory, e.g. on a second drive, each new backup is copied to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mirror_dir: Option<PathBuf>,
+    /// How empty directories are backed up and restored.
+    #[serde(default, skip_serializing_if = "EmptyDirs::is_default")]
+    empty_dirs: EmptyDirs,
+    /// Whether new backups are stored as directories or zip archives.
+    #[serde(default, skip_serializing_if = "Storage::is_default")]
+    storage: Storage,
+    /// Directory holding the backups instead of the saves directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    storage_dir: Option<PathBuf>,
+    /// How files that are only available online are backed up.
+    #[serde(default, skip_serializing_if = "CloudFiles::is_default")]
+    cloud_files: CloudFiles,
+    /// Whether NTFS alternate data streams and attributes are backed up and restored.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    ntfs_metadata: bool,
+    /// Shared directory and player name for pass-the-save co-op.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coop: Option<Coop>,
+    /// Whether the machine each backup is made on is recorded with it.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    capture_env: bool,
+    /// Whether the text of small files is indexed for `savefile search`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    content_index: bool,
+}
+
+impl Profile {
+    /// Create a new profile with the given base directory.
+    ///
+    /// Defaults:
+    /// - `include`: `[]`
+    /// - `delay`: `5.0`
+    /// - `watch_ignore`: `[]`
+    /// - `limits`: none
+    /// - `match_options`: case-sensitive, no literal separators or leading dots
+    /// - `version`: none
+    /// - `update_trigger`: none
+    /// - `validators`: `[]`
+    /// - `io_limit`: none (use the global setting)
+    /// - `pipeline`: `[]`
+    /// - `plugins`: `[]`
+    /// - `webhooks`: `[]`
+    /// - `remotes`: `[]`
+    /// - `cold_storage`: none
+    /// - `mirror_dir`: none
+    /// - `empty_dirs`: `matched`
+    /// - `storage`: `dir`
+    /// - `storage_dir`: none (the saves directory)
+    /// - `cloud_files`: `skip`
+    /// - `ntfs_metadata`: `false`
+    /// - `coop`: none
+    /// - `capture_env`: `false`
+    /// - `content_index`: `false`
+    pub fn new<P: AsRef<Path>>(base: P) -> Self {
+        Self {
+            base: base.as_ref().to_owned(),
+            include: Vec::new(),
+            delay: 5f32,
+            watch_ignore: Vec::new(),
+            limits: Limits::default(),
+            match_options: MatchSettings::default(),
+            version: None,
+            update_trigger: None,
+            validators: Vec::new(),
+            io_limit: None,
+            pipeline: Vec::new(),
+            plugins: Vec::new(),
+            webhooks: Vec::new(),
+            remotes: Vec::new(),
+            cold_storage: None,
+            mirror_dir: None,
+            empty_dirs: EmptyDirs::default(),
+            storage: Storage::default(),
+            storage_dir: None,
+            cloud_files: CloudFiles::default(),
+            ntfs_metadata: false,
+            coop: None,
+            capture_env: false,
+            content_index: false,
+        }
+    }
+
+    /// Open a profile from the given path.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_owned();
+        let contents = std::fs::read(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => ProfileError::NoSuchProfile(path.clone()).into(),
+            _ => Error::IoContext {
+                context: format!("failed reading profile {}", path.display()),
+                source: e,
+            },
+        })?;
+        let profile: Profile =
+            serde_json::from_slice(&contents).map_err(|_| ProfileError::InvalidFormat(path))?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Check the values that deserialization alone doesn't validate.
+    fn validate(&self) -> Result<()> {
+        RemoteBase::parse(&self.base)?;
+        if self.delay <= 0f32 {
+            Err(ProfileError::InvalidDelay(self.delay))?
+        }
+        for include in &self.include {
+            include.validate()?;
+        }
+        for validator in &self.validators {
+            validator.validate()?;
+        }
+        for webhook in &self.webhooks {
+            webhook.validate()?;
+        }
+        if let Some(cold_storage) = &self.cold_storage {
+            cold_storage.validate(&self.remotes)?;
+        }
+        if let Some(coop) = &self.coop {
+            coop.validate()?;
+        }
+        if matches!(&self.mirror_dir, Some(dir) if !dir.is_absolute()) {
+            Err(ProfileError::InvalidField {
+                key: "mirror_dir".to_owned(),
+                reason: "must be an absolute path".to_owned(),
+            })?
+        }
+        if matches!(&self.storage_dir, Some(dir) if !dir.is_absolute()) {
+            Err(ProfileError::InvalidField {
+                key: "storage_dir".to_owned(),
+                reason: "must be an absolute path".to_owned(),
+            })?
+        }
+        if self.plugins.iter().any(|command| command.trim().is_empty()) {
+            Err(ProfileError::InvalidField {
+                key: "plugins".to_owned(),
+                reason: "empty command".to_owned(),
+            })?
+        }
+        for pattern in &self.watch_ignore {
+            for expanded in expand_braces(pattern) {
+                glob::Pattern::new(&expanded)
+                    .map_err(|_| ProfileError::InvalidGlob(pattern.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns a single field by name, as JSON.
+    pub fn field(&self, key: &str) -> Result<serde_json::Value> {
+        let value = match key {
+            "base" => serde_json::to_value(&self.base),
+            "include" => serde_json::to_value(&self.include),
+            "delay" => serde_json::to_value(self.delay),
+            "watch_ignore" => serde_json::to_value(&self.watch_ignore),
+            "limits" => serde_json::to_value(&self.limits),
+            "match_options" => serde_json::to_value(&self.match_options),
+            "version" => serde_json::to_value(&self.version),
+            "update_trigger" => serde_json::to_value(&self.update_trigger),
+            "validators" => serde_json::to_value(&self.validators),
+            "io_limit" => serde_json::to_value(self.io_limit),
+            "pipeline" => serde_json::to_value(&self.pipeline),
+            "plugins" => serde_json::to_value(&self.plugins),
+            "webhooks" => serde_json::to_value(&self.webhooks),
+            "remotes" => serde_json::to_value(&self.remotes),
+            "cold_storage" => serde_json::to_value(&self.cold_storage),
+            "mirror_dir" => serde_json::to_value(&self.mirror_dir),
+            "empty_dirs" => serde_json::to_value(self.empty_dirs),
+            "storage" => serde_json::to_value(self.storage),
+            "storage_dir" => serde_json::to_value(&self.storage_dir),
+            "cloud_files" => serde_json::to_value(self.cloud_files),
+            "ntfs_metadata" => serde_json::to_value(self.ntfs_metadata),
+            "coop" => serde_json::to_value(&self.coop),
+            "capture_env" => serde_json::to_value(self.capture_env),
+            "content_index" => serde_json::to_value(self.content_index),
+            _ => Err(ProfileError::UnknownField(key.to_owned()))?,
+        };
+        Ok(value.expect("profile is always serializable"))
+    }
+
+    /// Set a single field by name, parsing `value` as JSON (or as a plain string).
+    ///
+    /// Returns the updated profile without saving it.
+    pub fn with_field(&self, key: &str, value: &str) -> Result<Self> {
+        // also rejects unknown keys
+        self.field(key)?;
+        let json = serde_json::to_value(self).expect("profile is always serializable");
+        let profile: Profile =
+            with_json_field(json, key, value).map_err(|e| ProfileError::InvalidField {
+                key: key.to_owned(),
+                reason: e.to_string(),
+            })?;
+        profile.validate()?;
+        Ok(profile)
+    }
+
+    /// Open the installed profile with the given name.
+    ///
+    /// If no such profile exists, the error suggests similarly named profiles.
+    pub fn open_named(name: &ProfileName) -> Result<Self> {
+        let path = profile_path(name)?;
+        if !path.is_file() {
+            Err(ProfileError::unknown_name(name))?
+        }
+        Self::open(path)
+    }
+
+    /// Returns the path to the target base directory.
+    pub fn base(&self) -> &Path {
+        &self.base
+    }
+
+    /// Returns the server directory the files live in, if the base is an
+    /// `sftp://` URL.
+    pub fn remote(&self) -> Option<RemoteBase> {
+        // the base was checked when the profile was opened
+        RemoteBase::parse(&self.base).ok().flatten()
+    }
+
+    /// Returns a copy of the profile with a different base directory.
+    pub(crate) fn with_base(&self, base: impl Into<PathBuf>) -> Self {
+        Self {
+            base: base.into(),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the patterns for files to watch/include in the backup.
+    pub fn includes(&self) -> &[Include] {
+        &self.include
+    }
+
+    /// Returns the time to wait after a save file is modified before backing up everything.
+    pub fn delay(&self) -> f32 {
+        self.delay
+    }
+
+    /// Returns the glob patterns for files whose changes do not trigger a backup.
+    pub fn watch_ignores(&self) -> &[String] {
+        &self.watch_ignore
+    }
+
+    /// Returns the options controlling how patterns are matched.
+    pub fn match_options(&self) -> &MatchSettings {
+        &self.match_options
+    }
+
+    /// Returns the size limits for each backup.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Returns where to read the version recorded with each backup, if anywhere.
+    pub fn version(&self) -> Option<&VersionSource> {
+        self.version.as_ref()
+    }
+
+    /// Returns the maximum rate at which backups copy files, per second, if set
+    /// for this profile.
+    pub fn io_limit(&self) -> Option<ByteSize> {
+        self.io_limit
+    }
+
+    /// Returns the plugins run on each finished backup.
+    ///
+    /// Plugin names are only checked when a backup is made, since plugins are
+    /// registered by the program using the profile.
+    pub fn pipeline(&self) -> &[Step] {
+        &self.pipeline
+    }
+
+    /// Returns the commands run on each finished backup after the pipeline.
+    pub fn plugins(&self) -> &[String] {
+        &self.plugins
+    }
+
+    /// Returns the webhooks notified when a backup succeeds or fails.
+    pub fn webhooks(&self) -> &[Webhook] {
+        &self.webhooks
+    }
+
+    /// Returns the stores each new backup is copied to.
+    pub fn remotes(&self) -> &[Remote] {
+        &self.remotes
+    }
+
+    /// Returns the rule moving old backups to cold storage, if any.
+    pub fn cold_storage(&self) -> Option<&ColdStorage> {
+        self.cold_storage.as_ref()
+    }
+
+    /// Returns the directory each new backup is copied to, if any.
+    pub fn mirror_dir(&self) -> Option<&Path> {
+        self.mirror_dir.as_deref()
+    }
+
+    /// Returns the co-op settings, if the profile's save is shared by turns.
+    pub fn coop(&self) -> Option<&Coop> {
+        self.coop.as_ref()
+    }
+
+    /// Returns how empty directories are backed up and restored.
+    pub fn empty_dirs(&self) -> EmptyDirs {
+        self.empty_dirs
+    }
+
+    /// Returns whether new backups are stored as directories or zip archives.
+    pub fn storage(&self) -> Storage {
+        self.storage
+    }
+
+    /// Returns the directory holding the backups instead of the saves
+    /// directory, if any. See [`crate::filesystem::profile_save_dir`].
+    pub fn storage_dir(&self) -> Option<&Path> {
+        self.storage_dir.as_deref()
+    }
+
+    /// Returns how files that are only available online are backed up.
+    pub fn cloud_files(&self) -> CloudFiles {
+        self.cloud_files
+    }
+
+    /// Returns whether NTFS alternate data streams and attributes are backed
+    /// up and restored, see [`crate::ntfs`].
+    pub fn ntfs_metadata(&self) -> bool {
+        self.ntfs_metadata
+    }
+
+    /// Returns whether the machine each backup is made on is recorded with it,
+    /// see [`crate::environment`].
+    pub fn capture_env(&self) -> bool {
+        self.capture_env
+    }
+
+    /// Returns whether the text of small files is indexed with each backup,
+    /// see [`crate::search`].
+    pub fn content_index(&self) -> bool {
+        self.content_index
+    }
+
+    /// Returns the checks the live files must pass before they are backed up.
+    pub fn validators(&self) -> &[Validator] {
+        &self.validators
+    }
+
+    /// Returns the absolute path of the file whose changes indicate a game update, if any.
+    pub fn update_trigger(&self) -> Option<PathBuf> {
+        self.update_trigger
+            .as_ref()
+            .map(|path| self.base.join(path))
+    }
+
+    /// Returns `true` if changes to the given absolute path should not trigger a backup.
+    pub fn is_watch_ignored(&self, path: &Path) -> bool {
+        let Ok(rel) = path.strip_prefix(self.base()) else {
+            return false;
+        };
+        let options = self.match_options.options(None);
+        self.watch_ignore
+            .iter()
+            .flat_map(|pattern| expand_braces(pattern))
+            .filter_map(|pattern| glob::Pattern::new(&pattern).ok())
+            .any(|pattern| pattern.matches_path_with(rel, options))
+    }
+
+    /// Save the profile to the given path.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let ser = serde_json::to_string_pretty(self)
+            .or(Err(ProfileError::InvalidFormat(path.as_ref().to_owned())))?;
+        std::fs::write(&path, ser).with_path("writing profile", &path)?;
+        Ok(())
+    }
+
+    /// Expand the glob patterns in `includes()`.
+    ///
+    /// Returned paths may either be absolute or relative to `base()`.
+    ///
+    /// Fails if a pattern is malformed, or if it matches a path outside of `base()`
+    /// (e.g. `../**`), since restoring such a path would write outside the target.
+    ///
+    /// Expansions are cached until a directory under `base()` changes.
+    pub fn expand_includes(&self, relative: bool) -> Result<Vec<PathBuf>> {
+        include_cache::expand(self, relative, || self.expand(self.includes(), relative))
+    }
+
+    /// Expand a single include, which need not be part of the profile.
+    ///
+    /// See [`Profile::expand_includes`].
+    pub fn expand_include(&self, include: &Include, relative: bool) -> Result<Vec<PathBuf>> {
+        self.expand(std::slice::from_ref(include), relative)
+    }
+
+    /// Expand the given includes with a single walk of `base()`.
+    fn expand(&self, includes: &[Include], relative: bool) -> Result<Vec<PathBuf>> {
+        let matcher = IncludeMatcher::new(includes, &self.match_options)?;
+        let mut paths = Vec::new();
+        for entry in matcher.walk(self.base()) {
+            let path = entry.path();
+            let rel = self.relative_to_base(path)?;
+            let accepted = matcher
+                .matches(&rel, entry.file_type().is_dir())
+                .into_iter()
+                .any(|index| self.accepts(&includes[index], path, &rel));
+            if accepted {
+                paths.push(if relative { rel } else { path.to_owned() });
+            }
+        }
+
+        // remove duplicate paths
+        paths.sort();
+        paths.dedup();
+
+        Ok(paths)
+    }
+
+    /// Returns `true` if the options of an include let it match the given path.
+    fn accepts(&self, include: &Include, path: &Path, rel: &Path) -> bool {
+        if !include.follow_symlinks && self.is_symlinked(rel) {
+            return false;
+        }
+        if let Some(max_size) = include.max_size {
+            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+            if path.is_file() && size > max_size.0 {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Add an include after validating its pattern.
+    ///
+    /// Returns `false` if an include with the same pattern already exists,
+    /// in which case the profile is left unchanged.
+    pub fn add_include(&mut self, include: Include) -> Result<bool> {
+        include.validate()?;
+        if self.include.iter().any(|i| i.glob == include.glob) {
+            return Ok(false);
+        }
+        self.include.push(include);
+        Ok(true)
+    }
+
+    /// Remove the include with the given pattern.
+    ///
+    /// Returns `false` if there is no such include.
+    pub fn remove_include(&mut self, glob: &str) -> bool {
+        let len = self.include.len();
+        self.include.retain(|include| include.glob != glob);
+        self.include.len() != len
+    }
+
+    /// Returns `true` if the given relative path, or any of its parents below
+    /// `base()`, is a symbolic link.
+    fn is_symlinked(&self, rel: &Path) -> bool {
+        let mut path = self.base().to_owned();
+        rel.components().any(|component| {
+            path.push(component);
+            path.symlink_metadata()
+                .map(|meta| meta.file_type().is_symlink())
+                .unwrap_or(false)
+        })
+    }
+
+    /// Returns `path` relative to `base()`, ensuring that it does not escape it.
+    fn relative_to_base(&self, path: &Path) -> Result<PathBuf> {
+        let outside = || ProfileError::IncludeOutsideBase(path.to_owned());
+        let rel = path.strip_prefix(self.base()).map_err(|_| outside())?;
+        let escapes = rel
+            .components()
+            .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+        if escapes {
+            Err(outside())?
+        }
+        Ok(rel.to_owned())
+    }
+
+    /// Start building a profile with the given base directory.
+    ///
+    /// Fields that aren't set keep the defaults of [`Profile::new`].
+    pub fn builder(base: impl AsRef<Path>) -> ProfileBuilder {
+        ProfileBuilder(Profile::new(base))
+    }
+}
+
+/// Builder for profiles, see [`Profile::builder`].
+#[derive(Clone, Debug)]
+pub struct ProfileBuilder(Profile);
+
+impl ProfileBuilder {
+    /// Add an include.
+    pub fn include(mut self, include: Include) -> Self {
+        self.0.include.push(include);
+        self
+    }
+
+    /// Set the time to wait after a change before backing up, in seconds.
+    pub fn delay(mut self, delay: f32) -> Self {
+        self.0.delay = delay;
+        self
+    }
+
+    /// Add a pattern for files whose changes don't trigger a backup.
+    pub fn watch_ignore(mut self, pattern: impl Into<String>) -> Self {
+        self.0.watch_ignore.push(pattern.into());
+        self
+    }
+
+    /// Set the size limits of each backup.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.0.limits = limits;
+        self
+    }
+
+    /// Set how `include` and `watch_ignore` patterns are matched.
+    pub fn match_options(mut self, options: MatchSettings) -> Self {
+        self.0.match_options = options;
+        self
+    }
+
+    /// Set where to read the game or app version recorded with each backup.
+    pub fn version(mut self, version: VersionSource) -> Self {
+        self.0.version = Some(version);
+        self
+    }
+
+    /// Set the file whose changes indicate a game update, triggering a
+    /// `pre-update` backup.
+    pub fn update_trigger(mut self, path: impl Into<PathBuf>) -> Self {
+        self.0.update_trigger = Some(path.into());
+        self
+    }
+
+    /// Add a check the live files must pass before they are backed up.
+    pub fn validator(mut self, validator: Validator) -> Self {
+        self.0.validators.push(validator);
+        self
+    }
+
+    /// Set the maximum rate at which files are copied per second.
+    pub fn io_limit(mut self, limit: ByteSize) -> Self {
+        self.0.io_limit = Some(limit);
+        self
+    }
+
+    /// Add a plugin run on each finished backup, after the earlier ones.
+    pub fn step(mut self, step: Step) -> Self {
+        self.0.pipeline.push(step);
+        self
+    }
+
+    /// Add a command run after the pipeline, which is given the backup as JSON.
+    pub fn plugin(mut self, command: impl Into<String>) -> Self {
+        self.0.plugins.push(command.into());
+        self
+    }
+
+    /// Add a webhook notified when a backup succeeds or fails.
+    pub fn webhook(mut self, webhook: Webhook) -> Self {
+        self.0.webhooks.push(webhook);
+        self
+    }
+
+    /// Add a store each new backup is copied to.
+    pub fn remote(mut self, remote: Remote) -> Self {
+        self.0.remotes.push(remote);
+        self
+    }
+
+    /// Set the rule moving old backups to cold storage.
+    pub fn cold_storage(mut self, cold_storage: ColdStorage) -> Self {
+        self.0.cold_storage = Some(cold_storage);
+        self
+    }
+
+    /// Set the directory each new backup is copied to.
+    pub fn mirror_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.0.mirror_dir = Some(dir.into());
+        self
+    }
+
+    /// Set how empty directories are backed up and restored.
+    pub fn empty_dirs(mut self, empty_dirs: EmptyDirs) -> Self {
+        self.0.empty_dirs = empty_dirs;
+        self
+    }
+
+    /// Set whether new backups are stored as directories or zip archives.
+    pub fn storage(mut self, storage: Storage) -> Self {
+        self.0.storage = storage;
+        self
+    }
+
+    /// Set the directory holding the backups instead of the saves directory.
+    pub fn storage_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.0.storage_dir = Some(dir.into());
+        self
+    }
+
+    /// Set how files that are only available online are backed up.
+    pub fn cloud_files(mut self, cloud_files: CloudFiles) -> Self {
+        self.0.cloud_files = cloud_files;
+        self
+    }
+
+    /// Set whether NTFS alternate data streams and attributes are backed up and restored.
+    pub fn ntfs_metadata(mut self, ntfs_metadata: bool) -> Self {
+        self.0.ntfs_metadata = ntfs_metadata;
+        self
+    }
+
+    /// Set the shared directory and player name for pass-the-save co-op.
+    pub fn coop(mut self, coop: Coop) -> Self {
+        self.0.coop = Some(coop);
+        self
+    }
+
+    /// Set whether the machine each backup is made on is recorded with it.
+    pub fn capture_env(mut self, capture_env: bool) -> Self {
+        self.0.capture_env = capture_env;
+        self
+    }
+
+    /// Set whether the text of small files is indexed with each backup.
+    pub fn content_index(mut self, content_index: bool) -> Self {
+        self.0.content_index = content_index;
+        self
+    }
+
+    /// Validate the profile and return it.
+    ///
+    /// Fails with the same errors as opening an invalid profile file.
+    pub fn build(self) -> Result<Profile> {
+        self.0.validate()?;
+        Ok(self.0)
+    }
+}
+
+/// List all profiles in the profiles directory.
+pub fn list_profiles() -> Result<Vec<(PathBuf, Profile)>> {
+    let profiles_dir = profiles_dir()?;
+    let profiles = std::fs::read_dir(&profiles_dir)
+        .with_path("listing profiles in", &profiles_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|entry| entry.is_file())
+        .map(|entry| {
+            let profile = Profile::open(&entry)?;
+            Ok((entry, profile))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(profiles)
+}
+
+/// List the names of all profiles in the profiles directory, sorted.
+///
+/// Unlike [`list_profiles`], this does not open the profiles, so malformed
+/// profiles are included. Files whose names are not valid profile names are
+/// skipped.
+pub fn list_profile_names() -> Result<Vec<ProfileName>> {
+    let profiles_dir = profiles_dir()?;
+    let mut names = std::fs::read_dir(&profiles_dir)
+        .with_path("listing profiles in", &profiles_dir)?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file())
+        .filter_map(|path| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| ProfileName::new(s).ok())
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    Ok(names)
+}
+
+/// Returns the names of installed profiles that are similar to `name`.
+///
+/// A name is similar if it differs by at most two edits, or if one
+/// contains the other (ignoring case).
+pub(crate) fn similar_profile_names(name: &str) -> Vec<String> {
+    let Ok(names) = list_profile_names() else {
+        return Vec::new();
+    };
+    let needle = name.to_lowercase();
+    names
+        .into_iter()
+        .filter(|candidate| {
+            let candidate = candidate.to_lowercase();
+            edit_distance(&candidate, &needle) <= 2
+                || candidate.contains(&needle)
+                || needle.contains(&candidate)
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// Levenshtein distance between two strings.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut curr = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        prev = curr;
+    }
+    prev[b.len()]
+}
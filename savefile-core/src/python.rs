@@ -0,0 +1,212 @@
+/// Python bindings, for automation scripts and notebooks working with backup
+/// history.
+///
+/// The module is named `savefile` and mirrors the library's API:
+/// `Profile.open(name)`, `Database.open(name)`, `Database.backups(profile)`,
+/// `backup(db, profile, tag=None)` and `restore_backup(db, profile, id)`, which
+/// takes a backup's ID or unique ID.
+use pyo3::{create_exception, exceptions::PyException, prelude::*};
+
+use crate::{
+    error::Error, list_profile_names, Backup, BackupId, BackupRef, BackupStatus, Database, Profile,
+    ProfileName, Timestamp,
+};
+
+create_exception!(savefile, SavefileError, PyException);
+
+impl From<Error> for PyErr {
+    fn from(e: Error) -> Self {
+        SavefileError::new_err(e.to_string())
+    }
+}
+
+/// Parse a profile name, raising `SavefileError` if it is invalid.
+fn profile_name(name: &str) -> PyResult<ProfileName> {
+    ProfileName::new(name).map_err(|e| Error::from(e).into())
+}
+
+/// An installed profile.
+#[pyclass(name = "Profile")]
+struct PyProfile {
+    name: ProfileName,
+    profile: Profile,
+}
+
+#[pymethods]
+impl PyProfile {
+    /// Open the installed profile with the given name.
+    #[staticmethod]
+    fn open(name: &str) -> PyResult<Self> {
+        let name = profile_name(name)?;
+        let profile = Profile::open_named(&name)?;
+        Ok(Self { name, profile })
+    }
+
+    #[getter]
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[getter]
+    fn base(&self) -> String {
+        self.profile.base().display().to_string()
+    }
+
+    #[getter]
+    fn includes(&self) -> Vec<String> {
+        self.profile
+            .includes()
+            .iter()
+            .map(|include| include.glob.clone())
+            .collect()
+    }
+
+    #[getter]
+    fn delay(&self) -> f32 {
+        self.profile.delay()
+    }
+
+    /// Returns the absolute paths of the files a backup would currently contain.
+    fn files(&self) -> PyResult<Vec<String>> {
+        Ok(self
+            .profile
+            .expand_includes(false)?
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Profile({:?})", self.name.as_str())
+    }
+}
+
+/// A backup's metadata.
+#[pyclass(name = "Backup")]
+struct PyBackup(Backup);
+
+#[pymethods]
+impl PyBackup {
+    #[getter]
+    fn id(&self) -> u32 {
+        self.0.id().get()
+    }
+
+    /// The backup's globally unique ID, if assigned.
+    #[getter]
+    fn uid(&self) -> Option<String> {
+        self.0.uid().map(|uid| uid.to_string())
+    }
+
+    #[getter]
+    fn tag(&self) -> Option<&str> {
+        self.0.tag()
+    }
+
+    #[getter]
+    fn timestamp(&self) -> Timestamp {
+        self.0.timestamp()
+    }
+
+    #[getter]
+    fn version(&self) -> Option<&str> {
+        self.0.version()
+    }
+
+    /// Whether the backup's files passed the profile's validators.
+    #[getter]
+    fn valid(&self) -> bool {
+        self.0.status() == BackupStatus::Valid
+    }
+
+    /// Why the backup was made, e.g. `"watcher"` or `"manual"`.
+    #[getter]
+    fn cause(&self) -> &'static str {
+        self.0.cause().as_str()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Backup(id={}, timestamp={})",
+            self.0.id(),
+            self.0.timestamp()
+        )
+    }
+}
+
+/// The backup metadata database.
+#[pyclass(name = "Database", unsendable)]
+struct PyDatabase(Database);
+
+#[pymethods]
+impl PyDatabase {
+    /// Open the database holding the given profile's backups.
+    #[staticmethod]
+    fn open(name: &str) -> PyResult<Self> {
+        Ok(Self(Database::open_for_profile(&profile_name(name)?)?))
+    }
+
+    /// Returns the profile's backups, oldest first.
+    fn backups(&self, profile: &PyProfile) -> PyResult<Vec<PyBackup>> {
+        let backups = self.0.backup_table(&profile.name)?.select_all()?;
+        Ok(backups.into_iter().map(PyBackup).collect())
+    }
+}
+
+/// Returns the names of the installed profiles.
+#[pyfunction]
+fn list_profiles() -> PyResult<Vec<String>> {
+    Ok(list_profile_names()?
+        .into_iter()
+        .map(String::from)
+        .collect())
+}
+
+/// Back up the profile, returning the new backup's ID.
+#[pyfunction]
+#[pyo3(signature = (db, profile, tag = None))]
+fn backup(db: &PyDatabase, profile: &PyProfile, tag: Option<&str>) -> PyResult<u32> {
+    let id = crate::backup(
+        &db.0,
+        &profile.profile,
+        &profile.name,
+        &[],
+        tag,
+        crate::BackupCause::Manual,
+    )?;
+    Ok(id.get())
+}
+
+/// A backup given from Python by its ID or its unique ID.
+#[derive(FromPyObject)]
+enum PyBackupRef {
+    /// A number, the backup's ID within its profile.
+    Id(u32),
+    /// A string, the backup's globally unique ID.
+    Uid(String),
+}
+
+/// Restore a backup of the profile, overwriting the live files.
+///
+/// `id` is the backup's ID or its unique ID.
+#[pyfunction]
+fn restore_backup(db: &PyDatabase, profile: &PyProfile, id: PyBackupRef) -> PyResult<()> {
+    let backup = match id {
+        PyBackupRef::Id(id) => BackupRef::Id(BackupId::new(id).map_err(Error::from)?),
+        PyBackupRef::Uid(uid) => uid.parse().map_err(Error::from)?,
+    };
+    let id = db.0.backup_table(&profile.name)?.select_ref(backup)?.id();
+    Ok(crate::restore_backup(&db.0, &profile.name, id)?)
+}
+
+#[pymodule]
+fn savefile(py: Python<'_>, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PyProfile>()?;
+    m.add_class::<PyBackup>()?;
+    m.add_class::<PyDatabase>()?;
+    m.add_function(wrap_pyfunction!(list_profiles, m)?)?;
+    m.add_function(wrap_pyfunction!(backup, m)?)?;
+    m.add_function(wrap_pyfunction!(restore_backup, m)?)?;
+    m.add("SavefileError", py.get_type::<SavefileError>())?;
+    Ok(())
+}
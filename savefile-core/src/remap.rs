@@ -0,0 +1,157 @@
+/// Finding the new location of a profile's base directory after it moved,
+/// e.g. because a drive letter changed or a Steam library was moved, and
+/// pointing the profile at it.
+use std::{
+    ffi::OsStr,
+    path::{Component, Path, PathBuf},
+};
+
+use crate::{
+    error::{ProfileError, Result},
+    filesystem::profile_path,
+    profile::{Profile, ProfileName},
+};
+
+/// Directories whose subdirectories are where removable and secondary drives
+/// are usually mounted on Linux and macOS.
+const MOUNT_DIRS: [&str; 3] = ["/mnt", "/media", "/Volumes"];
+
+/// Shortest trailing part of the base's path that a candidate must share, so
+/// that a lone `saves` directory elsewhere isn't mistaken for the base.
+const MIN_SHARED_COMPONENTS: usize = 2;
+
+/// Returns likely new locations of a profile's base directory.
+///
+/// Only local bases that no longer exist are looked for. Candidates keep the
+/// trailing part of the old path under another drive, mount point or Steam
+/// library, and are only returned if the profile's includes match files in
+/// them. Candidates keeping a longer part of the old path come first.
+pub fn find_moved_base(profile: &Profile) -> Result<Vec<PathBuf>> {
+    let base = profile.base();
+    if profile.remote().is_some() || base.is_dir() {
+        return Ok(Vec::new());
+    }
+    let parts = base
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(part) => Some(part),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let min_shared = MIN_SHARED_COMPONENTS.min(parts.len());
+    let mut candidates = Vec::new();
+    for skip in 0..=parts.len() - min_shared {
+        let suffix = parts[skip..].iter().collect::<PathBuf>();
+        for root in candidate_roots() {
+            candidates.push(root.join(&suffix));
+        }
+    }
+    // a moved Steam library keeps the layout below its steamapps directory
+    if let Some(index) = parts
+        .iter()
+        .position(|part| *part == OsStr::new("steamapps"))
+    {
+        let suffix = parts[index..].iter().collect::<PathBuf>();
+        for library in steam_libraries() {
+            candidates.push(library.join(&suffix));
+        }
+    }
+
+    let mut found: Vec<PathBuf> = Vec::new();
+    for candidate in candidates {
+        if candidate == base || !candidate.is_dir() || found.contains(&candidate) {
+            continue;
+        }
+        let matched = profile.includes().is_empty()
+            || !profile
+                .with_base(&candidate)
+                .expand_includes(true)?
+                .is_empty();
+        if matched {
+            found.push(candidate);
+        }
+    }
+    Ok(found)
+}
+
+/// Point a profile at a new base directory, e.g. one found by [`find_moved_base`].
+///
+/// The new base must be an existing directory, and unless `force` is set, the
+/// profile's includes must match files in it. Backups are not affected, and a
+/// running watcher picks up the change. Returns the number of files matched.
+pub fn remap_base(name: &ProfileName, base: &Path, force: bool) -> Result<usize> {
+    let profile = Profile::open_named(name)?;
+    if !base.is_absolute() || !base.is_dir() {
+        Err(ProfileError::InvalidBase(base.to_owned()))?
+    }
+    let value = serde_json::to_string(base).or(Err(ProfileError::InvalidBase(base.to_owned())))?;
+    let remapped = profile.with_field("base", &value)?;
+    let files = remapped.expand_includes(true)?.len();
+    if files == 0 && !remapped.includes().is_empty() && !force {
+        Err(ProfileError::RemapNoFiles(remapped.base().to_owned()))?
+    }
+    remapped.save(profile_path(name)?)?;
+    Ok(files)
+}
+
+/// Returns the directories a moved base may now be under.
+fn candidate_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+    if cfg!(windows) {
+        roots.extend(
+            (b'A'..=b'Z')
+                .map(|letter| PathBuf::from(format!("{}:\\", letter as char)))
+                .filter(|root| root.is_dir()),
+        );
+    } else {
+        roots.push(PathBuf::from("/"));
+        roots.extend(dirs::home_dir());
+        for dir in MOUNT_DIRS {
+            roots.extend(subdirs(Path::new(dir)));
+        }
+        // removable drives are mounted per user, e.g. /media/<user>/<label>
+        for dir in ["/media", "/run/media"] {
+            for user in subdirs(Path::new(dir)) {
+                roots.extend(subdirs(&user));
+            }
+        }
+    }
+    roots
+}
+
+/// Returns the subdirectories of a directory, or none if it can't be read.
+fn subdirs(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+/// Returns the Steam library folders listed by the local Steam installation.
+fn steam_libraries() -> Vec<PathBuf> {
+    let home = dirs::home_dir().unwrap_or_default();
+    let installs = [
+        PathBuf::from("C:\\Program Files (x86)\\Steam"),
+        home.join(".steam/steam"),
+        home.join(".local/share/Steam"),
+        home.join("Library/Application Support/Steam"),
+    ];
+    installs
+        .iter()
+        .filter_map(|install| {
+            std::fs::read_to_string(install.join("steamapps/libraryfolders.vdf")).ok()
+        })
+        .flat_map(|vdf| {
+            // entries look like: "path"		"D:\\SteamLibrary"
+            vdf.lines()
+                .filter_map(|line| line.trim().strip_prefix("\"path\""))
+                .map(|value| value.trim().trim_matches('"').replace("\\\\", "\\"))
+                .map(PathBuf::from)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
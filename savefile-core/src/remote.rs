@@ -0,0 +1,259 @@
+/// Profiles whose files live on another machine, such as a dedicated game
+/// server, reached over SFTP.
+///
+/// A profile whose base is e.g. `sftp://steam@example.com/home/steam/worlds` is
+/// backed up by first pulling the remote directory into a local mirror, which
+/// then serves as the base directory. Restores update the mirror and push the
+/// backup's files back to the server.
+use std::path::Path;
+
+use crate::error::{ProfileError, Result};
+
+/// Scheme of remote base directories.
+const SCHEME: &str = "sftp://";
+/// Default SSH port.
+const DEFAULT_PORT: u16 = 22;
+
+/// A directory on an SFTP server.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteBase {
+    /// User to log in as.
+    pub user: String,
+    /// Host name or address of the server.
+    pub host: String,
+    /// Port of the SSH server.
+    pub port: u16,
+    /// Absolute path of the directory on the server.
+    pub path: String,
+}
+
+impl RemoteBase {
+    /// Parse a base directory, returning `None` if it is a local path.
+    ///
+    /// Fails if the base has the `sftp://` scheme but isn't of the form
+    /// `sftp://user@host[:port]/path`.
+    pub fn parse(base: &Path) -> Result<Option<Self>, ProfileError> {
+        let Some(rest) = base.to_str().and_then(|base| base.strip_prefix(SCHEME)) else {
+            return Ok(None);
+        };
+        let invalid = || ProfileError::InvalidBase(base.to_owned());
+        let (authority, path) = rest.split_once('/').ok_or_else(invalid)?;
+        let (user, address) = authority.split_once('@').ok_or_else(invalid)?;
+        let (host, port) = match address.rsplit_once(':') {
+            Some((host, port)) => (host, port.parse().map_err(|_| invalid())?),
+            None => (address, DEFAULT_PORT),
+        };
+        if user.is_empty() || host.is_empty() {
+            Err(invalid())?
+        }
+        Ok(Some(Self {
+            user: user.to_owned(),
+            host: host.to_owned(),
+            port,
+            path: format!("/{}", path.trim_end_matches('/')),
+        }))
+    }
+}
+
+impl std::fmt::Display for RemoteBase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{}@{}", SCHEME, self.user, self.host)?;
+        if self.port != DEFAULT_PORT {
+            write!(f, ":{}", self.port)?;
+        }
+        write!(f, "{}", self.path)
+    }
+}
+
+#[cfg(feature = "sftp")]
+pub(crate) use transfer::{pull, push};
+
+/// Fail, since transfers need the `sftp` feature.
+#[cfg(not(feature = "sftp"))]
+pub(crate) fn pull(remote: &RemoteBase, _mirror: &Path) -> Result<()> {
+    Err(unsupported(remote))
+}
+
+/// Fail, since transfers need the `sftp` feature.
+#[cfg(not(feature = "sftp"))]
+pub(crate) fn push(remote: &RemoteBase, _src: &Path) -> Result<()> {
+    Err(unsupported(remote))
+}
+
+/// Error for transfers in builds without the `sftp` feature.
+#[cfg(not(feature = "sftp"))]
+fn unsupported(remote: &RemoteBase) -> crate::error::Error {
+    crate::error::Error::Remote {
+        url: remote.to_string(),
+        reason: "savefile was built without the `sftp` feature".to_owned(),
+    }
+}
+
+#[cfg(feature = "sftp")]
+mod transfer {
+    use std::{
+        collections::HashSet,
+        net::TcpStream,
+        path::{Path, PathBuf},
+    };
+
+    use ssh2::{CheckResult, KnownHostFileKind, Session, Sftp};
+
+    use super::RemoteBase;
+    use crate::{
+        error::{Error, IoResultExt, Result},
+        filesystem::walk_files,
+        secret,
+    };
+
+    /// Private keys tried, in order, when the SSH agent has no usable key.
+    const KEY_FILES: [&str; 3] = ["id_ed25519", "id_ecdsa", "id_rsa"];
+
+    /// Replace the contents of `mirror` with the remote directory.
+    ///
+    /// Local files that no longer exist on the server are removed.
+    pub(crate) fn pull(remote: &RemoteBase, mirror: &Path) -> Result<()> {
+        let sftp = connect(remote)?;
+        std::fs::create_dir_all(mirror).with_path("creating", mirror)?;
+        let mut pulled = HashSet::new();
+        pull_dir(remote, &sftp, Path::new(&remote.path), mirror, &mut pulled)?;
+        for file in walk_files(mirror)? {
+            if !pulled.contains(&file) {
+                std::fs::remove_file(&file).with_path("removing", &file)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Upload all files in `src` to the remote directory, replacing existing files.
+    pub(crate) fn push(remote: &RemoteBase, src: &Path) -> Result<()> {
+        let sftp = connect(remote)?;
+        for file in walk_files(src)? {
+            let rel = file.strip_prefix(src).expect("walked inside source");
+            // remote paths always use forward slashes
+            let mut dest = remote.path.clone();
+            for dir in rel.parent().into_iter().flat_map(Path::components) {
+                dest.push('/');
+                dest.push_str(&dir.as_os_str().to_string_lossy());
+                // fails if the directory exists, and other failures surface below
+                let _ = sftp.mkdir(Path::new(&dest), 0o755);
+            }
+            dest.push('/');
+            dest.push_str(&rel.file_name().expect("walked a file").to_string_lossy());
+            let dest = PathBuf::from(dest);
+            let mut reader = std::fs::File::open(&file).with_path("opening", &file)?;
+            let mut writer = sftp
+                .create(&dest)
+                .map_err(|e| failed(remote, format!("creating {}", dest.display()), e))?;
+            std::io::copy(&mut reader, &mut writer)
+                .context(|| format!("failed uploading {} to {}", file.display(), remote))?;
+        }
+        Ok(())
+    }
+
+    /// Download a remote directory recursively, recording the written files.
+    fn pull_dir(
+        remote: &RemoteBase,
+        sftp: &Sftp,
+        dir: &Path,
+        dest: &Path,
+        pulled: &mut HashSet<PathBuf>,
+    ) -> Result<()> {
+        let entries = sftp
+            .readdir(dir)
+            .map_err(|e| failed(remote, format!("listing {}", dir.display()), e))?;
+        for (path, stat) in entries {
+            let Some(name) = path.file_name() else {
+                continue;
+            };
+            let local = dest.join(name);
+            if stat.is_dir() {
+                std::fs::create_dir_all(&local).with_path("creating", &local)?;
+                pull_dir(remote, sftp, &path, &local, pulled)?;
+            } else if stat.is_file() {
+                let mut reader = sftp
+                    .open(&path)
+                    .map_err(|e| failed(remote, format!("opening {}", path.display()), e))?;
+                let mut writer = std::fs::File::create(&local).with_path("creating", &local)?;
+                std::io::copy(&mut reader, &mut writer)
+                    .context(|| format!("failed downloading {} from {}", path.display(), remote))?;
+                pulled.insert(local);
+            }
+        }
+        Ok(())
+    }
+
+    /// Log in to the server, checking its host key against `~/.ssh/known_hosts`.
+    ///
+    /// Authentication uses the SSH agent, then the default private keys, then the
+    /// password stored as the server's secret (see [`secret::sftp_password_name`]).
+    fn connect(remote: &RemoteBase) -> Result<Sftp> {
+        let tcp = TcpStream::connect((remote.host.as_str(), remote.port))
+            .context(|| format!("failed connecting to {}", remote))?;
+        let mut session = Session::new().map_err(|e| failed(remote, "starting session", e))?;
+        session.set_tcp_stream(tcp);
+        session
+            .handshake()
+            .map_err(|e| failed(remote, "SSH handshake", e))?;
+        let ssh_dir = dirs::home_dir().unwrap_or_default().join(".ssh");
+        check_host_key(remote, &session, &ssh_dir.join("known_hosts"))?;
+        if session.userauth_agent(&remote.user).is_err() {
+            for key in KEY_FILES.map(|name| ssh_dir.join(name)) {
+                if key.is_file()
+                    && session
+                        .userauth_pubkey_file(&remote.user, None, &key, None)
+                        .is_ok()
+                {
+                    break;
+                }
+            }
+        }
+        if !session.authenticated() {
+            let name = secret::sftp_password_name(&remote.user, &remote.host);
+            if let Some(password) = secret::lookup(&name) {
+                // checked below
+                let _ = session.userauth_password(&remote.user, &password);
+            }
+        }
+        if !session.authenticated() {
+            Err(Error::Remote {
+                url: remote.to_string(),
+                reason: "authentication failed (add a key to the SSH agent or ~/.ssh, or store a password with `savefile secret set`)".to_owned(),
+            })?
+        }
+        session
+            .sftp()
+            .map_err(|e| failed(remote, "starting SFTP", e))
+    }
+
+    /// Fail unless the server's key is listed in the known hosts file.
+    fn check_host_key(remote: &RemoteBase, session: &Session, known_hosts: &Path) -> Result<()> {
+        let mut hosts = session
+            .known_hosts()
+            .map_err(|e| failed(remote, "reading known hosts", e))?;
+        // a missing file leaves the list empty, which is reported below
+        let _ = hosts.read_file(known_hosts, KnownHostFileKind::OpenSSH);
+        let (key, _) = session.host_key().ok_or_else(|| Error::Remote {
+            url: remote.to_string(),
+            reason: "server sent no host key".to_owned(),
+        })?;
+        let reason = match hosts.check_port(&remote.host, remote.port, key) {
+            CheckResult::Match => return Ok(()),
+            CheckResult::NotFound => "unknown host key (connect once with `ssh` to trust it)",
+            CheckResult::Mismatch => "host key does not match known_hosts",
+            CheckResult::Failure => "failed checking host key",
+        };
+        Err(Error::Remote {
+            url: remote.to_string(),
+            reason: reason.to_owned(),
+        })
+    }
+
+    /// Describe a failed SSH operation.
+    fn failed(remote: &RemoteBase, op: impl std::fmt::Display, e: ssh2::Error) -> Error {
+        Error::Remote {
+            url: remote.to_string(),
+            reason: format!("{}: {}", op, e),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+/// Limits how many backups run at the same time within one process.
+///
+/// When several watchers run in one process (see [`crate::watch_all`]) and
+/// their profiles change at once, backups wait in a queue instead of copying
+/// all at once and competing for the disk.
+use std::sync::{Condvar, Mutex, OnceLock};
+
+use crate::config::Config;
+
+/// Queue of backups waiting to run, served in order of arrival.
+pub struct Scheduler {
+    /// Maximum number of backups running at once, or 0 for no limit.
+    limit: usize,
+    state: Mutex<State>,
+    changed: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    /// Number of backups currently running.
+    running: usize,
+    /// Ticket handed out to the next backup that asks to run.
+    next_ticket: u64,
+    /// Ticket of the backup allowed to start next.
+    serving: u64,
+    /// Whether the process is shutting down, so no further backups may start.
+    draining: bool,
+}
+
+impl Scheduler {
+    /// Create a scheduler that runs at most `limit` backups at once.
+    ///
+    /// A limit of 0 means no limit.
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            state: Mutex::new(State::default()),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// Returns the scheduler shared by the whole process.
+    ///
+    /// Its limit is read from [`Config::max_concurrent_backups`] on first use.
+    pub fn global() -> &'static Scheduler {
+        static GLOBAL: OnceLock<Scheduler> = OnceLock::new();
+        GLOBAL.get_or_init(|| {
+            let config = Config::load().unwrap_or_default();
+            Scheduler::new(config.max_concurrent_backups)
+        })
+    }
+
+    /// Wait until a backup may run.
+    ///
+    /// The backup counts as running until the returned permit is dropped.
+    pub fn acquire(&self) -> Permit<'_> {
+        let mut state = self.state.lock().expect("scheduler lock poisoned");
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        while state.draining
+            || state.serving != ticket
+            || (self.limit > 0 && state.running >= self.limit)
+        {
+            state = self.changed.wait(state).expect("scheduler lock poisoned");
+        }
+        state.serving += 1;
+        state.running += 1;
+        // the next ticket may be able to start as well
+        self.changed.notify_all();
+        Permit { scheduler: self }
+    }
+
+    /// Stop starting backups, and wait until the running ones have finished.
+    ///
+    /// Backups that ask to run afterwards wait forever, so this is only
+    /// meant for shutting down the process.
+    pub fn drain(&self) {
+        let mut state = self.state.lock().expect("scheduler lock poisoned");
+        state.draining = true;
+        while state.running > 0 {
+            state = self.changed.wait(state).expect("scheduler lock poisoned");
+        }
+    }
+}
+
+/// Permission for a single backup to run, see [`Scheduler::acquire`].
+pub struct Permit<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        let mut state = self
+            .scheduler
+            .state
+            .lock()
+            .expect("scheduler lock poisoned");
+        state.running -= 1;
+        self.scheduler.changed.notify_all();
+    }
+}
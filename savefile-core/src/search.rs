@@ -0,0 +1,288 @@
+/// Searching the contents of small text files across a profile's backups, e.g.
+/// to find the backups of a save whose character has a given name.
+///
+/// Profiles with `content_index` set keep the text of their backed up files in
+/// an index, `saves/<profile>/content.jsonl`. Each line holds one distinct
+/// content keyed by its hash, so files that didn't change take no extra space.
+/// Searches match the index against the backups' manifests, so backups stored
+/// as zip archives or in cold storage are found without extracting them.
+///
+/// The index isn't encrypted, so it can't be kept while the metadata databases
+/// are, see the `database_key` config value.
+use std::{
+    collections::{HashMap, HashSet},
+    fs::OpenOptions,
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    archive,
+    config::Config,
+    database::Database,
+    error::{BackupError, Error, IoResultExt, Result},
+    filesystem::{backup_dir, content_index_path},
+    profile::ProfileName,
+    Backup, FileRecord,
+};
+
+/// Largest file whose text is indexed, in bytes.
+const MAX_INDEXED_SIZE: u64 = 1024 * 1024;
+
+/// Longest excerpt of a matching line, in characters.
+const MAX_EXCERPT_LEN: usize = 80;
+
+/// The text of one distinct file content.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Entry {
+    /// SHA-256 hash of the content, as in [`FileRecord::hash`].
+    hash: String,
+    /// The content.
+    text: String,
+}
+
+/// A file in a backup containing the searched text.
+#[derive(Clone, Debug)]
+pub struct Match {
+    /// The backup holding the file.
+    pub backup: Backup,
+    /// Path of the file, relative to the profile's base directory.
+    pub path: PathBuf,
+    /// The first line containing the text, shortened around the text if long.
+    pub excerpt: String,
+}
+
+/// Find the files in a profile's backups containing `query`, ordered by
+/// backup ID and path.
+///
+/// Fails if the profile has no content index.
+pub fn search(
+    db: &Database,
+    profile: &ProfileName,
+    query: &str,
+    ignore_case: bool,
+) -> Result<Vec<Match>> {
+    let path = content_index_path(profile)?;
+    if !path.is_file() {
+        Err(BackupError::NoContentIndex(profile.to_string()))?
+    }
+    let query = match ignore_case {
+        true => query.to_lowercase(),
+        false => query.to_owned(),
+    };
+    let mut excerpts = HashMap::new();
+    for entry in read_index(&path)? {
+        let line = entry.text.lines().find(|line| match ignore_case {
+            true => line.to_lowercase().contains(&query),
+            false => line.contains(&query),
+        });
+        if let Some(line) = line {
+            excerpts.insert(entry.hash, excerpt(line, &query, ignore_case));
+        }
+    }
+    let mut matches = Vec::new();
+    if excerpts.is_empty() {
+        return Ok(matches);
+    }
+    let mut backups = db.backup_table(profile)?.select_all()?;
+    backups.sort_by_key(|backup| backup.id());
+    let file_table = db.file_table(profile)?;
+    for backup in backups {
+        for file in file_table.select_backup(backup.id())? {
+            if let Some(excerpt) = excerpts.get(&file.hash) {
+                matches.push(Match {
+                    backup: backup.clone(),
+                    path: file.path,
+                    excerpt: excerpt.clone(),
+                });
+            }
+        }
+    }
+    Ok(matches)
+}
+
+/// Add the text files of a backup to the profile's content index.
+///
+/// Only contents not indexed yet are added. Returns how many were added.
+///
+/// Fails if the metadata databases are encrypted.
+pub(crate) fn index_backup(
+    profile: &ProfileName,
+    backup_dir: &Path,
+    manifest: &[FileRecord],
+) -> Result<usize> {
+    check_unencrypted(profile)?;
+    let path = content_index_path(profile)?;
+    let mut known = match path.is_file() {
+        true => read_index(&path)?
+            .into_iter()
+            .map(|entry| entry.hash)
+            .collect(),
+        false => HashSet::new(),
+    };
+    let mut index = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_path("opening content index", &path)?;
+    let mut added = 0;
+    for file in manifest {
+        if file.size > MAX_INDEXED_SIZE || known.contains(&file.hash) {
+            continue;
+        }
+        let Some(text) = read_text(&backup_dir.join(&file.path)) else {
+            continue;
+        };
+        let entry = Entry {
+            hash: file.hash.clone(),
+            text,
+        };
+        let mut line = serde_json::to_vec(&entry).expect("index entry is always serializable");
+        line.push(b'\n');
+        index
+            .write_all(&line)
+            .with_path("writing content index", &path)?;
+        known.insert(file.hash.clone());
+        added += 1;
+    }
+    Ok(added)
+}
+
+/// Rebuild the content index of a profile from all of its backups, e.g. after
+/// setting `content_index` or deleting backups.
+///
+/// Backups stored as zip archives or in cold storage are extracted. Backups
+/// without a manifest are skipped. Returns the number of indexed contents.
+///
+/// Fails if the metadata databases are encrypted.
+pub fn rebuild_index(db: &Database, profile: &ProfileName) -> Result<usize> {
+    check_unencrypted(profile)?;
+    let path = content_index_path(profile)?;
+    if path.exists() {
+        std::fs::remove_file(&path).with_path("removing content index", &path)?;
+    }
+    // an empty index still marks the profile as indexed
+    std::fs::write(&path, "").with_path("writing content index", &path)?;
+    let mut backups = db.backup_table(profile)?.select_all()?;
+    backups.sort_by_key(|backup| backup.id());
+    let file_table = db.file_table(profile)?;
+    let mut indexed = 0;
+    for backup in backups {
+        let manifest = file_table.select_backup(backup.id())?;
+        if manifest.is_empty() {
+            continue;
+        }
+        let extracted = archive::extract_backup(profile, &backup)?;
+        let dir = match &extracted {
+            Some(extracted) => extracted.dir().to_owned(),
+            None => backup_dir(profile, &backup)?,
+        };
+        indexed += index_backup(profile, &dir, &manifest)?;
+    }
+    Ok(indexed)
+}
+
+/// Fail if the metadata databases are encrypted, since the index would keep
+/// the text of the saves unencrypted next to them.
+fn check_unencrypted(profile: &ProfileName) -> Result<()> {
+    let encrypted = Config::load()?.database_key.is_some();
+    #[cfg(feature = "sqlcipher")]
+    let encrypted = encrypted || crate::sqlite::keyring_database_key().is_some();
+    if encrypted {
+        Err(BackupError::ContentIndexEncrypted(profile.to_string()))?
+    }
+    Ok(())
+}
+
+/// Read the entries of a content index.
+fn read_index(path: &Path) -> Result<Vec<Entry>> {
+    let file = std::fs::File::open(path).with_path("opening content index", path)?;
+    BufReader::new(file)
+        .lines()
+        .filter(|line| !matches!(line, Ok(line) if line.trim().is_empty()))
+        .map(|line| {
+            let line = line.with_path("reading content index", path)?;
+            serde_json::from_str(&line).map_err(|_| Error::IoContext {
+                context: format!("failed parsing content index {}", path.display()),
+                source: io::ErrorKind::InvalidData.into(),
+            })
+        })
+        .collect()
+}
+
+/// Returns the contents of a file if it is text, i.e. valid UTF-8 without NUL
+/// characters.
+fn read_text(path: &Path) -> Option<String> {
+    let text = String::from_utf8(std::fs::read(path).ok()?).ok()?;
+    Some(text).filter(|text| !text.contains('\0'))
+}
+
+/// Shorten a matching line for display, keeping the part around the first
+/// occurrence of `query`.
+///
+/// With `ignore_case`, `query` must be lowercase.
+fn excerpt(line: &str, query: &str, ignore_case: bool) -> String {
+    let line = line.trim();
+    let chars = line.chars().collect::<Vec<_>>();
+    if chars.len() <= MAX_EXCERPT_LEN {
+        return line.to_owned();
+    }
+    // in characters, which lowercasing rarely changes the number of
+    let start = match ignore_case {
+        true => {
+            let lower = line.to_lowercase();
+            lower.find(query).map(|at| lower[..at].chars().count())
+        }
+        false => line.find(query).map(|at| line[..at].chars().count()),
+    }
+    .unwrap_or(0);
+    let middle = start + query.chars().count() / 2;
+    let from = middle
+        .saturating_sub(MAX_EXCERPT_LEN / 2)
+        .min(chars.len() - MAX_EXCERPT_LEN);
+    let to = from + MAX_EXCERPT_LEN;
+    format!(
+        "{}{}{}",
+        if from > 0 { "..." } else { "" },
+        chars[from..to].iter().collect::<String>(),
+        if to < chars.len() { "..." } else { "" },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_lines_are_kept_whole() {
+        assert_eq!(
+            excerpt("  \"name\": \"Aloy\"  ", "Aloy", false),
+            "\"name\": \"Aloy\""
+        );
+    }
+
+    #[test]
+    fn excerpts_are_centred_on_the_match() {
+        let line = format!(
+            "{{\"level\":{},\"name\":\"Aloy\",\"gold\":{}}}",
+            "1".repeat(200),
+            "2".repeat(200)
+        );
+        let shown = excerpt(&line, "aloy", true);
+        assert!(shown.starts_with("...") && shown.ends_with("..."));
+        assert!(shown.contains("\"name\":\"Aloy\""));
+        assert_eq!(shown.chars().count(), MAX_EXCERPT_LEN + 6);
+        let middle = shown.find("Aloy").unwrap();
+        assert!(middle.abs_diff(shown.len() / 2) <= 4, "{}", shown);
+    }
+
+    #[test]
+    fn excerpts_stay_within_the_line() {
+        let line = format!("{}Aloy", "x".repeat(200));
+        let shown = excerpt(&line, "Aloy", false);
+        assert!(shown.starts_with("...") && shown.ends_with("Aloy"));
+        assert_eq!(shown.chars().count(), MAX_EXCERPT_LEN + 3);
+        let line = format!("Aloy{}", "x".repeat(200));
+        assert!(excerpt(&line, "Aloy", false).starts_with("Aloy"));
+    }
+}
@@ -0,0 +1,72 @@
+/// Secrets such as passwords and encryption keys, kept in the OS keyring
+/// (Windows Credential Manager, macOS Keychain or the Secret Service on Linux)
+/// instead of in plain text in profiles or the configuration.
+///
+/// Secrets are stored with `savefile secret set <name>`. The subsystems that
+/// need one look it up by its well-known name, such as [`DATABASE_KEY`].
+use keyring::Entry;
+
+use crate::error::{Error, Result};
+
+/// Keyring service under which all secrets are stored.
+const SERVICE: &str = "savefile";
+
+/// Name of the key for the metadata databases, used if the config sets none.
+pub const DATABASE_KEY: &str = "database-key";
+/// Name of the SMTP password for email alerts, used if the config sets none.
+pub const SMTP_PASSWORD: &str = "smtp-password";
+
+/// Returns the name of the password for logging in to an SFTP server.
+pub fn sftp_password_name(user: &str, host: &str) -> String {
+    format!("sftp:{}@{}", user, host)
+}
+
+/// Store a secret, replacing any previous value.
+pub fn set(name: &str, value: &str) -> Result<()> {
+    entry(name)?
+        .set_password(value)
+        .map_err(|e| failed(name, e))
+}
+
+/// Delete a secret.
+///
+/// Returns `false` if there was no such secret.
+pub fn delete(name: &str) -> Result<bool> {
+    match entry(name)?.delete_password() {
+        Ok(()) => Ok(true),
+        Err(keyring::Error::NoEntry) => Ok(false),
+        Err(e) => Err(failed(name, e)),
+    }
+}
+
+/// Look up a secret, returning `None` if it isn't set.
+pub fn get(name: &str) -> Result<Option<String>> {
+    match entry(name)?.get_password() {
+        Ok(value) => Ok(Some(value)),
+        Err(keyring::Error::NoEntry) => Ok(None),
+        Err(e) => Err(failed(name, e)),
+    }
+}
+
+/// Look up an optional secret, for subsystems that work without it.
+///
+/// A keyring that can't be used, e.g. in a container, is reported as a warning.
+pub(crate) fn lookup(name: &str) -> Option<String> {
+    get(name).unwrap_or_else(|e| {
+        eprintln!("warning: {}", e);
+        None
+    })
+}
+
+/// Returns the keyring entry of a secret.
+fn entry(name: &str) -> Result<Entry> {
+    Entry::new(SERVICE, name).map_err(|e| failed(name, e))
+}
+
+/// Describe a failed keyring operation.
+fn failed(name: &str, e: keyring::Error) -> Error {
+    Error::Secret {
+        name: name.to_owned(),
+        reason: e.to_string(),
+    }
+}
@@ -0,0 +1,302 @@
+/// Selections of several backups at once, by ID ranges or by age.
+///
+/// These are parsed from command-line arguments like `--ids 3,5,9-12` and
+/// `--before 2024-05-01`.
+use std::{cmp::Reverse, num::NonZeroUsize, ops::RangeInclusive, str::FromStr};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+use crate::backup::{Backup, BackupId, BackupRef, BackupUid, Timestamp};
+
+/// A list of backup IDs and inclusive ID ranges, written like `3,5,9-12`.
+///
+/// Backups may also be listed by their unique IDs, like `01HZ3Q8R6V0MGGAE4W8N1C2J5K,7`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IdRanges {
+    /// The listed IDs and ID ranges.
+    ranges: Vec<RangeInclusive<u32>>,
+    /// The listed unique IDs.
+    uids: Vec<BackupUid>,
+}
+
+impl IdRanges {
+    /// Returns whether the given backup is part of the list.
+    pub fn contains(&self, backup: &Backup) -> bool {
+        self.ranges
+            .iter()
+            .any(|range| range.contains(&backup.id().get()))
+            || backup.uid().is_some_and(|uid| self.uids.contains(&uid))
+    }
+}
+
+impl From<BackupRef> for IdRanges {
+    fn from(backup: BackupRef) -> Self {
+        match backup {
+            BackupRef::Id(id) => Self {
+                ranges: vec![id.get()..=id.get()],
+                uids: Vec::new(),
+            },
+            BackupRef::Uid(uid) => Self {
+                ranges: Vec::new(),
+                uids: vec![uid],
+            },
+        }
+    }
+}
+
+impl FromStr for IdRanges {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid backup IDs: {:?} (expected e.g. \"3,5,9-12\")", s);
+        let mut ids = Self {
+            ranges: Vec::new(),
+            uids: Vec::new(),
+        };
+        for part in s.split(',') {
+            let part = part.trim();
+            let Some((start, end)) = part.split_once('-') else {
+                match part.parse().map_err(|_| invalid())? {
+                    BackupRef::Id(id) => ids.ranges.push(id.get()..=id.get()),
+                    BackupRef::Uid(uid) => ids.uids.push(uid),
+                }
+                continue;
+            };
+            let start: u32 = start.trim().parse().map_err(|_| invalid())?;
+            let end: u32 = end.trim().parse().map_err(|_| invalid())?;
+            if start == 0 || start > end {
+                return Err(invalid());
+            }
+            ids.ranges.push(start..=end);
+        }
+        Ok(ids)
+    }
+}
+
+/// A point in time bounding the age of selected backups.
+///
+/// Written as a date like `2024-05-01`, meaning midnight in local time, or as
+/// an RFC 3339 timestamp like `2024-05-01T18:30:00+02:00`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cutoff(pub Timestamp);
+
+impl FromStr for Cutoff {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(s) {
+            return Ok(Self(timestamp.with_timezone(&Utc)));
+        }
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+            .and_then(|midnight| Local.from_local_datetime(&midnight).earliest())
+            .map(|midnight| Self(midnight.with_timezone(&Utc)))
+            .ok_or_else(|| format!("invalid date: {:?} (expected e.g. \"2024-05-01\")", s))
+    }
+}
+
+/// Criteria for selecting several backups of a profile.
+///
+/// A backup is selected if it matches every criterion given. Without any
+/// criteria, all backups are selected.
+#[derive(Clone, Debug, Default)]
+pub struct Selection {
+    /// Only select backups with these IDs.
+    pub ids: Option<IdRanges>,
+    /// Only select backups made before this time.
+    pub before: Option<Cutoff>,
+    /// Only select backups made at or after this time.
+    pub after: Option<Cutoff>,
+}
+
+impl Selection {
+    /// Returns whether no criteria were given, so that all backups are selected.
+    pub fn is_all(&self) -> bool {
+        self.ids.is_none() && self.before.is_none() && self.after.is_none()
+    }
+
+    /// Returns whether the given backup is selected.
+    pub fn matches(&self, backup: &Backup) -> bool {
+        self.ids.as_ref().is_none_or(|ids| ids.contains(backup))
+            && self
+                .before
+                .is_none_or(|Cutoff(before)| backup.timestamp() < before)
+            && self
+                .after
+                .is_none_or(|Cutoff(after)| backup.timestamp() >= after)
+    }
+
+    /// Returns the IDs of the selected backups, in the order given.
+    pub fn resolve(&self, backups: &[Backup]) -> Vec<BackupId> {
+        backups
+            .iter()
+            .filter(|backup| self.matches(backup))
+            .map(Backup::id)
+            .collect()
+    }
+
+    /// Returns the IDs of the selected backups to delete so that only every
+    /// `keep_every`th of them remains, in time order from the most recent one.
+    ///
+    /// Backups are counted by their IDs, which number the whole history and
+    /// don't change when backups are deleted, so thinning the same backups
+    /// again deletes nothing more. Backups whose ID is a multiple of
+    /// `keep_every` are kept, as is the most recent selected one.
+    pub fn thin(&self, backups: &[Backup], keep_every: NonZeroUsize) -> Vec<BackupId> {
+        let mut selected = backups
+            .iter()
+            .filter(|backup| self.matches(backup))
+            .collect::<Vec<_>>();
+        selected.sort_by_key(|backup| Reverse(backup.timestamp()));
+        selected
+            .iter()
+            .skip(1)
+            .map(|backup| backup.id())
+            .filter(|id| !(id.get() as usize).is_multiple_of(keep_every.get()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::backup::BackupCause;
+
+    /// A backup with the given ID, made `hours` ago.
+    fn backup(id: u32, hours: i64) -> Backup {
+        Backup::new(
+            BackupId::new(id).unwrap(),
+            String::new(),
+            Utc::now() - Duration::hours(hours),
+            None,
+            None,
+            Default::default(),
+            BackupCause::Manual,
+            None,
+            None,
+            Some(BackupUid::new(&Utc::now())),
+        )
+    }
+
+    #[test]
+    fn id_ranges_parse_ids_and_ranges() {
+        let ids: IdRanges = " 3, 5 ,9-12".parse().unwrap();
+        let selected = (1..=13)
+            .filter(|&id| ids.contains(&backup(id, 0)))
+            .collect::<Vec<_>>();
+        assert_eq!(selected, [3, 5, 9, 10, 11, 12]);
+    }
+
+    #[test]
+    fn id_ranges_may_overlap() {
+        let ids: IdRanges = "2-6,4-8,5".parse().unwrap();
+        let selected = (1..=10)
+            .filter(|&id| ids.contains(&backup(id, 0)))
+            .collect::<Vec<_>>();
+        assert_eq!(selected, [2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn id_ranges_match_unique_ids() {
+        let listed = backup(1, 0);
+        let ids: IdRanges = format!("7,{}", listed.uid().unwrap()).parse().unwrap();
+        assert!(ids.contains(&listed));
+        assert!(ids.contains(&backup(7, 0)));
+        assert!(!ids.contains(&backup(2, 0)));
+    }
+
+    #[test]
+    fn id_ranges_reject_invalid_input() {
+        for input in [
+            "", "3,", "12-9", "0", "0-4", "3-", "-3", "1-2-3", "a", "3;5",
+        ] {
+            assert!(input.parse::<IdRanges>().is_err(), "{:?}", input);
+        }
+    }
+
+    /// Backups 1 to 7, made 7 to 1 hours ago.
+    fn hourly_backups() -> Vec<Backup> {
+        (1..=7).map(|id| backup(id, 8 - id as i64)).collect()
+    }
+
+    fn ids(ids: Vec<BackupId>) -> Vec<u32> {
+        ids.into_iter().map(BackupId::get).collect()
+    }
+
+    #[test]
+    fn thin_keeps_most_recent_and_every_nth() {
+        let keep_every = NonZeroUsize::new(3).unwrap();
+        let deleted = Selection::default().thin(&hourly_backups(), keep_every);
+        assert_eq!(ids(deleted), [5, 4, 2, 1]);
+    }
+
+    #[test]
+    fn thin_twice_deletes_nothing_more() {
+        let mut backups = (1..=23)
+            .map(|id| backup(id, 24 - id as i64))
+            .collect::<Vec<_>>();
+        let keep_every = NonZeroUsize::new(5).unwrap();
+        let deleted = Selection::default().thin(&backups, keep_every);
+        backups.retain(|backup| !deleted.contains(&backup.id()));
+        let kept = backups
+            .iter()
+            .map(|backup| backup.id().get())
+            .collect::<Vec<_>>();
+        assert_eq!(kept, [5, 10, 15, 20, 23]);
+        assert!(Selection::default().thin(&backups, keep_every).is_empty());
+    }
+
+    #[test]
+    fn thin_by_one_keeps_everything() {
+        let deleted = Selection::default().thin(&hourly_backups(), NonZeroUsize::MIN);
+        assert!(deleted.is_empty());
+    }
+
+    #[test]
+    fn thin_counts_in_time_order() {
+        let mut backups = hourly_backups();
+        backups.reverse();
+        backups.swap(1, 4);
+        let deleted = Selection::default().thin(&backups, NonZeroUsize::new(2).unwrap());
+        assert_eq!(ids(deleted), [5, 3, 1]);
+    }
+
+    #[test]
+    fn thin_keeps_most_recent_in_window() {
+        let window = Selection {
+            before: Some(Cutoff(Utc::now() - Duration::minutes(210))),
+            after: Some(Cutoff(Utc::now() - Duration::minutes(390))),
+            ..Default::default()
+        };
+        // backups 2 to 4, made 6 to 4 hours ago, are in the window
+        let deleted = window.thin(&hourly_backups(), NonZeroUsize::new(3).unwrap());
+        assert_eq!(ids(deleted), [2]);
+    }
+
+    #[test]
+    fn cutoffs_parse_dates_and_timestamps() {
+        let Cutoff(time) = "2024-05-01T18:30:00+02:00".parse().unwrap();
+        assert_eq!(time.to_rfc3339(), "2024-05-01T16:30:00+00:00");
+        let Cutoff(midnight) = " 2024-05-01 ".parse().unwrap();
+        let local = midnight.with_timezone(&Local);
+        assert_eq!(local.format("%F %T").to_string(), "2024-05-01 00:00:00");
+    }
+
+    #[test]
+    fn cutoffs_reject_invalid_input() {
+        for input in [
+            "",
+            "yesterday",
+            "2024-13-01",
+            "2024-02-30",
+            "01.05.2024",
+            "2024-05-01T18:30",
+        ] {
+            assert!(input.parse::<Cutoff>().is_err(), "{:?}", input);
+        }
+    }
+}
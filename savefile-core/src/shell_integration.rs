@@ -0,0 +1,164 @@
+/// Context-menu entries in Windows Explorer.
+///
+/// Entries are registered per user under `HKEY_CURRENT_USER`, one verb per
+/// profile and action, and only show up for the profile's base directory.
+/// Choosing an entry runs the CLI with the matching arguments.
+use std::path::{Path, PathBuf};
+
+use crate::{error::Result, profile::ProfileName};
+
+/// Registry key holding context-menu verbs for directories.
+#[cfg(windows)]
+const SHELL_KEY: &str = r"Software\Classes\Directory\shell";
+
+/// Prefix of the verbs registered by savefile, used to find them again.
+#[cfg_attr(not(windows), allow(dead_code))]
+const VERB_PREFIX: &str = "savefile.";
+
+/// Action performed by a context-menu entry.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuAction {
+    /// Create a backup of the profile.
+    Backup,
+    /// Restore the latest backup of the profile.
+    RestoreLatest,
+}
+
+impl MenuAction {
+    /// All supported actions, in menu order.
+    pub const ALL: [MenuAction; 2] = [MenuAction::Backup, MenuAction::RestoreLatest];
+
+    /// Returns the CLI arguments that perform the action on the given profile.
+    pub fn args(self, profile: &ProfileName) -> Vec<String> {
+        let args: &[&str] = match self {
+            MenuAction::Backup => &["backup", "create", "--name"],
+            MenuAction::RestoreLatest => &["backup", "restore", "--name"],
+        };
+        args.iter()
+            .map(|arg| arg.to_string())
+            .chain([profile.to_string()])
+            .collect()
+    }
+
+    /// Returns a short identifier used in the verb name.
+    #[cfg_attr(not(windows), allow(dead_code))]
+    fn id(self) -> &'static str {
+        match self {
+            MenuAction::Backup => "backup",
+            MenuAction::RestoreLatest => "restore",
+        }
+    }
+}
+
+/// A single context-menu entry.
+#[derive(Clone, Debug)]
+pub struct MenuEntry {
+    pub profile: ProfileName,
+    /// Directory the entry is shown for.
+    pub folder: PathBuf,
+    pub action: MenuAction,
+    /// Text shown in the menu.
+    pub label: String,
+}
+
+#[cfg_attr(not(windows), allow(dead_code))]
+impl MenuEntry {
+    /// Returns the name of the registry key for this entry.
+    fn verb(&self) -> String {
+        format!("{}{}.{}", VERB_PREFIX, self.profile, self.action.id())
+    }
+
+    /// Returns the command line run by Explorer when the entry is chosen.
+    fn command(&self, exe: &Path) -> String {
+        std::iter::once(exe.display().to_string())
+            .chain(self.action.args(&self.profile))
+            .map(|arg| format!("\"{}\"", arg))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Returns the condition restricting the entry to its folder.
+    fn applies_to(&self) -> String {
+        format!("System.ItemPathDisplay:=\"{}\"", self.folder.display())
+    }
+}
+
+/// Register the given entries, replacing all previously registered ones.
+///
+/// `exe` is the savefile executable that the entries run.
+#[cfg(windows)]
+pub fn install(exe: &Path, entries: &[MenuEntry]) -> Result<()> {
+    use crate::error::IoResultExt;
+    use winreg::{enums::HKEY_CURRENT_USER, RegKey};
+
+    uninstall()?;
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    for entry in entries {
+        let path = format!(r"{}\{}", SHELL_KEY, entry.verb());
+        let context = || format!("failed registering context-menu entry {}", path);
+        let (key, _) = hkcu.create_subkey(&path).context(context)?;
+        key.set_value("MUIVerb", &entry.label).context(context)?;
+        key.set_value("AppliesTo", &entry.applies_to())
+            .context(context)?;
+        key.set_value("Icon", &exe.display().to_string())
+            .context(context)?;
+        let (command, _) = key.create_subkey("command").context(context)?;
+        command
+            .set_value("", &entry.command(exe))
+            .context(context)?;
+    }
+    Ok(())
+}
+
+/// Remove all registered entries.
+///
+/// Returns the number of entries removed.
+#[cfg(windows)]
+pub fn uninstall() -> Result<usize> {
+    use crate::error::IoResultExt;
+    use winreg::{
+        enums::{HKEY_CURRENT_USER, KEY_READ, KEY_WRITE},
+        RegKey,
+    };
+
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+    let shell = match hkcu.open_subkey_with_flags(SHELL_KEY, KEY_READ | KEY_WRITE) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        other => other.context(|| format!("failed opening registry key {}", SHELL_KEY))?,
+    };
+    let verbs = shell
+        .enum_keys()
+        .filter_map(|key| key.ok())
+        .filter(|key| key.starts_with(VERB_PREFIX))
+        .collect::<Vec<_>>();
+    for verb in &verbs {
+        shell
+            .delete_subkey_all(verb)
+            .context(|| format!(r"failed removing registry key {}\{}", SHELL_KEY, verb))?;
+    }
+    Ok(verbs.len())
+}
+
+/// Register the given entries, replacing all previously registered ones.
+///
+/// Context-menu integration is only supported on Windows.
+#[cfg(not(windows))]
+pub fn install(_exe: &Path, _entries: &[MenuEntry]) -> Result<()> {
+    Err(unsupported())?
+}
+
+/// Remove all registered entries.
+///
+/// Context-menu integration is only supported on Windows.
+#[cfg(not(windows))]
+pub fn uninstall() -> Result<usize> {
+    Err(unsupported())?
+}
+
+#[cfg(not(windows))]
+fn unsupported() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "shell integration is not supported on this platform",
+    )
+}
@@ -0,0 +1,75 @@
+use std::{fmt, str::FromStr};
+
+/// A size in bytes, written in profiles as a number or a string like `"500MB"`.
+///
+/// Units are binary multiples (`1KB` = 1024 bytes) and case-insensitive.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ByteSize(pub u64);
+
+/// Supported units, largest first.
+const UNITS: [(&str, u64); 5] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+    ("B", 1),
+];
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let upper = s.to_ascii_uppercase();
+        let (number, multiplier) = UNITS
+            .iter()
+            .find_map(|(unit, multiplier)| {
+                upper
+                    .strip_suffix(unit)
+                    .map(|number| (number.trim(), *multiplier))
+            })
+            .unwrap_or((upper.as_str(), 1));
+        let number: f64 = number
+            .parse()
+            .map_err(|_| format!("invalid size: {:?}", s))?;
+        if number < 0.0 {
+            return Err(format!("invalid size: {:?}", s));
+        }
+        Ok(Self((number * multiplier as f64) as u64))
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (unit, multiplier) = UNITS
+            .iter()
+            .find(|(_, multiplier)| self.0 >= *multiplier)
+            .unwrap_or(&("B", 1));
+        if *multiplier == 1 {
+            write!(f, "{}B", self.0)
+        } else {
+            write!(f, "{:.1}{}", self.0 as f64 / *multiplier as f64, unit)
+        }
+    }
+}
+
+impl serde::Serialize for ByteSize {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for ByteSize {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Bytes(u64),
+            Text(String),
+        }
+        match Repr::deserialize(deserializer)? {
+            Repr::Bytes(bytes) => Ok(Self(bytes)),
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+        }
+    }
+}
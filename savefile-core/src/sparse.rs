@@ -0,0 +1,77 @@
+/// Copying sparse files without filling in their holes.
+///
+/// Some engines preallocate large save files that are mostly empty, which the
+/// filesystem stores sparsely. A plain copy writes out every zero, so a backup
+/// of a 4GB sparse save takes 4GB on disk. Copies of sparse files skip runs
+/// of zeros instead, leaving holes in the copy as well.
+use std::{fs::File, io};
+
+/// Size of the runs of zeros left as holes in copies.
+pub(crate) const BLOCK_SIZE: usize = 4096;
+
+/// Returns `true` if the file takes less space on disk than its length,
+/// because parts of it are holes.
+#[cfg(unix)]
+pub(crate) fn is_sparse(meta: &std::fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+
+    // blocks are always counted in units of 512 bytes
+    meta.is_file() && meta.blocks() * 512 < meta.len()
+}
+
+/// Returns `true` if the file is marked as sparse.
+#[cfg(windows)]
+pub(crate) fn is_sparse(meta: &std::fs::Metadata) -> bool {
+    use std::os::windows::fs::MetadataExt;
+
+    const FILE_ATTRIBUTE_SPARSE_FILE: u32 = 0x200;
+    meta.is_file() && meta.file_attributes() & FILE_ATTRIBUTE_SPARSE_FILE != 0
+}
+
+/// Returns `true` if the file is sparse.
+///
+/// Sparse files aren't detected on this platform.
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn is_sparse(_meta: &std::fs::Metadata) -> bool {
+    false
+}
+
+/// Prepare an empty file for holes to be left in it.
+///
+/// On Windows, the file must be marked as sparse, otherwise seeking past the
+/// end fills the gap with zeros. Other platforms leave holes in any file.
+#[cfg(windows)]
+pub(crate) fn mark_sparse(file: &File) -> io::Result<()> {
+    use std::os::windows::io::AsRawHandle;
+
+    use windows_sys::Win32::System::{Ioctl::FSCTL_SET_SPARSE, IO::DeviceIoControl};
+
+    let mut returned = 0;
+    // SAFETY: the handle is open for the lifetime of `file`, and the request
+    // has no input or output buffers
+    let ok = unsafe {
+        DeviceIoControl(
+            file.as_raw_handle() as _,
+            FSCTL_SET_SPARSE,
+            std::ptr::null(),
+            0,
+            std::ptr::null_mut(),
+            0,
+            &mut returned,
+            std::ptr::null_mut(),
+        )
+    };
+    if ok == 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Prepare an empty file for holes to be left in it.
+///
+/// On Windows, the file must be marked as sparse, otherwise seeking past the
+/// end fills the gap with zeros. Other platforms leave holes in any file.
+#[cfg(not(windows))]
+pub(crate) fn mark_sparse(_file: &File) -> io::Result<()> {
+    Ok(())
+}
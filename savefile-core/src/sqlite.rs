@@ -0,0 +1,573 @@
+/// Metadata store backed by SQLite, used when the `sqlite` feature is enabled.
+///
+/// Each profile has a backup table named after it and a file manifest table
+/// named `<profile>:files`.
+use std::path::Path;
+
+use rusqlite::{params, Connection, OptionalExtension, Row};
+
+#[cfg(feature = "sqlcipher")]
+use crate::secret;
+use crate::{
+    backup::{Backup, BackupCause, BackupId, BackupStatus, FileRecord, Timestamp},
+    config::Config,
+    database::{BackupField, MetadataStore},
+    error::Result,
+};
+
+/// A [`MetadataStore`] keeping backup metadata in an SQLite database.
+pub struct SqliteStore(Connection);
+
+impl SqliteStore {
+    /// Open the database at the given path, creating it if it does not exist.
+    ///
+    /// If the config sets a `database_key`, or the keyring holds the
+    /// [`secret::DATABASE_KEY`] secret, the database is encrypted with it, and
+    /// an unencrypted database is encrypted first.
+    pub fn open(path: &Path) -> Result<Self> {
+        let key = Config::load()?.database_key;
+        #[cfg(feature = "sqlcipher")]
+        let key = key.or_else(keyring_database_key);
+        #[cfg(feature = "sqlcipher")]
+        if let Some(key) = &key {
+            encrypt_plaintext(path, key)?;
+        }
+        let connection = Connection::open(path)?;
+        if let Some(key) = &key {
+            connection.pragma_update(None, "key", key)?;
+        }
+        Ok(Self(connection))
+    }
+
+    /// Create a store using the given connection.
+    pub fn new(connection: Connection) -> Self {
+        Self(connection)
+    }
+
+    /// Returns the underlying connection.
+    pub fn connection(&self) -> &Connection {
+        &self.0
+    }
+
+    /// Create the backup table if it does not exist.
+    fn create_backup_table(&self, profile: &str) -> Result<()> {
+        let sql = &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                id INTEGER PRIMARY KEY,
+                tag TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                dir TEXT,
+                version TEXT,
+                status TEXT,
+                cause TEXT,
+                changed INTEGER,
+                env TEXT,
+                uid TEXT
+            )",
+            quote_identifier(profile)
+        );
+        self.0.execute(sql, params![])?;
+        for (column, kind) in [
+            ("dir", "TEXT"),
+            ("version", "TEXT"),
+            ("status", "TEXT"),
+            ("cause", "TEXT"),
+            ("changed", "INTEGER"),
+            ("env", "TEXT"),
+            ("uid", "TEXT"),
+        ] {
+            self.add_column(profile, column, kind)?;
+        }
+        Ok(())
+    }
+
+    /// Add a nullable column of the given SQL type to backup tables created by
+    /// older versions.
+    ///
+    /// Rows without a directory name use their ID as the name, rows without a
+    /// version have none recorded, rows without a status are unchecked, rows
+    /// without a cause have an unknown one, and rows without a changed-file
+    /// count, environment or unique ID have none recorded.
+    fn add_column(&self, profile: &str, column: &str, kind: &str) -> Result<()> {
+        let exists: bool = self.0.query_row(
+            "SELECT count(*) > 0 FROM pragma_table_info(?) WHERE name = ?",
+            params![profile, column],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            let sql = format!(
+                "ALTER TABLE {} ADD COLUMN {} {}",
+                quote_identifier(profile),
+                quote_identifier(column),
+                kind
+            );
+            self.0.execute(&sql, params![])?;
+        }
+        Ok(())
+    }
+
+    /// Create the file manifest table if it does not exist.
+    fn create_file_table(&self, profile: &str) -> Result<()> {
+        let sql = &format!(
+            "CREATE TABLE IF NOT EXISTS {} (
+                backup_id INTEGER NOT NULL,
+                path TEXT NOT NULL,
+                hash TEXT NOT NULL,
+                size INTEGER NOT NULL,
+                changed INTEGER NOT NULL,
+                triggered INTEGER NOT NULL,
+                PRIMARY KEY (backup_id, path)
+            )",
+            files_table(profile)
+        );
+        self.0.execute(sql, params![])?;
+        // speeds up following a single file through the backups
+        let sql = format!(
+            "CREATE INDEX IF NOT EXISTS {} ON {} (path)",
+            quote_identifier(&format!("{}:files:path", profile)),
+            files_table(profile)
+        );
+        self.0.execute(&sql, params![])?;
+        Ok(())
+    }
+
+    /// Insert the manifest of a backup, replacing records with the same path,
+    /// as part of the caller's transaction.
+    fn insert_file_rows(
+        &self,
+        profile: &str,
+        backup_id: BackupId,
+        files: &[FileRecord],
+    ) -> Result<()> {
+        let sql = format!(
+            "INSERT OR REPLACE INTO {} (backup_id, path, hash, size, changed, triggered)
+             VALUES (?, ?, ?, ?, ?, ?)",
+            files_table(profile)
+        );
+        let mut stmt = self.0.prepare(&sql)?;
+        for file in files {
+            stmt.execute(params![
+                backup_id,
+                file.path.to_string_lossy(),
+                file.hash,
+                file.size,
+                file.changed,
+                file.triggered,
+            ])?;
+        }
+        Ok(())
+    }
+
+    /// Returns the last inserted ID.
+    fn last_id(&self) -> Result<BackupId> {
+        let rowid = self.0.last_insert_rowid();
+        let id =
+            u32::try_from(rowid).map_err(|_| rusqlite::Error::IntegralValueOutOfRange(0, rowid))?;
+        Ok(BackupId::new(id)?)
+    }
+}
+
+impl MetadataStore for SqliteStore {
+    fn backup_count(&self, profile: &str) -> Result<Option<usize>> {
+        let exists: bool = self.0.query_row(
+            "SELECT count(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = ?",
+            params![profile],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Ok(None);
+        }
+        let sql = format!("SELECT count(*) FROM {}", quote_identifier(profile));
+        let count: i64 = self.0.query_row(&sql, params![], |row| row.get(0))?;
+        Ok(Some(count as usize))
+    }
+
+    fn create_tables(&self, profile: &str) -> Result<()> {
+        self.create_backup_table(profile)?;
+        self.create_file_table(profile)
+    }
+
+    fn insert_backup(&self, profile: &str, tag: &str, timestamp: &Timestamp) -> Result<Backup> {
+        let sql = format!(
+            "INSERT INTO {} (tag, timestamp) VALUES (?, ?)",
+            quote_identifier(profile)
+        );
+        self.0.execute(&sql, params![tag, timestamp])?;
+        Ok(Backup::new(
+            self.last_id()?,
+            tag.to_owned(),
+            timestamp.to_owned(),
+            None,
+            None,
+            BackupStatus::Unchecked,
+            BackupCause::Unknown,
+            None,
+            None,
+            None,
+        ))
+    }
+
+    fn put_backup(&self, profile: &str, backup: &Backup) -> Result<()> {
+        let sql = format!(
+            "INSERT OR REPLACE INTO {} (id, tag, timestamp, dir, version, status, cause, changed, env, uid)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            quote_identifier(profile)
+        );
+        self.0.execute(
+            &sql,
+            params![
+                backup.id(),
+                backup.raw_tag(),
+                backup.timestamp(),
+                backup.raw_dir(),
+                backup.version(),
+                backup.status(),
+                backup.cause(),
+                backup.changed_files(),
+                backup.environment(),
+                backup.uid(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn update_backup(&self, profile: &str, id: BackupId, field: BackupField<'_>) -> Result<()> {
+        let (column, value): (_, &dyn rusqlite::ToSql) = match &field {
+            BackupField::Dir(dir) => ("dir", dir),
+            BackupField::Version(version) => ("version", version),
+            BackupField::Status(status) => ("status", status),
+            BackupField::Cause(cause) => ("cause", cause),
+            BackupField::Changed(count) => ("changed", count),
+            BackupField::Environment(env) => ("env", env),
+            BackupField::Uid(uid) => ("uid", uid),
+        };
+        let sql = format!(
+            "UPDATE {} SET {} = ? WHERE id = ?",
+            quote_identifier(profile),
+            column
+        );
+        self.0.execute(&sql, params![value, id])?;
+        Ok(())
+    }
+
+    fn select_backup(&self, profile: &str, id: BackupId) -> Result<Option<Backup>> {
+        let sql = format!(
+            "SELECT id, tag, timestamp, dir, version, status, cause, changed, env, uid FROM {} WHERE id = ?",
+            quote_identifier(profile)
+        );
+        let backup = self
+            .0
+            .query_row(&sql, params![id], backup_from_row)
+            .optional()?;
+        Ok(backup)
+    }
+
+    fn select_all(&self, profile: &str) -> Result<Vec<Backup>> {
+        let sql = format!(
+            "SELECT id, tag, timestamp, dir, version, status, cause, changed, env, uid FROM {}",
+            quote_identifier(profile)
+        );
+        let mut stmt = self.0.prepare(&sql)?;
+        let backups = stmt
+            .query_map(params![], backup_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(backups)
+    }
+
+    fn select_page(
+        &self,
+        profile: &str,
+        after: Option<BackupId>,
+        limit: usize,
+    ) -> Result<Vec<Backup>> {
+        let sql = format!(
+            "SELECT id, tag, timestamp, dir, version, status, cause, changed, env, uid FROM {} WHERE id > ? ORDER BY id LIMIT ?",
+            quote_identifier(profile)
+        );
+        let mut stmt = self.0.prepare(&sql)?;
+        let after = after.map_or(0, |id| i64::from(id.get()));
+        let backups = stmt
+            .query_map(params![after, limit as i64], backup_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(backups)
+    }
+
+    fn previous_id(&self, profile: &str, id: BackupId) -> Result<Option<BackupId>> {
+        let sql = format!(
+            "SELECT max(id) FROM {} WHERE id < ?",
+            quote_identifier(profile)
+        );
+        let prev = self.0.query_row(&sql, params![id], |row| row.get(0))?;
+        Ok(prev)
+    }
+
+    fn latest_valid(&self, profile: &str) -> Result<Option<Backup>> {
+        let sql = format!(
+            "SELECT id, tag, timestamp, dir, version, status, cause, changed, env, uid FROM {}
+             WHERE status = ? ORDER BY timestamp DESC, id DESC LIMIT 1",
+            quote_identifier(profile)
+        );
+        let backup = self
+            .0
+            .query_row(&sql, params![BackupStatus::Valid], backup_from_row)
+            .optional()?;
+        Ok(backup)
+    }
+
+    fn latest(&self, profile: &str) -> Result<Option<Backup>> {
+        let sql = format!(
+            "SELECT id, tag, timestamp, dir, version, status, cause, changed, env, uid FROM {}
+             ORDER BY timestamp DESC, id DESC LIMIT 1",
+            quote_identifier(profile)
+        );
+        let backup = self
+            .0
+            .query_row(&sql, params![], backup_from_row)
+            .optional()?;
+        Ok(backup)
+    }
+
+    fn total_size(&self, profile: &str) -> Result<u64> {
+        let sql = format!(
+            "SELECT coalesce(sum(size), 0) FROM {}",
+            files_table(profile)
+        );
+        let total: i64 = self.0.query_row(&sql, params![], |row| row.get(0))?;
+        Ok(total as u64)
+    }
+
+    fn remove_backup(&self, profile: &str, id: BackupId) -> Result<()> {
+        let sql = format!("DELETE FROM {} WHERE id = ?", quote_identifier(profile));
+        self.0.execute(&sql, params![id])?;
+        Ok(())
+    }
+
+    fn put_backups(&self, profile: &str, backups: &[Backup]) -> Result<()> {
+        let tx = self.0.unchecked_transaction()?;
+        for backup in backups {
+            self.put_backup(profile, backup)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn remove_backups(&self, profile: &str, ids: &[BackupId]) -> Result<()> {
+        let tx = self.0.unchecked_transaction()?;
+        {
+            let mut backups = tx.prepare(&format!(
+                "DELETE FROM {} WHERE id = ?",
+                quote_identifier(profile)
+            ))?;
+            let mut files = tx.prepare(&format!(
+                "DELETE FROM {} WHERE backup_id = ?",
+                files_table(profile)
+            ))?;
+            for id in ids {
+                backups.execute(params![id])?;
+                files.execute(params![id])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn drop_backups(&self, profile: &str) -> Result<()> {
+        let sql = format!("DROP TABLE IF EXISTS {}", quote_identifier(profile));
+        self.0.execute(&sql, params![])?;
+        Ok(())
+    }
+
+    fn insert_files(&self, profile: &str, backup_id: BackupId, files: &[FileRecord]) -> Result<()> {
+        let tx = self.0.unchecked_transaction()?;
+        self.insert_file_rows(profile, backup_id, files)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn put_backups_with_files(
+        &self,
+        profile: &str,
+        backups: &[(Backup, Vec<FileRecord>)],
+    ) -> Result<()> {
+        let tx = self.0.unchecked_transaction()?;
+        for (backup, files) in backups {
+            self.put_backup(profile, backup)?;
+            self.insert_file_rows(profile, backup.id(), files)?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn select_files(&self, profile: &str, backup_id: BackupId) -> Result<Vec<FileRecord>> {
+        let sql = format!(
+            "SELECT path, hash, size, changed, triggered FROM {}
+             WHERE backup_id = ? ORDER BY path",
+            files_table(profile)
+        );
+        let mut stmt = self.0.prepare(&sql)?;
+        let files = stmt
+            .query_map(params![backup_id], |row| {
+                Ok(FileRecord {
+                    path: row.get::<_, String>(0)?.into(),
+                    hash: row.get(1)?,
+                    size: row.get(2)?,
+                    changed: row.get(3)?,
+                    triggered: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(files)
+    }
+
+    fn select_file_history(&self, profile: &str, path: &Path) -> Result<Vec<(Backup, FileRecord)>> {
+        let sql = format!(
+            "SELECT b.id, b.tag, b.timestamp, b.dir, b.version, b.status, b.cause, b.changed, b.env, b.uid,
+                    f.path, f.hash, f.size, f.changed, f.triggered
+             FROM {} f JOIN {} b ON b.id = f.backup_id
+             WHERE f.path = ? ORDER BY b.timestamp, b.id",
+            files_table(profile),
+            quote_identifier(profile)
+        );
+        let mut stmt = self.0.prepare(&sql)?;
+        let history = stmt
+            .query_map(params![path.to_string_lossy()], |row| {
+                let file = FileRecord {
+                    path: row.get::<_, String>(10)?.into(),
+                    hash: row.get(11)?,
+                    size: row.get(12)?,
+                    changed: row.get(13)?,
+                    triggered: row.get(14)?,
+                };
+                Ok((backup_from_row(row)?, file))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(history)
+    }
+
+    fn remove_files(&self, profile: &str, backup_id: BackupId) -> Result<()> {
+        let sql = format!("DELETE FROM {} WHERE backup_id = ?", files_table(profile));
+        self.0.execute(&sql, params![backup_id])?;
+        Ok(())
+    }
+
+    fn drop_files(&self, profile: &str) -> Result<()> {
+        let sql = format!("DROP TABLE IF EXISTS {}", files_table(profile));
+        self.0.execute(&sql, params![])?;
+        Ok(())
+    }
+
+    fn connection(&self) -> Option<&Connection> {
+        Some(&self.0)
+    }
+
+    fn rewrite_naive_timestamps(&self, profile: &str, dry_run: bool) -> Result<usize> {
+        if self.backup_count(profile)?.is_none() {
+            return Ok(0);
+        }
+        let table = quote_identifier(profile);
+        let mut stmt = self
+            .0
+            .prepare(&format!("SELECT id, timestamp FROM {}", table))?;
+        let naive = stmt
+            .query_map(params![], |row| {
+                Ok((row.get::<_, BackupId>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+            .into_iter()
+            .filter(|(_, text)| chrono::DateTime::parse_from_str(text, "%F %T%.f%:z").is_err())
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        if !dry_run {
+            let tx = self.0.unchecked_transaction()?;
+            for id in &naive {
+                // reading a naive timestamp yields the same instant in UTC
+                let timestamp: Timestamp = tx.query_row(
+                    &format!("SELECT timestamp FROM {} WHERE id = ?", table),
+                    params![id],
+                    |row| row.get(0),
+                )?;
+                tx.execute(
+                    &format!("UPDATE {} SET timestamp = ? WHERE id = ?", table),
+                    params![timestamp, id],
+                )?;
+            }
+            tx.commit()?;
+        }
+        Ok(naive.len())
+    }
+}
+
+/// Returns the quoted name of the file manifest table for a profile.
+///
+/// Profile names cannot contain `:`, so this never collides with a backup table.
+fn files_table(profile: &str) -> String {
+    quote_identifier(&format!("{}:files", profile))
+}
+
+/// Map a row of `(id, tag, timestamp, dir, version, status, cause, changed, env,
+/// uid)` to a backup.
+fn backup_from_row(row: &Row) -> rusqlite::Result<Backup> {
+    Ok(Backup::new(
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+        row.get(8)?,
+        row.get(9)?,
+    ))
+}
+
+/// Quote an SQL identifier, escaping any embedded quotes.
+fn quote_identifier(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Returns the database key stored in the keyring, which is looked up once per process.
+#[cfg(feature = "sqlcipher")]
+pub(crate) fn keyring_database_key() -> Option<String> {
+    static KEY: std::sync::OnceLock<Option<String>> = std::sync::OnceLock::new();
+    KEY.get_or_init(|| secret::lookup(secret::DATABASE_KEY))
+        .clone()
+}
+
+/// Encrypt the unencrypted database at `path` with the given key, if there is one.
+///
+/// The contents are exported to a new, encrypted file which then replaces the
+/// original.
+#[cfg(feature = "sqlcipher")]
+fn encrypt_plaintext(path: &Path, key: &str) -> Result<()> {
+    use std::io::Read;
+
+    use crate::error::IoResultExt;
+
+    /// Header of unencrypted SQLite databases.
+    const PLAINTEXT_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+    let mut header = [0; 16];
+    let is_plaintext = std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut header))
+        .is_ok_and(|()| &header == PLAINTEXT_HEADER);
+    if !is_plaintext {
+        return Ok(());
+    }
+    let encrypted = path.with_extension("db.encrypting");
+    let _ = std::fs::remove_file(&encrypted);
+    let connection = Connection::open(path)?;
+    connection.execute(
+        "ATTACH DATABASE ?1 AS encrypted KEY ?2",
+        params![encrypted.to_string_lossy(), key],
+    )?;
+    connection.query_row(
+        "SELECT sqlcipher_export('encrypted')",
+        params![],
+        |_| Ok(()),
+    )?;
+    connection.execute("DETACH DATABASE encrypted", params![])?;
+    drop(connection);
+    std::fs::rename(&encrypted, path).with_path("replacing", path)?;
+    Ok(())
+}
@@ -0,0 +1,119 @@
+/// Server-sent event stream of watcher events, for web dashboards and stream
+/// overlays.
+///
+/// `GET /events` streams each [`WatchEvent`](crate::events::WatchEvent) as a
+/// JSON `data:` line, after one `snapshot` event per profile with its latest
+/// backup. `?profile=<name>` (which may be repeated) limits the stream to the
+/// given profiles.
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream},
+    sync::mpsc::RecvTimeoutError,
+    time::Duration,
+};
+
+use crate::{
+    error::{IoResultExt, Result},
+    events,
+    profile::{list_profile_names, ProfileName},
+    Database,
+};
+
+/// Interval of comments sent to keep idle connections open.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+/// Maximum size of a request head.
+const MAX_REQUEST_LEN: usize = 8 * 1024;
+
+/// Start serving on the given address in the background.
+///
+/// Fails if the address can't be bound.
+pub fn serve(addr: SocketAddr) -> Result<()> {
+    let listener = TcpListener::bind(addr).context(|| format!("failed listening on {}", addr))?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            std::thread::spawn(move || {
+                // the client went away
+                let _ = handle(stream);
+            });
+        }
+    });
+    Ok(())
+}
+
+/// Answer a single request.
+fn handle(mut stream: TcpStream) -> std::io::Result<()> {
+    let target = read_request_target(&stream)?;
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    if path != "/events" {
+        return stream.write_all(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        );
+    }
+    let profiles = query
+        .split('&')
+        .filter_map(|pair| pair.strip_prefix("profile="))
+        .map(str::to_owned)
+        .collect::<Vec<_>>();
+    let wanted = |profile: &str| profiles.is_empty() || profiles.iter().any(|p| p == profile);
+    // subscribe first, so that no event is missed while sending the snapshot
+    let events = events::subscribe();
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\n\
+          Content-Type: text/event-stream\r\n\
+          Cache-Control: no-cache\r\n\
+          Access-Control-Allow-Origin: *\r\n\
+          Connection: keep-alive\r\n\r\n",
+    )?;
+    for name in list_profile_names().unwrap_or_default() {
+        if wanted(&name) {
+            let snapshot = snapshot(&name);
+            write!(stream, "data: {}\n\n", snapshot)?;
+        }
+    }
+    stream.flush()?;
+    loop {
+        match events.recv_timeout(KEEPALIVE_INTERVAL) {
+            Ok(event) if wanted(&event.profile) => {
+                let json = serde_json::to_string(&event).expect("events are serializable");
+                write!(stream, "data: {}\n\n", json)?;
+            }
+            Ok(_) => continue,
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": keepalive\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+/// Read the request head and return its target, e.g. `/events?profile=a`.
+fn read_request_target(stream: &TcpStream) -> std::io::Result<String> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST_LEN as u64));
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    // skip the headers
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && !line.trim_end().is_empty() {
+        line.clear();
+    }
+    let mut parts = request_line.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => Ok(target.to_owned()),
+        _ => Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            "unsupported request",
+        )),
+    }
+}
+
+/// Describe the latest backup of a profile, as JSON.
+fn snapshot(name: &ProfileName) -> serde_json::Value {
+    let latest = Database::open_for_profile(name)
+        .and_then(|db| db.backup_table(name)?.latest())
+        .ok()
+        .flatten();
+    serde_json::json!({
+        "event": "snapshot",
+        "profile": name.as_str(),
+        "latest": latest,
+    })
+}
@@ -0,0 +1,61 @@
+/// Status file updated after each backup, for display in streaming software
+/// such as OBS text sources.
+use std::path::{Path, PathBuf};
+
+use crate::{
+    config::StatusFile,
+    error::{IoResultExt, Result},
+    profile::ProfileName,
+    template, Backup,
+};
+
+/// Placeholders available in [`StatusFile::template`].
+pub(crate) const PLACEHOLDERS: [&str; 6] = ["profile", "id", "time", "tag", "version", "files"];
+
+/// Check that a template only uses known placeholders and valid time formats.
+pub(crate) fn validate_template(template: &str) -> Result<(), String> {
+    template::validate(template, &PLACEHOLDERS)
+}
+
+/// Write the status file for a finished backup of `files` files.
+pub(crate) fn write(
+    status: &StatusFile,
+    name: &ProfileName,
+    backup: &Backup,
+    files: usize,
+) -> Result<()> {
+    let contents = match &status.template {
+        Some(template) => template::render(template, |placeholder, format| {
+            Ok(match placeholder {
+                "profile" => name.to_string(),
+                "id" => backup.id().to_string(),
+                "time" => template::format_time(&backup.timestamp(), format),
+                "tag" => backup.tag().unwrap_or_default().to_owned(),
+                "version" => backup.version().unwrap_or_default().to_owned(),
+                "files" => files.to_string(),
+                _ => unreachable!("templates are validated when the config is loaded"),
+            })
+        })
+        .expect("templates are validated when the config is loaded"),
+        None => serde_json::json!({
+            "profile": name.as_str(),
+            "id": backup.id(),
+            "time": backup.timestamp(),
+            "tag": backup.tag(),
+            "version": backup.version(),
+            "files": files,
+        })
+        .to_string(),
+    };
+    write_atomic(&status.path, &contents)
+}
+
+/// Replace the file's contents at once, so that readers never see a partial file.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    std::fs::write(&tmp, contents).with_path("writing", &tmp)?;
+    std::fs::rename(&tmp, path).with_path("replacing", path)?;
+    Ok(())
+}
@@ -0,0 +1,594 @@
+/// Copies of backups kept outside the save directory, such as on a NAS.
+///
+/// A profile's `remotes` list the stores each new backup is copied to. Deleting
+/// backups locally would leave these copies behind, so deletes of backups with
+/// remote copies are planned first with [`plan_delete`], and the copies are
+/// then removed with [`delete_remote_copies`]. [`verify_remotes`] checks the
+/// copies against the backups' manifests without downloading them, using the
+/// hashes the stores keep.
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use sha2::{Digest, Sha256};
+
+use crate::{
+    backup::{remove_backup_dir, set_tree_readonly},
+    copy::CopyEngine,
+    database::Database,
+    error::{BackupError, IoResultExt, Result},
+    filesystem::{hash_file, walk_files},
+    profile::{Profile, ProfileName},
+    Backup, BackupId,
+};
+
+/// A place that holds copies of backups.
+pub trait RemoteStore: Send + Sync {
+    /// Describe the store for reports, e.g. `dir:/mnt/nas/saves`.
+    fn describe(&self) -> String;
+
+    /// Returns `true` if the store holds a copy of the backup.
+    fn contains(&self, profile: &ProfileName, backup: &Backup) -> Result<bool>;
+
+    /// Copy a backup from its local directory into the store.
+    fn put(&self, profile: &ProfileName, backup: &Backup, dir: &Path) -> Result<()>;
+
+    /// Copy the files of a backup from the store into `dest`.
+    fn fetch(&self, profile: &ProfileName, backup: &Backup, dest: &Path) -> Result<()>;
+
+    /// Delete the copy of a backup. Deleting a missing copy succeeds.
+    fn delete(&self, profile: &ProfileName, backup: &Backup) -> Result<()>;
+
+    /// List the files of a backup's copy with their sizes and the hashes the
+    /// store keeps, without reading their contents.
+    ///
+    /// Returns `None` if the store can't list files, which is the default.
+    fn objects(&self, profile: &ProfileName, backup: &Backup) -> Result<Option<Vec<RemoteObject>>> {
+        let _ = (profile, backup);
+        Ok(None)
+    }
+}
+
+/// A file of a backup's copy in a remote store, see [`RemoteStore::objects`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RemoteObject {
+    /// Path relative to the backup directory.
+    pub path: PathBuf,
+    /// Size in bytes.
+    pub size: u64,
+    /// SHA-256 hash of the contents, hex-encoded, if the store keeps one.
+    pub hash: Option<String>,
+}
+
+/// A store in a profile's `remotes`.
+///
+/// In profiles, stores are written as e.g. `{ "type": "dir", "path": "//nas/saves" }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Remote {
+    /// A directory, e.g. on another drive or a network share, holding the
+    /// backups in the same layout as the save directory.
+    Dir { path: PathBuf },
+}
+
+impl Remote {
+    /// Returns the store this refers to.
+    pub fn open(&self) -> Box<dyn RemoteStore> {
+        match self {
+            Remote::Dir { path } => Box::new(DirStore::new(path)),
+        }
+    }
+}
+
+/// A store in a local directory, holding `<profile>/<backup directory>`.
+///
+/// Next to each copy, a `<backup directory>.sha256` file lists the SHA-256
+/// hashes of the copied files in the format of `sha256sum`, so that copies can
+/// be verified from their listing.
+pub struct DirStore {
+    root: PathBuf,
+}
+
+impl DirStore {
+    /// Create a store in the given directory.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Returns the directory of a backup's copy.
+    fn dir(&self, profile: &ProfileName, backup: &Backup) -> PathBuf {
+        self.root.join(profile).join(backup.dir_name())
+    }
+
+    /// Returns the file listing the hashes of a backup's copy.
+    fn hashes_path(&self, profile: &ProfileName, backup: &Backup) -> PathBuf {
+        self.root
+            .join(profile)
+            .join(format!("{}.sha256", backup.dir_name()))
+    }
+
+    /// Read the hashes of a backup's copy, by path relative to the copy.
+    ///
+    /// Copies made before hashes were recorded have none.
+    fn read_hashes(
+        &self,
+        profile: &ProfileName,
+        backup: &Backup,
+    ) -> Result<HashMap<PathBuf, String>> {
+        let path = self.hashes_path(profile, backup);
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(e) => return Err(e).with_path("reading", &path),
+        };
+        Ok(content
+            .lines()
+            .filter_map(|line| line.split_once("  "))
+            .map(|(hash, file)| (PathBuf::from(file), hash.to_owned()))
+            .collect())
+    }
+}
+
+impl RemoteStore for DirStore {
+    fn describe(&self) -> String {
+        format!("dir:{}", self.root.display())
+    }
+
+    fn contains(&self, profile: &ProfileName, backup: &Backup) -> Result<bool> {
+        Ok(self.dir(profile, backup).is_dir())
+    }
+
+    fn put(&self, profile: &ProfileName, backup: &Backup, dir: &Path) -> Result<()> {
+        let dest = self.dir(profile, backup);
+        CopyEngine::new().copy_tree(dir, &dest)?;
+        // hash what was written rather than the source, so that the listing
+        // describes the copy
+        let mut hashes = String::new();
+        for file in walk_files(&dest)? {
+            let relative = file.strip_prefix(&dest).expect("walked inside copy");
+            hashes.push_str(&format!("{}  {}\n", hash_file(&file)?, relative.display()));
+        }
+        let path = self.hashes_path(profile, backup);
+        std::fs::write(&path, hashes).with_path("writing", &path)?;
+        set_tree_readonly(&dest, true)
+    }
+
+    fn fetch(&self, profile: &ProfileName, backup: &Backup, dest: &Path) -> Result<()> {
+        CopyEngine::new().copy_tree(&self.dir(profile, backup), dest)
+    }
+
+    fn delete(&self, profile: &ProfileName, backup: &Backup) -> Result<()> {
+        let dir = self.dir(profile, backup);
+        if dir.is_dir() {
+            remove_backup_dir(&dir)?;
+        }
+        let hashes = self.hashes_path(profile, backup);
+        if hashes.is_file() {
+            std::fs::remove_file(&hashes).with_path("deleting", &hashes)?;
+        }
+        Ok(())
+    }
+
+    fn objects(&self, profile: &ProfileName, backup: &Backup) -> Result<Option<Vec<RemoteObject>>> {
+        let dir = self.dir(profile, backup);
+        let mut hashes = self.read_hashes(profile, backup)?;
+        walk_files(&dir)?
+            .into_iter()
+            .map(|file| {
+                let size = std::fs::metadata(&file)
+                    .with_path("reading metadata of", &file)?
+                    .len();
+                let path = file
+                    .strip_prefix(&dir)
+                    .expect("walked inside copy")
+                    .to_owned();
+                Ok(RemoteObject {
+                    hash: hashes.remove(&path),
+                    path,
+                    size,
+                })
+            })
+            .collect::<Result<_>>()
+            .map(Some)
+    }
+}
+
+/// Copy a new backup to each of the profile's remotes.
+///
+/// Failures are reported as warnings, since the backup itself is saved.
+pub(crate) fn put_remote_copies(
+    profile: &Profile,
+    name: &ProfileName,
+    backup: &Backup,
+    dir: &Path,
+) {
+    for remote in profile.remotes() {
+        let store = remote.open();
+        if let Err(e) = store.put(name, backup, dir) {
+            eprintln!(
+                "warning: failed copying backup {} to {}: {}",
+                backup.id(),
+                store.describe(),
+                e
+            );
+        }
+    }
+}
+
+/// A copy of a backup in a remote store.
+#[derive(Clone, Debug)]
+pub struct RemoteCopy {
+    /// The copied backup.
+    pub backup: Backup,
+    /// Description of the store, see [`RemoteStore::describe`].
+    pub store: String,
+    /// Index of the store in the profile's `remotes`.
+    remote: usize,
+}
+
+/// The remote copies that deleting some backups would leave behind.
+#[derive(Clone, Debug)]
+pub struct DeletePlan {
+    /// Copies of the backups, by backup and then by store.
+    pub copies: Vec<RemoteCopy>,
+}
+
+impl DeletePlan {
+    /// Returns `true` if none of the backups have remote copies.
+    pub fn is_empty(&self) -> bool {
+        self.copies.is_empty()
+    }
+
+    /// Returns a short token identifying the plan.
+    ///
+    /// The token is shown with the plan and must be given back to carry it
+    /// out, so that only the copies that were reviewed are deleted.
+    pub fn token(&self) -> String {
+        let mut hasher = Sha256::new();
+        for copy in &self.copies {
+            hasher.update(format!("{}\t{}\n", copy.backup.id(), copy.store));
+        }
+        format!("{:x}", hasher.finalize())[..8].to_owned()
+    }
+}
+
+/// Find the remote copies of the given backups.
+pub fn plan_delete(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    ids: &[BackupId],
+) -> Result<DeletePlan> {
+    let backup_table = db.backup_table(name)?;
+    let stores = profile
+        .remotes()
+        .iter()
+        .map(Remote::open)
+        .collect::<Vec<_>>();
+    let mut copies = Vec::new();
+    for &id in ids {
+        let backup = backup_table
+            .select_id(id)?
+            .ok_or(BackupError::NoSuchBackup(id))?;
+        for (remote, store) in stores.iter().enumerate() {
+            if store.contains(name, &backup)? {
+                copies.push(RemoteCopy {
+                    backup: backup.clone(),
+                    store: store.describe(),
+                    remote,
+                });
+            }
+        }
+    }
+    Ok(DeletePlan { copies })
+}
+
+/// What became of a remote copy that was to be deleted.
+#[derive(Debug)]
+pub enum DeleteOutcome {
+    /// The copy is gone.
+    Deleted,
+    /// The store reported success, but still holds the copy.
+    StillPresent,
+    /// Deleting the copy failed.
+    Failed(crate::error::Error),
+}
+
+/// Delete the remote copies of a plan, and check which of them are really gone.
+///
+/// Returns the outcome for each copy, in the order of the plan.
+pub fn delete_remote_copies(
+    profile: &Profile,
+    name: &ProfileName,
+    plan: &DeletePlan,
+) -> Vec<(RemoteCopy, DeleteOutcome)> {
+    let stores = profile
+        .remotes()
+        .iter()
+        .map(Remote::open)
+        .collect::<Vec<_>>();
+    plan.copies
+        .iter()
+        .map(|copy| {
+            let store = &stores[copy.remote];
+            let outcome = match store.delete(name, &copy.backup) {
+                Err(e) => DeleteOutcome::Failed(e),
+                Ok(()) => match store.contains(name, &copy.backup) {
+                    Ok(false) => DeleteOutcome::Deleted,
+                    Ok(true) => DeleteOutcome::StillPresent,
+                    Err(e) => DeleteOutcome::Failed(e),
+                },
+            };
+            (copy.clone(), outcome)
+        })
+        .collect()
+}
+
+/// Outcome of [`verify_remotes`].
+#[derive(Clone, Debug, Default)]
+pub struct Verification {
+    /// Number of remote copies checked.
+    pub checked: usize,
+    /// Number of checked copies whose store keeps no hashes, so that their
+    /// files were only compared by size.
+    pub size_only: usize,
+    /// Number of copies that are present but weren't compared, because their
+    /// backup has no manifest or their store can't list files.
+    pub skipped: usize,
+    /// Description of each difference found.
+    pub problems: Vec<String>,
+}
+
+/// Check the remote copies of a profile's backups against their manifests,
+/// using the file sizes and hashes reported by the stores.
+///
+/// Nothing is downloaded: copies are found missing, incomplete or with extra
+/// files from their listing, and changed from their sizes and hashes. Files
+/// the store keeps no hash for are only compared by size. Copies of backups
+/// made before manifests were recorded are only checked for being present,
+/// and counted as skipped. With `id`, only that backup is checked.
+pub fn verify_remotes(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    id: Option<BackupId>,
+) -> Result<Verification> {
+    let stores = profile
+        .remotes()
+        .iter()
+        .map(Remote::open)
+        .collect::<Vec<_>>();
+    let file_table = db.file_table(name)?;
+    let backups = match id {
+        Some(id) => vec![db
+            .backup_table(name)?
+            .select_id(id)?
+            .ok_or(BackupError::NoSuchBackup(id))?],
+        None => db.backup_table(name)?.iter().collect::<Result<Vec<_>>>()?,
+    };
+    let mut verification = Verification::default();
+    for backup in backups {
+        let manifest = file_table.select_backup(backup.id())?;
+        for store in &stores {
+            if !store.contains(name, &backup)? {
+                verification.problems.push(format!(
+                    "backup {} is missing from {}",
+                    backup.id(),
+                    store.describe()
+                ));
+                continue;
+            }
+            let objects = if manifest.is_empty() {
+                // backups made before manifests were recorded
+                None
+            } else {
+                store.objects(name, &backup)?
+            };
+            let Some(objects) = objects else {
+                verification.skipped += 1;
+                continue;
+            };
+            verification.checked += 1;
+            if objects.iter().all(|object| object.hash.is_none()) {
+                verification.size_only += 1;
+            }
+            let mut expected = manifest
+                .iter()
+                .map(|file| (file.path.as_path(), file))
+                .collect::<HashMap<_, _>>();
+            let (mut changed, mut extra) = (0, 0);
+            for object in &objects {
+                match expected.remove(object.path.as_path()) {
+                    Some(file)
+                        if object.size != file.size
+                            || object.hash.as_ref().is_some_and(|hash| *hash != file.hash) =>
+                    {
+                        changed += 1
+                    }
+                    Some(_) => {}
+                    None => extra += 1,
+                }
+            }
+            let missing = expected.len();
+            if missing + changed + extra > 0 {
+                verification.problems.push(format!(
+                    "backup {} differs in {}: {} missing, {} changed and {} extra file(s)",
+                    backup.id(),
+                    store.describe(),
+                    missing,
+                    changed,
+                    extra
+                ));
+            }
+        }
+    }
+    Ok(verification)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::{BackupCause, FileRecord};
+
+    fn copy(id: u32, store: &str) -> RemoteCopy {
+        RemoteCopy {
+            backup: Backup::new(
+                BackupId::new(id).unwrap(),
+                String::new(),
+                Utc::now(),
+                None,
+                None,
+                Default::default(),
+                BackupCause::Manual,
+                None,
+                None,
+                None,
+            ),
+            store: store.to_owned(),
+            remote: 0,
+        }
+    }
+
+    fn token(copies: &[RemoteCopy]) -> String {
+        DeletePlan {
+            copies: copies.to_vec(),
+        }
+        .token()
+    }
+
+    #[test]
+    fn token_is_short_hex() {
+        let token = token(&[copy(1, "dir:/mnt/nas")]);
+        assert_eq!(token.len(), 8);
+        assert!(token.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn token_is_stable_for_same_copies() {
+        let copies = [copy(1, "dir:/mnt/nas"), copy(2, "dir:/mnt/nas")];
+        assert_eq!(token(&copies), token(&copies.clone()));
+        assert_eq!(token(&[]), token(&[]));
+    }
+
+    #[test]
+    fn token_changes_with_copies() {
+        let plan = token(&[copy(1, "dir:/mnt/nas"), copy(2, "dir:/mnt/nas")]);
+        let others = [
+            token(&[copy(1, "dir:/mnt/nas")]),
+            token(&[copy(1, "dir:/mnt/nas"), copy(3, "dir:/mnt/nas")]),
+            token(&[copy(1, "dir:/mnt/nas"), copy(2, "dir:/mnt/usb")]),
+            token(&[
+                copy(1, "dir:/mnt/nas"),
+                copy(2, "dir:/mnt/nas"),
+                copy(2, "dir:/mnt/usb"),
+            ]),
+            token(&[]),
+        ];
+        for other in others {
+            assert_ne!(plan, other);
+        }
+    }
+
+    /// A profile with a directory remote at `remote`, and a backup of it
+    /// holding `files` in `local`.
+    fn remote_backup(
+        local: &Path,
+        remote: &Path,
+        files: &[(&str, &str)],
+    ) -> (Database, Profile, ProfileName, Backup) {
+        let db = Database::from_store(crate::json_store::JsonStore::in_memory());
+        let name = ProfileName::new("game").unwrap();
+        let profile = Profile::builder(local)
+            .remote(Remote::Dir {
+                path: remote.to_owned(),
+            })
+            .build()
+            .unwrap();
+        let backup = db
+            .backup_table(&name)
+            .unwrap()
+            .insert("", &Utc::now())
+            .unwrap();
+        for (path, content) in files {
+            std::fs::write(local.join(path), content).unwrap();
+        }
+        DirStore::new(remote).put(&name, &backup, local).unwrap();
+        (db, profile, name, backup)
+    }
+
+    fn manifest(files: &[(&str, &str)]) -> Vec<FileRecord> {
+        files
+            .iter()
+            .map(|(path, content)| FileRecord {
+                path: PathBuf::from(path),
+                hash: format!("{:x}", Sha256::digest(content)),
+                size: content.len() as u64,
+                changed: true,
+                triggered: false,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dir_store_lists_hashes_of_copies() {
+        let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let files = [("a.sav", "first"), ("b.sav", "second")];
+        let (_db, _profile, name, backup) = remote_backup(local.path(), remote.path(), &files);
+
+        let store = DirStore::new(remote.path());
+        let mut objects = store.objects(&name, &backup).unwrap().unwrap();
+        objects.sort_by(|a, b| a.path.cmp(&b.path));
+        let expected = manifest(&files);
+        assert_eq!(objects.len(), 2);
+        for (object, file) in objects.iter().zip(&expected) {
+            assert_eq!(object.path, file.path);
+            assert_eq!(object.hash.as_ref(), Some(&file.hash));
+        }
+
+        store.delete(&name, &backup).unwrap();
+        assert!(!store.hashes_path(&name, &backup).exists());
+    }
+
+    #[test]
+    fn verify_compares_hashes() {
+        let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let files = [("a.sav", "first"), ("b.sav", "second")];
+        let (db, profile, name, backup) = remote_backup(local.path(), remote.path(), &files);
+        let file_table = db.file_table(&name).unwrap();
+        file_table.insert(backup.id(), &manifest(&files)).unwrap();
+
+        let verification = verify_remotes(&db, &profile, &name, None).unwrap();
+        assert_eq!(verification.checked, 1);
+        assert_eq!(verification.size_only, 0);
+        assert!(verification.problems.is_empty());
+    }
+
+    #[test]
+    fn verify_finds_changes_that_keep_sizes() {
+        let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let copied = [("a.sav", "fir5t"), ("b.sav", "second")];
+        let (db, profile, name, backup) = remote_backup(local.path(), remote.path(), &copied);
+        let file_table = db.file_table(&name).unwrap();
+        let files = [("a.sav", "first"), ("b.sav", "second")];
+        file_table.insert(backup.id(), &manifest(&files)).unwrap();
+
+        let verification = verify_remotes(&db, &profile, &name, None).unwrap();
+        assert_eq!(verification.checked, 1);
+        assert_eq!(verification.problems.len(), 1);
+        assert!(verification.problems[0].contains("1 changed"));
+    }
+
+    #[test]
+    fn verify_skips_backups_without_manifest() {
+        let (local, remote) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (db, profile, name, _) = remote_backup(local.path(), remote.path(), &[("a.sav", "x")]);
+
+        let verification = verify_remotes(&db, &profile, &name, None).unwrap();
+        assert_eq!(verification.checked, 0);
+        assert_eq!(verification.skipped, 1);
+        assert!(verification.problems.is_empty());
+    }
+}
@@ -0,0 +1,80 @@
+use chrono::{
+    format::{Item, StrftimeItems},
+    Local,
+};
+
+use crate::Timestamp;
+
+/// Default format of `{time}` placeholders.
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Render `template`, replacing each `{name}` placeholder with the value
+/// returned by `value` for its name and format.
+///
+/// A placeholder may carry a format after a colon, e.g. `{time:%H:%M}`, which
+/// is interpreted by `value`. `{{` and `}}` stand for literal braces.
+///
+/// Fails if the template is malformed, or if `value` returns an error for a
+/// placeholder, such as an unknown name.
+pub(crate) fn render(
+    template: &str,
+    mut value: impl FnMut(&str, Option<&str>) -> Result<String, String>,
+) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        output.push_str(&rest[..start]);
+        let brace = &rest[start..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            output.push_str(&brace[..1]);
+            rest = &brace[2..];
+            continue;
+        }
+        if brace.starts_with('}') {
+            Err(format!("unmatched '}}' in template {:?}", template))?
+        }
+        let end = brace
+            .find('}')
+            .ok_or_else(|| format!("unclosed '{{' in template {:?}", template))?;
+        let placeholder = &brace[1..end];
+        let (name, format) = match placeholder.split_once(':') {
+            Some((name, format)) => (name, Some(format)),
+            None => (placeholder, None),
+        };
+        output.push_str(&value(name, format)?);
+        rest = &brace[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// Check that a template only uses the given placeholders, and that only
+/// `{time}` has a format, which must be a valid time format.
+pub(crate) fn validate(template: &str, placeholders: &[&str]) -> Result<(), String> {
+    render(template, |name, format| {
+        if !placeholders.contains(&name) {
+            Err(format!(
+                "unknown placeholder {{{}}}, expected one of {}",
+                name,
+                placeholders.join(", ")
+            ))?
+        }
+        if let Some(format) = format {
+            if name != "time" {
+                Err(format!("only {{time}} takes a format, not {{{}}}", name))?
+            }
+            if StrftimeItems::new(format).any(|item| item == Item::Error) {
+                Err(format!("invalid time format {:?}", format))?
+            }
+        }
+        Ok(String::new())
+    })
+    .map(drop)
+}
+
+/// Format a `{time}` placeholder in local time, with the given or default format.
+pub(crate) fn format_time(time: &Timestamp, format: Option<&str>) -> String {
+    time.with_timezone(&Local)
+        .format(format.unwrap_or(DEFAULT_TIME_FORMAT))
+        .to_string()
+}
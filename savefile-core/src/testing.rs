@@ -0,0 +1,153 @@
+/// Utilities for benchmarks and tests, enabled by the `testing` feature.
+///
+/// [`generate_save_tree`] lays out a synthetic save directory and
+/// [`populate_backups`] fills a database with backup metadata, so that changes
+/// can be measured at sizes which are impractical to create by hand.
+use std::path::{Path, PathBuf};
+
+use chrono::{Duration, Utc};
+
+use crate::{
+    backup::{Backup, BackupCause, BackupId, BackupStatus, FileRecord, UNTAGGED},
+    database::Database,
+    error::{IoResultExt, Result},
+    include_cache,
+    profile::ProfileName,
+};
+
+/// Number of subdirectories of each directory in a generated tree.
+const FANOUT: usize = 10;
+
+/// Extensions of generated files, in turn, so that includes can match some of them.
+const EXTENSIONS: [&str; 4] = ["sav", "bak", "dat", "tmp"];
+
+/// Shape of a synthetic save tree, see [`generate_save_tree`].
+#[derive(Clone, Debug)]
+pub struct SaveTree {
+    /// Number of directories below the root. Each directory has up to ten
+    /// subdirectories, so `10` gives one level, `110` two, and so on.
+    pub dirs: usize,
+    /// Number of files in each directory, including the root.
+    pub files_per_dir: usize,
+    /// Size of each file in bytes.
+    pub file_size: usize,
+    /// Seed of the file contents. Trees with the same shape and seed are identical.
+    pub seed: u64,
+}
+
+impl Default for SaveTree {
+    /// 10,000 files of 4KB in 100 directories.
+    fn default() -> Self {
+        Self {
+            dirs: 99,
+            files_per_dir: 100,
+            file_size: 4096,
+            seed: 0,
+        }
+    }
+}
+
+impl SaveTree {
+    /// Returns the number of files in the tree.
+    pub fn file_count(&self) -> usize {
+        (self.dirs + 1) * self.files_per_dir
+    }
+
+    /// Returns the total size of the files in the tree, in bytes.
+    pub fn total_size(&self) -> u64 {
+        (self.file_count() * self.file_size) as u64
+    }
+}
+
+/// Generate a save tree with the given shape under `root`, which is created
+/// if necessary.
+///
+/// Directories are named `d<n>` and files `save<n>.<ext>`, with extensions
+/// `sav`, `bak`, `dat` and `tmp` in turn. Returns the paths of the files
+/// relative to `root`, sorted.
+pub fn generate_save_tree(root: &Path, tree: &SaveTree) -> Result<Vec<PathBuf>> {
+    let mut dirs = vec![PathBuf::new()];
+    for n in 1..=tree.dirs {
+        let parent = dirs[(n - 1) / FANOUT].clone();
+        dirs.push(parent.join(format!("d{}", n)));
+    }
+    let mut files = Vec::with_capacity(tree.file_count());
+    for (d, dir) in dirs.iter().enumerate() {
+        let abs = root.join(dir);
+        std::fs::create_dir_all(&abs).with_path("creating", &abs)?;
+        for f in 0..tree.files_per_dir {
+            let rel = dir.join(format!("save{}.{}", f, EXTENSIONS[f % EXTENSIONS.len()]));
+            let seed = tree.seed ^ ((d as u64) << 32 | f as u64);
+            let path = root.join(&rel);
+            std::fs::write(&path, contents(seed, tree.file_size)).with_path("writing", &path)?;
+            files.push(rel);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Returns `len` pseudo-random bytes derived from `seed`.
+fn contents(seed: u64, len: usize) -> Vec<u8> {
+    // xorshift64, which must not start at zero
+    let mut state = seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1;
+    let mut bytes = Vec::with_capacity(len + 8);
+    while bytes.len() < len {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        bytes.extend_from_slice(&state.to_le_bytes());
+    }
+    bytes.truncate(len);
+    bytes
+}
+
+/// Record `count` untagged backups of a profile, one minute apart and ending
+/// now, each with a manifest of `files_per_backup` files.
+///
+/// Only the metadata is written; the backups have no directories.
+pub fn populate_backups(
+    db: &Database,
+    profile: &ProfileName,
+    count: u32,
+    files_per_backup: usize,
+) -> Result<()> {
+    let now = Utc::now();
+    let backups = (1..=count)
+        .map(|id| {
+            Ok(Backup::new(
+                BackupId::new(id)?,
+                UNTAGGED.to_owned(),
+                now - Duration::minutes(i64::from(count - id)),
+                None,
+                None,
+                BackupStatus::Unchecked,
+                BackupCause::Watcher,
+                None,
+                None,
+                None,
+            ))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    db.backup_table(profile)?.insert_many(&backups)?;
+    let file_table = db.file_table(profile)?;
+    for backup in &backups {
+        let files = (0..files_per_backup)
+            .map(|f| FileRecord {
+                path: format!("save{}.sav", f).into(),
+                hash: format!("{:064x}", f),
+                size: 4096,
+                changed: f == 0,
+                triggered: f == 0,
+            })
+            .collect::<Vec<_>>();
+        file_table.insert(backup.id(), &files)?;
+    }
+    Ok(())
+}
+
+/// Forget all cached include expansions, so that the next expansion walks
+/// the base directory again.
+pub fn clear_include_cache() {
+    include_cache::clear();
+}
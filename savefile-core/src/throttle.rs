@@ -0,0 +1,92 @@
+/// Rate limiting for the file copies of a backup.
+///
+/// Copying a large save directory at full speed can cause frame drops in a
+/// game running at the same time, so backups may be limited to a number of
+/// bytes per second.
+use std::{
+    fs::File,
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use crate::{size::ByteSize, sparse};
+
+/// Size of the chunks copied between rate checks.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Limits the combined rate of a sequence of copies.
+pub(crate) struct Throttle {
+    /// Maximum number of bytes per second, or `None` for no limit.
+    rate: Option<u64>,
+    /// Time of the first copy.
+    started: Option<Instant>,
+    /// Bytes copied so far.
+    copied: u64,
+}
+
+impl Throttle {
+    /// Create a throttle allowing `rate` bytes per second, or any rate if `None`.
+    pub(crate) fn new(rate: Option<ByteSize>) -> Self {
+        Self {
+            rate: rate.map(|rate| rate.0).filter(|rate| *rate > 0),
+            started: None,
+            copied: 0,
+        }
+    }
+
+    /// Copy a file like [`std::fs::copy`], pausing as needed to stay within the rate.
+    ///
+    /// Sparse files are copied with the same holes, see [`crate::sparse`].
+    pub(crate) fn copy(&mut self, src: &Path, dest: &Path) -> std::io::Result<u64> {
+        let mut reader = File::open(src)?;
+        let meta = reader.metadata()?;
+        let sparse = sparse::is_sparse(&meta);
+        if self.rate.is_none() && !sparse {
+            return std::fs::copy(src, dest);
+        }
+        let mut writer = File::create(dest)?;
+        if sparse {
+            sparse::mark_sparse(&writer)?;
+        }
+        let mut buf = vec![0; CHUNK_SIZE];
+        let mut total = 0;
+        loop {
+            let n = reader.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            if sparse {
+                for block in buf[..n].chunks(sparse::BLOCK_SIZE) {
+                    if block.iter().all(|b| *b == 0) {
+                        writer.seek(SeekFrom::Current(block.len() as i64))?;
+                    } else {
+                        writer.write_all(block)?;
+                    }
+                }
+            } else {
+                writer.write_all(&buf[..n])?;
+            }
+            total += n as u64;
+            self.wait(n as u64);
+        }
+        // a trailing hole is only a seek, which doesn't extend the file
+        writer.set_len(total)?;
+        writer.flush()?;
+        std::fs::set_permissions(dest, meta.permissions())?;
+        Ok(total)
+    }
+
+    /// Record that `n` more bytes were copied, and sleep while ahead of the rate.
+    fn wait(&mut self, n: u64) {
+        let Some(rate) = self.rate else {
+            return;
+        };
+        let started = *self.started.get_or_insert_with(Instant::now);
+        self.copied += n;
+        let due = Duration::from_secs_f64(self.copied as f64 / rate as f64);
+        if let Some(ahead) = due.checked_sub(started.elapsed()) {
+            std::thread::sleep(ahead);
+        }
+    }
+}
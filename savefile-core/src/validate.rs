@@ -0,0 +1,200 @@
+/// Checks run on the live files before each backup.
+///
+/// Backing up a save that is already corrupted would replace a good backup
+/// with a useless one, so a backup is skipped if any validator fails.
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[cfg(feature = "wasm")]
+use crate::wasm::run_module;
+use crate::{
+    error::{IoResultExt, ProfileError, Result},
+    pattern::expand_braces,
+    process::shell,
+};
+
+/// A single check of the live files.
+///
+/// In profiles, validators are written as e.g. `{ "type": "non_empty", "glob": "*.sav" }`,
+/// `{ "type": "magic", "glob": "*.sav", "bytes": "53415645" }`,
+/// `{ "type": "command", "command": "check-saves.exe" }` or
+/// `{ "type": "wasm", "module": "playtime.wasm" }`. If `glob` is omitted,
+/// all included files are checked.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Validator {
+    /// Matching files must not be empty.
+    NonEmpty {
+        #[serde(default = "all_files")]
+        glob: String,
+    },
+    /// Matching files must start with the given bytes, written in hex.
+    Magic {
+        #[serde(default = "all_files")]
+        glob: String,
+        bytes: String,
+    },
+    /// A shell command, run in the base directory, must exit successfully.
+    Command { command: String },
+    /// A WebAssembly module, relative to the base directory, must accept the
+    /// files (requires the `wasm` feature).
+    Wasm { module: PathBuf },
+}
+
+/// Default glob of validators, matching every included file.
+fn all_files() -> String {
+    "**".to_owned()
+}
+
+impl Validator {
+    /// Check that the validator's settings are well-formed.
+    pub(crate) fn validate(&self) -> Result<(), ProfileError> {
+        match self {
+            Validator::NonEmpty { glob } => {
+                patterns(glob)?;
+            }
+            Validator::Magic { glob, bytes } => {
+                patterns(glob)?;
+                decode_hex(bytes).ok_or_else(|| {
+                    ProfileError::InvalidValidator(format!("invalid hex bytes {:?}", bytes))
+                })?;
+            }
+            Validator::Command { command } => {
+                if command.trim().is_empty() {
+                    Err(ProfileError::InvalidValidator("empty command".to_owned()))?
+                }
+            }
+            Validator::Wasm { .. } => {
+                if !cfg!(feature = "wasm") {
+                    Err(ProfileError::InvalidValidator(
+                        "WASM validators require the `wasm` feature".to_owned(),
+                    ))?
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the given files, which are relative to `base`.
+    ///
+    /// `previous` is the directory of the most recent backup, if any.
+    /// Returns a description of each problem found.
+    pub fn check(
+        &self,
+        base: &Path,
+        files: &[PathBuf],
+        previous: Option<&Path>,
+    ) -> Result<Vec<String>> {
+        let mut problems = Vec::new();
+        match self {
+            Validator::NonEmpty { glob } => {
+                for file in matching(glob, files)? {
+                    let path = base.join(file);
+                    let len = std::fs::metadata(&path)
+                        .with_path("reading metadata of", &path)?
+                        .len();
+                    if len == 0 {
+                        problems.push(format!("{} is empty", file.display()));
+                    }
+                }
+            }
+            Validator::Magic { glob, bytes } => {
+                let magic = decode_hex(bytes).ok_or_else(|| {
+                    ProfileError::InvalidValidator(format!("invalid hex bytes {:?}", bytes))
+                })?;
+                for file in matching(glob, files)? {
+                    let path = base.join(file);
+                    let mut head = Vec::with_capacity(magic.len());
+                    std::fs::File::open(&path)
+                        .and_then(|f| f.take(magic.len() as u64).read_to_end(&mut head))
+                        .with_path("reading", &path)?;
+                    if head != magic {
+                        problems.push(format!("{} doesn't start with {}", file.display(), bytes));
+                    }
+                }
+            }
+            Validator::Command { command } => {
+                let status = shell(command)
+                    .current_dir(base)
+                    .status()
+                    .context(|| format!("failed running validator {:?}", command))?;
+                if !status.success() {
+                    problems.push(format!("{:?} exited with {}", command, status));
+                }
+            }
+            #[cfg(feature = "wasm")]
+            Validator::Wasm { module } => {
+                problems.extend(run_module(&base.join(module), base, files, previous)?);
+            }
+            #[cfg(not(feature = "wasm"))]
+            Validator::Wasm { .. } => {
+                let _ = previous;
+                Err(ProfileError::InvalidValidator(
+                    "WASM validators require the `wasm` feature".to_owned(),
+                ))?
+            }
+        }
+        Ok(problems)
+    }
+}
+
+/// Run all validators on the given files, which are relative to `base`.
+///
+/// `previous` is the directory of the most recent backup, if any.
+/// Returns a description of each problem found.
+pub fn run_validators(
+    validators: &[Validator],
+    base: &Path,
+    files: &[PathBuf],
+    previous: Option<&Path>,
+) -> Result<Vec<String>> {
+    let files = files
+        .iter()
+        .filter(|file| base.join(file).is_file())
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut problems = Vec::new();
+    for validator in validators {
+        problems.extend(validator.check(base, &files, previous)?);
+    }
+    Ok(problems)
+}
+
+/// Compile a glob, including its brace alternatives.
+fn patterns(glob: &str) -> Result<Vec<glob::Pattern>, ProfileError> {
+    expand_braces(glob)
+        .iter()
+        .map(|pattern| {
+            glob::Pattern::new(pattern).map_err(|_| ProfileError::InvalidGlob(glob.to_owned()))
+        })
+        .collect()
+}
+
+/// Returns the files matching `glob`.
+fn matching<'f>(glob: &str, files: &'f [PathBuf]) -> Result<Vec<&'f PathBuf>> {
+    let patterns = patterns(glob)?;
+    Ok(files
+        .iter()
+        .filter(|file| patterns.iter().any(|pattern| pattern.matches_path(file)))
+        .collect())
+}
+
+/// Decode a string of hex digit pairs, ignoring whitespace.
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    let digits = hex
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<Vec<_>>>()?;
+    if digits.is_empty() || digits.len() % 2 != 0 {
+        return None;
+    }
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair[0] << 4 | pair[1])
+            .collect(),
+    )
+}
@@ -0,0 +1,97 @@
+/// Capturing the version of the game or app whose files are backed up.
+///
+/// The version is recorded with each backup, so that a save can be matched
+/// to the patch it was made with before restoring it.
+use std::path::{Path, PathBuf};
+
+use crate::{
+    error::{IoResultExt, Result},
+    process::shell,
+};
+
+/// Maximum length of a captured version, in characters.
+const MAX_VERSION_LEN: usize = 128;
+
+/// Where to read the version from.
+///
+/// In profiles, this is written as e.g. `{ "file": "version.txt" }`,
+/// `{ "command": "game --version" }` or `{ "executable": "Game.exe" }`.
+/// Relative paths are relative to the profile's base directory.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    /// The first non-empty line of a file.
+    File(PathBuf),
+    /// The first non-empty line printed by a shell command, run in the base directory.
+    Command(String),
+    /// The product version of an executable (Windows only).
+    Executable(PathBuf),
+}
+
+impl VersionSource {
+    /// Read the current version.
+    ///
+    /// Returns `None` if the source yields no text.
+    pub fn capture(&self, base: &Path) -> Result<Option<String>> {
+        let output = match self {
+            VersionSource::File(path) => {
+                let path = base.join(path);
+                std::fs::read_to_string(&path).with_path("reading version from", &path)?
+            }
+            VersionSource::Command(command) => run_shell(command, base)?,
+            VersionSource::Executable(path) => product_version(&base.join(path))?,
+        };
+        Ok(first_line(&output))
+    }
+}
+
+/// Returns the first non-empty line of `text`, trimmed and truncated.
+fn first_line(text: &str) -> Option<String> {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(|line| line.chars().take(MAX_VERSION_LEN).collect())
+}
+
+/// Run a command with the platform's shell and return its standard output.
+fn run_shell(command: &str, dir: &Path) -> Result<String> {
+    let output = shell(command)
+        .current_dir(dir)
+        .output()
+        .context(|| format!("failed running version command {:?}", command))?;
+    if !output.status.success() {
+        Err(std::io::Error::other(format!(
+            "version command {:?} exited with {}",
+            command, output.status
+        )))?
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the product version stored in an executable's version resource.
+#[cfg(windows)]
+fn product_version(path: &Path) -> Result<String> {
+    // the literal path is passed as an argument to avoid quoting issues
+    let output = std::process::Command::new("powershell")
+        .args([
+            "-NoProfile",
+            "-NonInteractive",
+            "-Command",
+            "(Get-Item -LiteralPath $args[0]).VersionInfo.ProductVersion",
+        ])
+        .arg(path)
+        .stdin(std::process::Stdio::null())
+        .output()
+        .with_path("reading product version of", path)?;
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Returns the product version stored in an executable's version resource.
+#[cfg(not(windows))]
+fn product_version(path: &Path) -> Result<String> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "product versions can only be read on Windows",
+    ))
+    .with_path("reading product version of", path)
+}
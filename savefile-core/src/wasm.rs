@@ -0,0 +1,173 @@
+/// Sandboxed validators written as WebAssembly modules.
+///
+/// A module only sees the list of included files and, through a host function,
+/// the contents of those files and of the previous backup. It has no other
+/// access to the system, and runs with a bounded amount of fuel.
+use std::{
+    collections::HashSet,
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
+
+use wasmtime::{Caller, Engine, Linker, Module, Store};
+
+use crate::{
+    error::{ProfileError, Result},
+    filesystem::walk_files,
+};
+
+/// Fuel given to each run, roughly the number of instructions executed.
+const FUEL: u64 = 10_000_000_000;
+/// `source` argument of `read_file` for the live files.
+const SOURCE_LIVE: i32 = 0;
+/// `source` argument of `read_file` for the files of the previous backup.
+const SOURCE_PREVIOUS: i32 = 1;
+
+/// Files visible to a module.
+struct Sandbox {
+    base: PathBuf,
+    live: HashSet<PathBuf>,
+    previous: Option<(PathBuf, HashSet<PathBuf>)>,
+    /// Reason given by the module for rejecting the files.
+    reason: Option<String>,
+}
+
+impl Sandbox {
+    /// Returns the absolute path of a file the module may read.
+    fn resolve(&self, source: i32, path: &Path) -> Option<PathBuf> {
+        match source {
+            SOURCE_LIVE if self.live.contains(path) => Some(self.base.join(path)),
+            SOURCE_PREVIOUS => match &self.previous {
+                Some((dir, files)) if files.contains(path) => Some(dir.join(path)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
+
+/// Run the module at `module` on the given files, which are relative to `base`.
+///
+/// `previous` is the directory of the most recent backup, if any. Returns a
+/// description of the problem if the module rejects the files.
+pub(crate) fn run_module(
+    module: &Path,
+    base: &Path,
+    files: &[PathBuf],
+    previous: Option<&Path>,
+) -> Result<Option<String>> {
+    let previous = match previous.filter(|dir| dir.is_dir()) {
+        Some(dir) => {
+            let files = walk_files(dir)?
+                .into_iter()
+                .filter_map(|path| path.strip_prefix(dir).ok().map(Path::to_owned))
+                .collect::<HashSet<_>>();
+            Some((dir.to_owned(), files))
+        }
+        None => None,
+    };
+    let input = serde_json::json!({
+        "files": files.iter().map(|path| describe(base, path)).collect::<Vec<_>>(),
+        "previous": previous.as_ref().map(|(dir, files)| {
+            files.iter().map(|path| describe(dir, path)).collect::<Vec<_>>()
+        }),
+    })
+    .to_string();
+    let sandbox = Sandbox {
+        base: base.to_owned(),
+        live: files.iter().cloned().collect(),
+        previous,
+        reason: None,
+    };
+    let fail = |e: wasmtime::Error| {
+        ProfileError::InvalidValidator(format!("{}: {:#}", module.display(), e))
+    };
+    let (accepted, reason) = run(module, sandbox, &input).map_err(fail)?;
+    Ok((!accepted).then(|| reason.unwrap_or_else(|| format!("rejected by {}", module.display()))))
+}
+
+/// Instantiate the module and call its `check` export with the JSON input.
+///
+/// Returns whether the files were accepted, and the reason given if not.
+fn run(module: &Path, sandbox: Sandbox, input: &str) -> wasmtime::Result<(bool, Option<String>)> {
+    let mut config = wasmtime::Config::new();
+    config.consume_fuel(true);
+    let engine = Engine::new(&config)?;
+    let module = Module::from_file(&engine, module)?;
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap("savefile", "read_file", read_file)?;
+    linker.func_wrap(
+        "savefile",
+        "reject",
+        |mut caller: Caller<'_, Sandbox>, ptr: i32, len: i32| {
+            let reason = read_memory(&mut caller, ptr, len)
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned());
+            caller.data_mut().reason = reason;
+        },
+    )?;
+    let mut store = Store::new(&engine, sandbox);
+    store.add_fuel(FUEL)?;
+    let instance = linker.instantiate(&mut store, &module)?;
+    let memory = instance
+        .get_memory(&mut store, "memory")
+        .ok_or_else(|| wasmtime::Error::msg("module doesn't export its memory"))?;
+    let alloc = instance.get_typed_func::<i32, i32>(&mut store, "alloc")?;
+    let check = instance.get_typed_func::<(i32, i32), i32>(&mut store, "check")?;
+    let len = i32::try_from(input.len())?;
+    let ptr = alloc.call(&mut store, len)?;
+    memory.write(&mut store, ptr as usize, input.as_bytes())?;
+    let accepted = check.call(&mut store, (ptr, len))? == 0;
+    Ok((accepted, store.into_data().reason))
+}
+
+/// Host function reading part of a file into the module's memory.
+///
+/// Returns the number of bytes read, or -1 if the file isn't visible to the
+/// module or can't be read.
+fn read_file(
+    mut caller: Caller<'_, Sandbox>,
+    source: i32,
+    path_ptr: i32,
+    path_len: i32,
+    offset: i64,
+    buf_ptr: i32,
+    buf_len: i32,
+) -> i64 {
+    let Some(path) = read_memory(&mut caller, path_ptr, path_len)
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+    else {
+        return -1;
+    };
+    let Some(path) = caller.data().resolve(source, Path::new(&path)) else {
+        return -1;
+    };
+    let (Ok(offset), Ok(buf_len)) = (u64::try_from(offset), usize::try_from(buf_len)) else {
+        return -1;
+    };
+    let mut buf = Vec::new();
+    let read = std::fs::File::open(path).and_then(|mut file| {
+        file.seek(SeekFrom::Start(offset))?;
+        file.take(buf_len as u64).read_to_end(&mut buf)
+    });
+    let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+        return -1;
+    };
+    match read {
+        Ok(n) if memory.write(&mut caller, buf_ptr as usize, &buf).is_ok() => n as i64,
+        _ => -1,
+    }
+}
+
+/// Copy a range of the module's memory.
+fn read_memory(caller: &mut Caller<'_, Sandbox>, ptr: i32, len: i32) -> Option<Vec<u8>> {
+    let memory = caller.get_export("memory")?.into_memory()?;
+    let mut bytes = vec![0; usize::try_from(len).ok()?];
+    memory.read(caller, ptr as usize, &mut bytes).ok()?;
+    Some(bytes)
+}
+
+/// Describe a file for the module's input.
+fn describe(dir: &Path, path: &Path) -> serde_json::Value {
+    let size = std::fs::metadata(dir.join(path)).map_or(0, |meta| meta.len());
+    serde_json::json!({ "path": path, "size": size })
+}
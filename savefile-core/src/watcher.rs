@@ -0,0 +1,626 @@
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::mpsc::{RecvTimeoutError, Sender},
+    time::{Duration, Instant, SystemTime},
+};
+
+use chrono::Utc;
+use notify::{Event, RecommendedWatcher, RecursiveMode};
+
+use crate::{
+    alert,
+    backup::{backup, BackupCause},
+    cold,
+    config::Config,
+    control::{is_paused, write_pause_file},
+    database::Database,
+    error::{ErrorCategory, IoResultExt, ProfileError, Result},
+    events::{self, EventKind},
+    filesystem::{profile_path, profiles_dir},
+    ipc::{self, Control, Request, Response},
+    lock::{BackupOutcome, WatchLock, HEARTBEAT_INTERVAL},
+    priority::Background,
+    profile::{Profile, ProfileName},
+    scheduler::Scheduler,
+};
+
+pub type Watcher = RecommendedWatcher;
+
+/// Tag of backups made because the game was updated.
+const PRE_UPDATE_TAG: &str = "pre-update";
+
+/// Delay before restarting a failed watcher for the first time.
+const MIN_RESTART_DELAY: Duration = Duration::from_secs(1);
+/// Upper limit for the delay between restarts of a repeatedly failing watcher.
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(60);
+/// A watcher that ran at least this long is considered healthy again.
+const HEALTHY_AFTER: Duration = Duration::from_secs(300);
+/// Time after resuming during which changes are still ignored, since events
+/// of whatever the watcher was paused for may arrive late.
+const RESUME_SETTLE: Duration = Duration::from_secs(2);
+/// Interval between maintenance runs, see [`maintain`].
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Message handled by the watch loop.
+enum Message {
+    /// Included files changed on disk.
+    Changed(Vec<PathBuf>),
+    /// The profile file was edited.
+    ProfileChanged,
+    /// The profile's update trigger changed on disk.
+    Updated,
+    /// A request arrived on the control endpoint.
+    Control(Control),
+}
+
+impl From<Control> for Message {
+    fn from(control: Control) -> Self {
+        Message::Control(control)
+    }
+}
+
+/// Watch the given profile and create a backup whenever its files change.
+///
+/// Holds the profile's watcher lock while running, and answers requests on
+/// the profile's control endpoint. A failed backup is reported and recorded
+/// in the lock file, but does not stop the watcher.
+///
+/// Edits to the profile file are picked up without restarting. If the edited
+/// profile is invalid, the previous one stays in effect.
+///
+/// If the profile has an update trigger, a backup tagged `pre-update` is made
+/// whenever the trigger's modification time or the captured version changes,
+/// before the updated game gets a chance to modify the saves.
+///
+/// Once an hour while idle, old backups are moved to cold storage if the
+/// profile has a `cold_storage` rule.
+///
+/// If the base directory becomes unavailable, e.g. because it is on a removable
+/// or network drive that was disconnected, watching pauses until it returns.
+///
+/// While paused with [`crate::control::set_paused`], changes are ignored and no automatic
+/// backups are made. Requested backups are still made.
+///
+/// Returns when asked to stop with [`crate::control::stop_watcher`]. Changes
+/// not yet backed up are dropped, as when the process is killed.
+pub fn watch(db: &Database, profile: &Profile, name: &ProfileName) -> Result<()> {
+    let mut lock = WatchLock::acquire(name)?;
+    let (tx, rx) = std::sync::mpsc::channel();
+    let mut profile = profile.clone();
+    let mut watcher = watch_base(&profile, tx.clone())?;
+    let mut _update_watcher = create_update_watcher(&profile, tx.clone())?;
+    let _profile_watcher = create_profile_watcher(name, tx.clone())?;
+    let endpoint = ipc::endpoint_name(name)?;
+    ipc::listen(&endpoint, tx.clone())?;
+    lock.update(|state| state.endpoint = Some(endpoint))?;
+    let mut delay = Duration::from_secs_f32(profile.delay());
+    // time of the most recent change not yet backed up
+    let mut changed: Option<Instant> = None;
+    // files whose changes will trigger the next backup
+    let mut triggers = BTreeSet::new();
+    // state of the update trigger when it was last checked
+    let mut fingerprint = UpdateFingerprint::read(&profile);
+    // time of the most recent change to the update trigger not yet checked
+    let mut updated: Option<Instant> = None;
+    // time of the most recent maintenance run
+    let mut maintained: Option<Instant> = None;
+    // time automatic backups were last resumed
+    let mut resumed: Option<Instant> = None;
+    events::publish(name, EventKind::Started);
+    loop {
+        // also applies pauses requested on the control endpoint, before
+        // handling any further changes
+        match sync_pause(&mut lock, name)? {
+            Some(true) => {
+                changed = None;
+                updated = None;
+                triggers.clear();
+            }
+            Some(false) => resumed = Some(Instant::now()),
+            None => {}
+        }
+        // checked on every wake-up, since a watch on a disconnected drive
+        // may simply stop reporting changes
+        if !profile.base().is_dir() {
+            watcher = None;
+            if !lock.state().unavailable {
+                println!("{:?}: base directory is unavailable, pausing", name);
+                changed = None;
+                triggers.clear();
+                set_unavailable(&mut lock, name, true)?;
+            }
+        } else if watcher.is_none() {
+            match create_watcher(&profile, tx.clone()) {
+                Ok(new_watcher) => {
+                    watcher = Some(new_watcher);
+                    if lock.state().unavailable {
+                        println!("{:?}: base directory is available again, resuming", name);
+                        set_unavailable(&mut lock, name, false)?;
+                    }
+                }
+                // retried on the next wake-up
+                Err(e) => eprintln!("{:?}: failed watching base directory: {}", name, e),
+            }
+        }
+        // wake up at least once per heartbeat interval, to notice when the
+        // base directory comes and goes
+        let timeout = [changed, updated]
+            .into_iter()
+            .flatten()
+            .map(|time| delay.saturating_sub(time.elapsed()))
+            .min()
+            .unwrap_or(HEARTBEAT_INTERVAL)
+            .min(HEARTBEAT_INTERVAL);
+        match rx.recv_timeout(timeout) {
+            // queued before the base directory became unavailable, or caused
+            // by whatever the watcher was paused for (e.g. a restore)
+            Ok(Message::Changed(_))
+                if watcher.is_none()
+                    || lock.state().paused
+                    || resumed.is_some_and(|time| time.elapsed() < RESUME_SETTLE) => {}
+            Ok(Message::Updated) if lock.state().paused => {}
+            Ok(Message::Changed(paths)) => {
+                events::publish(
+                    name,
+                    EventKind::Changed {
+                        files: paths.clone(),
+                    },
+                );
+                // when we time out we'll back up everything, but remember
+                // which files caused it for the backup's manifest
+                triggers.extend(paths);
+                changed = Some(Instant::now());
+                let delay =
+                    chrono::Duration::from_std(delay).unwrap_or_else(|_| chrono::Duration::zero());
+                lock.update(|state| state.next_backup = Some(Utc::now() + delay))?;
+            }
+            Ok(Message::Updated) => updated = Some(Instant::now()),
+            Ok(Message::ProfileChanged) => match reload(name, &tx) {
+                Ok((new_profile, new_watcher, new_update_watcher)) => {
+                    println!("{:?}: profile changed, reloaded", name);
+                    delay = Duration::from_secs_f32(new_profile.delay());
+                    fingerprint = UpdateFingerprint::read(&new_profile);
+                    profile = new_profile;
+                    watcher = new_watcher;
+                    _update_watcher = new_update_watcher;
+                }
+                Err(e) => eprintln!("{:?}: keeping previous profile: {}", name, e),
+            },
+            Ok(Message::Control(Control { request, reply })) => {
+                let response = match request {
+                    Request::Backup { cause, tag } => {
+                        changed = None;
+                        println!("--------------------------------------------------");
+                        println!("{:?}: backup requested", name);
+                        let cause = cause.unwrap_or(BackupCause::Manual);
+                        let tag = tag.as_deref();
+                        let outcome =
+                            run_backup(db, &profile, name, &mut triggers, tag, cause, &mut lock)?;
+                        match outcome {
+                            BackupOutcome { id: Some(id), .. } => Response::BackedUp { id },
+                            outcome => Response::Error {
+                                message: outcome.error.unwrap_or_default(),
+                            },
+                        }
+                    }
+                    Request::Pause | Request::Resume => {
+                        // applied at the start of the next iteration
+                        let paused = matches!(request, Request::Pause);
+                        write_pause_file(name, paused)?;
+                        Response::Paused { paused }
+                    }
+                    Request::Stop => {
+                        println!("{:?}: stop requested", name);
+                        let _ = reply.send(Response::Stopped);
+                        return Ok(());
+                    }
+                    Request::Status => match db.backup_table(name).and_then(|t| t.latest()) {
+                        Ok(latest) => Response::Status {
+                            state: lock.state(),
+                            latest: latest.map(Box::new),
+                        },
+                        Err(e) => Response::Error {
+                            message: e.to_string(),
+                        },
+                    },
+                };
+                // the client may have disconnected in the meantime
+                let _ = reply.send(response);
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                // wait for the update to finish writing before checking it
+                if matches!(updated, Some(time) if time.elapsed() >= delay) {
+                    updated = None;
+                    let current = UpdateFingerprint::read(&profile);
+                    if current != fingerprint {
+                        fingerprint = current;
+                        // the pre-update backup includes any pending changes
+                        changed = None;
+                        println!("--------------------------------------------------");
+                        println!("{:?}: game update detected", name);
+                        let tag = Some(PRE_UPDATE_TAG);
+                        let cause = BackupCause::PreUpdate;
+                        run_backup(db, &profile, name, &mut triggers, tag, cause, &mut lock)?;
+                    }
+                }
+                match changed {
+                    Some(time) if time.elapsed() >= delay => {
+                        changed = None;
+                        println!("--------------------------------------------------");
+                        println!("{:?}: contents changed on disk", name);
+                        let cause = BackupCause::Watcher;
+                        run_backup(db, &profile, name, &mut triggers, None, cause, &mut lock)?;
+                    }
+                    _ => {}
+                }
+                if changed.is_none()
+                    && !lock.state().paused
+                    && maintained.is_none_or(|time| time.elapsed() >= MAINTENANCE_INTERVAL)
+                {
+                    maintained = Some(Instant::now());
+                    maintain(db, &profile, name);
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => {
+                panic!("what! impossible!")
+            }
+        }
+    }
+}
+
+/// Watch several profiles at once, each on its own thread.
+///
+/// A watcher that fails or panics is restarted after a delay, which grows while
+/// it keeps failing. Watchers that fail for reasons a restart won't fix (such as
+/// a missing or invalid profile, or another watcher holding the lock) are not
+/// restarted. Returns once no watchers are left.
+pub fn watch_all(names: &[ProfileName]) {
+    let supervisors = names
+        .iter()
+        .cloned()
+        .map(|name| std::thread::spawn(move || supervise(name)))
+        .collect::<Vec<_>>();
+    for supervisor in supervisors {
+        let _ = supervisor.join();
+    }
+}
+
+/// Run the watcher of a single profile, restarting it when it fails.
+fn supervise(name: ProfileName) {
+    let mut delay = MIN_RESTART_DELAY;
+    loop {
+        let started = Instant::now();
+        let thread_name = name.clone();
+        let watcher = std::thread::spawn(move || {
+            let profile = Profile::open_named(&thread_name)?;
+            let db = Database::open_for_profile(&thread_name)?;
+            watch(&db, &profile, &thread_name)
+        });
+        match watcher.join() {
+            Ok(Ok(())) => return,
+            Ok(Err(e)) => {
+                eprintln!("{:?}: watcher failed: {}", name, e);
+                let permanent = matches!(
+                    e.category(),
+                    ErrorCategory::NoSuchProfile
+                        | ErrorCategory::InvalidProfile
+                        | ErrorCategory::Locked
+                );
+                if permanent {
+                    eprintln!("{:?}: not restarting the watcher", name);
+                    return;
+                }
+            }
+            Err(_) => eprintln!("{:?}: watcher panicked", name),
+        }
+        if started.elapsed() >= HEALTHY_AFTER {
+            delay = MIN_RESTART_DELAY;
+        }
+        eprintln!("{:?}: restarting the watcher in {:?}", name, delay);
+        std::thread::sleep(delay);
+        delay = (delay * 2).min(MAX_RESTART_DELAY);
+    }
+}
+
+/// Back up the profile and record the outcome in the lock file.
+///
+/// Only fails if the lock file can't be written; a failed backup is reported
+/// in the returned outcome.
+fn run_backup(
+    db: &Database,
+    profile: &Profile,
+    name: &ProfileName,
+    triggers: &mut BTreeSet<PathBuf>,
+    tag: Option<&str>,
+    cause: BackupCause,
+    lock: &mut WatchLock,
+) -> Result<BackupOutcome> {
+    let paths = std::mem::take(triggers).into_iter().collect::<Vec<_>>();
+    let permit = Scheduler::global().acquire();
+    events::publish(name, EventKind::BackupStarted { cause });
+    let priority = Config::load()
+        .map_or(true, |config| config.background_priority)
+        .then(Background::enter);
+    let result = backup(db, profile, name, &paths, tag, cause);
+    drop(priority);
+    drop(permit);
+    let outcome = match result {
+        Ok(id) => {
+            let tag = tag.map(str::to_owned);
+            events::publish(name, EventKind::BackedUp { id, tag });
+            alert::record(name, None);
+            BackupOutcome {
+                time: Utc::now(),
+                id: Some(id),
+                error: None,
+            }
+        }
+        Err(e) => {
+            eprintln!("{:?}: backup failed: {}", name, e);
+            let error = e.to_string();
+            alert::record(name, Some(&error));
+            events::publish(name, EventKind::BackupFailed { error });
+            BackupOutcome {
+                time: Utc::now(),
+                id: None,
+                error: Some(e.to_string()),
+            }
+        }
+    };
+    lock.update(|state| {
+        state.last_backup = Some(outcome.clone());
+        state.next_backup = None;
+    })?;
+    Ok(outcome)
+}
+
+/// Move the profile's old backups to cold storage, see [`crate::cold`].
+///
+/// Runs on the watch loop, so that it never overlaps a backup of the profile.
+/// Moving many backups can take a while, during which the watcher lock's
+/// heartbeat is kept fresh by its background thread.
+///
+/// Failures are reported, and retried at the next maintenance run.
+fn maintain(db: &Database, profile: &Profile, name: &ProfileName) {
+    if profile.cold_storage().is_none() {
+        return;
+    }
+    let _permit = Scheduler::global().acquire();
+    let _priority = Config::load()
+        .map_or(true, |config| config.background_priority)
+        .then(Background::enter);
+    match cold::apply_rule(db, profile, name) {
+        Ok(0) => {}
+        Ok(moved) => println!("{:?}: moved {} backup(s) to cold storage", name, moved),
+        Err(e) => eprintln!("{:?}: moving backups to cold storage failed: {}", name, e),
+    }
+}
+
+/// Bring the watcher's state in line with the profile's pause file, reporting
+/// any change.
+///
+/// Returns whether the watcher is now paused if that changed.
+fn sync_pause(lock: &mut WatchLock, name: &ProfileName) -> Result<Option<bool>> {
+    let paused = is_paused(name)?;
+    if paused == lock.state().paused {
+        return Ok(None);
+    }
+    if paused {
+        println!("{:?}: automatic backups paused", name);
+        events::publish(name, EventKind::Paused);
+    } else {
+        println!("{:?}: automatic backups resumed", name);
+        events::publish(name, EventKind::Resumed);
+    }
+    lock.update(|state| {
+        state.paused = paused;
+        state.next_backup = None;
+    })?;
+    Ok(Some(paused))
+}
+
+/// Record whether the base directory is unavailable, and report the change.
+fn set_unavailable(lock: &mut WatchLock, name: &ProfileName, unavailable: bool) -> Result<()> {
+    let kind = if unavailable {
+        EventKind::Unavailable
+    } else {
+        EventKind::Available
+    };
+    events::publish(name, kind);
+    lock.update(|state| {
+        state.unavailable = unavailable;
+        state.next_backup = None;
+    })
+}
+
+/// Open the profile again and create its watchers.
+fn reload(
+    name: &ProfileName,
+    tx: &Sender<Message>,
+) -> Result<(Profile, Option<Watcher>, Option<Watcher>)> {
+    let profile = Profile::open_named(name)?;
+    let watcher = watch_base(&profile, tx.clone())?;
+    let update_watcher = create_update_watcher(&profile, tx.clone())?;
+    Ok((profile, watcher, update_watcher))
+}
+
+/// State of a profile's update trigger, compared to detect game updates.
+#[derive(Debug, PartialEq)]
+struct UpdateFingerprint {
+    /// Modification time of the trigger file.
+    modified: Option<SystemTime>,
+    /// Version captured from the profile's version source.
+    version: Option<String>,
+}
+
+impl UpdateFingerprint {
+    /// Read the current state, or `None` if the profile has no update trigger.
+    fn read(profile: &Profile) -> Option<Self> {
+        let path = profile.update_trigger()?;
+        let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        let version = profile
+            .version()
+            .and_then(|source| source.capture(profile.base()).ok().flatten());
+        Some(Self { modified, version })
+    }
+}
+
+/// Create a watcher that reports changes to the profile's update trigger, if any.
+///
+/// The trigger's directory is watched, so that the file may be replaced.
+fn create_update_watcher(profile: &Profile, tx: Sender<Message>) -> Result<Option<Watcher>> {
+    use notify::Watcher;
+    let Some(path) = profile.update_trigger() else {
+        return Ok(None);
+    };
+    let dir = path.parent().unwrap_or(&path).to_owned();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
+        if let Ok(event) = res {
+            if !event.kind.is_access() && event.paths.contains(&path) {
+                // the receiver only goes away when the watcher stops
+                let _ = tx.send(Message::Updated);
+            }
+        }
+    })
+    .expect("failed to create watcher");
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(std::io::Error::other)
+        .with_path("watching", &dir)?;
+    Ok(Some(watcher))
+}
+
+/// Create a watcher that reports edits to the profile file.
+fn create_profile_watcher(name: &ProfileName, tx: Sender<Message>) -> Result<Watcher> {
+    use notify::Watcher;
+    let path = profile_path(name)?;
+    let dir = profiles_dir()?;
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
+        if let Ok(event) = res {
+            let edited = event.kind.is_create() || event.kind.is_modify();
+            if edited && event.paths.contains(&path) {
+                // the receiver only goes away when the watcher stops
+                let _ = tx.send(Message::ProfileChanged);
+            }
+        }
+    })
+    .expect("failed to create watcher");
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(std::io::Error::other)
+        .with_path("watching", &dir)?;
+    Ok(watcher)
+}
+
+/// Create the watcher of the profile's included files, or return `None` if the
+/// base directory is currently unavailable.
+fn watch_base(profile: &Profile, tx: Sender<Message>) -> Result<Option<Watcher>> {
+    if profile.remote().is_none() && !profile.base().is_dir() {
+        return Ok(None);
+    }
+    create_watcher(profile, tx).map(Some)
+}
+
+/// Create a watcher that sends the included paths affected by each event.
+fn create_watcher(profile: &Profile, tx: Sender<Message>) -> Result<Watcher> {
+    use notify::Watcher;
+    if let Some(remote) = profile.remote() {
+        Err(ProfileError::RemoteNotWatchable(remote.to_string()))?
+    }
+    let idkbro = profile.clone();
+    let mut watcher = notify::recommended_watcher(move |res: Result<Event, _>| {
+        let include = idkbro.expand_includes(false).expect("invalid profile");
+        if let Ok(event) = res {
+            let paths = event
+                .paths
+                .iter()
+                .filter(|path| include.contains(path) && !idkbro.is_watch_ignored(path))
+                .cloned()
+                .collect::<Vec<_>>();
+            if !paths.is_empty() {
+                tx.send(Message::Changed(paths))
+                    .expect("failed to send event")
+            } else {
+                println!("ignoring event: {:?}", event);
+            }
+        }
+    })
+    .expect("failed to create watcher");
+    watcher
+        .watch(profile.base(), RecursiveMode::Recursive)
+        .or(Err(ProfileError::InvalidBase(profile.base().to_owned())))?;
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::{control, filesystem::use_test_home, json_store::JsonStore, lock};
+
+    /// Start watching a new profile on another thread, returning once the
+    /// watcher accepts requests. The thread returns the watcher's database.
+    fn start_watcher(
+        name: &ProfileName,
+        base: &Path,
+    ) -> std::thread::JoinHandle<(Database, Result<()>)> {
+        use_test_home();
+        let profile = Profile::builder(base).build().unwrap();
+        profile.save(profile_path(name).unwrap()).unwrap();
+        let watcher = {
+            let name = name.clone();
+            std::thread::spawn(move || {
+                let db = Database::from_store(JsonStore::in_memory());
+                let result = watch(&db, &profile, &name);
+                (db, result)
+            })
+        };
+        let started = Instant::now();
+        while lock::read_state(name)
+            .unwrap()
+            .and_then(|state| state.endpoint)
+            .is_none()
+        {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "watcher didn't start"
+            );
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        watcher
+    }
+
+    #[test]
+    fn stop_requests_end_the_watcher() {
+        let base = tempfile::tempdir().unwrap();
+        let name = ProfileName::new("watch-stop").unwrap();
+        let watcher = start_watcher(&name, base.path());
+
+        assert!(control::stop_watcher(&name).unwrap());
+        watcher.join().unwrap().1.unwrap();
+        assert!(lock::read_state(&name).unwrap().is_none());
+        assert!(!control::stop_watcher(&name).unwrap());
+    }
+
+    #[test]
+    fn requested_backups_keep_their_tag() {
+        let base = tempfile::tempdir().unwrap();
+        std::fs::write(base.path().join("save.dat"), "level 1").unwrap();
+        let name = ProfileName::new("watch-tag").unwrap();
+        let watcher = start_watcher(&name, base.path());
+
+        let id = control::backup_now(&name, Some("boss"), BackupCause::Manual).unwrap();
+        assert!(control::stop_watcher(&name).unwrap());
+        let (db, result) = watcher.join().unwrap();
+        result.unwrap();
+        let backup = db
+            .backup_table(&name)
+            .unwrap()
+            .select_id(id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(backup.tag(), Some("boss"));
+    }
+}
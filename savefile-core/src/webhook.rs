@@ -0,0 +1,244 @@
+/// Notifications about finished backups, posted to webhooks such as a Discord
+/// channel.
+use std::time::Duration;
+
+use crate::{
+    error::{BackupError, ProfileError, Result},
+    profile::ProfileName,
+    template, Backup, BackupId, Database,
+};
+
+/// Placeholders available in success messages.
+const SUCCESS_PLACEHOLDERS: [&str; 6] = ["profile", "id", "time", "tag", "version", "files"];
+/// Placeholders available in failure messages.
+const FAILURE_PLACEHOLDERS: [&str; 3] = ["profile", "time", "error"];
+/// Default message for successful backups.
+const DEFAULT_SUCCESS: &str = "Backed up {profile} (backup {id}, {files} files)";
+/// Default message for failed backups.
+const DEFAULT_FAILURE: &str = "Backup of {profile} failed: {error}";
+/// Time allowed for each request.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A webhook notified when backups of a profile finish.
+///
+/// In profiles, webhooks are written as e.g.
+/// `{ "url": "https://discord.com/api/webhooks/...", "on": ["failure"] }`.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Webhook {
+    /// URL to post to.
+    pub url: String,
+    /// Which outcomes to post about.
+    #[serde(default = "all_outcomes")]
+    pub on: Vec<Outcome>,
+    /// Shape of the request body; detected from the URL if omitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<WebhookFormat>,
+    /// Message template for successful backups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub success: Option<String>,
+    /// Message template for failed backups.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub failure: Option<String>,
+}
+
+/// Outcome of a backup.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// Shape of a webhook's request body.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// `{ "content": message }`
+    Discord,
+    /// `{ "text": message }`
+    Slack,
+    /// The message along with the details of the backup.
+    Json,
+}
+
+/// Default outcomes webhooks are notified of.
+fn all_outcomes() -> Vec<Outcome> {
+    vec![Outcome::Success, Outcome::Failure]
+}
+
+impl Webhook {
+    /// A webhook at the given URL, with the default settings.
+    pub(crate) fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            on: all_outcomes(),
+            format: None,
+            success: None,
+            failure: None,
+        }
+    }
+
+    /// Check that the URL and message templates are well-formed.
+    pub(crate) fn validate(&self) -> Result<(), ProfileError> {
+        if !self.url.starts_with("https://") && !self.url.starts_with("http://") {
+            Err(ProfileError::InvalidWebhook(format!(
+                "{:?} is not an HTTP(S) URL",
+                self.url
+            )))?
+        }
+        let templates = [
+            (&self.success, &SUCCESS_PLACEHOLDERS[..]),
+            (&self.failure, &FAILURE_PLACEHOLDERS[..]),
+        ];
+        for (template, placeholders) in templates {
+            if let Some(template) = template {
+                template::validate(template, placeholders).map_err(ProfileError::InvalidWebhook)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the body format, detecting well-known services from the URL.
+    fn format(&self) -> WebhookFormat {
+        self.format.unwrap_or_else(|| {
+            let host = self
+                .url
+                .split("://")
+                .nth(1)
+                .and_then(|rest| rest.split('/').next())
+                .unwrap_or_default();
+            match host {
+                "discord.com" | "discordapp.com" => WebhookFormat::Discord,
+                "hooks.slack.com" => WebhookFormat::Slack,
+                _ => WebhookFormat::Json,
+            }
+        })
+    }
+
+    /// Post a message, with `details` of the backup for the JSON format.
+    pub(crate) fn post(&self, message: &str, details: serde_json::Value) -> Result<(), String> {
+        let body = match self.format() {
+            WebhookFormat::Discord => serde_json::json!({ "content": message }),
+            WebhookFormat::Slack => serde_json::json!({ "text": message }),
+            WebhookFormat::Json => {
+                let mut details = details;
+                details["message"] = message.into();
+                details
+            }
+        };
+        ureq::post(&self.url)
+            .timeout(TIMEOUT)
+            .send_json(body)
+            .map(drop)
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// Post the outcome of a backup to the given webhooks.
+///
+/// Failures to post are reported as warnings, since the backup itself is done.
+pub(crate) fn notify(
+    webhooks: &[Webhook],
+    db: &Database,
+    name: &ProfileName,
+    result: &Result<BackupId>,
+) {
+    let outcome = match result {
+        Ok(_) => Outcome::Success,
+        Err(_) => Outcome::Failure,
+    };
+    let webhooks = webhooks
+        .iter()
+        .filter(|webhook| webhook.on.contains(&outcome))
+        .collect::<Vec<_>>();
+    if webhooks.is_empty() {
+        return;
+    }
+    let details = match result {
+        Ok(id) => match success_details(db, name, *id) {
+            Ok(details) => details,
+            Err(e) => {
+                eprintln!("warning: not notifying webhooks: {}", e);
+                return;
+            }
+        },
+        Err(e) => Details::Failure(e.to_string()),
+    };
+    for webhook in webhooks {
+        let message = details.message(webhook, name);
+        if let Err(e) = webhook.post(&message, details.json(name)) {
+            eprintln!("warning: failed posting to webhook: {}", e);
+        }
+    }
+}
+
+/// What is known about a finished backup.
+enum Details {
+    Success { backup: Backup, files: usize },
+    Failure(String),
+}
+
+/// Look up the details of a successful backup.
+fn success_details(db: &Database, name: &ProfileName, id: BackupId) -> Result<Details> {
+    let backup = db
+        .backup_table(name)?
+        .select_id(id)?
+        .ok_or(BackupError::NoSuchBackup(id))?;
+    let files = db.file_table(name)?.select_backup(id)?.len();
+    Ok(Details::Success { backup, files })
+}
+
+impl Details {
+    /// Render the webhook's message for this outcome.
+    fn message(&self, webhook: &Webhook, name: &ProfileName) -> String {
+        let now = chrono::Utc::now();
+        let (template, result) = match self {
+            Details::Success { backup, files } => {
+                let template = webhook.success.as_deref().unwrap_or(DEFAULT_SUCCESS);
+                let result = template::render(template, |placeholder, format| {
+                    Ok(match placeholder {
+                        "profile" => name.to_string(),
+                        "id" => backup.id().to_string(),
+                        "time" => template::format_time(&backup.timestamp(), format),
+                        "tag" => backup.tag().unwrap_or_default().to_owned(),
+                        "version" => backup.version().unwrap_or_default().to_owned(),
+                        "files" => files.to_string(),
+                        _ => Err(format!("unknown placeholder {{{}}}", placeholder))?,
+                    })
+                });
+                (template, result)
+            }
+            Details::Failure(error) => {
+                let template = webhook.failure.as_deref().unwrap_or(DEFAULT_FAILURE);
+                let result = template::render(template, |placeholder, format| {
+                    Ok(match placeholder {
+                        "profile" => name.to_string(),
+                        "time" => template::format_time(&now, format),
+                        "error" => error.clone(),
+                        _ => Err(format!("unknown placeholder {{{}}}", placeholder))?,
+                    })
+                });
+                (template, result)
+            }
+        };
+        // templates are validated with the profile, so this is only a fallback
+        result.unwrap_or_else(|_| template.to_owned())
+    }
+
+    /// Describe the outcome for JSON webhooks.
+    fn json(&self, name: &ProfileName) -> serde_json::Value {
+        match self {
+            Details::Success { backup, files } => serde_json::json!({
+                "profile": name.as_str(),
+                "outcome": Outcome::Success,
+                "backup": backup,
+                "files": files,
+            }),
+            Details::Failure(error) => serde_json::json!({
+                "profile": name.as_str(),
+                "outcome": Outcome::Failure,
+                "error": error,
+            }),
+        }
+    }
+}
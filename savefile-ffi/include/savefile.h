@@ -0,0 +1,43 @@
+/*
+ * C interface to savefile.
+ *
+ * Every function returns 0 on success, or the exit code of the error's
+ * category (see the README), in which case savefile_last_error() describes
+ * the error. Strings are UTF-8 and null-terminated. Strings returned through
+ * out-parameters must be freed with savefile_string_free().
+ */
+#ifndef SAVEFILE_H
+#define SAVEFILE_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Description of the last error on the calling thread, or NULL. Owned by the library. */
+const char *savefile_last_error(void);
+
+/* Free a string returned through an out-parameter. Does nothing for NULL. */
+void savefile_string_free(char *s);
+
+/* List the installed profiles as a JSON array of names. */
+int32_t savefile_list_profiles(char **out_json);
+
+/* List the backups of a profile as a JSON array of objects, oldest first. */
+int32_t savefile_list_backups(const char *profile, char **out_json);
+
+/* Back up a profile, through its watcher if one is running. tag may be NULL; out_id may be NULL. */
+int32_t savefile_backup(const char *profile, const char *tag, uint32_t *out_id);
+
+/* Restore a backup, overwriting the live files. A running watcher is paused meanwhile. */
+int32_t savefile_restore(const char *profile, uint32_t id);
+
+/* Restore a backup given by its unique ID (ULID), overwriting the live files. */
+int32_t savefile_restore_uid(const char *profile, const char *uid);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* SAVEFILE_H */
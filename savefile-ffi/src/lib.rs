@@ -0,0 +1,235 @@
+/// C ABI over the core savefile operations, for embedding in launchers and
+/// other programs not written in Rust.
+///
+/// Every function returns a status code: `0` on success, or the exit code of
+/// the error's category (see the README), in which case
+/// [`savefile_last_error`] describes the error. Strings are UTF-8 and
+/// null-terminated. Strings returned through out-parameters are owned by the
+/// caller and must be freed with [`savefile_string_free`]. Lists are returned
+/// as JSON arrays.
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+use savefile::{
+    control,
+    error::{BackupError, Error},
+    list_profile_names, BackupCause, BackupId, BackupUid, Database, ProfileName,
+};
+
+/// Status code for success.
+const OK: i32 = 0;
+/// Status code for invalid arguments, such as null pointers or invalid UTF-8.
+const INVALID_ARGUMENT: i32 = 1;
+
+thread_local! {
+    /// Description of the last error on this thread.
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+/// Record an error for [`savefile_last_error`] and return its status code.
+fn fail(code: i32, message: impl ToString) -> i32 {
+    // interior null bytes can't be represented, so they are dropped
+    let message = message.to_string().replace('\0', "");
+    let message = CString::new(message).expect("null bytes were removed");
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// Run `f`, converting its result into a status code.
+fn run(f: impl FnOnce() -> Result<(), i32>) -> i32 {
+    match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
+        Ok(Ok(())) => OK,
+        Ok(Err(code)) => code,
+        Err(_) => fail(1, "internal error"),
+    }
+}
+
+/// Convert a library error into a status code.
+fn error(e: Error) -> i32 {
+    fail(e.exit_code(), e)
+}
+
+/// Read a required string argument.
+///
+/// # Safety
+///
+/// `s` must be null or point to a null-terminated string.
+unsafe fn string_arg<'a>(s: *const c_char, name: &str) -> Result<&'a str, i32> {
+    if s.is_null() {
+        return Err(fail(INVALID_ARGUMENT, format!("{} is null", name)));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|_| fail(INVALID_ARGUMENT, format!("{} is not valid UTF-8", name)))
+}
+
+/// Read a profile name argument.
+///
+/// # Safety
+///
+/// `s` must be null or point to a null-terminated string.
+unsafe fn name_arg(s: *const c_char) -> Result<ProfileName, i32> {
+    ProfileName::new(string_arg(s, "profile")?).map_err(|e| error(e.into()))
+}
+
+/// Store a string in an out-parameter.
+///
+/// # Safety
+///
+/// `out` must be null or valid for writes.
+unsafe fn write_string(out: *mut *mut c_char, s: String) -> Result<(), i32> {
+    if out.is_null() {
+        return Err(fail(INVALID_ARGUMENT, "output pointer is null"));
+    }
+    let s = CString::new(s).map_err(|e| fail(1, e))?;
+    *out = s.into_raw();
+    Ok(())
+}
+
+/// Returns a description of the last error on the calling thread, or null if
+/// no error occurred.
+///
+/// The string is owned by the library and valid until the next call on the
+/// same thread.
+#[no_mangle]
+pub extern "C" fn savefile_last_error() -> *const c_char {
+    LAST_ERROR.with(|last| last.borrow().as_ref().map_or(ptr::null(), |s| s.as_ptr()))
+}
+
+/// Free a string returned by this library. Does nothing if `s` is null.
+///
+/// # Safety
+///
+/// `s` must be null or a string returned through an out-parameter of this
+/// library, and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// List the installed profiles as a JSON array of names.
+///
+/// # Safety
+///
+/// `out_json` must be valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_list_profiles(out_json: *mut *mut c_char) -> i32 {
+    run(|| {
+        let names = list_profile_names().map_err(error)?;
+        let names = names.iter().map(|name| name.as_str()).collect::<Vec<_>>();
+        write_string(
+            out_json,
+            serde_json::to_string(&names).map_err(|e| fail(1, e))?,
+        )
+    })
+}
+
+/// List the backups of a profile as a JSON array, oldest first.
+///
+/// Each backup is an object with the fields `id`, `tag`, `timestamp`, `dir`,
+/// `version`, `status`, `cause` (why it was made, e.g. `"watcher"`),
+/// `changed` (the number of changed files), `env` (an object with the `host`,
+/// `os` and `savefile_version` it was made with) and `uid` (its ULID).
+/// `changed`, `env` and `uid` may be null for backups that don't record them.
+///
+/// # Safety
+///
+/// `profile` must be a null-terminated string and `out_json` must be valid
+/// for writes.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_list_backups(
+    profile: *const c_char,
+    out_json: *mut *mut c_char,
+) -> i32 {
+    run(|| {
+        let name = name_arg(profile)?;
+        let db = Database::open_for_profile(&name).map_err(error)?;
+        let backups = db
+            .backup_table(&name)
+            .and_then(|table| table.select_all())
+            .map_err(error)?;
+        write_string(
+            out_json,
+            serde_json::to_string(&backups).map_err(|e| fail(1, e))?,
+        )
+    })
+}
+
+/// Back up a profile, storing the new backup's ID in `out_id`.
+///
+/// `tag` may be null for an untagged backup. If a watcher is running for the
+/// profile, it is asked to make the backup.
+///
+/// # Safety
+///
+/// `profile` must be a null-terminated string, `tag` null or a null-terminated
+/// string, and `out_id` null or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_backup(
+    profile: *const c_char,
+    tag: *const c_char,
+    out_id: *mut u32,
+) -> i32 {
+    run(|| {
+        let name = name_arg(profile)?;
+        let tag = if tag.is_null() {
+            None
+        } else {
+            Some(string_arg(tag, "tag")?)
+        };
+        let id = control::backup_now(&name, tag, BackupCause::Manual).map_err(error)?;
+        if !out_id.is_null() {
+            *out_id = id.get();
+        }
+        Ok(())
+    })
+}
+
+/// Restore a backup of a profile, overwriting the live files.
+///
+/// A watcher running for the profile is paused while the files are restored,
+/// so that they aren't backed up again.
+///
+/// # Safety
+///
+/// `profile` must be a null-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_restore(profile: *const c_char, id: u32) -> i32 {
+    run(|| {
+        let name = name_arg(profile)?;
+        let id = BackupId::new(id).map_err(|e| error(e.into()))?;
+        let db = Database::open_for_profile(&name).map_err(error)?;
+        savefile::restore_backup(&db, &name, id).map_err(error)
+    })
+}
+
+/// Restore a backup of a profile given by its unique ID, overwriting the live
+/// files.
+///
+/// Like [`savefile_restore`], but `uid` is the backup's ULID as listed by
+/// [`savefile_list_backups`].
+///
+/// # Safety
+///
+/// `profile` and `uid` must be null-terminated strings.
+#[no_mangle]
+pub unsafe extern "C" fn savefile_restore_uid(profile: *const c_char, uid: *const c_char) -> i32 {
+    run(|| {
+        let name = name_arg(profile)?;
+        let uid: BackupUid = string_arg(uid, "uid")?
+            .parse()
+            .map_err(|e: BackupError| error(e.into()))?;
+        let db = Database::open_for_profile(&name).map_err(error)?;
+        let id = db
+            .backup_table(&name)
+            .and_then(|table| table.select_ref(uid.into()))
+            .map_err(error)?
+            .id();
+        savefile::restore_backup(&db, &name, id).map_err(error)
+    })
+}
@@ -1,13 +0,0 @@
-mod backup;
-pub mod database;
-pub mod error;
-pub mod filesystem;
-mod profile;
-pub mod watcher;
-
-pub use backup::{
-    backup, delete_all_backups, delete_one_backup, restore_backup, Backup, Id, Timestamp,
-};
-pub use database::Database;
-pub use profile::{list_profiles, Profile};
-pub use watcher::watch;